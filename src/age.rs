@@ -0,0 +1,100 @@
+//! How long each particle has been around, and recycling of particles whose
+//! inlet gave them a finite [lifetime](crate::boundary::Inlet::lifetime).
+
+use crate::boundary::{self, Boundaries};
+use crate::sim::{self, Instance};
+
+/// Seconds over which a particle fades out before its lifetime ends.
+pub const FADE_OUT: f32 = 0.5;
+/// Age shown as the oldest color, for particles without a lifetime.
+pub const COLOR_RANGE: f32 = 10.0;
+
+#[derive(Debug, Clone)]
+pub struct Ages {
+    age: Vec<f32>,
+    /// Infinite unless the particle came from an inlet with a lifetime.
+    lifetime: Vec<f32>,
+    /// Which particles were removed last update, to spot slots an inlet reused.
+    removed: Vec<bool>,
+}
+
+impl Ages {
+    pub fn new(particles: &[Instance]) -> Self {
+        Self {
+            age: vec![0.0; particles.len()],
+            lifetime: vec![f32::INFINITY; particles.len()],
+            removed: particles.iter().map(boundary::is_removed).collect(),
+        }
+    }
+
+    /// Seconds since each particle was spawned, 0 for removed ones.
+    pub fn ages(&self) -> &[f32] {
+        &self.age
+    }
+
+    /// Ages every particle by `dt`, starting particles that just came out of an
+    /// inlet at 0. Particles past their lifetime are removed. Returns whether
+    /// any were, so the caller can collect their slots.
+    pub fn update(&mut self, particles: &mut [Instance], boundaries: &Boundaries, dt: f32) -> bool {
+        let mut expired = false;
+        for (i, p) in particles.iter_mut().enumerate() {
+            if boundary::is_removed(p) {
+                self.age[i] = 0.0;
+                self.removed[i] = true;
+                continue;
+            }
+
+            if self.removed[i] {
+                self.age[i] = 0.0;
+                self.lifetime[i] = boundaries
+                    .nearest_inlet(p.pos)
+                    .and_then(|inlet| inlet.lifetime)
+                    .unwrap_or(f32::INFINITY);
+                self.removed[i] = false;
+            } else {
+                self.age[i] += dt;
+            }
+
+            if self.age[i] >= self.lifetime[i] {
+                *p = boundary::REMOVED;
+                self.age[i] = 0.0;
+                self.removed[i] = true;
+                expired = true;
+            }
+        }
+        expired
+    }
+
+    /// Opacity of particle `i`, fading from 1 to 0 over the last [`FADE_OUT`]
+    /// seconds of its lifetime.
+    pub fn fade(&self, i: usize) -> f32 {
+        ((self.lifetime[i] - self.age[i]) / FADE_OUT).clamp(0.0, 1.0)
+    }
+
+    /// Scales the alpha of packed `colors` by each particle's [`fade`](Self::fade).
+    pub fn fade_colors(&self, colors: &mut [u32]) {
+        for (i, color) in colors.iter_mut().enumerate() {
+            let alpha = ((*color >> 24) as f32 * self.fade(i)) as u32;
+            *color = (*color & 0x00ff_ffff) | alpha << 24;
+        }
+    }
+
+    /// [`sim::colormap`] of the age, relative to the lifetime where there is
+    /// one and to [`COLOR_RANGE`] otherwise. Removed particles are transparent.
+    pub fn colors(&self, particles: &[Instance]) -> Vec<u32> {
+        particles
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match boundary::is_removed(p) {
+                true => 0,
+                false => {
+                    let range = match self.lifetime[i].is_finite() {
+                        true => self.lifetime[i],
+                        false => COLOR_RANGE,
+                    };
+                    sim::colormap(self.age[i] / range)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,572 @@
+//! The window: shows the frames of the [simulation thread](crate::simulation)
+//! and passes input on to it. With `--debug-window` a second window shows
+//! the speed of the flow, sharing the device with the main one but with its
+//! own surface and camera. With `--split` the main window is divided into
+//! panes side by side, each showing a simulation thread of its own.
+
+use crate::dye;
+use crate::hud::FrameTimings;
+use crate::measure::Measurement;
+use crate::options::{Options, PresentMode};
+use crate::phase::Phase;
+use crate::plots::Plots;
+use crate::png;
+use crate::probe::Heatmap;
+use crate::render;
+use crate::sim;
+use crate::simulation::{Command, Frame, SimThread};
+use crate::timestep::FrameLimiter;
+use crate::trace::Track;
+use crate::views::Views;
+use crate::TIME_STEP;
+use std::fs::File;
+use std::io::BufReader;
+use std::iter;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
+use winit::window;
+
+/// Frames the plots scroll over.
+const PLOT_WINDOW: usize = 300;
+/// How often the event loop wakes up while no window is visible.
+const HIDDEN_POLL: Duration = Duration::from_millis(100);
+/// Steps Backspace goes back, a second.
+const REWIND_STEPS: u64 = (1.0 / TIME_STEP) as u64;
+
+pub async fn run(mut options: Options) {
+    let event_loop = EventLoop::new().expect("could not create event loop");
+    let window = Arc::new(window::WindowBuilder::new().build(&event_loop).unwrap());
+    let debug_window = options.debug_window.then(|| {
+        let window = window::WindowBuilder::new()
+            .with_title("pos-based-fluids | speed")
+            .build(&event_loop)
+            .unwrap();
+        Arc::new(window)
+    });
+
+    let (oit, sort_by) = (options.oit, options.sort_by);
+    let mut render_params = render::RenderParams {
+        radius_scale: options.particle_scale,
+        ..render::RenderParams::default()
+    };
+    render_params.set_discs(options.discs);
+    render_params.set_flag(render::RenderParams::OPAQUE, options.opaque);
+    render_params.set_flag(render::RenderParams::CLIP, options.clip);
+    let background_image = options.background_image.clone();
+    let background_rect = options.background_rect;
+    let theme = options.theme.clone();
+    let pause_hidden = options.pause_hidden;
+    let extrapolate = options.extrapolate;
+    let present_mode = options.present_mode;
+    let aspect = options.aspect;
+    let mut limiter = options.fps_cap.map(FrameLimiter::new);
+    let mut measurement = options.measure.map(Measurement::new);
+    // frames drawn so far and when the last one was
+    let mut drawn = 0;
+    let mut last_drawn: Option<Instant> = None;
+    let mut show_timings = options.timings;
+    let views_path = options.views.clone();
+    let mut views = Views::load(&views_path).unwrap_or_else(|err| {
+        eprintln!("could not restore the view, {err}");
+        Views::default()
+    });
+    let mut modifiers = ModifiersState::empty();
+    let splits = std::mem::take(&mut options.panes);
+    let mut sims = iter::once(options)
+        .chain(splits)
+        .map(SimThread::spawn)
+        .collect::<Vec<_>>();
+    // the render passes go on the timeline of the first simulation
+    let trace = sims[0].trace();
+    let mut frame = None;
+    // of the simulations shown next to the first
+    let mut split_frames = vec![None; sims.len() - 1];
+    let mut cursor = None;
+    // in pixels, for picking
+    let mut pointer = None;
+    // with I, the particle under the cursor is shown in the title
+    let mut inspecting = false;
+    let mut inspected = None;
+    // painted while a mouse button is held
+    let mut brush: Option<Brush> = None;
+
+    // with Space or by rewinding, the simulation is also paused while hidden
+    // with --pause-hidden
+    let mut held = false;
+    let mut paused = false;
+    let mut plots = Plots::new(PLOT_WINDOW);
+    plots.frame = theme.overlay;
+    let mut plot_vertices = vec![];
+    // of the last frame drawn, shown with the next
+    let mut timings = FrameTimings::default();
+
+    let mut state = render::RenderState::new(window.clone()).await;
+    state.set_panes(sims.len());
+    if let Some(view) = views.current {
+        state.camera.set_bounds(view);
+    }
+    state.set_oit(oit);
+    state.set_sort_key(sort_by);
+    state.set_render_params(render_params);
+    state.set_aspect_mode(aspect);
+    if let Some(path) = background_image {
+        let loaded = File::open(&path)
+            .and_then(|file| png::read_png(BufReader::new(file)))
+            .map_err(|err| err.to_string())
+            .and_then(|image| {
+                // as wide as the domain, keeping the aspect ratio
+                let height = image.height as f32 / image.width as f32;
+                let (min, max) = background_rect.unwrap_or(([0.0, 0.0], [1.0, height]));
+                state.set_background_image(&image, min, max)
+            });
+        if let Err(err) = loaded {
+            eprintln!("could not show background image {}: {err}", path.display());
+        }
+    }
+    state.set_theme(&theme);
+    let mode = match present_mode {
+        PresentMode::Vsync => wgpu::PresentMode::Fifo,
+        PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    };
+    if !state.context.set_present_mode(mode) {
+        eprintln!("present mode {present_mode:?} is not supported here, using vsync");
+    }
+    let mut debug = debug_window.map(|window| {
+        let mut debug = state.for_window(window);
+        debug.set_theme(&theme);
+        debug.set_aspect_mode(aspect);
+        debug
+    });
+
+    event_loop
+        .run(|event, elwt| match event {
+            Event::Suspended => {
+                state.context.suspend();
+                if let Some(debug) = &mut debug {
+                    debug.context.suspend();
+                }
+            }
+            Event::LoopExiting => {
+                if let Some(measurement) = &measurement {
+                    match measurement.summary() {
+                        Some(summary) => println!("frames: {summary}"),
+                        None => println!(
+                            "frames: none measured after {} warmup frames",
+                            measurement.warmup
+                        ),
+                    }
+                }
+            }
+            Event::Resumed => {
+                state.context.resume();
+                if let Some(debug) = &mut debug {
+                    debug.context.resume();
+                }
+            }
+            Event::AboutToWait => {
+                for sim in &mut sims {
+                    if let Err(err) = sim.check() {
+                        eprintln!("{err}");
+                        elwt.exit();
+                    }
+                }
+                let pause = held || (pause_hidden && state.context.is_hidden());
+                if paused != pause {
+                    paused = pause;
+                    send(&sims, Command::SetPaused(paused));
+                }
+                let now = Instant::now();
+                if let Some(next) = limiter.as_ref().and_then(FrameLimiter::next) {
+                    if next > now {
+                        elwt.set_control_flow(ControlFlow::WaitUntil(next));
+                        return;
+                    }
+                }
+                let mut hidden = true;
+                for state in iter::once(&state).chain(&debug) {
+                    if !state.context.is_hidden() {
+                        if let Some(window) = state.context.window() {
+                            window.request_redraw();
+                        }
+                        hidden = false;
+                    }
+                }
+                if let Some(limiter) = &mut limiter {
+                    limiter.due(now);
+                }
+                // nothing to draw, but the simulation thread still needs checking on
+                elwt.set_control_flow(match hidden {
+                    true => ControlFlow::WaitUntil(now + HIDDEN_POLL),
+                    false => ControlFlow::Wait,
+                });
+            }
+            Event::WindowEvent { event, window_id }
+                if debug
+                    .as_ref()
+                    .is_some_and(|d| d.context.window_id == Some(window_id)) =>
+            {
+                let Some(state) = &mut debug else {
+                    return;
+                };
+                match event {
+                    WindowEvent::CloseRequested => debug = None,
+                    WindowEvent::Occluded(occluded) => state.context.occluded = occluded,
+                    WindowEvent::Resized(physical_size) => state.context.resize(physical_size),
+                    WindowEvent::RedrawRequested => {
+                        state.update();
+                        present(state, elwt);
+                    }
+                    _ => (),
+                }
+            }
+            Event::WindowEvent { event, window_id }
+                if Some(window_id) == state.context.window_id =>
+            {
+                if state.input(&event) {
+                    return;
+                }
+
+                match event {
+                    WindowEvent::CloseRequested => {
+                        views.current = Some(state.camera.bounds());
+                        save_views(&views, &views_path);
+                        elwt.exit();
+                    }
+                    WindowEvent::Occluded(occluded) => state.context.occluded = occluded,
+                    WindowEvent::ModifiersChanged(new) => modifiers = new.state(),
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(code),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } if bookmark(code).is_some() => {
+                        let n = bookmark(code).unwrap_or_default();
+                        if modifiers.control_key() {
+                            views.set_bookmark(n, state.camera.bounds());
+                            save_views(&views, &views_path);
+                        } else if let Some(view) = views.bookmark(n) {
+                            state.camera.set_bounds(view);
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let pos = state.to_world(position);
+                        cursor = Some(pos);
+                        pointer = Some(position);
+                        if let Some(brush) = brush {
+                            send(&sims, brush.at(pos));
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: button_state,
+                        button,
+                        ..
+                    } => {
+                        let paint = match modifiers.shift_key() {
+                            true => Brush::Viscosity,
+                            false => Brush::Dye,
+                        };
+                        brush = match (button_state, button) {
+                            (ElementState::Pressed, MouseButton::Left) => Some(paint(true)),
+                            (ElementState::Pressed, MouseButton::Right) => Some(paint(false)),
+                            (ElementState::Released, _) => None,
+                            _ => brush,
+                        };
+                        if let (Some(pos), Some(brush)) = (cursor, brush) {
+                            send(&sims, brush.at(pos));
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key: Key::Character(key),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => match key.as_str() {
+                        "l" => send(&sims, Command::ToggleStreamlines),
+                        "v" => send(&sims, Command::ToggleVorticity),
+                        "h" => send(&sims, Command::ToggleHeatmap),
+                        "p" => send(&sims, Command::TogglePlots),
+                        "t" => show_timings = !show_timings,
+                        "o" => {
+                            let mut params = state.render_params();
+                            params.set_discs(!params.discs());
+                            state.set_render_params(params);
+                        }
+                        "i" => {
+                            inspecting = !inspecting;
+                            inspected = None;
+                        }
+                        "," => {
+                            held = true;
+                            send(&sims, Command::Rewind(1));
+                        }
+                        "." if held => send(&sims, Command::StepForward),
+                        _ => (),
+                    },
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key: Key::Named(key),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => match key {
+                        NamedKey::Space => held = !held,
+                        NamedKey::Backspace => {
+                            held = true;
+                            send(&sims, Command::Rewind(REWIND_STEPS));
+                        }
+                        _ => (),
+                    },
+                    WindowEvent::Resized(physical_size) => {
+                        state.context.resize(physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        let new_size = winit::dpi::PhysicalSize {
+                            width: (state.context.config.width as f64 * scale_factor) as u32,
+                            height: (state.context.config.height as f64 * scale_factor) as u32,
+                        };
+                        state.context.resize(new_size);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        let uploading = Instant::now();
+                        if let (Some(measurement), Some(last)) = (&mut measurement, last_drawn) {
+                            measurement.record(drawn, (uploading - last).as_secs_f32());
+                        }
+                        last_drawn = Some(uploading);
+                        drawn += 1;
+                        for (i, sim) in sims.iter().enumerate().skip(1) {
+                            if let Some(latest) = sim.latest() {
+                                upload(state.pane_mut(i), &latest);
+                                split_frames[i - 1] = Some(latest);
+                            }
+                        }
+                        if let Some(latest) = sims[0].latest() {
+                            let mut title = format!(
+                                "pos-based-fluids | step {} | max speed {:.3} | energy {:.3}",
+                                latest.step, latest.stats.max_speed, latest.stats.kinetic_energy,
+                            );
+                            for (i, split) in split_frames.iter().flatten().enumerate() {
+                                title = format!(
+                                    "{title} | pane {}: step {} energy {:.3}",
+                                    i + 2,
+                                    split.step,
+                                    split.stats.kinetic_energy,
+                                );
+                            }
+                            if show_timings {
+                                title = format!("{title} | {}", timings.summary());
+                            }
+                            let particle = inspected
+                                .and_then(|i: u32| Some((i, latest.current.get(i as usize)?)));
+                            if let Some((i, p)) = particle {
+                                title = format!(
+                                    "{title} | particle {i} at {:.3},{:.3} moving {:.3},{:.3}",
+                                    p.pos[0], p.pos[1], p.vel[0], p.vel[1],
+                                );
+                            }
+                            window.set_title(&title);
+                            state.update_params(&latest.params);
+                            state.update_obstacles(&latest.blades);
+                            upload(state.pane_mut(0), &latest);
+                            if let (Some(debug), Some(speed)) = (&mut debug, &latest.speed) {
+                                let scale = speed.max_abs().max(1e-3);
+                                let colors = speed
+                                    .values
+                                    .iter()
+                                    .map(|v| sim::colormap(v / scale))
+                                    .collect::<Vec<_>>();
+                                debug.update_background(speed.resolution, &colors);
+                            }
+                            match &latest.metrics {
+                                Some(metrics) => {
+                                    plots.push(metrics);
+                                    plot_vertices = plots.vertices();
+                                }
+                                None => plot_vertices.clear(),
+                            }
+                            frame = Some(latest);
+                        }
+
+                        let now = Instant::now();
+                        let frames = iter::once(&frame).chain(&split_frames);
+                        for (i, frame) in frames.enumerate() {
+                            if let Some(frame) = frame {
+                                let instances = frame.instances_at(now, extrapolate);
+                                state.pane_mut(i).update_instances(&instances);
+                            }
+                        }
+                        state.update();
+                        let mut overlay = plot_vertices.clone();
+                        let inspected_pos = inspected
+                            .and_then(|i| Some(frame.as_ref()?.current.get(i as usize)?.pos));
+                        if let Some(pos) = inspected_pos {
+                            overlay.extend(marker(&state, pos, theme.overlay));
+                        }
+                        if show_timings {
+                            overlay.extend(timings.vertices(theme.overlay));
+                        }
+                        if let Some(histograms) = frame.as_ref().and_then(|f| f.histograms.as_ref())
+                        {
+                            overlay.extend(histograms.vertices(theme.overlay));
+                        }
+                        state.update_overlay(&overlay);
+                        timings.instances = uploading.elapsed().as_secs_f32();
+                        // particles move under a still cursor too
+                        if let Some(position) = pointer.filter(|_| inspecting) {
+                            state.pick(position);
+                        }
+                        present(&mut state, elwt);
+                        if let (Some(trace), Some((frame, spans))) =
+                            (&trace, state.take_gpu_spans())
+                        {
+                            let now = Instant::now();
+                            trace
+                                .lock()
+                                .unwrap()
+                                .add_device(Track::Render, &spans, frame, now);
+                        }
+                        if let Some(hit) = state.take_pick().filter(|_| inspecting) {
+                            inspected = hit;
+                        }
+                        timings.step = frame.as_ref().and_then(|frame| frame.timings);
+                        timings.render = state.gpu_time();
+                        timings.present = state.present_time();
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        })
+        .unwrap();
+}
+
+/// Sends `command` to every simulation, so they stay in step.
+fn send(sims: &[SimThread], command: Command) {
+    for sim in sims {
+        sim.send(command);
+    }
+}
+
+/// Uploads what a pane draws of a new `frame`, apart from the particles,
+/// which move on between frames.
+/// A square around the particle at `pos` for the overlay, a bit larger
+/// than the particle is drawn.
+fn marker(state: &render::RenderState, pos: [f32; 2], color: u32) -> Vec<sim::OverlayVertex> {
+    let r = 1.5 * state.params().particle_radius * state.render_params().radius_scale;
+    let corners = [[-r, -r], [r, -r], [r, r], [-r, r]]
+        .map(|[dx, dy]| state.world_to_overlay([pos[0] + dx, pos[1] + dy]));
+    (0..4)
+        .flat_map(|i| [corners[i], corners[(i + 1) % 4]])
+        .map(|pos| sim::OverlayVertex { pos, color })
+        .collect()
+}
+
+fn upload(pane: &mut render::Pane, frame: &Frame) {
+    pane.update_colors(&frame.colors);
+    pane.update_diffuse(&frame.diffuse);
+    pane.update_streamlines(&frame.streamlines);
+    match (&frame.heatmap, &frame.vorticity) {
+        (Some((heatmap, grid)), _) => {
+            // spikes in full color, never amplifying the slight
+            // compression of fluid at rest
+            let scale = grid.max_abs().max(0.2);
+            let colors = grid
+                .values
+                .iter()
+                .map(|v| match heatmap {
+                    Heatmap::Pressure => sim::heat(v / scale),
+                    Heatmap::DensityError => sim::diverging(v / scale),
+                })
+                .collect::<Vec<_>>();
+            pane.update_background(grid.resolution, &colors);
+        }
+        (None, Some(vorticity)) => {
+            // strongest rotation in full color, never amplifying noise
+            let scale = vorticity.max_abs().max(1.0);
+            let colors = vorticity
+                .values
+                .iter()
+                .map(|w| sim::diverging(w / scale))
+                .collect::<Vec<_>>();
+            pane.update_background(vorticity.resolution, &colors);
+        }
+        (None, None) => pane.update_background(0, &[]),
+    }
+}
+
+/// What dragging the mouse paints, dye or with Shift the viscosity of syrup,
+/// and whether it puts it on or takes it off.
+#[derive(Debug, Clone, Copy)]
+enum Brush {
+    Dye(bool),
+    Viscosity(bool),
+}
+
+impl Brush {
+    fn at(self, pos: [f32; 2]) -> Command {
+        let radius = dye::BRUSH_RADIUS;
+        match self {
+            Brush::Dye(on) => Command::Paint {
+                pos,
+                radius,
+                value: if on { 1.0 } else { 0.0 },
+            },
+            Brush::Viscosity(on) => Command::PaintViscosity {
+                pos,
+                radius,
+                viscosity: match on {
+                    true => Phase::SYRUP.viscosity.unwrap_or_default(),
+                    false => 0.0,
+                },
+            },
+        }
+    }
+}
+
+/// The number of the bookmark a digit key stands for.
+fn bookmark(code: KeyCode) -> Option<usize> {
+    Some(match code {
+        KeyCode::Digit1 => 1,
+        KeyCode::Digit2 => 2,
+        KeyCode::Digit3 => 3,
+        KeyCode::Digit4 => 4,
+        KeyCode::Digit5 => 5,
+        KeyCode::Digit6 => 6,
+        KeyCode::Digit7 => 7,
+        KeyCode::Digit8 => 8,
+        KeyCode::Digit9 => 9,
+        _ => return None,
+    })
+}
+
+fn save_views(views: &Views, path: &std::path::Path) {
+    if let Err(err) = views.save(path) {
+        eprintln!("could not save the view to {}: {err}", path.display());
+    }
+}
+
+/// Renders a window, reconfiguring its surface when it went stale.
+fn present(state: &mut render::RenderState, elwt: &EventLoopWindowTarget<()>) {
+    match state.render() {
+        Ok(()) => {}
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            state.context.resize(state.context.size())
+        }
+        Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+        Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
+    }
+}
@@ -0,0 +1,264 @@
+use crate::age::Ages;
+use crate::cpu::CpuState;
+use crate::histogram::Histogram;
+use crate::ids::ParticleIds;
+#[cfg(feature = "opencl")]
+use crate::opencl::OpenClState;
+use crate::scene::{Scene, SceneEdit};
+use crate::sim::Instance;
+use crate::stats::ParticleStats;
+use crate::trace::DeviceSpan;
+use crate::verify::GridCells;
+use crate::wcsph::{WcsphParams, WcsphState};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Tuning knobs shared by all backends. Backends ignore what doesn't apply to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Up to this many particles, the OpenCL backend builds the grid and
+    /// resolves collisions in a single fused kernel launch.
+    pub fused_threshold: usize,
+    /// Particles that end [`sleep_after`](Self::sleep_after) steps in a row
+    /// slower than this fall asleep: they stop moving and skip collisions
+    /// with other sleeping particles until a collision with an awake one
+    /// speeds them up again. The speed is the one a step leaves, before the
+    /// next adds gravity and the other forces, so resting fluid counts as
+    /// slow however strong gravity is.
+    pub sleep_speed: f32,
+    /// Steps until a slow particle falls asleep, 0 (the default) disables
+    /// sleeping.
+    pub sleep_after: u32,
+    /// Kinematic [viscosity](crate::viscosity) of the fluid, for the backends
+    /// that solve for it.
+    pub viscosity: f32,
+    /// The viscosity of every particle is divided by `e` every
+    /// `1 / viscosity_thinning` degrees above ambient, 0 for none.
+    pub viscosity_thinning: f32,
+    /// Every particle is slowed by [drag](crate::stability::drag) of this
+    /// much per unit of velocity, 0 for none.
+    pub linear_drag: f32,
+    /// On top of the linear drag, this much per unit of velocity and speed,
+    /// slowing fast particles more.
+    pub quadratic_drag: f32,
+    /// Velocities are [clamped](crate::stability) to this speed.
+    pub max_speed: f32,
+    /// Velocities are clamped to move particles at most this far per step.
+    pub max_displacement: f32,
+    /// Shrink the step while clamping keeps triggering, see
+    /// [`Brake`](crate::stability::Brake). Only the cpu backend does.
+    pub brake: bool,
+    /// Copy the grid into images after sorting, so the collision kernel reads
+    /// it through the texture cache. Only the OpenCL backend does, on devices
+    /// with image support.
+    pub grid_images: bool,
+    /// Keep the particles in double precision as well, see
+    /// [`crate::precision`]. The OpenCL backend needs `cl_khr_fp64` for it.
+    pub double_precision: bool,
+    /// Give the same particles the same state on every run, whatever order
+    /// the device got to them in. The cpu and wcsph backends always do, the
+    /// OpenCL backend orders the ids of every grid cell after sorting and
+    /// skips the fused kernel.
+    pub deterministic: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            fused_threshold: 1024,
+            sleep_speed: 1e-3,
+            sleep_after: 0,
+            viscosity: 0.0,
+            viscosity_thinning: 0.0,
+            linear_drag: 0.0,
+            quadratic_drag: 0.0,
+            max_speed: f32::INFINITY,
+            max_displacement: f32::INFINITY,
+            brake: false,
+            grid_images: false,
+            double_precision: false,
+            deterministic: false,
+        }
+    }
+}
+
+/// Where the time of a step went, in seconds of device time for backends
+/// that run on one, see [`Backend::timings`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepTimings {
+    /// Copying the particles and everything else that changed to the device.
+    pub upload: f32,
+    /// Applying forces and boundaries and moving the particles.
+    pub integrate: f32,
+    /// Sorting the particles into the grid.
+    pub grid: f32,
+    /// Resolving collisions.
+    pub solve: f32,
+    /// Copying the results back from the device.
+    pub readback: f32,
+    /// What the device kernels launch with, for backends that choose it.
+    pub work_groups: Option<WorkGroupSizes>,
+}
+
+/// Work items per work group of each per-particle kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkGroupSizes {
+    pub integrate: usize,
+    pub sort: usize,
+    pub collide: usize,
+    /// Sorting and colliding in one launch instead, when set.
+    pub fused: Option<usize>,
+}
+
+impl StepTimings {
+    pub fn total(&self) -> f32 {
+        self.upload + self.integrate + self.grid + self.solve + self.readback
+    }
+
+    /// Adds `other` on, for averaging over several steps.
+    pub fn add(&mut self, other: &StepTimings) {
+        self.upload += other.upload;
+        self.integrate += other.integrate;
+        self.grid += other.grid;
+        self.solve += other.solve;
+        self.readback += other.readback;
+        self.work_groups = self.work_groups.or(other.work_groups);
+    }
+
+    pub fn scale(&mut self, factor: f32) {
+        self.upload *= factor;
+        self.integrate *= factor;
+        self.grid *= factor;
+        self.solve *= factor;
+        self.readback *= factor;
+    }
+}
+
+/// A simulation implementation that can advance the particle state.
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    /// What the backend runs on, for backends that run on a device.
+    fn device(&self) -> Option<String> {
+        None
+    }
+
+    /// Advances the simulation by one step. Once this returns and
+    /// [`read_complete`](Backend::read_complete) says so,
+    /// [`particles`](Backend::particles) reflects the new state.
+    fn step(&mut self) -> Result<(), Error>;
+
+    /// The particles as of the last step that arrived on the host.
+    fn particles(&self) -> &[Instance];
+
+    /// Whether [`particles`](Backend::particles) caught up with the last
+    /// step, taking the new state in if it arrived. Never blocks. Backends
+    /// that read the particles back from a device while the host goes on
+    /// return `false` until the read is done.
+    fn read_complete(&mut self) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Blocks until [`particles`](Backend::particles) caught up with the
+    /// last step.
+    fn wait_read(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Temperature per particle relative to ambient, see [`crate::thermal`].
+    fn temperatures(&self) -> &[f32];
+
+    fn ages(&self) -> &Ages;
+
+    /// Stable identities of the particles, see [`crate::ids`].
+    fn ids(&self) -> &ParticleIds;
+
+    /// Changes the scene while running, from the next step on.
+    fn edit(&mut self, edit: &SceneEdit) -> Result<(), Error>;
+
+    /// Goes back to `particles` at `time`, as taken from
+    /// [`particles`](Backend::particles) earlier, see [`crate::rewind`].
+    /// Sleeping particles wake up.
+    fn restore(&mut self, particles: &[Instance], time: f32) -> Result<(), Error> {
+        let _ = (particles, time);
+        Err(format!("the {} backend can't rewind", self.name()).into())
+    }
+
+    /// How long the parts of the last step took, for backends that keep
+    /// track.
+    fn timings(&self) -> Option<StepTimings> {
+        None
+    }
+
+    /// The commands of the last step on the device, for backends that
+    /// profile them, see [`crate::trace`].
+    fn device_spans(&self) -> &[DeviceSpan] {
+        &[]
+    }
+
+    /// Reductions over the current state. Backends that compute these on the
+    /// device may return results that are a few steps old.
+    fn stats(&mut self) -> Result<ParticleStats, Error> {
+        Ok(ParticleStats::from_particles(self.particles()))
+    }
+
+    /// The grid the last step collided the particles with, for backends
+    /// that keep it, see [`crate::verify`].
+    fn grid_cells(&self) -> Option<GridCells<'_>> {
+        None
+    }
+
+    /// Speeds of the particles, from zero to about the fastest. Like the
+    /// [`stats`](Backend::stats), may be a few steps old.
+    fn speed_histogram(&mut self) -> Result<Histogram, Error> {
+        Ok(Histogram::speeds(self.particles()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    OpenCl,
+    Cpu,
+    /// The CPU backend with [weakly compressible SPH](crate::wcsph) instead
+    /// of particle collisions.
+    Wcsph,
+}
+
+impl BackendKind {
+    /// Falls back to the [CPU backend](crate::cpu) when OpenCL isn't
+    /// available, saying so on stderr.
+    pub fn create(self, scene: &Scene, config: &Config) -> Result<Box<dyn Backend>, Error> {
+        Ok(match self {
+            #[cfg(feature = "opencl")]
+            BackendKind::OpenCl => match OpenClState::new(scene, config) {
+                Ok(state) => Box::new(state),
+                Err(err) => {
+                    eprintln!("could not start OpenCL ({err}), falling back to the cpu backend");
+                    Box::new(CpuState::new(scene, config))
+                }
+            },
+            #[cfg(not(feature = "opencl"))]
+            BackendKind::OpenCl => {
+                eprintln!("built without the `opencl` feature, using the cpu backend");
+                Box::new(CpuState::new(scene, config))
+            }
+            BackendKind::Cpu => Box::new(CpuState::new(scene, config)),
+            BackendKind::Wcsph => Box::new(WcsphState::new(scene, config, WcsphParams::default())),
+        })
+    }
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "opencl" | "cl" => Ok(BackendKind::OpenCl),
+            "cpu" => Ok(BackendKind::Cpu),
+            "wcsph" => Ok(BackendKind::Wcsph),
+            _ => Err(format!(
+                "unknown backend `{s}`, expected `opencl`, `cpu` or `wcsph`"
+            )),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+struct CameraUniform {
+    transform: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var background: texture_2d<f32>;
+@group(1) @binding(1)
+var background_sampler: sampler;
+// min and max corner in world space
+@group(1) @binding(2)
+var<uniform> rect: vec4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// stretches the texture over `rect`, the first texture row is at the bottom
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.uv = model.position * 0.5 + 0.5;
+    let pos = mix(rect.xy, rect.zw, out.uv);
+    out.position = camera.transform * vec4<f32>(pos, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(background, background_sampler, in.uv);
+}
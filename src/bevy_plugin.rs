@@ -0,0 +1,160 @@
+//! Embedding the fluid in a [Bevy](https://bevyengine.org) app, with the
+//! `bevy` feature.
+//!
+//! [`FluidPlugin`] starts the simulation the [`Options`] describe and ticks it
+//! in `Update` by Bevy's frame time, like [`TickedSimulation`] does for any
+//! host loop. Every particle is an entity with a [`FluidParticle`] and a
+//! `Transform` that follows it, the unit square of the domain scaled by
+//! [`FluidPlugin::scale`]. The plugin draws nothing itself: give the entities
+//! a mesh or sprite as they are `Added`, or fill an instance buffer from
+//! [`FluidParticles`] to draw them all with one instanced mesh.
+//!
+//! The backends hold OpenCL handles, so the simulation is a non-send resource,
+//! [`Fluid`], and steps on the main thread.
+
+use crate::boundary;
+use crate::options::Options;
+use crate::sim::Instance;
+use crate::simulation::{Simulation, TickedSimulation};
+use crate::timestep;
+use bevy::prelude::*;
+
+pub struct FluidPlugin {
+    pub options: Options,
+    /// World units per unit of the domain.
+    pub scale: f32,
+}
+
+impl FluidPlugin {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+/// The systems that step the fluid and move the particle entities, to order
+/// systems reading them after.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FluidSet;
+
+/// The simulation and the particles as of the step before the last, to
+/// interpolate between.
+pub struct Fluid {
+    pub sim: TickedSimulation,
+    previous: Vec<Instance>,
+    /// Set once a step fails, after which the fluid stands still.
+    failed: bool,
+}
+
+/// A particle of the fluid, by its index in [`FluidParticles`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FluidParticle {
+    pub index: usize,
+    /// In world units per second.
+    pub vel: Vec2,
+    /// Whether the particle is [removed](boundary::REMOVED) at the moment,
+    /// its transform stays where it was last.
+    pub removed: bool,
+}
+
+/// The particles interpolated to the frame, in world units.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct FluidParticles {
+    pub instances: Vec<Instance>,
+}
+
+/// [`FluidPlugin::scale`], for the systems.
+#[derive(Resource, Debug, Clone, Copy)]
+struct FluidScale(f32);
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        let sim = self
+            .options
+            .scene()
+            .and_then(|scene| Simulation::new(&scene, &self.options).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| panic!("could not start the fluid: {err}"));
+        let previous = sim.particles().to_vec();
+        app.insert_non_send_resource(Fluid {
+            sim: TickedSimulation::new(sim),
+            previous,
+            failed: false,
+        })
+        .insert_resource(FluidScale(self.scale))
+        .init_resource::<FluidParticles>()
+        .add_systems(Startup, spawn_particles)
+        .add_systems(
+            Update,
+            (step_fluid, move_particles).chain().in_set(FluidSet),
+        );
+    }
+}
+
+fn spawn_particles(mut commands: Commands, fluid: NonSend<Fluid>) {
+    let count = fluid.sim.sim.particles().len();
+    commands.spawn_batch((0..count).map(|index| {
+        (
+            FluidParticle {
+                index,
+                vel: Vec2::ZERO,
+                removed: true,
+            },
+            TransformBundle::default(),
+        )
+    }));
+}
+
+fn step_fluid(
+    mut fluid: NonSendMut<Fluid>,
+    mut particles: ResMut<FluidParticles>,
+    scale: Res<FluidScale>,
+    time: Res<Time>,
+) {
+    let fluid = &mut *fluid;
+    if !fluid.failed {
+        let before = fluid.sim.sim.particles().to_vec();
+        match fluid.sim.tick(time.delta_seconds()) {
+            // a tick of several steps is interpolated across as a whole
+            Ok(advanced) if advanced > 0.0 => fluid.previous = before,
+            Ok(_) => {}
+            Err(err) => {
+                log::error!("the fluid stopped: {err}");
+                fluid.failed = true;
+            }
+        }
+    }
+
+    let current = fluid.sim.sim.particles();
+    particles.instances = timestep::interpolate(&fluid.previous, current, fluid.sim.alpha());
+    for p in &mut particles.instances {
+        if !boundary::is_removed(p) {
+            p.pos = p.pos.map(|x| x * scale.0);
+            p.vel = p.vel.map(|v| v * scale.0);
+        }
+    }
+}
+
+fn move_particles(
+    particles: Res<FluidParticles>,
+    mut query: Query<(&mut FluidParticle, &mut Transform)>,
+) {
+    for (mut particle, mut transform) in &mut query {
+        let Some(p) = particles.instances.get(particle.index) else {
+            continue;
+        };
+        particle.removed = boundary::is_removed(p);
+        if particle.removed {
+            continue;
+        }
+        particle.vel = Vec2::from(p.vel);
+        transform.translation.x = p.pos[0];
+        transform.translation.y = p.pos[1];
+    }
+}
@@ -0,0 +1,483 @@
+//! Times the kernels of `sorting.ocl` that go over the particles, each on its
+//! own, over a sweep of particle counts and grid sizes, so changes to them can
+//! be measured without the rest of a step in the way.
+//!
+//! usage: bench [--counts <n,...>] [--cells <n,...>] [--runs <n>]
+//!
+//! The particles are scattered over the whole domain with random velocities,
+//! with a radius of half a cell. Every kernel runs once to warm up and then
+//! `--runs` times, timed on the device, with whatever it needs done first
+//! (clearing and sorting the grid) left out of the timing. The bandwidth
+//! counts the bytes a kernel has to read or write at least once, see
+//! [`Kernel::bytes`]: a lower bound that compares well between changes, not
+//! what went over the memory bus.
+
+use opencl3 as cl;
+use opencl3::{command_queue, context, device, kernel, memory, program, types};
+use pos_based_fluids::backend::Config;
+use pos_based_fluids::capabilities::{DeviceCaps, Svm, Variants};
+use pos_based_fluids::grid::Grid;
+use pos_based_fluids::histogram;
+use pos_based_fluids::rng::Rng;
+use pos_based_fluids::sim::{Instance, SimParams};
+use pos_based_fluids::stats::ParticleStats;
+use pos_based_fluids::{MAX_PARTICLES_PER_CELL, PROGRAM_SOURCE};
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::ptr;
+
+const USAGE: &str = "usage: bench [--counts <n,...>] [--cells <n,...>] [--runs <n>]";
+
+struct Args {
+    counts: Vec<usize>,
+    /// Cells along each side of the grid.
+    cells: Vec<u32>,
+    runs: u32,
+}
+
+fn list<T: std::str::FromStr>(value: Option<String>, name: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .ok_or(USAGE)?
+        .split(',')
+        .map(|n| {
+            n.parse()
+                .map_err(|err| format!("invalid {name} `{n}`: {err}"))
+        })
+        .collect()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut parsed = Args {
+        counts: vec![1 << 14, 1 << 16, 1 << 18, 1 << 20],
+        cells: vec![64, 256, 1024],
+        runs: 20,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--counts" => parsed.counts = list(args.next(), "--counts")?,
+            "--cells" => parsed.cells = list(args.next(), "--cells")?,
+            "--runs" => {
+                parsed.runs = args
+                    .next()
+                    .ok_or(USAGE)?
+                    .parse()
+                    .map_err(|err| format!("invalid --runs: {err}"))?
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unexpected argument `{arg}`\n{USAGE}")),
+        }
+    }
+    if parsed.counts.contains(&0) || parsed.cells.contains(&0) || parsed.runs == 0 {
+        return Err("counts, cells and runs have to be positive".into());
+    }
+    Ok(parsed)
+}
+
+/// The kernels measured, in the order of a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kernel {
+    Integrate,
+    Sort,
+    Order,
+    Collide,
+    Fused,
+    Reduce,
+    Histogram,
+}
+
+impl Kernel {
+    const ALL: [Kernel; 7] = [
+        Kernel::Integrate,
+        Kernel::Sort,
+        Kernel::Order,
+        Kernel::Collide,
+        Kernel::Fused,
+        Kernel::Reduce,
+        Kernel::Histogram,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Kernel::Integrate => "integrate_particles",
+            Kernel::Sort => "sort_particles",
+            Kernel::Order => "order_cells",
+            Kernel::Collide => "collide_particles",
+            Kernel::Fused => "sort_and_collide_particles",
+            Kernel::Reduce => "reduce_particles",
+            Kernel::Histogram => "histogram_speeds",
+        }
+    }
+
+    /// Bytes the kernel reads or writes at least once for `particles` in a
+    /// grid of `cells` squared: the particles, the per-particle state it
+    /// keeps and the grid, but not the neighbors read again from cache.
+    fn bytes(self, particles: usize, cells: usize) -> usize {
+        let particle = size_of::<Instance>();
+        let grid = cells * cells * (1 + MAX_PARTICLES_PER_CELL) * size_of::<u32>();
+        match self {
+            // the particle both ways, the quiet steps both ways, the temperature
+            Kernel::Integrate => particles * (2 * particle + 3 * size_of::<u32>()),
+            // the particle, its count both ways and its id
+            Kernel::Sort => particles * (particle + 3 * size_of::<u32>()),
+            // the grid both ways, the particles to order by
+            Kernel::Order => 2 * grid + particles * particle,
+            // the particle both ways, its quiet steps, the grid
+            Kernel::Collide => particles * (2 * particle + size_of::<u32>()) + grid,
+            // the grid stays in local memory
+            Kernel::Fused => particles * (2 * particle + size_of::<u32>()),
+            Kernel::Reduce | Kernel::Histogram => particles * particle,
+        }
+    }
+}
+
+/// The program built for the first device, like the OpenCL backend does.
+struct Device {
+    device: device::Device,
+    context: context::Context,
+    queue: command_queue::CommandQueue,
+    program: program::Program,
+}
+
+impl Device {
+    fn new() -> Result<Self, String> {
+        let device_id = [device::CL_DEVICE_TYPE_GPU, device::CL_DEVICE_TYPE_ALL]
+            .into_iter()
+            .find_map(|ty| device::get_all_devices(ty).ok()?.into_iter().next())
+            .ok_or("no OpenCL device found")?;
+        let device = device::Device::new(device_id);
+        let context = context::Context::from_device(&device).map_err(|err| err.to_string())?;
+        let queue = command_queue::CommandQueue::create_default_with_properties(
+            &context,
+            command_queue::CL_QUEUE_PROFILING_ENABLE,
+            0,
+        )
+        .map_err(|err| err.to_string())?;
+        let caps = DeviceCaps::new(
+            &device.extensions().map_err(|err| err.to_string())?,
+            device.local_mem_size().map_err(|err| err.to_string())?,
+            Svm::from_bits(device.svm_mem_capability()),
+        );
+        // with the fused kernel, whether it fits or not is up to the grid
+        let options = Variants::select(&caps, Some(0)).build_options();
+        let program =
+            program::Program::create_and_build_from_source(&context, PROGRAM_SOURCE, &options)
+                .map_err(|log| format!("could not build the kernels:\n{log}"))?;
+        println!(
+            "{} with `{options}`",
+            device.name().map_err(|err| err.to_string())?
+        );
+        Ok(Self {
+            device,
+            context,
+            queue,
+            program,
+        })
+    }
+
+    fn buffer<T>(&self, data: &[T]) -> cl::Result<memory::Buffer<T>> {
+        unsafe {
+            memory::Buffer::<T>::create(
+                &self.context,
+                memory::CL_MEM_READ_WRITE | memory::CL_MEM_COPY_HOST_PTR,
+                data.len(),
+                data.as_ptr() as *mut c_void,
+            )
+        }
+    }
+
+    fn kernel(&self, name: &str) -> cl::Result<kernel::Kernel> {
+        kernel::Kernel::create(&self.program, name)
+    }
+}
+
+/// Everything the kernels run on for one particle count and grid size.
+struct Bench<'a> {
+    cl: &'a Device,
+    runs: u32,
+    initial: Vec<Instance>,
+    cells: u32,
+    particles: memory::Buffer<Instance>,
+    params: memory::Buffer<SimParams>,
+    counts: memory::Buffer<u32>,
+    ids: memory::Buffer<i32>,
+    quiet: memory::Buffer<u32>,
+    temperatures: memory::Buffer<f32>,
+    /// Bound to the arguments of `integrate_particles` for features that are
+    /// off, which never read it.
+    unused: memory::Buffer<u32>,
+}
+
+impl<'a> Bench<'a> {
+    fn new(cl: &'a Device, runs: u32, count: usize, cells: u32) -> cl::Result<Self> {
+        let mut rng = Rng::new(count as u64 ^ cells as u64);
+        let initial = (0..count)
+            .map(|_| Instance {
+                pos: [rng.next_f32(), rng.next_f32()],
+                vel: [rng.range(-1.0, 1.0), rng.range(-1.0, 1.0)],
+            })
+            .collect::<Vec<_>>();
+        let grid = Grid::with_cells(cells);
+        let params = SimParams {
+            particle_radius: 0.5 * grid.cell_size(),
+            ..SimParams::new(&grid, &Config::default(), count)
+        };
+        let cell_count = grid.cell_count();
+        Ok(Self {
+            cl,
+            runs,
+            cells,
+            particles: cl.buffer(&initial)?,
+            params: cl.buffer(&[params])?,
+            counts: cl.buffer(&vec![0u32; cell_count])?,
+            ids: cl.buffer(&vec![-1i32; cell_count * MAX_PARTICLES_PER_CELL])?,
+            quiet: cl.buffer(&vec![0u32; count])?,
+            temperatures: cl.buffer(&vec![0.0f32; count])?,
+            unused: cl.buffer(&[0u32; 64])?,
+            initial,
+        })
+    }
+
+    /// Puts the particles back where they started.
+    fn reset(&mut self) -> cl::Result<()> {
+        unsafe {
+            self.cl.queue.enqueue_write_buffer(
+                &mut self.particles,
+                types::CL_BLOCKING,
+                0,
+                &self.initial,
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn clear_grid(&mut self) -> cl::Result<()> {
+        let cells = self.cells as usize * self.cells as usize;
+        unsafe {
+            self.cl.queue.enqueue_fill_buffer(
+                &mut self.counts,
+                &[0],
+                0,
+                cells * size_of::<u32>(),
+                &[],
+            )?;
+            self.cl.queue.enqueue_fill_buffer(
+                &mut self.ids,
+                &[-1],
+                0,
+                cells * MAX_PARTICLES_PER_CELL * size_of::<i32>(),
+                &[],
+            )?;
+        }
+        self.cl.queue.finish()
+    }
+
+    fn sort(&mut self, kernel: &kernel::Kernel) -> cl::Result<()> {
+        self.clear_grid()?;
+        self.enqueue(kernel, self.initial.len(), None)?.wait()
+    }
+
+    fn enqueue(
+        &self,
+        kernel: &kernel::Kernel,
+        global: usize,
+        local: Option<usize>,
+    ) -> cl::Result<cl::event::Event> {
+        unsafe {
+            self.cl.queue.enqueue_nd_range_kernel(
+                kernel.get(),
+                1,
+                ptr::null(),
+                &global,
+                local.as_ref().map_or(ptr::null(), |local| local),
+                &[],
+            )
+        }
+    }
+
+    /// Mean seconds of `kernel` over `global` work items, after `prepare`
+    /// every time.
+    fn time(
+        &mut self,
+        kernel: &kernel::Kernel,
+        global: usize,
+        local: Option<usize>,
+        mut prepare: impl FnMut(&mut Self) -> cl::Result<()>,
+    ) -> cl::Result<f64> {
+        self.reset()?;
+        let mut nanos = 0;
+        for run in 0..=self.runs {
+            prepare(self)?;
+            let event = self.enqueue(kernel, global, local)?;
+            event.wait()?;
+            // the first run warms up
+            if run > 0 {
+                nanos += event.profiling_command_end()? - event.profiling_command_start()?;
+            }
+        }
+        Ok(nanos as f64 * 1e-9 / self.runs as f64)
+    }
+
+    /// Seconds of a run of `which`, `None` if it can't run on this grid.
+    fn measure(&mut self, which: Kernel) -> cl::Result<Option<f64>> {
+        let count = self.initial.len();
+        let kernel = self.cl.kernel(which.name())?;
+        let seconds = unsafe {
+            match which {
+                Kernel::Integrate => {
+                    let zero = [0.0f32; 4];
+                    kernel.set_arg(0, &self.particles)?;
+                    kernel.set_arg(1, &self.params)?;
+                    kernel.set_arg(2, &self.unused)?;
+                    kernel.set_arg(3, &0u32)?;
+                    kernel.set_arg(4, &0u32)?;
+                    kernel.set_arg(5, &self.quiet)?;
+                    kernel.set_arg(6, &0u32)?;
+                    kernel.set_arg(7, &zero)?;
+                    kernel.set_arg(8, &zero)?;
+                    kernel.set_arg(9, &self.temperatures)?;
+                    kernel.set_arg(10, &0.0f32)?;
+                    kernel.set_arg(11, &self.unused)?;
+                    kernel.set_arg(12, &0u32)?;
+                    kernel.set_arg(13, &[0.0f32, -9.81])?;
+                    kernel.set_arg(14, &self.unused)?;
+                    kernel.set_arg(15, &0u32)?;
+                    kernel.set_arg(16, &0u32)?;
+                    kernel.set_arg(17, &0u32)?;
+                    kernel.set_arg(18, &zero)?;
+                    kernel.set_arg(19, &zero)?;
+                    kernel.set_arg(20, &self.unused)?;
+                    kernel.set_arg(21, &0u32)?;
+                    kernel.set_arg(22, &self.unused)?;
+                    kernel.set_arg(23, &self.unused)?;
+                    kernel.set_arg(24, &0u32)?;
+                    kernel.set_arg(25, &zero)?;
+                    kernel.set_arg(26, &zero)?;
+                    kernel.set_arg(27, &self.unused)?;
+                    kernel.set_arg(28, &self.unused)?;
+                    kernel.set_arg(29, &0u32)?;
+                    kernel.set_arg(30, &0.0f32)?;
+                    self.time(&kernel, count, None, |_| Ok(()))?
+                }
+                Kernel::Sort => {
+                    self.bind_grid(&kernel)?;
+                    self.time(&kernel, count, None, Self::clear_grid)?
+                }
+                Kernel::Order => {
+                    let sort = self.cl.kernel(Kernel::Sort.name())?;
+                    self.bind_grid(&sort)?;
+                    self.bind_grid(&kernel)?;
+                    let cells = self.cells as usize * self.cells as usize;
+                    self.time(&kernel, cells, None, |bench| bench.sort(&sort))?
+                }
+                Kernel::Collide => {
+                    let sort = self.cl.kernel(Kernel::Sort.name())?;
+                    self.bind_grid(&sort)?;
+                    self.bind_grid(&kernel)?;
+                    kernel.set_arg(4, &self.quiet)?;
+                    self.time(&kernel, count, None, |bench| bench.sort(&sort))?
+                }
+                Kernel::Fused => {
+                    let cells = self.cells as usize * self.cells as usize;
+                    let local_mem = cells * (1 + MAX_PARTICLES_PER_CELL) * size_of::<u32>();
+                    if local_mem as u64 > self.cl.device.local_mem_size()? {
+                        return Ok(None);
+                    }
+                    let work_size = kernel.get_work_group_size(self.cl.device.id())?.min(count);
+                    kernel.set_arg(0, &self.particles)?;
+                    kernel.set_arg_local_buffer(1, cells * size_of::<u32>())?;
+                    kernel.set_arg_local_buffer(
+                        2,
+                        cells * MAX_PARTICLES_PER_CELL * size_of::<i32>(),
+                    )?;
+                    kernel.set_arg(3, &self.params)?;
+                    kernel.set_arg(4, &self.quiet)?;
+                    self.time(&kernel, work_size, Some(work_size), |_| Ok(()))?
+                }
+                Kernel::Reduce => {
+                    let work_size = 1
+                        << kernel
+                            .get_work_group_size(self.cl.device.id())?
+                            .min(256)
+                            .ilog2();
+                    let groups = count.div_ceil(work_size).clamp(1, 64);
+                    let partials = self.cl.buffer(&vec![ParticleStats::default(); groups])?;
+                    kernel.set_arg(0, &self.particles)?;
+                    kernel.set_arg(1, &(count as u32))?;
+                    kernel.set_arg(2, &partials)?;
+                    kernel.set_arg_local_buffer(3, work_size * size_of::<ParticleStats>())?;
+                    self.time(&kernel, work_size * groups, Some(work_size), |_| Ok(()))?
+                }
+                Kernel::Histogram => {
+                    let work_size = kernel.get_work_group_size(self.cl.device.id())?.min(256);
+                    let bins = self.cl.buffer(&[0u32; histogram::BINS])?;
+                    kernel.set_arg(0, &self.particles)?;
+                    kernel.set_arg(1, &(count as u32))?;
+                    kernel.set_arg(2, &2.0f32)?;
+                    kernel.set_arg(3, &bins)?;
+                    let global = work_size * count.div_ceil(work_size).min(64);
+                    self.time(&kernel, global, Some(work_size), |_| Ok(()))?
+                }
+            }
+        };
+        Ok(Some(seconds))
+    }
+
+    /// Binds the grid, the particles and the parameters, the first four
+    /// arguments of the kernels working on the grid.
+    fn bind_grid(&self, kernel: &kernel::Kernel) -> cl::Result<()> {
+        unsafe {
+            kernel.set_arg(0, &self.counts)?;
+            kernel.set_arg(1, &self.ids)?;
+            kernel.set_arg(2, &self.particles)?;
+            kernel.set_arg(3, &self.params)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    let cl = Device::new().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+
+    println!(
+        "{:<28} {:>9} {:>6} {:>10} {:>13} {:>8}",
+        "kernel", "particles", "cells", "ms", "Mparticles/s", "GB/s"
+    );
+    for &cells in &args.cells {
+        for &count in &args.counts {
+            let result = Bench::new(&cl, args.runs, count, cells).and_then(|mut bench| {
+                for kernel in Kernel::ALL {
+                    let Some(seconds) = bench.measure(kernel)? else {
+                        continue;
+                    };
+                    let bytes = kernel.bytes(count, cells as usize);
+                    println!(
+                        "{:<28} {:>9} {:>6} {:>10.4} {:>13.1} {:>8.2}",
+                        kernel.name(),
+                        count,
+                        cells,
+                        seconds * 1e3,
+                        count as f64 / seconds * 1e-6,
+                        bytes as f64 / seconds * 1e-9,
+                    );
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                eprintln!("{count} particles in {cells}x{cells} cells failed: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
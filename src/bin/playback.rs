@@ -0,0 +1,147 @@
+//! Plays back a particle recording made with `--record`, without a backend,
+//! so results can be looked at on machines without OpenCL.
+//!
+//! usage: playback <recording> [--speed <factor>]
+//!
+//! Space pauses, the recording loops at the end.
+
+use pos_based_fluids::recording::{Playback, RecordedFrame};
+use pos_based_fluids::render::RenderState;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::keyboard::{Key, NamedKey};
+use winit::window;
+
+const USAGE: &str = "usage: playback <recording> [--speed <factor>]";
+
+struct Args {
+    path: PathBuf,
+    speed: f32,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    let mut speed = 1.0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                speed = args
+                    .next()
+                    .ok_or(USAGE)?
+                    .parse()
+                    .map_err(|err| format!("invalid --speed: {err}"))?
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if path.is_none() => path = Some(arg.into()),
+            _ => return Err(format!("unexpected argument `{arg}`\n{USAGE}")),
+        }
+    }
+    Ok(Args {
+        path: path.ok_or(USAGE)?,
+        speed,
+    })
+}
+
+/// The frame to show `elapsed` into the recording, looping at the end.
+fn frame_at(frames: &[RecordedFrame], elapsed: Duration) -> usize {
+    let (first, last) = (frames[0].time(), frames[frames.len() - 1].time());
+    let length = last - first;
+    let time = match length > 0.0 {
+        true => first + elapsed.as_secs_f32() % length,
+        false => first,
+    };
+    frames
+        .partition_point(|frame| frame.time() <= time)
+        .saturating_sub(1)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    let frames = Playback::open(&args.path)
+        .and_then(|mut playback| playback.read_all())
+        .unwrap_or_else(|err| {
+            eprintln!("could not read {}: {err}", args.path.display());
+            std::process::exit(1);
+        });
+    if frames.is_empty() {
+        eprintln!("{} has no frames", args.path.display());
+        std::process::exit(1);
+    }
+    pollster::block_on(run(frames, args.speed));
+}
+
+async fn run(frames: Vec<RecordedFrame>, speed: f32) {
+    let event_loop = EventLoop::new().expect("could not create event loop");
+    let window = Arc::new(window::WindowBuilder::new().build(&event_loop).unwrap());
+    let mut state = RenderState::new(window.clone()).await;
+
+    // playback time, advanced by wall clock time times `speed` while playing
+    let mut elapsed = Duration::ZERO;
+    let mut last = Instant::now();
+    let mut paused = false;
+    let mut shown = None;
+
+    event_loop
+        .run(|event, elwt| match event {
+            Event::Suspended => state.context.suspend(),
+            Event::Resumed => state.context.resume(),
+            Event::AboutToWait => window.request_redraw(),
+            Event::WindowEvent { event, window_id }
+                if Some(window_id) == state.context.window_id =>
+            {
+                match event {
+                    WindowEvent::CloseRequested => elwt.exit(),
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key: Key::Named(NamedKey::Space),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => paused = !paused,
+                    WindowEvent::Resized(physical_size) => state.context.resize(physical_size),
+                    WindowEvent::RedrawRequested => {
+                        let now = Instant::now();
+                        if !paused {
+                            elapsed += now.saturating_duration_since(last).mul_f32(speed);
+                        }
+                        last = now;
+
+                        let i = frame_at(&frames, elapsed);
+                        if shown != Some(i) {
+                            let frame = &frames[i];
+                            window.set_title(&format!(
+                                "pos-based-fluids playback | step {}{}",
+                                frame.step,
+                                if paused { " | paused" } else { "" }
+                            ));
+                            state.update_instances(&frame.particles);
+                            state.update_colors(&frame.colors);
+                            shown = Some(i);
+                        }
+                        state.update();
+                        match state.render() {
+                            Ok(()) => {}
+                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                                state.context.resize(state.context.size())
+                            }
+                            Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+                            Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        })
+        .unwrap();
+}
@@ -0,0 +1,625 @@
+//! What happens to particles at the edges of the unit domain.
+
+use crate::material;
+use crate::sim::Instance;
+use std::f32::consts::TAU;
+
+/// Stand-in for a particle that left through an [`Open`](Boundary::Open) edge.
+///
+/// Its slot stays in the particle buffer so indices don't shift, but it is
+/// skipped by every kernel until an emitter reuses it.
+pub const REMOVED: Instance = Instance {
+    pos: [f32::NAN, f32::NAN],
+    vel: [0.0, 0.0],
+};
+
+pub fn is_removed(p: &Instance) -> bool {
+    p.pos[0].is_nan()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Boundary {
+    /// Particles may leave the domain, they just stop interacting.
+    #[default]
+    Free,
+    /// Particles re-enter on the opposite edge, which has to be periodic as well.
+    Periodic,
+    /// Particles crossing the edge are removed.
+    Open,
+    /// New particles flow in through the edge, see [`Emitter`].
+    Inlet(Inlet),
+    /// A solid edge that particles can't pass and may stick to.
+    Wall(Wall),
+    /// A wall that moves in and out to make waves, see [`Wavemaker`].
+    Wavemaker(Wavemaker),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wall {
+    /// Acceleration towards the wall right at its surface, fading linearly to
+    /// zero at `range`. Lets fluid cling to the wall and run down along it.
+    pub adhesion: f32,
+    /// Distance from the wall over which the adhesion acts.
+    pub range: f32,
+    /// Whether fluid beads up on the wall or spreads over it, see
+    /// [`Material::wetting`](crate::material::Material::wetting).
+    pub wetting: f32,
+}
+
+impl Default for Wall {
+    fn default() -> Self {
+        Self {
+            adhesion: 0.0,
+            range: 0.05,
+            wetting: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stroke {
+    /// The whole edge moves in and out together.
+    #[default]
+    Piston,
+    /// The edge swings around a hinge at its start, the bottom of a vertical
+    /// edge or the left of a horizontal one.
+    Flap,
+}
+
+/// A wall that oscillates into the domain and back, starting at rest at the
+/// edge. The far end of a flap moves the full `amplitude`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavemaker {
+    pub stroke: Stroke,
+    /// Furthest the wall moves into the domain.
+    pub amplitude: f32,
+    /// Strokes per second.
+    pub frequency: f32,
+}
+
+impl Default for Wavemaker {
+    fn default() -> Self {
+        Self {
+            stroke: Stroke::default(),
+            amplitude: 0.05,
+            frequency: 0.5,
+        }
+    }
+}
+
+impl Wavemaker {
+    /// How far the wall is in from the edge at `time`, and how fast it moves inwards.
+    pub fn motion(&self, time: f32) -> (f32, f32) {
+        let phase = TAU * self.frequency * time;
+        (
+            0.5 * self.amplitude * (1.0 - phase.cos()),
+            0.5 * self.amplitude * TAU * self.frequency * phase.sin(),
+        )
+    }
+}
+
+/// How the inflow speed varies along an inlet edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    Uniform,
+    /// Zero at both ends of the edge and the full speed in the middle, like
+    /// a fully developed channel flow.
+    #[default]
+    Parabolic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Inlet {
+    /// Peak inflow speed, perpendicular to the edge.
+    pub speed: f32,
+    pub profile: Profile,
+    /// Particles per second.
+    pub rate: f32,
+    /// Minimum distance of a new particle to any other one. Spawning is held
+    /// back while that space is taken, so a blocked inlet doesn't pile up.
+    pub spacing: f32,
+    /// [Dye](crate::dye) of the particles coming in.
+    pub dye: f32,
+    /// Seconds until the particles coming in fade out and are recycled, `None`
+    /// keeps them around. See [`crate::age`].
+    pub lifetime: Option<f32>,
+}
+
+impl Default for Inlet {
+    fn default() -> Self {
+        Self {
+            speed: 0.5,
+            profile: Profile::default(),
+            rate: 60.0,
+            spacing: 0.02,
+            dye: 1.0,
+            lifetime: None,
+        }
+    }
+}
+
+impl Inlet {
+    /// Inflow speed at `t` in `[0, 1]` along the edge.
+    pub fn speed_at(&self, t: f32) -> f32 {
+        match self.profile {
+            Profile::Uniform => self.speed,
+            Profile::Parabolic => self.speed * 4.0 * t * (1.0 - t),
+        }
+    }
+}
+
+impl std::str::FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "free" => Ok(Boundary::Free),
+            "periodic" => Ok(Boundary::Periodic),
+            "open" => Ok(Boundary::Open),
+            "inlet" => Ok(Boundary::Inlet(Inlet::default())),
+            "wall" => Ok(Boundary::Wall(Wall::default())),
+            "piston" => Ok(Boundary::Wavemaker(Wavemaker::default())),
+            "flap" => Ok(Boundary::Wavemaker(Wavemaker {
+                stroke: Stroke::Flap,
+                ..Wavemaker::default()
+            })),
+            _ => {
+                let parse = |value: &str, what: &str| {
+                    value
+                        .parse::<f32>()
+                        .map_err(|err| format!("invalid {what} `{value}`: {err}"))
+                };
+                match s.split_once(':') {
+                    Some(("inlet", speed)) => Ok(Boundary::Inlet(Inlet {
+                        speed: parse(speed, "inlet speed")?,
+                        ..Inlet::default()
+                    })),
+                    Some(("wall", params)) => {
+                        let (adhesion, wetting) = match params.split_once(':') {
+                            Some((adhesion, wetting)) => {
+                                (adhesion, parse(wetting, "wall wetting")?)
+                            }
+                            None => (params, 0.0),
+                        };
+                        if !(-1.0..=1.0).contains(&wetting) {
+                            return Err(format!(
+                                "invalid wall wetting {wetting}, expected -1 to 1"
+                            ));
+                        }
+                        Ok(Boundary::Wall(Wall {
+                            adhesion: parse(adhesion, "wall adhesion")?,
+                            wetting,
+                            ..Wall::default()
+                        }))
+                    }
+                    Some((stroke @ ("piston" | "flap"), motion)) => {
+                        let stroke = match stroke {
+                            "piston" => Stroke::Piston,
+                            _ => Stroke::Flap,
+                        };
+                        let (amplitude, frequency) = motion.split_once(':').ok_or(format!(
+                            "invalid wavemaker `{s}`, expected {stroke:?}:<amplitude>:<frequency>"
+                        ))?;
+                        Ok(Boundary::Wavemaker(Wavemaker {
+                            stroke,
+                            amplitude: parse(amplitude, "wavemaker amplitude")?,
+                            frequency: parse(frequency, "wavemaker frequency")?,
+                        }))
+                    }
+                    _ => Err(format!(
+                        "unknown boundary `{s}`, expected `free`, `periodic`, `open`, \
+                         `inlet[:<speed>]`, `wall[:<adhesion>[:<wetting>]]`, \
+                         `piston[:<amplitude>:<frequency>]` or `flap[:<amplitude>:<frequency>]`"
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// `x = 0`
+    Left,
+    /// `x = 1`
+    Right,
+    /// `y = 0`
+    Bottom,
+    /// `y = 1`
+    Top,
+}
+
+impl Edge {
+    pub const ALL: [Edge; 4] = [Edge::Left, Edge::Right, Edge::Bottom, Edge::Top];
+
+    /// Bit of [`Boundaries::open_mask`] for this edge. Mirrors `EDGE_*` in `sorting.ocl`.
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl std::str::FromStr for Edge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Edge::Left),
+            "right" => Ok(Edge::Right),
+            "bottom" => Ok(Edge::Bottom),
+            "top" => Ok(Edge::Top),
+            _ => Err(format!(
+                "unknown edge `{s}`, expected `left`, `right`, `bottom` or `top`"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Boundaries {
+    pub left: Boundary,
+    pub right: Boundary,
+    pub bottom: Boundary,
+    pub top: Boundary,
+}
+
+impl Boundaries {
+    pub fn get(&self, edge: Edge) -> Boundary {
+        match edge {
+            Edge::Left => self.left,
+            Edge::Right => self.right,
+            Edge::Bottom => self.bottom,
+            Edge::Top => self.top,
+        }
+    }
+
+    pub fn set(&mut self, edge: Edge, boundary: Boundary) {
+        *match edge {
+            Edge::Left => &mut self.left,
+            Edge::Right => &mut self.right,
+            Edge::Bottom => &mut self.bottom,
+            Edge::Top => &mut self.top,
+        } = boundary;
+    }
+
+    /// Makes both edges of the x and/or y axis periodic.
+    pub fn with_periodic(mut self, periodic: [bool; 2]) -> Self {
+        if periodic[0] {
+            self.left = Boundary::Periodic;
+            self.right = Boundary::Periodic;
+        }
+        if periodic[1] {
+            self.bottom = Boundary::Periodic;
+            self.top = Boundary::Periodic;
+        }
+        self
+    }
+
+    /// Whether each axis wraps around, as taken by [`Grid::with_periodic`](crate::grid::Grid::with_periodic).
+    pub fn periodic(&self) -> [bool; 2] {
+        [
+            self.left == Boundary::Periodic,
+            self.bottom == Boundary::Periodic,
+        ]
+    }
+
+    /// Bit set of the [`Open`](Boundary::Open) edges, see [`Edge::bit`].
+    pub fn open_mask(&self) -> u32 {
+        Edge::ALL
+            .into_iter()
+            .filter(|&edge| self.get(edge) == Boundary::Open)
+            .fold(0, |mask, edge| mask | edge.bit())
+    }
+
+    /// Periodic edges only make sense in pairs.
+    pub fn validate(&self) -> Result<(), String> {
+        let pairs = [(Edge::Left, Edge::Right), (Edge::Bottom, Edge::Top)];
+        for (a, b) in pairs {
+            if (self.get(a) == Boundary::Periodic) != (self.get(b) == Boundary::Periodic) {
+                return Err(format!(
+                    "the {a:?} and {b:?} edges have to be periodic together"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bit set of the [`Wall`](Boundary::Wall) edges, see [`Edge::bit`].
+    pub fn wall_mask(&self) -> u32 {
+        Edge::ALL
+            .into_iter()
+            .filter(|&edge| matches!(self.get(edge), Boundary::Wall(_)))
+            .fold(0, |mask, edge| mask | edge.bit())
+    }
+
+    /// The [pull](material::surface_pull) of the walls and [`Wall::range`]
+    /// per edge, in [`Edge::ALL`] order. Zero for edges that aren't walls.
+    pub fn wall_params(&self) -> ([f32; 4], [f32; 4]) {
+        let mut adhesion = [0.0; 4];
+        let mut range = [0.0; 4];
+        for (i, edge) in Edge::ALL.into_iter().enumerate() {
+            if let Boundary::Wall(wall) = self.get(edge) {
+                adhesion[i] = material::surface_pull(wall.adhesion, wall.wetting);
+                range[i] = wall.range;
+            }
+        }
+        (adhesion, range)
+    }
+
+    /// Acceleration pulling `pos` towards the walls it is close to.
+    /// Mirrors `wall_adhesion` in `sorting.ocl`.
+    pub fn wall_adhesion(&self, pos: [f32; 2]) -> [f32; 2] {
+        let mut acc = [0.0, 0.0];
+        for edge in Edge::ALL {
+            let Boundary::Wall(wall) = self.get(edge) else {
+                continue;
+            };
+            let (dist, axis, sign) = match edge {
+                Edge::Left => (pos[0], 0, -1.0),
+                Edge::Right => (1.0 - pos[0], 0, 1.0),
+                Edge::Bottom => (pos[1], 1, -1.0),
+                Edge::Top => (1.0 - pos[1], 1, 1.0),
+            };
+            if (0.0..wall.range).contains(&dist) {
+                let pull = material::surface_pull(wall.adhesion, wall.wetting);
+                acc[axis] += sign * pull * (1.0 - dist / wall.range);
+            }
+        }
+        acc
+    }
+
+    /// Keeps a particle inside the walls, dropping the velocity into them.
+    /// Mirrors `collide_walls` in `sorting.ocl`.
+    pub fn collide_walls(&self, pos: &mut [f32; 2], vel: &mut [f32; 2]) {
+        // the domain is half-open, the far walls sit just below 1
+        let max = 1.0f32.next_down();
+        let mask = self.wall_mask();
+        if mask & Edge::Left.bit() != 0 && pos[0] < 0.0 {
+            pos[0] = 0.0;
+            vel[0] = vel[0].max(0.0);
+        }
+        if mask & Edge::Right.bit() != 0 && pos[0] > max {
+            pos[0] = max;
+            vel[0] = vel[0].min(0.0);
+        }
+        if mask & Edge::Bottom.bit() != 0 && pos[1] < 0.0 {
+            pos[1] = 0.0;
+            vel[1] = vel[1].max(0.0);
+        }
+        if mask & Edge::Top.bit() != 0 && pos[1] > max {
+            pos[1] = max;
+            vel[1] = vel[1].min(0.0);
+        }
+    }
+
+    /// Bit sets of the [`Wavemaker`] edges, and of those among them that are
+    /// [flaps](Stroke::Flap), see [`Edge::bit`].
+    pub fn wavemaker_masks(&self) -> (u32, u32) {
+        Edge::ALL
+            .into_iter()
+            .fold((0, 0), |(all, flaps), edge| match self.get(edge) {
+                Boundary::Wavemaker(w) => (
+                    all | edge.bit(),
+                    match w.stroke {
+                        Stroke::Flap => flaps | edge.bit(),
+                        Stroke::Piston => flaps,
+                    },
+                ),
+                _ => (all, flaps),
+            })
+    }
+
+    /// [`Wavemaker::motion`] of every edge at `time`, in [`Edge::ALL`] order.
+    /// Zero for edges that aren't wavemakers.
+    pub fn wavemaker_motion(&self, time: f32) -> ([f32; 4], [f32; 4]) {
+        let mut offset = [0.0; 4];
+        let mut speed = [0.0; 4];
+        for (i, edge) in Edge::ALL.into_iter().enumerate() {
+            if let Boundary::Wavemaker(w) = self.get(edge) {
+                (offset[i], speed[i]) = w.motion(time);
+            }
+        }
+        (offset, speed)
+    }
+
+    /// Keeps a particle on the inner side of the wavemakers, at least as fast
+    /// inwards as the wall pushing it. `offset` and `speed` are the
+    /// [`wavemaker_motion`](Self::wavemaker_motion).
+    /// Mirrors `collide_wavemakers` in `sorting.ocl`.
+    pub fn collide_wavemakers(
+        &self,
+        pos: &mut [f32; 2],
+        vel: &mut [f32; 2],
+        offset: [f32; 4],
+        speed: [f32; 4],
+    ) {
+        let (mask, flaps) = self.wavemaker_masks();
+        for (i, edge) in Edge::ALL.into_iter().enumerate() {
+            if mask & edge.bit() == 0 {
+                continue;
+            }
+            // axis across the edge, the direction into the domain and where
+            // the particle is along the edge
+            let (axis, inwards, along) = match edge {
+                Edge::Left => (0, 1.0, pos[1]),
+                Edge::Right => (0, -1.0, pos[1]),
+                Edge::Bottom => (1, 1.0, pos[0]),
+                Edge::Top => (1, -1.0, pos[0]),
+            };
+            let scale = match flaps & edge.bit() != 0 {
+                true => along.clamp(0.0, 1.0),
+                false => 1.0,
+            };
+            // the domain is half-open, the far edges sit just below 1
+            let start = match inwards > 0.0 {
+                true => 0.0,
+                false => 1.0f32.next_down(),
+            };
+
+            let wall = start + inwards * offset[i] * scale;
+            if (pos[axis] - wall) * inwards < 0.0 {
+                pos[axis] = wall;
+                let push = speed[i] * scale;
+                if vel[axis] * inwards < push {
+                    vel[axis] = inwards * push;
+                }
+            }
+        }
+    }
+
+    /// The inlet on the edge closest to `pos`, if there is any. Tells which
+    /// inlet a freshly spawned particle came from.
+    pub fn nearest_inlet(&self, pos: [f32; 2]) -> Option<Inlet> {
+        Edge::ALL
+            .into_iter()
+            .filter_map(|edge| match self.get(edge) {
+                Boundary::Inlet(inlet) => {
+                    let dist = match edge {
+                        Edge::Left => pos[0],
+                        Edge::Right => 1.0 - pos[0],
+                        Edge::Bottom => pos[1],
+                        Edge::Top => 1.0 - pos[1],
+                    };
+                    Some((dist, inlet))
+                }
+                _ => None,
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, inlet)| inlet)
+    }
+
+    /// Whether `pos` has crossed one of the open edges.
+    /// Mirrors `crossed_open_edge` in `sorting.ocl`.
+    pub fn crossed_open_edge(&self, pos: [f32; 2]) -> bool {
+        let mask = self.open_mask();
+        (mask & Edge::Left.bit() != 0 && pos[0] < 0.0)
+            || (mask & Edge::Right.bit() != 0 && pos[0] >= 1.0)
+            || (mask & Edge::Bottom.bit() != 0 && pos[1] < 0.0)
+            || (mask & Edge::Top.bit() != 0 && pos[1] >= 1.0)
+    }
+}
+
+/// Slots of [removed](REMOVED) particles, ready to be handed out again.
+#[derive(Debug, Clone, Default)]
+pub struct FreeList {
+    slots: Vec<u32>,
+}
+
+impl FreeList {
+    pub fn from_particles(particles: &[Instance]) -> Self {
+        let mut free = Self::default();
+        free.collect(particles);
+        free
+    }
+
+    /// Rebuilds the list from the current particle state, keeping the storage.
+    pub fn collect(&mut self, particles: &[Instance]) {
+        self.slots.clear();
+        self.slots.extend(
+            particles
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| is_removed(p))
+                .map(|(i, _)| i as u32),
+        );
+    }
+
+    /// Takes a free slot.
+    pub fn pop(&mut self) -> Option<u32> {
+        self.slots.pop()
+    }
+
+    /// Hands back a slot taken by [`pop`](Self::pop) that ended up unused.
+    pub fn push(&mut self, slot: u32) {
+        self.slots.push(slot);
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// Golden ratio conjugate, spreads successive spawn points evenly along an edge.
+const SPAWN_SEQUENCE_STEP: f32 = 0.618_034;
+/// Spawn points tried per particle before giving up until the next step.
+const SPAWN_ATTEMPTS: u32 = 4;
+
+/// Spawns particles at the [`Inlet`] edges into free slots.
+///
+/// Runs on the host, ahead of the upload at the start of a step.
+#[derive(Debug, Clone, Default)]
+pub struct Emitter {
+    /// Particles owed per edge, in [`Edge::ALL`] order.
+    pending: [f32; 4],
+    sequence: f32,
+}
+
+impl Emitter {
+    pub fn emit(
+        &mut self,
+        boundaries: &Boundaries,
+        dt: f32,
+        particles: &mut [Instance],
+        free: &mut FreeList,
+    ) {
+        for (i, edge) in Edge::ALL.into_iter().enumerate() {
+            let Boundary::Inlet(inlet) = boundaries.get(edge) else {
+                continue;
+            };
+
+            // never owe more than one row along the edge, or a blocked inlet
+            // would burst once it clears
+            let row = (1.0 / inlet.spacing).max(1.0);
+            self.pending[i] = (self.pending[i] + inlet.rate * dt).min(row);
+
+            while self.pending[i] >= 1.0 {
+                let Some(slot) = free.pop() else {
+                    return;
+                };
+                match self.spawn(edge, &inlet, particles) {
+                    Some(p) => {
+                        particles[slot as usize] = p;
+                        self.pending[i] -= 1.0;
+                    }
+                    None => {
+                        // back-pressure: the inlet is crowded, try again next step
+                        free.push(slot);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// A new particle at the next free spot along `edge`, if there is one.
+    fn spawn(&mut self, edge: Edge, inlet: &Inlet, particles: &[Instance]) -> Option<Instance> {
+        for _ in 0..SPAWN_ATTEMPTS {
+            self.sequence = (self.sequence + SPAWN_SEQUENCE_STEP).fract();
+            let t = self.sequence;
+            let inset = 0.5 * inlet.spacing;
+            let speed = inlet.speed_at(t);
+
+            let (pos, vel) = match edge {
+                Edge::Left => ([inset, t], [speed, 0.0]),
+                Edge::Right => ([1.0 - inset, t], [-speed, 0.0]),
+                Edge::Bottom => ([t, inset], [0.0, speed]),
+                Edge::Top => ([t, 1.0 - inset], [0.0, -speed]),
+            };
+
+            // linear in the particle count, but only runs a few times per step
+            let spacing_sq = inlet.spacing * inlet.spacing;
+            let crowded = particles.iter().any(|p| {
+                let dx = p.pos[0] - pos[0];
+                let dy = p.pos[1] - pos[1];
+                dx * dx + dy * dy < spacing_sq
+            });
+            if !crowded {
+                return Some(Instance { pos, vel });
+            }
+        }
+        None
+    }
+}
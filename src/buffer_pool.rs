@@ -0,0 +1,123 @@
+//! Reusing OpenCL buffers whose size follows the particle count, so features
+//! that grow and shrink them every few steps, such as emitters, compaction
+//! or adaptive grids, don't create and release memory objects each time.
+//!
+//! Sizes are rounded up to [buckets](bucket_bytes) of powers of two, so a
+//! buffer freed at one count serves the next few counts around it. Buffers
+//! handed back wait in the pool until one of the same bucket and flags is
+//! asked for again, up to [`BufferPool::max_idle_bytes`] in total.
+
+use opencl3 as cl;
+use opencl3::memory::{Buffer, ClMem};
+use opencl3::types::cl_mem_flags;
+use std::collections::HashMap;
+use std::mem::{size_of, ManuallyDrop};
+
+/// The smallest bucket, so tiny buffers share one size.
+pub const MIN_BUCKET_BYTES: usize = 4096;
+
+/// Bytes a buffer of `bytes` is created with, the next power of two of at
+/// least [`MIN_BUCKET_BYTES`].
+pub fn bucket_bytes(bytes: usize) -> usize {
+    bytes.max(MIN_BUCKET_BYTES).next_power_of_two()
+}
+
+/// A buffer from a [`BufferPool`], with room for at least `len` elements.
+/// Handed back with [`BufferPool::release`], or simply dropped to free it.
+pub struct PooledBuffer<T> {
+    pub buffer: Buffer<T>,
+    pub len: usize,
+    flags: cl_mem_flags,
+    bytes: usize,
+}
+
+impl<T> PooledBuffer<T> {
+    /// Elements the buffer has room for, `len` rounded up to its bucket.
+    pub fn capacity(&self) -> usize {
+        self.bytes / size_of::<T>().max(1)
+    }
+}
+
+#[derive(Default)]
+pub struct BufferPool {
+    /// Idle buffers by flags and bucket.
+    free: HashMap<(cl_mem_flags, usize), Vec<Buffer<u8>>>,
+    idle_bytes: usize,
+    /// Buffers released beyond this are freed instead of kept.
+    pub max_idle_bytes: usize,
+    /// Buffers created and handed out again, to see how well the pool works.
+    pub created: usize,
+    pub reused: usize,
+}
+
+impl BufferPool {
+    pub fn new(max_idle_bytes: usize) -> Self {
+        Self {
+            max_idle_bytes,
+            ..Self::default()
+        }
+    }
+
+    /// A buffer for `len` elements with `flags`, taken from the pool if one
+    /// of its bucket is idle. The contents are whatever was left in it.
+    pub fn acquire<T>(
+        &mut self,
+        context: &cl::context::Context,
+        flags: cl_mem_flags,
+        len: usize,
+    ) -> cl::Result<PooledBuffer<T>> {
+        let bytes = bucket_bytes(len * size_of::<T>());
+        let idle = self.free.get_mut(&(flags, bytes)).and_then(Vec::pop);
+        let buffer = match idle {
+            Some(buffer) => {
+                self.idle_bytes -= bytes;
+                self.reused += 1;
+                retype(buffer)
+            }
+            None => {
+                self.created += 1;
+                let count = bytes / size_of::<T>().max(1);
+                unsafe { Buffer::<T>::create(context, flags, count, std::ptr::null_mut())? }
+            }
+        };
+        Ok(PooledBuffer {
+            buffer,
+            len,
+            flags,
+            bytes,
+        })
+    }
+
+    /// Keeps `pooled` for a later [`acquire`](Self::acquire), unless that
+    /// would hold on to more than `max_idle_bytes`. Commands still using it
+    /// have to be enqueued before it is acquired again, the queue runs in
+    /// order.
+    pub fn release<T>(&mut self, pooled: PooledBuffer<T>) {
+        if self.idle_bytes + pooled.bytes > self.max_idle_bytes {
+            return;
+        }
+        self.idle_bytes += pooled.bytes;
+        self.free
+            .entry((pooled.flags, pooled.bytes))
+            .or_default()
+            .push(retype(pooled.buffer));
+    }
+
+    /// Bytes of the buffers waiting in the pool.
+    pub fn idle_bytes(&self) -> usize {
+        self.idle_bytes
+    }
+
+    /// Frees every idle buffer.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        self.idle_bytes = 0;
+    }
+}
+
+/// The memory object of `buffer` as a buffer of another element type, which
+/// takes over releasing it.
+fn retype<T, U>(buffer: Buffer<T>) -> Buffer<U> {
+    let buffer = ManuallyDrop::new(buffer);
+    Buffer::new(buffer.get())
+}
@@ -0,0 +1,157 @@
+//! What an OpenCL device offers beyond the core profile, and which variants
+//! of the kernels in `sorting.ocl` get built for it.
+//!
+//! The variants are selected with preprocessor defines, so code for features
+//! the device lacks is never compiled: `FUSED` for the single work-group
+//! sort and collide kernel, which keeps the grid in local memory, `FP16`
+//! enables half precision. `SUBGROUPS` switches the grid build to claiming
+//! cell slots with one atomic per subgroup and cell, and the stats reductions
+//! to folding within subgroups before going through local memory. Subgroup
+//! functions need OpenCL C 2.0, so that variant is built as such.
+//! `GRID_IMAGES` has the collision kernel read the grid from images instead
+//! of buffers. `FP64` keeps the particles in double precision as well, see
+//! [`crate::precision`].
+
+use std::fmt;
+
+/// Shared virtual memory, `CL_DEVICE_SVM_CAPABILITIES`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Svm {
+    #[default]
+    None,
+    CoarseGrain,
+    FineGrain,
+}
+
+impl Svm {
+    /// From the bits of `CL_DEVICE_SVM_CAPABILITIES`, zero before OpenCL 2.0.
+    pub fn from_bits(bits: u64) -> Self {
+        const COARSE_GRAIN_BUFFER: u64 = 1 << 0;
+        const FINE_GRAIN_BUFFER: u64 = 1 << 1;
+        if bits & FINE_GRAIN_BUFFER != 0 {
+            Svm::FineGrain
+        } else if bits & COARSE_GRAIN_BUFFER != 0 {
+            Svm::CoarseGrain
+        } else {
+            Svm::None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceCaps {
+    pub extensions: Vec<String>,
+    /// Bytes of local memory per work group.
+    pub local_mem_size: u64,
+    pub svm: Svm,
+    /// Largest 2D image, zero without image support.
+    pub image2d_max: [usize; 2],
+}
+
+impl DeviceCaps {
+    /// `extensions` as `CL_DEVICE_EXTENSIONS` lists them, separated by spaces.
+    pub fn new(extensions: &str, local_mem_size: u64, svm: Svm) -> Self {
+        Self {
+            extensions: extensions.split_whitespace().map(String::from).collect(),
+            local_mem_size,
+            svm,
+            image2d_max: [0; 2],
+        }
+    }
+
+    pub fn with_image2d_max(mut self, width: usize, height: usize) -> Self {
+        self.image2d_max = [width, height];
+        self
+    }
+
+    /// Whether a 2D image of `width` by `height` fits.
+    pub fn image2d_fits(&self, width: usize, height: usize) -> bool {
+        width > 0 && height > 0 && width <= self.image2d_max[0] && height <= self.image2d_max[1]
+    }
+
+    pub fn has(&self, extension: &str) -> bool {
+        self.extensions.iter().any(|e| e == extension)
+    }
+
+    pub fn fp16(&self) -> bool {
+        self.has("cl_khr_fp16")
+    }
+
+    pub fn subgroups(&self) -> bool {
+        self.has("cl_khr_subgroups")
+    }
+
+    pub fn fp64(&self) -> bool {
+        self.has("cl_khr_fp64")
+    }
+}
+
+impl fmt::Display for DeviceCaps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let svm = match self.svm {
+            Svm::None => "no",
+            Svm::CoarseGrain => "coarse grain",
+            Svm::FineGrain => "fine grain",
+        };
+        write!(
+            f,
+            "fp16 {}, subgroups {}, SVM {svm}, {} KiB local memory",
+            yes_no(self.fp16()),
+            yes_no(self.subgroups()),
+            self.local_mem_size / 1024
+        )
+    }
+}
+
+/// The kernel variants to build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Variants {
+    pub fused: bool,
+    pub fp16: bool,
+    pub subgroups: bool,
+    pub grid_images: bool,
+    pub fp64: bool,
+}
+
+impl Variants {
+    /// Everything `caps` supports. The fused kernel is built if it is wanted
+    /// and its `fused_local_mem` bytes fit into local memory.
+    pub fn select(caps: &DeviceCaps, fused_local_mem: Option<usize>) -> Self {
+        Self {
+            fused: fused_local_mem.is_some_and(|bytes| bytes as u64 <= caps.local_mem_size),
+            fp16: caps.fp16(),
+            subgroups: caps.subgroups(),
+            grid_images: false,
+            fp64: false,
+        }
+    }
+
+    /// With the grid in images of `width` by `height` if `wanted` and the
+    /// device can hold them.
+    pub fn with_grid_images(mut self, caps: &DeviceCaps, wanted: bool, size: [usize; 2]) -> Self {
+        self.grid_images = wanted && caps.image2d_fits(size[0], size[1]);
+        self
+    }
+
+    /// In double precision as well if `wanted` and the device supports it.
+    pub fn with_fp64(mut self, caps: &DeviceCaps, wanted: bool) -> Self {
+        self.fp64 = wanted && caps.fp64();
+        self
+    }
+
+    /// Options for building `sorting.ocl`.
+    pub fn build_options(&self) -> String {
+        [
+            (self.fused, "-D FUSED"),
+            (self.fp16, "-D FP16"),
+            (self.subgroups, "-D SUBGROUPS -cl-std=CL2.0"),
+            (self.grid_images, "-D GRID_IMAGES"),
+            (self.fp64, "-D FP64"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, define)| enabled.then_some(define))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
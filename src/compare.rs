@@ -0,0 +1,126 @@
+//! Runs the same scene on two backends in lockstep and tracks how far they drift apart.
+
+use crate::backend::{Backend, Error};
+use crate::boundary;
+use crate::sim::{rgba_to_u32, Instance};
+
+pub const COLOR_A: u32 = rgba_to_u32(255, 140, 40, 255);
+pub const COLOR_B: u32 = rgba_to_u32(60, 160, 255, 200);
+
+/// Position difference between the two backends after one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub step: usize,
+    pub max: f32,
+    pub mean: f32,
+    /// Index of the particle with the largest difference.
+    pub worst: usize,
+}
+
+impl Divergence {
+    pub fn between(step: usize, a: &[Instance], b: &[Instance]) -> Self {
+        assert_eq!(a.len(), b.len(), "backends disagree on the particle count");
+
+        let mut max = 0.0f32;
+        let mut worst = 0;
+        let mut sum = 0.0;
+        for (i, (a, b)) in a.iter().zip(b).enumerate() {
+            let dist = match (boundary::is_removed(a), boundary::is_removed(b)) {
+                (true, true) => 0.0,
+                // only one of them lost the particle
+                (true, false) | (false, true) => f32::INFINITY,
+                (false, false) => {
+                    let dx = a.pos[0] - b.pos[0];
+                    let dy = a.pos[1] - b.pos[1];
+                    (dx * dx + dy * dy).sqrt()
+                }
+            };
+            sum += dist;
+            if dist > max {
+                max = dist;
+                worst = i;
+            }
+        }
+
+        Self {
+            step,
+            max,
+            mean: if a.is_empty() {
+                0.0
+            } else {
+                sum / a.len() as f32
+            },
+            worst,
+        }
+    }
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "step {}: max {:.3e} (particle {}), mean {:.3e}",
+            self.step, self.max, self.worst, self.mean
+        )
+    }
+}
+
+/// Hash of the exact state of `particles`, bit for bit, to tell whether two
+/// runs of a [deterministic](crate::backend::Config::deterministic) backend
+/// came out the same. 64 bit FNV-1a over the positions and velocities.
+pub fn state_hash(particles: &[Instance]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for p in particles {
+        for x in [p.pos[0], p.pos[1], p.vel[0], p.vel[1]] {
+            for byte in x.to_bits().to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+    hash
+}
+
+pub struct Comparison {
+    pub a: Box<dyn Backend>,
+    pub b: Box<dyn Backend>,
+    pub history: Vec<Divergence>,
+}
+
+impl Comparison {
+    pub fn new(a: Box<dyn Backend>, b: Box<dyn Backend>) -> Self {
+        Self {
+            a,
+            b,
+            history: vec![],
+        }
+    }
+
+    /// Steps both backends and records their divergence.
+    pub fn step(&mut self) -> Result<Divergence, Error> {
+        self.a.step()?;
+        self.b.step()?;
+        self.a.wait_read()?;
+        self.b.wait_read()?;
+
+        let divergence = Divergence::between(
+            self.history.len() + 1,
+            self.a.particles(),
+            self.b.particles(),
+        );
+        self.history.push(divergence);
+        Ok(divergence)
+    }
+
+    /// Both particle sets back to back, `a` first.
+    pub fn instances(&self) -> Vec<Instance> {
+        [self.a.particles(), self.b.particles()].concat()
+    }
+
+    /// Colors matching [`instances`](Comparison::instances).
+    pub fn colors(&self) -> Vec<u32> {
+        let mut colors = vec![COLOR_A; self.a.particles().len()];
+        colors.resize(colors.len() + self.b.particles().len(), COLOR_B);
+        colors
+    }
+}
@@ -0,0 +1,444 @@
+//! Single threaded reference implementation of the kernels in `sorting.ocl`.
+
+use crate::age::Ages;
+use crate::backend::{self, Backend, Config, StepTimings};
+use crate::boundary::{self, Boundaries, Emitter, FreeList};
+use crate::forces::{self, Force, ForcePrimitive};
+use crate::grid::Grid;
+use crate::ids::ParticleIds;
+use crate::paddle::{self, Blades, Paddle};
+use crate::precision::{self, Precise};
+use crate::scene::{Scene, SceneEdit};
+use crate::sdf::DistanceField;
+use crate::sim::Instance;
+#[cfg(feature = "simd")]
+use crate::simd::Slots;
+use crate::stability::{self, Brake};
+use crate::terrain::Heightfield;
+use crate::thermal::Thermal;
+use crate::turbulence::{Surface, Turbulence};
+use crate::verify::GridCells;
+use crate::wind::Wind;
+use crate::{MAX_PARTICLES_PER_CELL, PARTICLE_RADIUS, TIME_STEP};
+use std::time::Instant;
+
+pub struct CpuState {
+    particles: Vec<Instance>,
+    gravity: [f32; 2],
+    force_primitives: Vec<ForcePrimitive>,
+    forces: Vec<Force>,
+    paddles: Vec<Paddle>,
+    blades: Vec<Blades>,
+    time: f32,
+    boundaries: Boundaries,
+    free: FreeList,
+    emitter: Emitter,
+    thermal: Thermal,
+    wind: Wind,
+    turbulence: Turbulence,
+    surface: Surface,
+    temperatures: Vec<f32>,
+    terrain: Heightfield,
+    distance_field: DistanceField,
+    ages: Ages,
+    ids: ParticleIds,
+    quiet_steps: Vec<u32>,
+    sleep_speed: f32,
+    sleep_after: u32,
+    /// The [stability] limits.
+    config: Config,
+    /// The particles in double precision, with [`Config::double_precision`].
+    precise: Option<Vec<Precise>>,
+    brake: Option<Brake>,
+    /// Particles clamped since the start of the last [`Backend::step`].
+    clamped: usize,
+    /// [`SceneEdit::Damping`].
+    damping: f32,
+    /// Of the last [`Backend::step`], substeps included.
+    timings: StepTimings,
+    count_per_cell: Vec<u32>,
+    cell_ids: Vec<i32>,
+    n_per_cell: u32,
+    #[cfg(feature = "simd")]
+    slots: Slots,
+    grid: Grid,
+    /// Whether particles collide with each other, off when another solver
+    /// such as [`crate::wcsph`] keeps them apart.
+    collisions: bool,
+}
+
+impl CpuState {
+    pub fn new(scene: &Scene, config: &Config) -> Self {
+        let grid = Grid::new(PARTICLE_RADIUS * 2.0).with_periodic(scene.boundaries.periodic());
+
+        Self {
+            particles: scene.particles.clone(),
+            gravity: scene.gravity,
+            force_primitives: scene.forces.clone(),
+            forces: Vec::with_capacity(scene.forces.len()),
+            paddles: scene.paddles.clone(),
+            blades: Vec::with_capacity(scene.paddles.len()),
+            time: 0.0,
+            boundaries: scene.boundaries,
+            free: FreeList::from_particles(&scene.particles),
+            emitter: Emitter::default(),
+            thermal: scene.thermal.clone(),
+            wind: scene.wind,
+            turbulence: scene.turbulence.clone(),
+            surface: Surface::default(),
+            temperatures: vec![0.0; scene.particles.len()],
+            terrain: scene.terrain.clone(),
+            distance_field: scene.distance_field.clone(),
+            ages: Ages::new(&scene.particles),
+            ids: ParticleIds::new(&scene.particles),
+            quiet_steps: vec![0; scene.particles.len()],
+            sleep_speed: config.sleep_speed,
+            sleep_after: config.sleep_after,
+            config: config.clone(),
+            precise: config
+                .double_precision
+                .then(|| precision::track(&scene.particles)),
+            brake: config.brake.then(Brake::default),
+            clamped: 0,
+            damping: 0.0,
+            timings: StepTimings::default(),
+            count_per_cell: vec![0; grid.cell_count()],
+            cell_ids: vec![-1; grid.cell_count() * MAX_PARTICLES_PER_CELL],
+            n_per_cell: MAX_PARTICLES_PER_CELL as u32,
+            #[cfg(feature = "simd")]
+            slots: Slots::default(),
+            grid,
+            collisions: true,
+        }
+    }
+
+    /// Only integrates and handles the boundaries, leaving the interaction
+    /// between particles to the caller.
+    pub fn without_collisions(mut self) -> Self {
+        self.collisions = false;
+        self
+    }
+
+    pub fn boundaries(&self) -> &Boundaries {
+        &self.boundaries
+    }
+
+    /// For solvers built on top that change the velocities between steps.
+    pub fn particles_mut(&mut self) -> &mut [Instance] {
+        &mut self.particles
+    }
+
+    /// [`particles_mut`](Self::particles_mut) along with the temperatures.
+    pub fn particles_mut_with_temperatures(&mut self) -> (&mut [Instance], &[f32]) {
+        (&mut self.particles, &self.temperatures)
+    }
+
+    /// Scales every velocity by `keep`, for settling a scene before it starts.
+    pub fn damp(&mut self, keep: f32) {
+        for p in &mut self.particles {
+            p.vel[0] *= keep;
+            p.vel[1] *= keep;
+        }
+    }
+
+    /// How many particles are asleep, see [`Config::sleep_after`].
+    pub fn asleep(&self) -> usize {
+        (0..self.particles.len())
+            .filter(|&id| self.is_asleep(id))
+            .count()
+    }
+
+    /// A step of `dt` instead of [`TIME_STEP`], for solvers built on top that
+    /// need smaller ones.
+    pub fn step_by(&mut self, dt: f32) {
+        self.emitter
+            .emit(&self.boundaries, dt, &mut self.particles, &mut self.free);
+        if self.thermal.is_active() {
+            self.thermal
+                .update(&self.particles, &mut self.temperatures, dt);
+        }
+        if self.turbulence.is_active() {
+            self.surface.update(&self.particles);
+        }
+        let started = Instant::now();
+        self.integrate_particles(dt);
+        self.timings.integrate += started.elapsed().as_secs_f32();
+        if self.collisions {
+            let started = Instant::now();
+            self.sort_particles();
+            self.timings.grid += started.elapsed().as_secs_f32();
+            let started = Instant::now();
+            self.collide_particles();
+            self.timings.solve += started.elapsed().as_secs_f32();
+        }
+        if self.ages.update(&mut self.particles, &self.boundaries, dt) {
+            self.free.collect(&self.particles);
+        }
+        self.ids.update(&self.particles);
+    }
+
+    /// `integrate_particles`
+    fn integrate_particles(&mut self, dt: f32) {
+        forces::evaluate(&self.force_primitives, self.time, &mut self.forces);
+        paddle::evaluate(&self.paddles, self.time, &mut self.blades);
+        let (offset, speed) = self.boundaries.wavemaker_motion(self.time);
+        let limit = stability::speed_limit(&self.config, dt);
+
+        let mut precise = self.precise.as_mut().map(|precise| precise.iter_mut());
+        let particles = self.particles.iter_mut().zip(&self.temperatures);
+        for ((p, temperature), quiet) in particles.zip(&mut self.quiet_steps) {
+            let mut shadow = precise.as_mut().and_then(Iterator::next);
+            if boundary::is_removed(p) {
+                continue;
+            }
+            if let Some(shadow) = &mut shadow {
+                shadow.sync(p);
+            }
+            // as the last step left it, before gravity speeds up resting particles
+            let slow =
+                p.vel[0] * p.vel[0] + p.vel[1] * p.vel[1] < self.sleep_speed * self.sleep_speed;
+
+            let mut kick = |vel: &mut [f32; 2], [ax, ay]: [f32; 2]| match &mut shadow {
+                Some(shadow) => shadow.kick(vel, [ax, ay], dt),
+                None => {
+                    vel[0] += ax * dt;
+                    vel[1] += ay * dt;
+                }
+            };
+            kick(&mut p.vel, self.gravity);
+            for force in &self.forces {
+                kick(&mut p.vel, force.acceleration(p.pos));
+            }
+            kick(&mut p.vel, self.boundaries.wall_adhesion(p.pos));
+            kick(&mut p.vel, self.distance_field.adhesion(p.pos));
+            kick(&mut p.vel, [0.0, self.thermal.buoyancy * temperature]);
+            let wind = self.wind.acceleration(p.pos, p.vel, self.time);
+            kick(&mut p.vel, wind);
+            let turbulence = self
+                .turbulence
+                .acceleration(p.pos, &self.surface, self.time);
+            kick(&mut p.vel, turbulence);
+            let drag = stability::drag(
+                p.vel,
+                self.config.linear_drag + self.damping,
+                self.config.quadratic_drag,
+                dt,
+            );
+            kick(&mut p.vel, drag);
+            if stability::clamp_velocity(&mut p.vel, limit) {
+                self.clamped += 1;
+            }
+
+            if self.sleep_after > 0 {
+                *quiet = if slow {
+                    (*quiet + 1).min(self.sleep_after)
+                } else {
+                    0
+                };
+                if *quiet >= self.sleep_after {
+                    p.vel = [0.0, 0.0];
+                }
+            }
+            match &mut shadow {
+                Some(shadow) => {
+                    // takes the velocity the clamping and sleeping left
+                    shadow.sync(p);
+                    shadow.drift(&mut p.pos, dt, &self.grid);
+                }
+                None => {
+                    p.pos = self
+                        .grid
+                        .wrap_position([p.pos[0] + p.vel[0] * dt, p.pos[1] + p.vel[1] * dt])
+                }
+            }
+            self.boundaries.collide_walls(&mut p.pos, &mut p.vel);
+            self.boundaries
+                .collide_wavemakers(&mut p.pos, &mut p.vel, offset, speed);
+            self.terrain.collide(&mut p.pos, &mut p.vel);
+            self.distance_field.collide(&mut p.pos, &mut p.vel);
+            for blades in &self.blades {
+                blades.collide(&mut p.pos, &mut p.vel);
+            }
+            if self.boundaries.crossed_open_edge(p.pos) {
+                *p = boundary::REMOVED;
+                *quiet = 0;
+            }
+            if let Some(shadow) = &mut shadow {
+                shadow.sync(p);
+            }
+        }
+        self.time += dt;
+        self.free.collect(&self.particles);
+    }
+
+    /// `is_asleep`
+    fn is_asleep(&self, id: usize) -> bool {
+        asleep(self.quiet_steps[id], self.sleep_after)
+    }
+
+    /// `sort_particles`
+    fn sort_particles(&mut self) {
+        self.cell_ids.iter_mut().for_each(|id| *id = -1);
+        self.count_per_cell.iter_mut().for_each(|c| *c = 0);
+
+        for (id, p) in self.particles.iter().enumerate() {
+            let Some(cell) = self.grid.cell_index(p.pos) else {
+                continue;
+            };
+
+            let count = &mut self.count_per_cell[cell as usize];
+            if *count < self.n_per_cell {
+                self.cell_ids[(cell * self.n_per_cell + *count) as usize] = id as i32;
+            }
+            *count += 1;
+        }
+    }
+
+    /// `collide_particles`
+    #[cfg(not(feature = "simd"))]
+    fn collide_particles(&mut self) {
+        for id in 0..self.particles.len() {
+            let Some(own_cell) = self.grid.cell_index(self.particles[id].pos) else {
+                continue;
+            };
+            let asleep = self.is_asleep(id);
+
+            for cell in self.grid.neighbors(own_cell) {
+                let count = self.count_per_cell[cell as usize].min(self.n_per_cell);
+                let start = (cell * self.n_per_cell) as usize;
+
+                for &other_id in &self.cell_ids[start..start + count as usize] {
+                    if other_id as usize == id || (asleep && self.is_asleep(other_id as usize)) {
+                        continue;
+                    }
+                    let other = self.particles[other_id as usize];
+                    collide(&mut self.particles[id], &other, PARTICLE_RADIUS, &self.grid);
+                }
+            }
+        }
+    }
+
+    /// `collide_particles`, 8 neighbors at a time.
+    #[cfg(feature = "simd")]
+    fn collide_particles(&mut self) {
+        let (quiet_steps, sleep_after) = (&self.quiet_steps, self.sleep_after);
+        let is_asleep = |id: usize| asleep(quiet_steps[id], sleep_after);
+        self.slots
+            .gather(&self.cell_ids, &self.particles, is_asleep);
+        let n_per_cell = self.n_per_cell as usize;
+        for (id, p) in self.particles.iter_mut().enumerate() {
+            let Some(cell) = self.grid.cell_index(p.pos) else {
+                continue;
+            };
+            let touches = self.slots.touches(
+                &self.grid,
+                n_per_cell,
+                cell,
+                id,
+                p.pos,
+                PARTICLE_RADIUS,
+                is_asleep(id),
+            );
+            if touches {
+                p.vel[0] = 1.0;
+            }
+        }
+    }
+}
+
+/// Whether a particle slow for `quiet` steps sleeps, see [`Config::sleep_after`].
+fn asleep(quiet: u32, sleep_after: u32) -> bool {
+    sleep_after > 0 && quiet >= sleep_after
+}
+
+#[cfg(not(feature = "simd"))]
+fn collide(p: &mut Instance, other: &Instance, radius: f32, grid: &Grid) {
+    let [dist_x, dist_y] = grid.wrap_delta(p.pos, other.pos);
+    let dist = dist_x * dist_x + dist_y * dist_y;
+    if dist <= radius * radius {
+        p.vel[0] = 1.0;
+    }
+}
+
+impl Backend for CpuState {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn step(&mut self) -> Result<(), backend::Error> {
+        let substeps = self.brake.as_ref().map_or(1, Brake::substeps);
+        self.clamped = 0;
+        self.timings = StepTimings::default();
+        for _ in 0..substeps {
+            self.step_by(TIME_STEP / substeps as f32);
+        }
+        if let Some(brake) = &mut self.brake {
+            let active = self.particles.len() - self.free.len();
+            if let Some(substeps) = brake.record(self.clamped, active) {
+                log::warn!("velocities keep getting clamped, now {substeps} substeps per step");
+            }
+        }
+        Ok(())
+    }
+
+    fn particles(&self) -> &[Instance] {
+        &self.particles
+    }
+
+    fn temperatures(&self) -> &[f32] {
+        &self.temperatures
+    }
+
+    fn ages(&self) -> &Ages {
+        &self.ages
+    }
+
+    fn ids(&self) -> &ParticleIds {
+        &self.ids
+    }
+
+    fn restore(&mut self, particles: &[Instance], time: f32) -> Result<(), backend::Error> {
+        if particles.len() != self.particles.len() {
+            return Err(format!(
+                "can't restore {} particles into {}",
+                particles.len(),
+                self.particles.len()
+            )
+            .into());
+        }
+        self.particles.copy_from_slice(particles);
+        if let Some(precise) = &mut self.precise {
+            *precise = precision::track(particles);
+        }
+        self.time = time;
+        self.quiet_steps.fill(0);
+        self.free.collect(&self.particles);
+        self.ids.update(&self.particles);
+        Ok(())
+    }
+
+    fn timings(&self) -> Option<StepTimings> {
+        Some(self.timings)
+    }
+
+    fn grid_cells(&self) -> Option<GridCells<'_>> {
+        self.collisions.then_some(GridCells {
+            grid: self.grid,
+            n_per_cell: self.n_per_cell,
+            counts: &self.count_per_cell,
+            ids: &self.cell_ids,
+        })
+    }
+
+    fn edit(&mut self, edit: &SceneEdit) -> Result<(), backend::Error> {
+        if let SceneEdit::Damping(damping) = *edit {
+            self.damping = damping;
+        }
+        Ok(edit.apply_to(
+            &mut self.gravity,
+            &mut self.force_primitives,
+            &mut self.paddles,
+            &mut self.boundaries,
+        )?)
+    }
+}
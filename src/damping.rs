@@ -0,0 +1,54 @@
+//! Bringing the fluid to rest quickly, for screenshots and for demos that
+//! should settle, apart from any physical viscosity or drag.
+//!
+//! A [`Damping`] takes a fixed share of the kinetic energy away every
+//! second. With a target it also steers the energy: while the fluid has more
+//! energy per particle than the target, it is damped down to the target
+//! within [`settle`](Damping::settle) seconds, and left alone below it. The
+//! simulation thread works out the damping from the [stats](ParticleStats)
+//! of every frame and passes it to the backends as
+//! [`SceneEdit::Damping`](crate::scene::SceneEdit::Damping), a drag on every
+//! particle on top of [`Config::linear_drag`](crate::backend::Config::linear_drag).
+
+use crate::stats::ParticleStats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Damping {
+    /// Rate the kinetic energy decays at, the share of it lost per second
+    /// for small rates. 0 for none.
+    pub rate: f32,
+    /// Kinetic energy per particle to damp down to, if any.
+    pub target: Option<f32>,
+    /// Seconds to reach the target in.
+    pub settle: f32,
+}
+
+impl Default for Damping {
+    fn default() -> Self {
+        Self {
+            rate: 0.0,
+            target: None,
+            settle: 0.5,
+        }
+    }
+}
+
+impl Damping {
+    pub fn is_active(&self) -> bool {
+        self.rate > 0.0 || self.target.is_some()
+    }
+
+    /// Drag per unit of velocity for the particles to lose energy as they
+    /// should with `stats` as they are now.
+    pub fn drag(&self, stats: &ParticleStats) -> f32 {
+        let mut rate = self.rate;
+        if let Some(target) = self.target {
+            let energy = stats.kinetic_energy / stats.count.max(1) as f32;
+            if energy > target {
+                rate += (energy / target).ln() / self.settle;
+            }
+        }
+        // the energy goes with the square of the velocity, so falls twice as fast
+        0.5 * rate
+    }
+}
@@ -0,0 +1,234 @@
+//! Secondary foam, spray and bubble particles.
+//!
+//! Spawned where the fluid traps air or tears apart, then carried along by the
+//! fluid without feeding back into it. Runs on the host next to whichever
+//! backend drives the fluid.
+
+use crate::neighbors::CellList;
+use crate::rng::Rng;
+use crate::sim::{DiffuseInstance, Instance};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffuseKind {
+    /// Few fluid neighbors, flies ballistically.
+    Spray,
+    /// On the surface, drifts with the fluid.
+    Foam,
+    /// Deep inside the fluid, rises.
+    Bubble,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffuseParticle {
+    pub pos: [f32; 2],
+    pub vel: [f32; 2],
+    /// Seconds left to live.
+    pub life: f32,
+    pub kind: DiffuseKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffuseParams {
+    /// Neighborhood radius for the potentials and the classification.
+    pub radius: f32,
+    /// Diffuse particles spawned per fluid particle and second at full potential.
+    pub spawn_rate: f32,
+    /// Trapped air potential mapped to `[0, 1]` between these bounds.
+    pub trapped_air: (f32, f32),
+    /// Positive velocity divergence mapped to `[0, 1]` between these bounds.
+    pub divergence: (f32, f32),
+    /// Kinetic energy mapped to `[0, 1]` between these bounds, scales both potentials.
+    pub kinetic_energy: (f32, f32),
+    pub lifetime: f32,
+    /// Fewer fluid neighbors than this make a particle spray.
+    pub spray_below: u32,
+    /// More fluid neighbors than this make a particle a bubble.
+    pub bubble_above: u32,
+    /// Upward acceleration of bubbles.
+    pub buoyancy: f32,
+    /// How quickly bubbles take on the surrounding fluid velocity, per second.
+    pub drag: f32,
+    pub max_particles: usize,
+}
+
+impl Default for DiffuseParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            spawn_rate: 40.0,
+            trapped_air: (0.5, 5.0),
+            divergence: (0.5, 5.0),
+            kinetic_energy: (0.01, 0.5),
+            lifetime: 2.0,
+            spray_below: 4,
+            bubble_above: 16,
+            buoyancy: 1.0,
+            drag: 4.0,
+            max_particles: 20_000,
+        }
+    }
+}
+
+/// Maps `x` linearly from `[min, max]` to `[0, 1]`, clamped.
+fn clamp_unit(x: f32, (min, max): (f32, f32)) -> f32 {
+    ((x - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+pub struct DiffuseSystem {
+    params: DiffuseParams,
+    particles: Vec<DiffuseParticle>,
+    cells: CellList,
+    rng: Rng,
+}
+
+/// Weighted sums over the fluid around a point.
+#[derive(Default)]
+struct Neighborhood {
+    count: u32,
+    weight: f32,
+    /// Weighted fluid velocity, divide by `weight` for the average.
+    vel: [f32; 2],
+}
+
+impl DiffuseSystem {
+    pub fn new(params: DiffuseParams) -> Self {
+        Self {
+            cells: CellList::new(params.radius),
+            params,
+            particles: vec![],
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn particles(&self) -> &[DiffuseParticle] {
+        &self.particles
+    }
+
+    /// Spawns new diffuse particles from `fluid` and advances the existing ones by `dt`.
+    pub fn step(&mut self, fluid: &[Instance], dt: f32) {
+        self.cells.build(fluid);
+        self.advect(fluid, dt);
+        self.spawn(fluid, dt);
+    }
+
+    fn neighborhood(&self, fluid: &[Instance], pos: [f32; 2]) -> Neighborhood {
+        let mut n = Neighborhood::default();
+        self.cells.for_each_neighbor(fluid, pos, |id, _, dist| {
+            let w = 1.0 - dist / self.params.radius;
+            n.count += 1;
+            n.weight += w;
+            n.vel[0] += fluid[id].vel[0] * w;
+            n.vel[1] += fluid[id].vel[1] * w;
+        });
+        n
+    }
+
+    fn advect(&mut self, fluid: &[Instance], dt: f32) {
+        let mut particles = std::mem::take(&mut self.particles);
+
+        for p in &mut particles {
+            let n = self.neighborhood(fluid, p.pos);
+            let fluid_vel = match n.weight > 0.0 {
+                true => [n.vel[0] / n.weight, n.vel[1] / n.weight],
+                false => p.vel,
+            };
+
+            p.kind = if n.count < self.params.spray_below {
+                DiffuseKind::Spray
+            } else if n.count > self.params.bubble_above {
+                DiffuseKind::Bubble
+            } else {
+                DiffuseKind::Foam
+            };
+
+            match p.kind {
+                DiffuseKind::Spray => (),
+                DiffuseKind::Foam => p.vel = fluid_vel,
+                DiffuseKind::Bubble => {
+                    let drag = (self.params.drag * dt).min(1.0);
+                    p.vel[0] += (fluid_vel[0] - p.vel[0]) * drag;
+                    p.vel[1] += (fluid_vel[1] - p.vel[1]) * drag + self.params.buoyancy * dt;
+                }
+            }
+
+            p.pos[0] += p.vel[0] * dt;
+            p.pos[1] += p.vel[1] * dt;
+            p.life -= dt;
+        }
+
+        particles.retain(|p| p.life > 0.0 && self.cells.grid().cell_index(p.pos).is_some());
+        self.particles = particles;
+    }
+
+    fn spawn(&mut self, fluid: &[Instance], dt: f32) {
+        for (i, p) in fluid.iter().enumerate() {
+            if self.particles.len() >= self.params.max_particles {
+                return;
+            }
+
+            let mut trapped_air = 0.0;
+            let mut divergence = 0.0;
+            self.cells.for_each_neighbor(fluid, p.pos, |j, d, dist| {
+                if j == i || dist == 0.0 {
+                    return;
+                }
+                let w = 1.0 - dist / self.params.radius;
+                let dir = [d[0] / dist, d[1] / dist];
+                let dv = [fluid[j].vel[0] - p.vel[0], fluid[j].vel[1] - p.vel[1]];
+                let dv_len = (dv[0] * dv[0] + dv[1] * dv[1]).sqrt();
+
+                // neighbors moving apart along their offset
+                divergence += (dv[0] * dir[0] + dv[1] * dir[1]) * w;
+                // neighbors moving towards each other, the more head-on the more air they trap
+                if dv_len > 0.0 {
+                    let alignment = (dv[0] * dir[0] + dv[1] * dir[1]) / dv_len;
+                    trapped_air += dv_len * (1.0 - alignment) * w;
+                }
+            });
+
+            let energy = 0.5 * (p.vel[0] * p.vel[0] + p.vel[1] * p.vel[1]);
+            let potential = (clamp_unit(trapped_air, self.params.trapped_air)
+                + clamp_unit(divergence, self.params.divergence))
+                * clamp_unit(energy, self.params.kinetic_energy);
+            let expected = self.params.spawn_rate * potential * dt;
+            if expected <= 0.0 {
+                continue;
+            }
+
+            let mut count = expected.floor() as u32;
+            if self.rng.next_f32() < expected.fract() {
+                count += 1;
+            }
+            for _ in 0..count {
+                let angle = self.rng.next_f32() * std::f32::consts::TAU;
+                let dist = self.rng.next_f32() * 0.5 * self.params.radius;
+                self.particles.push(DiffuseParticle {
+                    pos: [p.pos[0] + angle.cos() * dist, p.pos[1] + angle.sin() * dist],
+                    vel: p.vel,
+                    life: self.params.lifetime,
+                    kind: DiffuseKind::Foam,
+                });
+            }
+        }
+    }
+
+    /// What the renderer draws, fading out towards the end of each particle's life.
+    pub fn instances(&self) -> Vec<DiffuseInstance> {
+        self.particles
+            .iter()
+            .map(|p| DiffuseInstance {
+                pos: p.pos,
+                size: match p.kind {
+                    DiffuseKind::Spray => 0.004,
+                    DiffuseKind::Foam => 0.008,
+                    DiffuseKind::Bubble => 0.006,
+                },
+                alpha: (p.life / self.params.lifetime).clamp(0.0, 1.0)
+                    * match p.kind {
+                        DiffuseKind::Bubble => 0.5,
+                        _ => 0.9,
+                    },
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,45 @@
+struct CameraUniform {
+    transform: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) size: f32,
+    @location(4) alpha: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) local_pos: vec2<f32>,
+    @location(1) alpha: f32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let pos = instance.position + model.position * instance.size;
+
+    out.local_pos = model.position;
+    out.position = camera.transform * vec4<f32>(pos, 0.0, 1.0);
+    out.alpha = instance.alpha;
+
+    return out;
+}
+
+// soft white dots, brightest in the middle
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dist = dot(in.local_pos, in.local_pos);
+    let falloff = 1.0 - smoothstep(0.0, 1.0, dist);
+    return vec4(1.0, 1.0, 1.0, in.alpha * falloff);
+}
@@ -0,0 +1,114 @@
+//! A passive scalar carried by the particles, to make mixing visible.
+//!
+//! The dye moves with the particles for free and slowly evens out between
+//! neighbors. It never feeds back into the simulation, so it lives on the host
+//! next to the backend.
+
+use crate::boundary::{self, Boundaries};
+use crate::neighbors::CellList;
+use crate::sim::{self, Instance};
+
+/// Radius of the area painted by one mouse event.
+pub const BRUSH_RADIUS: f32 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DyeParams {
+    /// Neighborhood radius the dye spreads over.
+    pub radius: f32,
+    /// Fraction of the difference to the neighborhood average that evens out per second.
+    pub diffusion: f32,
+}
+
+impl Default for DyeParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            diffusion: 0.2,
+        }
+    }
+}
+
+pub struct DyeField {
+    params: DyeParams,
+    dye: Vec<f32>,
+    /// Next values while diffusing.
+    scratch: Vec<f32>,
+    /// Which particles were removed last step, to spot slots an inlet reused.
+    removed: Vec<bool>,
+    cells: CellList,
+}
+
+impl DyeField {
+    pub fn new(params: DyeParams, particles: &[Instance]) -> Self {
+        Self {
+            cells: CellList::new(params.radius),
+            params,
+            dye: vec![0.0; particles.len()],
+            scratch: vec![],
+            removed: particles.iter().map(boundary::is_removed).collect(),
+        }
+    }
+
+    /// Dye per particle, in `[0, 1]`.
+    pub fn values(&self) -> &[f32] {
+        &self.dye
+    }
+
+    /// Sets the dye of every particle within `radius` of `center` to `value`.
+    pub fn paint(&mut self, particles: &[Instance], center: [f32; 2], radius: f32, value: f32) {
+        for (dye, p) in self.dye.iter_mut().zip(particles) {
+            let dx = p.pos[0] - center[0];
+            let dy = p.pos[1] - center[1];
+            if dx * dx + dy * dy < radius * radius {
+                *dye = value.clamp(0.0, 1.0);
+            }
+        }
+    }
+
+    /// Gives particles that just came out of an inlet its dye, then lets the
+    /// dye diffuse for `dt`.
+    pub fn step(&mut self, particles: &[Instance], boundaries: &Boundaries, dt: f32) {
+        for (i, p) in particles.iter().enumerate() {
+            let removed = boundary::is_removed(p);
+            if self.removed[i] && !removed {
+                self.dye[i] = boundaries
+                    .nearest_inlet(p.pos)
+                    .map_or(0.0, |inlet| inlet.dye);
+            }
+            self.removed[i] = removed;
+        }
+
+        self.cells.build(particles);
+        let rate = (self.params.diffusion * dt).min(1.0);
+
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.dye);
+        for (i, p) in particles.iter().enumerate() {
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            self.cells
+                .for_each_neighbor(particles, p.pos, |j, _, dist| {
+                    let w = 1.0 - dist / self.params.radius;
+                    sum += self.dye[j] * w;
+                    weight += w;
+                });
+            // the particle itself is always part of its neighborhood
+            if weight > 0.0 {
+                self.scratch[i] += (sum / weight - self.dye[i]) * rate;
+            }
+        }
+        std::mem::swap(&mut self.dye, &mut self.scratch);
+    }
+
+    /// [`sim::colormap`] of the dye, removed particles are transparent.
+    pub fn colors(&self, particles: &[Instance]) -> Vec<u32> {
+        self.dye
+            .iter()
+            .zip(particles)
+            .map(|(&dye, p)| match boundary::is_removed(p) {
+                true => 0,
+                false => sim::colormap(dye),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,179 @@
+//! The particle velocities interpolated onto a regular grid, for the
+//! visualizations that need a continuous flow field.
+
+use crate::neighbors::CellList;
+use crate::sim::Instance;
+
+/// Values on a square grid, row by row from the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalarGrid {
+    pub resolution: u32,
+    pub values: Vec<f32>,
+}
+
+impl ScalarGrid {
+    /// The largest magnitude of any value, 0 for an empty grid.
+    pub fn max_abs(&self) -> f32 {
+        self.values.iter().fold(0.0, |max, v| v.abs().max(max))
+    }
+}
+
+/// Velocity at the center of every grid cell, as the kernel weighted average
+/// of the particles around it. Cells without particles nearby have no velocity.
+pub struct VelocityField {
+    resolution: u32,
+    radius: f32,
+    cells: CellList,
+    vel: Vec<[f32; 2]>,
+    /// Sum of the kernel weights per node, 0 where there is no fluid.
+    weight: Vec<f32>,
+}
+
+impl VelocityField {
+    pub fn new(resolution: u32, radius: f32) -> Self {
+        let n = resolution as usize;
+        Self {
+            resolution,
+            radius,
+            cells: CellList::new(radius),
+            vel: vec![[0.0; 2]; n * n],
+            weight: vec![0.0; n * n],
+        }
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// World position of node `(x, y)`.
+    pub fn node_pos(&self, x: u32, y: u32) -> [f32; 2] {
+        let n = self.resolution as f32;
+        [(x as f32 + 0.5) / n, (y as f32 + 0.5) / n]
+    }
+
+    /// Velocity at node `(x, y)`, `None` without fluid there.
+    pub fn node(&self, x: u32, y: u32) -> Option<[f32; 2]> {
+        let i = (x + y * self.resolution) as usize;
+        (self.weight[i] > 0.0).then_some(self.vel[i])
+    }
+
+    /// Interpolates the velocities of `particles` onto the nodes.
+    pub fn build(&mut self, particles: &[Instance]) {
+        self.cells.build(particles);
+        let radius_sq = self.radius * self.radius;
+
+        for y in 0..self.resolution {
+            for x in 0..self.resolution {
+                let mut vel = [0.0; 2];
+                let mut weight = 0.0;
+                self.cells
+                    .for_each_neighbor(particles, self.node_pos(x, y), |id, _, dist| {
+                        let falloff = 1.0 - dist * dist / radius_sq;
+                        let w = falloff * falloff * falloff;
+                        vel[0] += particles[id].vel[0] * w;
+                        vel[1] += particles[id].vel[1] * w;
+                        weight += w;
+                    });
+
+                let i = (x + y * self.resolution) as usize;
+                self.weight[i] = weight;
+                self.vel[i] = match weight > 0.0 {
+                    true => [vel[0] / weight, vel[1] / weight],
+                    false => [0.0; 2],
+                };
+            }
+        }
+    }
+
+    /// Bilinear interpolation of the nodes around `pos`, leaving out nodes
+    /// without fluid. `None` outside the domain or away from the fluid.
+    pub fn sample(&self, pos: [f32; 2]) -> Option<[f32; 2]> {
+        if !(0.0..1.0).contains(&pos[0]) || !(0.0..1.0).contains(&pos[1]) {
+            return None;
+        }
+
+        let n = self.resolution as f32;
+        let last = self.resolution - 1;
+        let gx = (pos[0] * n - 0.5).clamp(0.0, last as f32);
+        let gy = (pos[1] * n - 0.5).clamp(0.0, last as f32);
+        let (x0, y0) = ((gx as u32).min(last), (gy as u32).min(last));
+        let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+
+        let mut vel = [0.0; 2];
+        let mut total = 0.0;
+        for (dx, dy, w) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let (x, y) = ((x0 + dx).min(last), (y0 + dy).min(last));
+            if let Some(v) = self.node(x, y) {
+                vel[0] += v[0] * w;
+                vel[1] += v[1] * w;
+                total += w;
+            }
+        }
+
+        (total > 0.0).then(|| [vel[0] / total, vel[1] / total])
+    }
+
+    /// Derivative of velocity component `c` along `axis` at node `(x, y)`, by
+    /// central differences where both neighbors have fluid, one-sided where
+    /// only one does, 0 otherwise.
+    fn derivative(&self, x: u32, y: u32, axis: usize, c: usize) -> f32 {
+        let step = |d: i32| {
+            let (nx, ny) = match axis {
+                0 => (x as i32 + d, y as i32),
+                _ => (x as i32, y as i32 + d),
+            };
+            let inside = (0..self.resolution as i32).contains(&nx)
+                && (0..self.resolution as i32).contains(&ny);
+            inside
+                .then(|| self.node(nx as u32, ny as u32))
+                .flatten()
+                .map(|v| v[c])
+        };
+        let h = 1.0 / self.resolution as f32;
+        let center = self.node(x, y).map(|v| v[c]);
+
+        match (step(-1), center, step(1)) {
+            (Some(a), _, Some(b)) => (b - a) / (2.0 * h),
+            (Some(a), Some(v), None) => (v - a) / h,
+            (None, Some(v), Some(b)) => (b - v) / h,
+            _ => 0.0,
+        }
+    }
+
+    /// The speed at every node, 0 where there is no fluid.
+    pub fn speed(&self) -> ScalarGrid {
+        let mut values = Vec::with_capacity((self.resolution * self.resolution) as usize);
+        for y in 0..self.resolution {
+            for x in 0..self.resolution {
+                values.push(self.node(x, y).map_or(0.0, |[vx, vy]| vx.hypot(vy)));
+            }
+        }
+        ScalarGrid {
+            resolution: self.resolution,
+            values,
+        }
+    }
+
+    /// The curl `dvy/dx - dvx/dy` at every node, 0 where there is no fluid.
+    /// Positive values turn counterclockwise.
+    pub fn vorticity(&self) -> ScalarGrid {
+        let mut values = Vec::with_capacity((self.resolution * self.resolution) as usize);
+        for y in 0..self.resolution {
+            for x in 0..self.resolution {
+                values.push(match self.node(x, y) {
+                    Some(_) => self.derivative(x, y, 0, 1) - self.derivative(x, y, 1, 0),
+                    None => 0.0,
+                });
+            }
+        }
+        ScalarGrid {
+            resolution: self.resolution,
+            values,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Built-in force primitives that can be placed in a [`Scene`](crate::scene::Scene).
+
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceKind {
+    /// Pulls particles towards the center. Negative strength pushes them away.
+    GravityWell = 0,
+    /// Swirls particles around the center, counter-clockwise for positive strength.
+    Vortex = 1,
+    /// Pushes particles outwards in periodic bursts.
+    RadialPulse = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animation {
+    Static,
+    /// Moves the center around a circle.
+    Orbit {
+        center: [f32; 2],
+        radius: f32,
+        /// Radians per second.
+        angular_speed: f32,
+    },
+    /// Scales the strength by `sin(2π · frequency · t)`.
+    Oscillate {
+        frequency: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForcePrimitive {
+    pub kind: ForceKind,
+    pub center: [f32; 2],
+    /// Acceleration at the center, fading linearly to zero at `radius`.
+    pub strength: f32,
+    pub radius: f32,
+    pub animation: Animation,
+}
+
+impl ForcePrimitive {
+    pub fn gravity_well(center: [f32; 2], strength: f32, radius: f32) -> Self {
+        Self::new(ForceKind::GravityWell, center, strength, radius)
+    }
+
+    pub fn vortex(center: [f32; 2], strength: f32, radius: f32) -> Self {
+        Self::new(ForceKind::Vortex, center, strength, radius)
+    }
+
+    /// A pulse firing `frequency` times per second.
+    pub fn radial_pulse(center: [f32; 2], strength: f32, radius: f32, frequency: f32) -> Self {
+        Self::new(ForceKind::RadialPulse, center, strength, radius)
+            .animated(Animation::Oscillate { frequency })
+    }
+
+    fn new(kind: ForceKind, center: [f32; 2], strength: f32, radius: f32) -> Self {
+        Self {
+            kind,
+            center,
+            strength,
+            radius,
+            animation: Animation::Static,
+        }
+    }
+
+    pub fn animated(mut self, animation: Animation) -> Self {
+        self.animation = animation;
+        self
+    }
+
+    /// The force as it acts at simulation time `time`.
+    pub fn at(&self, time: f32) -> Force {
+        let mut center = self.center;
+        let mut strength = self.strength;
+
+        match self.animation {
+            Animation::Static => (),
+            Animation::Orbit {
+                center: c,
+                radius,
+                angular_speed,
+            } => {
+                let angle = angular_speed * time;
+                center = [c[0] + radius * angle.cos(), c[1] + radius * angle.sin()];
+            }
+            Animation::Oscillate { frequency } => {
+                let wave = (TAU * frequency * time).sin();
+                // a pulse only ever pushes, the negative half of the wave is a pause
+                strength *= match self.kind {
+                    ForceKind::RadialPulse => wave.max(0.0),
+                    _ => wave,
+                };
+            }
+        }
+
+        Force {
+            kind: self.kind as u32,
+            center,
+            strength,
+            radius: self.radius,
+            _pad: [0; 3],
+        }
+    }
+}
+
+/// Evaluates every primitive at `time` into `out`, reusing its storage.
+pub fn evaluate(primitives: &[ForcePrimitive], time: f32, out: &mut Vec<Force>) {
+    out.clear();
+    out.extend(primitives.iter().map(|f| f.at(time)));
+}
+
+/// A force primitive evaluated at one point in time.
+///
+/// Mirrors `Force` in `sorting.ocl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Force {
+    pub kind: u32,
+    pub center: [f32; 2],
+    pub strength: f32,
+    pub radius: f32,
+    pub _pad: [u32; 3],
+}
+
+impl Force {
+    /// Acceleration this force applies at `pos`. Mirrors `force_acceleration` in `sorting.ocl`.
+    pub fn acceleration(&self, pos: [f32; 2]) -> [f32; 2] {
+        let dx = self.center[0] - pos[0];
+        let dy = self.center[1] - pos[1];
+        let dist = (dx * dx + dy * dy).sqrt().max(1e-4);
+        if dist >= self.radius {
+            return [0.0, 0.0];
+        }
+
+        let scale = self.strength * (1.0 - dist / self.radius) / dist;
+        match self.kind {
+            k if k == ForceKind::GravityWell as u32 => [dx * scale, dy * scale],
+            k if k == ForceKind::Vortex as u32 => [-dy * scale, dx * scale],
+            k if k == ForceKind::RadialPulse as u32 => [-dx * scale, -dy * scale],
+            _ => [0.0, 0.0],
+        }
+    }
+}
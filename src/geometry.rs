@@ -0,0 +1,74 @@
+//! Obstacle outlines given as polygons.
+
+/// A closed polygon, the last point connects back to the first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<[f32; 2]>) -> Self {
+        Self { points }
+    }
+
+    /// An axis aligned rectangle, counter-clockwise from `min`.
+    pub fn rect(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self::new(vec![min, [max[0], min[1]], max, [min[0], max[1]]])
+    }
+
+    /// Every edge from one point to the next, including the closing one.
+    pub fn edges(&self) -> impl Iterator<Item = ([f32; 2], [f32; 2])> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points.iter().copied().zip(next.copied())
+    }
+
+    /// Whether `p` is inside, by the even-odd rule.
+    pub fn contains(&self, p: [f32; 2]) -> bool {
+        self.edges()
+            .filter(|(a, b)| (a[1] > p[1]) != (b[1] > p[1]))
+            .filter(|(a, b)| p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]))
+            .count()
+            % 2
+            == 1
+    }
+
+    /// Min and max corner of the points.
+    pub fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        self.points
+            .iter()
+            .fold(([f32::MAX; 2], [f32::MIN; 2]), |(min, max), p| {
+                (
+                    [min[0].min(p[0]), min[1].min(p[1])],
+                    [max[0].max(p[0]), max[1].max(p[1])],
+                )
+            })
+    }
+}
+
+impl std::str::FromStr for Polygon {
+    type Err = String;
+
+    /// At least three `<x>,<y>` points separated by `;`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points = s
+            .split(';')
+            .map(|point| {
+                let (x, y) = point
+                    .split_once(',')
+                    .ok_or(format!("invalid point `{point}`, expected <x>,<y>"))?;
+                let coord = |c: &str| {
+                    c.trim()
+                        .parse::<f32>()
+                        .map_err(|err| format!("invalid point `{point}`: {err}"))
+                };
+                Ok([coord(x)?, coord(y)?])
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        match points.len() >= 3 {
+            true => Ok(Polygon::new(points)),
+            false => Err(format!(
+                "invalid polygon `{s}`, expected at least three points"
+            )),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Ghost particles behind the walls, so kernel sums near a wall see a full
+//! neighborhood instead of the empty space beyond it.
+//!
+//! Without them the density estimated next to a wall falls off, which makes
+//! fluid at rest against a wall look rarefied and pulls it towards the wall.
+//! Every particle near a [`Wall`](Boundary::Wall) edge has a ghost mirrored
+//! across it, with the velocity into the wall reversed, and one mirrored
+//! across both walls of a corner. The ghosts are never stored: the distance
+//! from a point to a ghost is that from the mirrored point to its particle,
+//! so they are found with the same [`CellList`] as the particles.
+
+use crate::boundary::{Boundaries, Boundary, Edge};
+use crate::neighbors::CellList;
+use crate::sim::Instance;
+
+/// A point mirrored across one or two walls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mirror {
+    pos: [f32; 2],
+    /// Multiplies the velocity of a particle into that of its ghost.
+    flip: [f32; 2],
+}
+
+/// Mirror images of `pos` across the walls within `radius` of it.
+fn mirrors(boundaries: &Boundaries, pos: [f32; 2], radius: f32) -> impl Iterator<Item = Mirror> {
+    let wall = |edge| matches!(boundaries.get(edge), Boundary::Wall(_));
+    let across = |axis: usize, low: Edge, high: Edge| {
+        let x = pos[axis];
+        if wall(low) && x < radius {
+            Some(-x)
+        } else if wall(high) && 1.0 - x < radius {
+            Some(2.0 - x)
+        } else {
+            None
+        }
+    };
+    let mx = across(0, Edge::Left, Edge::Right);
+    let my = across(1, Edge::Bottom, Edge::Top);
+
+    let x = mx.map(|x| Mirror {
+        pos: [x, pos[1]],
+        flip: [-1.0, 1.0],
+    });
+    let y = my.map(|y| Mirror {
+        pos: [pos[0], y],
+        flip: [1.0, -1.0],
+    });
+    let corner = mx.zip(my).map(|(x, y)| Mirror {
+        pos: [x, y],
+        flip: [-1.0, -1.0],
+    });
+    [x, y, corner].into_iter().flatten()
+}
+
+/// Calls `f` with every ghost within the radius of `cells` around `pos`: the
+/// particle it mirrors, its offset from `pos`, its velocity and its distance.
+/// `cells` has to be built from `particles`.
+pub fn for_each_ghost(
+    cells: &CellList,
+    particles: &[Instance],
+    boundaries: &Boundaries,
+    pos: [f32; 2],
+    mut f: impl FnMut(usize, [f32; 2], [f32; 2], f32),
+) {
+    for mirror in mirrors(boundaries, pos, cells.radius()) {
+        cells.for_each_neighbor_near(particles, mirror.pos, |id, d, dist| {
+            let flip = |v: [f32; 2]| [v[0] * mirror.flip[0], v[1] * mirror.flip[1]];
+            // mirroring both points keeps their distance and flips the offset
+            f(id, flip(d), flip(particles[id].vel), dist);
+        });
+    }
+}
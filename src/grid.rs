@@ -0,0 +1,156 @@
+//! Uniform grid over the unit domain `[0, 1)²`.
+//!
+//! This is the reference for the cell math in `sorting.ocl` (`get_cell_index`,
+//! `get_neighbor_cell`, `neighbor_range`, `wrap_position`, `wrap_delta`); any
+//! change here has to be mirrored there.
+
+/// Bit of [`Grid::periodic_mask`] set when the x axis wraps around.
+pub const PERIODIC_X: u32 = 1;
+/// Bit of [`Grid::periodic_mask`] set when the y axis wraps around.
+pub const PERIODIC_Y: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    n_cells: u32,
+    /// Whether each axis wraps around, so that leaving the domain on one side
+    /// re-enters it on the opposite one.
+    periodic: [bool; 2],
+}
+
+impl Grid {
+    /// Creates the finest grid whose cells are at least `cell_size` wide.
+    pub fn new(cell_size: f32) -> Self {
+        let n_cells = (1.0 / cell_size).floor().max(1.0) as u32;
+        Self::with_cells(n_cells)
+    }
+
+    pub fn with_cells(n_cells: u32) -> Self {
+        assert!(n_cells > 0, "grid needs at least one cell");
+        Self {
+            n_cells,
+            periodic: [false; 2],
+        }
+    }
+
+    pub fn with_periodic(mut self, periodic: [bool; 2]) -> Self {
+        self.periodic = periodic;
+        self
+    }
+
+    pub fn periodic(&self) -> [bool; 2] {
+        self.periodic
+    }
+
+    /// [`periodic`](Self::periodic) as the bit set the kernels take.
+    pub fn periodic_mask(&self) -> u32 {
+        let [x, y] = self.periodic;
+        (x as u32 * PERIODIC_X) | (y as u32 * PERIODIC_Y)
+    }
+
+    /// Number of cells along one axis.
+    pub fn n_cells(&self) -> u32 {
+        self.n_cells
+    }
+
+    /// Total number of cells.
+    pub fn cell_count(&self) -> usize {
+        self.n_cells as usize * self.n_cells as usize
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        1.0 / self.n_cells as f32
+    }
+
+    /// Cell coordinates containing `pos`, or `None` if it lies outside the domain.
+    ///
+    /// The domain is half-open: `0.0` is inside, `1.0` is not. NaN is outside.
+    pub fn cell_coords(&self, pos: [f32; 2]) -> Option<[u32; 2]> {
+        let [x, y] = pos;
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            return None;
+        }
+
+        let n = self.n_cells as f32;
+        let last = self.n_cells - 1;
+        Some([((x * n) as u32).min(last), ((y * n) as u32).min(last)])
+    }
+
+    pub fn cell_index(&self, pos: [f32; 2]) -> Option<u32> {
+        self.cell_coords(pos).map(|[x, y]| self.index_of(x, y))
+    }
+
+    pub fn index_of(&self, x: u32, y: u32) -> u32 {
+        x + y * self.n_cells
+    }
+
+    pub fn coords_of(&self, cell: u32) -> [u32; 2] {
+        [cell % self.n_cells, cell / self.n_cells]
+    }
+
+    /// The cell offset by `(dx, dy)` from `cell`, or `None` if that leaves the grid.
+    /// Periodic axes wrap around instead.
+    pub fn neighbor_cell(&self, cell: u32, dx: i32, dy: i32) -> Option<u32> {
+        let [x, y] = self.coords_of(cell);
+        let n = self.n_cells as i64;
+        let offset = |c: u32, d: i32, periodic: bool| {
+            let c = c as i64 + d as i64;
+            match periodic {
+                true => Some(c.rem_euclid(n) as u32),
+                false => (0..n).contains(&c).then_some(c as u32),
+            }
+        };
+
+        Some(self.index_of(
+            offset(x, dx, self.periodic[0])?,
+            offset(y, dy, self.periodic[1])?,
+        ))
+    }
+
+    /// Offsets to visit along `axis` so that every cell within one step is seen
+    /// exactly once. Only differs from `-1..=1` when a periodic axis is too short
+    /// for both directions to reach different cells.
+    fn neighbor_range(&self, axis: usize) -> std::ops::RangeInclusive<i32> {
+        if self.periodic[axis] && self.n_cells < 3 {
+            0..=self.n_cells as i32 - 1
+        } else {
+            -1..=1
+        }
+    }
+
+    /// The 3x3 block of cells around `cell` (including itself) that lies inside the
+    /// grid, wrapping around periodic axes. No cell is returned twice.
+    pub fn neighbors(&self, cell: u32) -> impl Iterator<Item = u32> + '_ {
+        self.neighbor_range(1)
+            .flat_map(|dy| self.neighbor_range(0).map(move |dx| (dx, dy)))
+            .filter_map(move |(dx, dy)| self.neighbor_cell(cell, dx, dy))
+    }
+
+    /// Moves `pos` back into the domain along periodic axes.
+    pub fn wrap_position(&self, pos: [f32; 2]) -> [f32; 2] {
+        let wrap = |x: f32, periodic: bool| {
+            if !periodic {
+                return x;
+            }
+            let wrapped = x - x.floor();
+            // tiny negative values round up to exactly 1, which is outside
+            if wrapped >= 1.0 {
+                0.0
+            } else {
+                wrapped
+            }
+        };
+        [
+            wrap(pos[0], self.periodic[0]),
+            wrap(pos[1], self.periodic[1]),
+        ]
+    }
+
+    /// `a - b`, taking the shorter way around along periodic axes.
+    pub fn wrap_delta(&self, a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+        let delta = |d: f32, periodic: bool| if periodic { d - d.round() } else { d };
+        [
+            delta(a[0] - b[0], self.periodic[0]),
+            delta(a[1] - b[1], self.periodic[1]),
+        ]
+    }
+}
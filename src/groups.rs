@@ -0,0 +1,174 @@
+//! Named subsets of the particles, for following a dyed patch or everything
+//! an inlet brought in.
+//!
+//! A particle can be in any of up to [`MAX_GROUPS`] groups at once, kept as a
+//! bitmask per particle slot. Particles join a group by starting out in its
+//! region or, for groups that take them, by coming out of an inlet. Removed
+//! particles leave every group. A group can give its particles a phase to
+//! start with and a color to be drawn in.
+
+use crate::boundary;
+use crate::phase::Phase;
+use crate::scene::Scene;
+use crate::sim::Instance;
+
+/// Groups per scene at most, one bit each.
+pub const MAX_GROUPS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Particles inside at the start join, as min and max corner.
+    pub region: Option<([f32; 2], [f32; 2])>,
+    /// Whether particles coming out of inlets join.
+    pub inlets: bool,
+    /// Drawn in this color over any other coloring.
+    pub color: Option<u32>,
+    /// Phase the particles in `region` start with.
+    pub phase: Option<Phase>,
+}
+
+impl Group {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            region: None,
+            inlets: false,
+            color: None,
+            phase: None,
+        }
+    }
+
+    pub fn contains(&self, pos: [f32; 2]) -> bool {
+        self.region.is_some_and(|(min, max)| {
+            (min[0]..max[0]).contains(&pos[0]) && (min[1]..max[1]).contains(&pos[1])
+        })
+    }
+}
+
+impl std::str::FromStr for Group {
+    type Err = String;
+
+    /// `<name>[=<x0>,<y0>,<x1>,<y1>][:inlets][:color=<rrggbb>][:phase=<phase>]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let head = parts.next().unwrap_or_default();
+        let mut group = match head.split_once('=') {
+            Some((name, rect)) => Group {
+                region: Some(crate::scene::parse_rect(rect)?),
+                ..Group::new(name)
+            },
+            None => Group::new(head),
+        };
+        if group.name.is_empty() {
+            return Err(format!("invalid group `{s}`, the name is missing"));
+        }
+        for part in parts {
+            match part.split_once('=') {
+                None if part == "inlets" => group.inlets = true,
+                Some(("color", hex)) => {
+                    let rgb = u32::from_str_radix(hex.trim_start_matches('#'), 16)
+                        .ok()
+                        .filter(|_| hex.trim_start_matches('#').len() == 6)
+                        .ok_or(format!("invalid group color `{hex}`, expected rrggbb"))?;
+                    group.color = Some(0xff00_0000 | rgb);
+                }
+                Some(("phase", phase)) => group.phase = Some(phase.parse()?),
+                _ => {
+                    return Err(format!(
+                        "invalid group option `{part}`, expected inlets, color=<rrggbb> or phase=<phase>"
+                    ))
+                }
+            }
+        }
+        Ok(group)
+    }
+}
+
+/// Which groups each particle is in while the simulation runs.
+#[derive(Debug, Clone)]
+pub struct Groups {
+    groups: Vec<Group>,
+    masks: Vec<u32>,
+    /// Groups particles coming out of an inlet join.
+    inlet_mask: u32,
+    /// Which particles were removed last update, to spot slots an inlet reused.
+    removed: Vec<bool>,
+}
+
+impl Groups {
+    /// Starts from [`Scene::group_masks`].
+    pub fn new(scene: &Scene, particles: &[Instance]) -> Self {
+        let inlet_mask = scene
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.inlets)
+            .fold(0, |mask, (i, _)| mask | 1 << i);
+        Self {
+            groups: scene.groups.clone(),
+            masks: scene.group_masks.clone(),
+            inlet_mask,
+            removed: particles.iter().map(boundary::is_removed).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Puts particles that just came out of an inlet into the inlet groups and
+    /// takes removed ones out of all groups.
+    pub fn update(&mut self, particles: &[Instance]) {
+        for (i, p) in particles.iter().enumerate() {
+            let removed = boundary::is_removed(p);
+            if removed {
+                self.masks[i] = 0;
+            } else if self.removed[i] {
+                self.masks[i] = self.inlet_mask;
+            }
+            self.removed[i] = removed;
+        }
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Group bits per particle slot, bit `i` for `groups()[i]`.
+    pub fn masks(&self) -> &[u32] {
+        &self.masks
+    }
+
+    /// The bit of the group called `name`.
+    pub fn mask(&self, name: &str) -> Option<u32> {
+        let i = self.groups.iter().position(|group| group.name == name)?;
+        Some(1 << i)
+    }
+
+    /// Slots of the particles in the group called `name`, empty if there is
+    /// no such group.
+    pub fn members(&self, name: &str) -> Vec<usize> {
+        let mask = self.mask(name).unwrap_or(0);
+        (0..self.masks.len())
+            .filter(|&i| self.masks[i] & mask != 0)
+            .collect()
+    }
+
+    /// Paints the particles of groups with a color in it, the last group a
+    /// particle is in wins.
+    pub fn apply_colors(&self, colors: &mut [u32]) {
+        for (color, &mask) in colors.iter_mut().zip(&self.masks) {
+            let painted = self
+                .groups
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & 1 << i != 0)
+                .filter_map(|(_, group)| group.color)
+                .next_back();
+            if let Some(painted) = painted {
+                *color = painted;
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Running a scene without a window, for batch runs on machines without a
+//! display or GPU. The simulation thread steps as fast as it can, and the
+//! progress is printed every second instead of drawn.
+
+use crate::backend;
+use crate::options::Options;
+use crate::simulation::SimThread;
+use std::thread;
+use std::time::Duration;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs until the simulation stops, after [`Options::steps`] or an error.
+pub fn run(options: Options) -> Result<(), backend::Error> {
+    let sim = SimThread::spawn(options);
+    while !sim.is_finished() {
+        thread::sleep(REPORT_INTERVAL);
+        if let Some(frame) = sim.latest() {
+            println!(
+                "step {} | max speed {:.3} | energy {:.3}",
+                frame.step, frame.stats.max_speed, frame.stats.kinetic_energy,
+            );
+        }
+    }
+    sim.join()
+}
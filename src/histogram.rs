@@ -0,0 +1,153 @@
+//! Distributions of particle speeds and density errors, to judge how well the
+//! solver does at a glance, shown in the top right corner with `--histograms`.
+//!
+//! The OpenCL backend counts the speeds on the device next to its
+//! [stats](crate::stats), the others from the particles. The density error
+//! is the compression beyond rest that the [probes](crate::probe) measure
+//! around each particle.
+
+use crate::boundary;
+use crate::sim::{self, Instance, OverlayVertex};
+
+/// Bins of every histogram, mirrored as `HISTOGRAM_BINS` in `sorting.ocl`.
+pub const BINS: usize = 32;
+/// Density errors binned, as a share of rest density. Anything more
+/// compressed goes into the last bin.
+pub const DENSITY_ERROR_RANGE: f32 = 1.0;
+
+/// Counts of values in [`BINS`] bins of equal width from zero to `max`, with
+/// larger values in the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub max: f32,
+    pub bins: Vec<u32>,
+}
+
+impl Histogram {
+    pub fn new(max: f32) -> Self {
+        Self {
+            max,
+            bins: vec![0; BINS],
+        }
+    }
+
+    pub fn from_values(max: f32, values: impl IntoIterator<Item = f32>) -> Self {
+        let mut histogram = Self::new(max);
+        for value in values {
+            histogram.add(value);
+        }
+        histogram
+    }
+
+    /// Speeds of the particles not [removed](boundary::REMOVED), up to the
+    /// fastest.
+    pub fn speeds(particles: &[Instance]) -> Self {
+        let speeds = particles
+            .iter()
+            .filter(|p| !boundary::is_removed(p))
+            .map(|p| p.vel[0].hypot(p.vel[1]))
+            .collect::<Vec<_>>();
+        let max = speeds.iter().copied().fold(0.0, f32::max);
+        Self::from_values(max, speeds)
+    }
+
+    /// Bin of `value`, the same as the kernel picks.
+    pub fn bin(&self, value: f32) -> usize {
+        let scale = match self.max > 0.0 {
+            true => BINS as f32 / self.max,
+            false => 0.0,
+        };
+        // saturates, NaN ends up in the first bin
+        ((value * scale) as usize).min(BINS - 1)
+    }
+
+    pub fn add(&mut self, value: f32) {
+        let bin = self.bin(value);
+        self.bins[bin] += 1;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.bins.iter().sum()
+    }
+
+    /// Upper edge of the bin where a share `q` of the values is reached, so
+    /// at least that share is below. Zero when empty.
+    pub fn quantile(&self, q: f32) -> f32 {
+        let target = (q.clamp(0.0, 1.0) * self.count() as f32).ceil() as u32;
+        let mut sum = 0;
+        for (i, &count) in self.bins.iter().enumerate() {
+            sum += count;
+            if sum >= target && sum > 0 {
+                return self.max * (i + 1) as f32 / BINS as f32;
+            }
+        }
+        0.0
+    }
+
+    /// A bar per bin in the rectangle from `min` to `max` in normalized device
+    /// coordinates, scaled to the fullest bin, and an outline in `frame`.
+    pub fn vertices(
+        &self,
+        min: [f32; 2],
+        max: [f32; 2],
+        color: u32,
+        frame: u32,
+    ) -> Vec<OverlayVertex> {
+        let mut vertices = vec![];
+        let mut segment = |a: [f32; 2], b: [f32; 2], color: u32| {
+            vertices.push(OverlayVertex { pos: a, color });
+            vertices.push(OverlayVertex { pos: b, color });
+        };
+        let fullest = self.bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let width = (max[0] - min[0]) / self.bins.len() as f32;
+        for (i, &count) in self.bins.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let top = min[1] + (max[1] - min[1]) * count as f32 / fullest;
+            // a few lines side by side fill the bar
+            for j in 1..4 {
+                let x = min[0] + width * (i as f32 + j as f32 / 4.0);
+                segment([x, min[1]], [x, top], color);
+            }
+        }
+        segment([min[0], min[1]], [max[0], min[1]], frame);
+        segment([max[0], min[1]], [max[0], max[1]], frame);
+        segment([max[0], max[1]], [min[0], max[1]], frame);
+        segment([min[0], max[1]], [min[0], min[1]], frame);
+        vertices
+    }
+}
+
+/// The histograms of a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histograms {
+    pub speed: Histogram,
+    pub density_error: Histogram,
+}
+
+/// Screen rectangle of the first histogram, in normalized device coordinates.
+const RIGHT: f32 = 0.97;
+const WIDTH: f32 = 0.4;
+const TOP: f32 = 0.97;
+const HEIGHT: f32 = 0.16;
+const GAP: f32 = 0.04;
+
+impl Histograms {
+    /// Both histograms in the top right corner, the speeds on top.
+    pub fn vertices(&self, frame: u32) -> Vec<OverlayVertex> {
+        let colors = [
+            sim::rgba_to_u32(80, 160, 250, 255),
+            sim::rgba_to_u32(240, 80, 80, 255),
+        ];
+        [&self.speed, &self.density_error]
+            .into_iter()
+            .zip(colors)
+            .enumerate()
+            .flat_map(|(i, (histogram, color))| {
+                let top = TOP - i as f32 * (HEIGHT + GAP);
+                histogram.vertices([RIGHT - WIDTH, top - HEIGHT], [RIGHT, top], color, frame)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,125 @@
+//! Where the time of a frame went, as a stacked bar along the bottom of the
+//! window and a line in its title, toggled with `--timings` or T.
+//!
+//! The simulation parts come from the backend, measured on the device for
+//! OpenCL, and are per step. The render pass is measured with wgpu timestamp
+//! queries where the device has them, present is wall clock time waiting on
+//! the surface.
+
+use crate::backend::StepTimings;
+use crate::sim::{self, OverlayVertex};
+
+/// Seconds the whole width of the bar stands for.
+pub const SCALE: f32 = 1.0 / 30.0;
+/// A tick on the bar marks the time a frame has at this rate.
+const TICK_FPS: f32 = 60.0;
+/// Screen rectangle of the bar, in normalized device coordinates.
+const LEFT: f32 = -0.97;
+const WIDTH: f32 = 1.94;
+const BOTTOM: f32 = -0.97;
+const HEIGHT: f32 = 0.03;
+/// Horizontal lines filling the height of the bar.
+const LINES: usize = 6;
+
+/// How long the parts of the last frame took, in seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTimings {
+    /// `None` for backends that don't keep track.
+    pub step: Option<StepTimings>,
+    /// Uploading the particles and everything else drawn to the GPU.
+    pub instances: f32,
+    /// `None` without timestamp queries.
+    pub render: Option<f32>,
+    pub present: f32,
+}
+
+impl FrameTimings {
+    /// Name, seconds and color of every part measured, in drawing order.
+    pub fn parts(&self) -> Vec<(&'static str, f32, u32)> {
+        let mut parts = vec![];
+        if let Some(step) = &self.step {
+            parts.extend([
+                ("upload", step.upload, sim::rgba_to_u32(170, 120, 250, 255)),
+                (
+                    "integrate",
+                    step.integrate,
+                    sim::rgba_to_u32(90, 210, 110, 255),
+                ),
+                ("grid", step.grid, sim::rgba_to_u32(250, 200, 60, 255)),
+                ("solve", step.solve, sim::rgba_to_u32(240, 80, 80, 255)),
+                (
+                    "readback",
+                    step.readback,
+                    sim::rgba_to_u32(240, 140, 200, 255),
+                ),
+            ]);
+        }
+        parts.push((
+            "instances",
+            self.instances,
+            sim::rgba_to_u32(150, 150, 150, 255),
+        ));
+        if let Some(render) = self.render {
+            parts.push(("render", render, sim::rgba_to_u32(80, 160, 250, 255)));
+        }
+        parts.push(("present", self.present, sim::rgba_to_u32(80, 220, 220, 255)));
+        parts
+    }
+
+    /// Every part in milliseconds, for the window title.
+    pub fn summary(&self) -> String {
+        let mut summary = self
+            .parts()
+            .iter()
+            .map(|(name, secs, _)| format!("{name} {:.2}ms", secs * 1e3))
+            .collect::<Vec<_>>();
+        if let Some(sizes) = self.step.and_then(|step| step.work_groups) {
+            summary.push(match sizes.fused {
+                Some(fused) => format!("work groups {} / fused {fused}", sizes.integrate),
+                None => format!(
+                    "work groups {} / {} / {}",
+                    sizes.integrate, sizes.sort, sizes.collide
+                ),
+            });
+        }
+        summary.join(" | ")
+    }
+
+    /// Line segments of the bar, two vertices each: the parts one after the
+    /// other, [`SCALE`] seconds to the full width, then the frame around it
+    /// in `frame` with a tick at a 60 fps frame.
+    pub fn vertices(&self, frame: u32) -> Vec<OverlayVertex> {
+        let mut vertices = vec![];
+        let mut segment = |a: [f32; 2], b: [f32; 2], color: u32| {
+            vertices.push(OverlayVertex { pos: a, color });
+            vertices.push(OverlayVertex { pos: b, color });
+        };
+        let x = |secs: f32| LEFT + WIDTH * (secs / SCALE).clamp(0.0, 1.0);
+        let top = BOTTOM + HEIGHT;
+
+        let mut start = 0.0;
+        for (_, secs, color) in self.parts() {
+            let end = start + secs.max(0.0);
+            if x(end) > x(start) {
+                for i in 0..LINES {
+                    let y = BOTTOM + HEIGHT * (i as f32 + 0.5) / LINES as f32;
+                    segment([x(start), y], [x(end), y], color);
+                }
+            }
+            start = end;
+        }
+
+        let right = LEFT + WIDTH;
+        segment([LEFT, BOTTOM], [right, BOTTOM], frame);
+        segment([right, BOTTOM], [right, top], frame);
+        segment([right, top], [LEFT, top], frame);
+        segment([LEFT, top], [LEFT, BOTTOM], frame);
+        let tick = x(1.0 / TICK_FPS);
+        segment(
+            [tick, BOTTOM - HEIGHT * 0.5],
+            [tick, top + HEIGHT * 0.5],
+            frame,
+        );
+        vertices
+    }
+}
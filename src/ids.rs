@@ -0,0 +1,62 @@
+//! Identities that stay with a particle for as long as it lives.
+//!
+//! The backends keep every particle in its slot, but a slot freed by an open
+//! edge or an expired lifetime is handed to the next particle an inlet emits.
+//! Anything that follows particles over time, like an export, refers to them
+//! by id instead, which is never reused.
+
+use crate::boundary;
+use crate::sim::Instance;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct ParticleIds {
+    ids: Vec<Option<u32>>,
+    slots: HashMap<u32, usize>,
+    next: u32,
+}
+
+impl ParticleIds {
+    /// Numbers the particles in slot order.
+    pub fn new(particles: &[Instance]) -> Self {
+        let mut ids = Self {
+            ids: vec![None; particles.len()],
+            ..Self::default()
+        };
+        ids.update(particles);
+        ids
+    }
+
+    /// Forgets the ids of removed particles and hands new ones to particles
+    /// that showed up since the last update.
+    pub fn update(&mut self, particles: &[Instance]) {
+        for (slot, p) in particles.iter().enumerate() {
+            match (self.ids[slot], boundary::is_removed(p)) {
+                (Some(id), true) => {
+                    self.slots.remove(&id);
+                    self.ids[slot] = None;
+                }
+                (None, false) => {
+                    self.ids[slot] = Some(self.next);
+                    self.slots.insert(self.next, slot);
+                    self.next += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Id per slot, `None` for removed particles.
+    pub fn ids(&self) -> &[Option<u32>] {
+        &self.ids
+    }
+
+    pub fn id(&self, slot: usize) -> Option<u32> {
+        self.ids.get(slot).copied().flatten()
+    }
+
+    /// Where the particle with `id` is, `None` once it is gone.
+    pub fn slot(&self, id: u32) -> Option<usize> {
+        self.slots.get(&id).copied()
+    }
+}
@@ -0,0 +1,91 @@
+//! A 2-d tree over particle positions, for finding neighbors without relying
+//! on a grid. Too slow to step with, but simple enough to check the grids
+//! against, see [`crate::verify`].
+
+use crate::boundary;
+use crate::sim::Instance;
+
+/// Points with their ids, ordered so that every subslice has its median
+/// along the axis of its depth in the middle, lower coordinates before it and
+/// higher after.
+#[derive(Debug, Clone, Default)]
+pub struct KdTree {
+    points: Vec<([f32; 2], usize)>,
+}
+
+impl KdTree {
+    /// Points with NaN coordinates are left out.
+    pub fn new(points: impl IntoIterator<Item = (usize, [f32; 2])>) -> Self {
+        let mut points = points
+            .into_iter()
+            .filter(|(_, pos)| !pos[0].is_nan() && !pos[1].is_nan())
+            .map(|(id, pos)| (pos, id))
+            .collect::<Vec<_>>();
+        build(&mut points, 0);
+        Self { points }
+    }
+
+    /// The particles that haven't been [removed](boundary::REMOVED), by index.
+    pub fn from_particles(particles: &[Instance]) -> Self {
+        Self::new(
+            particles
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !boundary::is_removed(p))
+                .map(|(id, p)| (id, p.pos)),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Ids of the points within `radius` of `pos`, in ascending order.
+    pub fn within(&self, pos: [f32; 2], radius: f32) -> Vec<usize> {
+        let mut found = vec![];
+        query(&self.points, 0, pos, radius, &mut found);
+        found.sort_unstable();
+        found
+    }
+}
+
+fn build(points: &mut [([f32; 2], usize)], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+    let axis = depth % 2;
+    let mid = points.len() / 2;
+    points.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    let (lower, rest) = points.split_at_mut(mid);
+    build(lower, depth + 1);
+    build(&mut rest[1..], depth + 1);
+}
+
+fn query(
+    points: &[([f32; 2], usize)],
+    depth: usize,
+    pos: [f32; 2],
+    radius: f32,
+    found: &mut Vec<usize>,
+) {
+    if points.is_empty() {
+        return;
+    }
+    let mid = points.len() / 2;
+    let (median, id) = points[mid];
+    let d = [pos[0] - median[0], pos[1] - median[1]];
+    if d[0] * d[0] + d[1] * d[1] <= radius * radius {
+        found.push(id);
+    }
+    let axis = depth % 2;
+    if d[axis] <= radius {
+        query(&points[..mid], depth + 1, pos, radius, found);
+    }
+    if d[axis] >= -radius {
+        query(&points[mid + 1..], depth + 1, pos, radius, found);
+    }
+}
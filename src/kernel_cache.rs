@@ -0,0 +1,83 @@
+//! Compiled OpenCL programs kept on disk, so later runs load the binary
+//! instead of compiling `sorting.ocl` again, which takes seconds on some
+//! drivers.
+//!
+//! A binary is only good for the device and driver that built it, from the
+//! same source with the same options, so all of those go into its
+//! [`key`]. A different driver version or an edited kernel simply misses
+//! the cache, and stale entries are never read again. The files live in the
+//! user's cache directory, see [`KernelCache::in_user_cache`], and deleting
+//! them at any time is fine.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the cache under the user's cache directory.
+const DIR: &str = "pos-based-fluids/kernels";
+
+/// Identifies a program binary: 64 bit FNV-1a over the device, the source
+/// and the build options, in hex.
+pub fn key(device: &str, source: &str, options: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in [device, source, options] {
+        // a separator, so moving text from one part to the next changes the key
+        for &byte in part.as_bytes().iter().chain(&[0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelCache {
+    dir: PathBuf,
+}
+
+impl KernelCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Under `$XDG_CACHE_HOME`, `~/Library/Caches` on macOS, `%LOCALAPPDATA%`
+    /// on Windows or `~/.cache` otherwise. `None` if none of those are set.
+    pub fn in_user_cache() -> Option<Self> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        let base = match var("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None if cfg!(target_os = "macos") => PathBuf::from(var("HOME")?).join("Library/Caches"),
+            None if cfg!(windows) => PathBuf::from(var("LOCALAPPDATA")?),
+            None => PathBuf::from(var("HOME")?).join(".cache"),
+        };
+        Some(Self::new(base.join(DIR)))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension("bin")
+    }
+
+    /// The binary stored under `key`, `None` if there is none or it can't
+    /// be read.
+    pub fn load(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.path(key))
+            .ok()
+            .filter(|binary| !binary.is_empty())
+    }
+
+    /// Stores `binary` under `key`. Written to a temporary file first, so
+    /// another run starting at the same time never reads half a binary.
+    pub fn store(&self, key: &str, binary: &[u8]) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let partial = self
+            .dir
+            .join(format!("{key}.{}.partial", std::process::id()));
+        std::fs::write(&partial, binary)?;
+        std::fs::rename(&partial, self.path(key)).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })
+    }
+}
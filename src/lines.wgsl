@@ -0,0 +1,24 @@
+struct CameraUniform {
+    transform: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// from the theme, RGBA 0-1
+@group(1) @binding(0)
+var<uniform> color: vec4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.transform * vec4<f32>(model.position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return color;
+}
@@ -1,5 +1,29 @@
-use pos_based_fluids::run;
+use pos_based_fluids::headless;
+use pos_based_fluids::options::Options;
+use pos_based_fluids::stress;
 
 fn main() {
-    pollster::block_on(run());
+    let options = Options::from_env().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+
+    if options.stress {
+        if let Err(err) = stress::run(options) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    #[cfg(feature = "render")]
+    if !options.headless {
+        pollster::block_on(pos_based_fluids::app::run(options));
+        return;
+    }
+
+    if let Err(err) = headless::run(options) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,128 @@
+//! What the particles do when they hit an obstacle, set per obstacle.
+//!
+//! The [distance field](crate::sdf) remembers the obstacle closest to each of
+//! its samples, and the collision looks up the material of that obstacle in
+//! a table indexed by its position in
+//! [`Scene::obstacles`](crate::scene::Scene::obstacles). The default material
+//! stops the particles going in and lets them slide along, as obstacles
+//! did before they had materials.
+//!
+//! Whether fluid beads up on a surface or spreads over it comes down to how
+//! strongly the surface pulls on the fluid against how strongly the fluid
+//! holds together. The collisions keep the fluid together, so the
+//! [wetting](Material::wetting) of a surface shifts its pull instead: a
+//! hydrophilic surface draws the fluid close and it spreads out in a thin
+//! film, a hydrophobic one keeps it at arm's length and it gathers into
+//! drops that roll off. [Walls](crate::boundary::Wall) have a wetting too.
+
+/// Acceleration a wetting of 1 adds to the pull right at a surface, about
+/// gravity. Mirrors `WETTING_PULL` in `sorting.ocl`.
+pub const WETTING_PULL: f32 = 10.0;
+
+/// Acceleration towards a surface right at it, from its adhesion and wetting.
+pub fn surface_pull(adhesion: f32, wetting: f32) -> f32 {
+    adhesion + wetting * WETTING_PULL
+}
+
+/// Mirrors `Material` in `sorting.ocl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Material {
+    /// Share of the speed into the obstacle a particle bounces back with,
+    /// 0 stops it and 1 reflects it fully.
+    pub restitution: f32,
+    /// Share of the speed along the surface a particle loses on impact.
+    pub friction: f32,
+    /// Acceleration towards the obstacle right at its surface, fading
+    /// linearly to zero at `range`, like [`Wall::adhesion`](crate::boundary::Wall::adhesion).
+    pub adhesion: f32,
+    pub range: f32,
+    /// From -1 for a surface the fluid beads up on to 1 for one it spreads
+    /// over, the cosine of the contact angle. Neutral at 0.
+    pub wetting: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            restitution: 0.0,
+            friction: 0.0,
+            adhesion: 0.0,
+            range: 0.05,
+            wetting: 0.0,
+        }
+    }
+}
+
+impl Material {
+    /// Response of a particle hitting the surface with `normal` pointing out
+    /// of the obstacle. Nothing happens to particles leaving it.
+    /// Mirrors `respond` in `sorting.ocl`.
+    pub fn respond(&self, vel: &mut [f32; 2], normal: [f32; 2]) {
+        let into = vel[0] * normal[0] + vel[1] * normal[1];
+        if into >= 0.0 {
+            return;
+        }
+        let keep = 1.0 - self.friction;
+        for i in 0..2 {
+            let tangential = vel[i] - into * normal[i];
+            vel[i] = tangential * keep - self.restitution * into * normal[i];
+        }
+    }
+
+    /// Acceleration towards the surface at `distance` outside of it, along
+    /// `normal` pointing out of the obstacle.
+    pub fn adhesion(&self, distance: f32, normal: [f32; 2]) -> [f32; 2] {
+        if !(0.0..self.range).contains(&distance) {
+            return [0.0, 0.0];
+        }
+        let pull = surface_pull(self.adhesion, self.wetting) * (1.0 - distance / self.range);
+        [-pull * normal[0], -pull * normal[1]]
+    }
+}
+
+impl std::str::FromStr for Material {
+    type Err = String;
+
+    /// Comma separated `restitution=`, `friction=`, `adhesion=`, `range=` and
+    /// `wetting=`, the ones left out keep their defaults.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut material = Material::default();
+        for part in s.split(',').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=').ok_or(format!(
+                "invalid material `{s}`, expected <property>=<value>"
+            ))?;
+            let value: f32 = value
+                .parse()
+                .map_err(|err| format!("invalid material {key} `{value}`: {err}"))?;
+            match key {
+                "restitution" => material.restitution = value,
+                "friction" => material.friction = value,
+                "adhesion" => material.adhesion = value,
+                "range" => material.range = value,
+                "wetting" => material.wetting = value,
+                _ => {
+                    return Err(format!(
+                        "unknown material property `{key}`, expected restitution, \
+                         friction, adhesion, range or wetting"
+                    ))
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&material.restitution) || !(0.0..=1.0).contains(&material.friction)
+        {
+            return Err(format!(
+                "invalid material `{s}`, restitution and friction go from 0 to 1"
+            ));
+        }
+        if !(-1.0..=1.0).contains(&material.wetting) {
+            return Err(format!("invalid material `{s}`, wetting goes from -1 to 1"));
+        }
+        if material.range <= 0.0 {
+            return Err(format!(
+                "invalid material `{s}`, the range has to be positive"
+            ));
+        }
+        Ok(material)
+    }
+}
@@ -0,0 +1,92 @@
+//! Steady-state frame and step times for benchmarks, printed on exit with
+//! `--measure`.
+//!
+//! The first frames of a run are slow for reasons that have little to do
+//! with the simulation: the driver builds shaders and kernels on first use,
+//! the surface gets configured, caches and clocks are cold. A
+//! [`Measurement`] leaves out whatever happened in the first
+//! [`warmup`](Measurement::warmup) frames and keeps every sample after, so
+//! the mean and percentiles describe the run once it settled. The window
+//! measures the time from one frame to the next, the simulation thread the
+//! wall clock time of every step.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Default)]
+pub struct Measurement {
+    /// Frames left out at the start.
+    pub warmup: u64,
+    /// In seconds.
+    samples: Vec<f32>,
+}
+
+impl Measurement {
+    pub fn new(warmup: u64) -> Self {
+        Self {
+            warmup,
+            samples: vec![],
+        }
+    }
+
+    /// Takes in `seconds` measured during frame `frame`, counting from 0.
+    pub fn record(&mut self, frame: u64, seconds: f32) {
+        if frame >= self.warmup {
+            self.samples.push(seconds);
+        }
+    }
+
+    /// `None` while nothing was measured after the warmup.
+    pub fn summary(&self) -> Option<Summary> {
+        Summary::of(&self.samples)
+    }
+}
+
+/// Mean and percentiles of some times, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    /// Percentiles by nearest rank, `None` without samples.
+    pub fn of(samples: &[f32]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| {
+            let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        let sum = samples.iter().map(|&s| s as f64).sum::<f64>();
+        Some(Self {
+            count: samples.len(),
+            mean: (sum / samples.len() as f64) as f32,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.3}ms | p50 {:.3}ms | p95 {:.3}ms | p99 {:.3}ms | max {:.3}ms over {}",
+            self.mean * 1e3,
+            self.p50 * 1e3,
+            self.p95 * 1e3,
+            self.p99 * 1e3,
+            self.max * 1e3,
+            self.count,
+        )
+    }
+}
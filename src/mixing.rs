@@ -0,0 +1,113 @@
+//! Miscible fluids that blend into each other.
+//!
+//! Every particle starts with the color of its [phase](crate::phase), and
+//! colors even out between neighbors like the [dye](crate::dye) does, so two
+//! fluids meeting turn into their mixture over time.
+
+use crate::boundary;
+use crate::neighbors::CellList;
+use crate::scene::Scene;
+use crate::sim::{self, Instance};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixParams {
+    /// Neighborhood radius the colors mix over.
+    pub radius: f32,
+    /// Fraction of the difference to the neighborhood average that evens out per second.
+    pub rate: f32,
+}
+
+impl Default for MixParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            rate: 0.5,
+        }
+    }
+}
+
+fn unpack(color: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((color >> shift) & 0xff) as f32 / 255.0)
+}
+
+pub struct ColorMix {
+    params: MixParams,
+    colors: Vec<[f32; 3]>,
+    /// Next colors while mixing.
+    scratch: Vec<[f32; 3]>,
+    /// Color of particles coming out of an inlet, that of the first phase.
+    inflow: [f32; 3],
+    /// Which particles were removed last step, to spot slots an inlet reused.
+    removed: Vec<bool>,
+    cells: CellList,
+}
+
+impl ColorMix {
+    pub fn new(params: MixParams, scene: &Scene) -> Self {
+        Self {
+            cells: CellList::new(params.radius),
+            params,
+            colors: scene
+                .phase_ids
+                .iter()
+                .map(|&phase| unpack(scene.phases[phase as usize].color))
+                .collect(),
+            scratch: vec![],
+            inflow: unpack(scene.phases[0].color),
+            removed: scene.particles.iter().map(boundary::is_removed).collect(),
+        }
+    }
+
+    /// Gives particles that just came out of an inlet the inflow color, then
+    /// lets the colors mix for `dt`.
+    pub fn step(&mut self, particles: &[Instance], dt: f32) {
+        for (i, p) in particles.iter().enumerate() {
+            let removed = boundary::is_removed(p);
+            if self.removed[i] && !removed {
+                self.colors[i] = self.inflow;
+            }
+            self.removed[i] = removed;
+        }
+
+        self.cells.build(particles);
+        let rate = (self.params.rate * dt).min(1.0);
+
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.colors);
+        for (i, p) in particles.iter().enumerate() {
+            let mut sum = [0.0; 3];
+            let mut weight = 0.0;
+            self.cells
+                .for_each_neighbor(particles, p.pos, |j, _, dist| {
+                    let w = 1.0 - dist / self.params.radius;
+                    for (s, c) in sum.iter_mut().zip(self.colors[j]) {
+                        *s += c * w;
+                    }
+                    weight += w;
+                });
+            // the particle itself is always part of its neighborhood
+            if weight > 0.0 {
+                let own = self.colors[i];
+                for ((next, s), c) in self.scratch[i].iter_mut().zip(sum).zip(own) {
+                    *next += (s / weight - c) * rate;
+                }
+            }
+        }
+        std::mem::swap(&mut self.colors, &mut self.scratch);
+    }
+
+    /// The mixed colors, removed particles are transparent.
+    pub fn colors(&self, particles: &[Instance]) -> Vec<u32> {
+        self.colors
+            .iter()
+            .zip(particles)
+            .map(|(&[r, g, b], p)| match boundary::is_removed(p) {
+                true => 0,
+                false => {
+                    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0) as u8;
+                    sim::rgba_to_u32(channel(r), channel(g), channel(b), 255)
+                }
+            })
+            .collect()
+    }
+}
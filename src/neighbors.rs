@@ -0,0 +1,145 @@
+//! Host-side fixed radius neighbor search, for the systems that run next to a backend.
+
+use crate::grid::Grid;
+use crate::sim::Instance;
+use std::ops::Range;
+
+/// Particle ids bucketed by grid cell, rebuilt from scratch with [`build`](Self::build).
+#[derive(Debug, Clone)]
+pub struct CellList {
+    radius: f32,
+    grid: Grid,
+    /// Particle ids sorted by cell, `cell_start[c]..cell_start[c + 1]` is cell `c`.
+    sorted: Vec<u32>,
+    cell_start: Vec<u32>,
+    /// Next free slot per cell while sorting.
+    cursor: Vec<u32>,
+}
+
+impl CellList {
+    pub fn new(radius: f32) -> Self {
+        let grid = Grid::new(radius);
+        Self {
+            radius,
+            grid,
+            sorted: vec![],
+            cell_start: vec![0; grid.cell_count() + 1],
+            cursor: vec![],
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    /// Particle ids in the order they were sorted into the cells.
+    pub fn sorted(&self) -> &[u32] {
+        &self.sorted
+    }
+
+    /// Where the particles of `cell` are in [`sorted`](Self::sorted).
+    pub fn cell_range(&self, cell: u32) -> Range<usize> {
+        self.cell_start[cell as usize] as usize..self.cell_start[cell as usize + 1] as usize
+    }
+
+    /// Where the particles of the cells around the one `pos` is in are in
+    /// [`sorted`](Self::sorted), a range per row as the cells of a row are
+    /// next to each other and the grid doesn't wrap around. None outside the
+    /// domain.
+    pub fn neighbor_rows(&self, pos: [f32; 2]) -> impl Iterator<Item = Range<usize>> + '_ {
+        let last = self.grid.n_cells() - 1;
+        let start = move |cell: u32| self.cell_start[cell as usize] as usize;
+        self.grid
+            .cell_coords(pos)
+            .into_iter()
+            .flat_map(move |[x, y]| {
+                let (first, end) = (x.saturating_sub(1), (x + 1).min(last));
+                (y.saturating_sub(1)..=(y + 1).min(last)).map(move |row| {
+                    start(self.grid.index_of(first, row))..start(self.grid.index_of(end, row) + 1)
+                })
+            })
+    }
+
+    /// Counting sort of the particles into the grid cells. Particles outside the
+    /// domain are left out.
+    pub fn build(&mut self, particles: &[Instance]) {
+        self.cell_start.iter_mut().for_each(|c| *c = 0);
+        for p in particles {
+            if let Some(cell) = self.grid.cell_index(p.pos) {
+                self.cell_start[cell as usize + 1] += 1;
+            }
+        }
+        for c in 1..self.cell_start.len() {
+            self.cell_start[c] += self.cell_start[c - 1];
+        }
+
+        self.cursor.clear();
+        self.cursor.extend_from_slice(&self.cell_start);
+        self.sorted.clear();
+        self.sorted
+            .resize(*self.cell_start.last().unwrap() as usize, 0);
+        for (id, p) in particles.iter().enumerate() {
+            if let Some(cell) = self.grid.cell_index(p.pos) {
+                let slot = &mut self.cursor[cell as usize];
+                self.sorted[*slot as usize] = id as u32;
+                *slot += 1;
+            }
+        }
+    }
+
+    /// Calls `f` with every particle within [`radius`](Self::radius) of `pos`, its
+    /// offset from `pos` and its distance. `particles` has to be what the list was
+    /// last built from.
+    pub fn for_each_neighbor(
+        &self,
+        particles: &[Instance],
+        pos: [f32; 2],
+        f: impl FnMut(usize, [f32; 2], f32),
+    ) {
+        if let Some(own_cell) = self.grid.cell_index(pos) {
+            self.visit(own_cell, particles, pos, f);
+        }
+    }
+
+    /// Like [`for_each_neighbor`](Self::for_each_neighbor), but also for a
+    /// `pos` less than [`radius`](Self::radius) outside the domain, such as a
+    /// point [mirrored across a wall](crate::ghost).
+    pub fn for_each_neighbor_near(
+        &self,
+        particles: &[Instance],
+        pos: [f32; 2],
+        f: impl FnMut(usize, [f32; 2], f32),
+    ) {
+        if !(pos[0].is_finite() && pos[1].is_finite()) {
+            return;
+        }
+        let inside = pos.map(|x| x.clamp(0.0, 1.0f32.next_down()));
+        if let Some(own_cell) = self.grid.cell_index(inside) {
+            self.visit(own_cell, particles, pos, f);
+        }
+    }
+
+    /// The particles within the radius of `pos` in the cells around `own_cell`.
+    fn visit(
+        &self,
+        own_cell: u32,
+        particles: &[Instance],
+        pos: [f32; 2],
+        mut f: impl FnMut(usize, [f32; 2], f32),
+    ) {
+        for cell in self.grid.neighbors(own_cell) {
+            for &id in &self.sorted[self.cell_range(cell)] {
+                let other = particles[id as usize].pos;
+                let d = [other[0] - pos[0], other[1] - pos[1]];
+                let dist = (d[0] * d[0] + d[1] * d[1]).sqrt();
+                if dist < self.radius {
+                    f(id as usize, d, dist);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Smooth random fields for ambient motion, such as [wind](crate::wind).
+//!
+//! [`simplex`] is Gustavson and McEwan's tiling simplex noise, psrdnoise:
+//! 2D simplex noise that repeats with a given period and whose gradients
+//! rotate by an angle, which animates it without it drifting anywhere. It
+//! returns the gradient along with the value, for fields that are built
+//! from derivatives of the noise. Everything is in single precision and
+//! done the same way in `sorting.ocl`.
+
+/// The period closest to `cells` noise cells across the unit domain that
+/// [`simplex`] tiles with along both axes, an even number of at least 2.
+pub fn tiling_period(cells: f32) -> f32 {
+    ((cells * 0.5).round() * 2.0).max(2.0)
+}
+
+/// GLSL's `mod`, the result takes the sign of `y`.
+fn floor_mod(x: f32, y: f32) -> f32 {
+    x - y * (x / y).floor()
+}
+
+/// Noise at `x` in about `[-1, 1]` and its gradient. Repeats every
+/// `period` along each axis with a period above zero, which has to be a
+/// whole number along x and an even one along y to line up. The gradients
+/// at the lattice points are rotated by `alpha` radians.
+/// Mirrors `simplex` in `sorting.ocl`.
+pub fn simplex(x: [f32; 2], period: [f32; 2], alpha: f32) -> (f32, [f32; 2]) {
+    // the lattice skewed into right triangles
+    let uv = [x[0] + x[1] * 0.5, x[1]];
+    let i0 = uv.map(f32::floor);
+    let o1 = match uv[0] - i0[0] >= uv[1] - i0[1] {
+        true => [1.0, 0.0],
+        false => [0.0, 1.0],
+    };
+    let v0 = [i0[0] - i0[1] * 0.5, i0[1]];
+    let v1 = [v0[0] + o1[0] - o1[1] * 0.5, v0[1] + o1[1]];
+    let v2 = [v0[0] + 0.5, v0[1] + 1.0];
+
+    let mut noise = 0.0;
+    let mut gradient = [0.0, 0.0];
+    for v in [v0, v1, v2] {
+        let mut wrapped = v;
+        for axis in 0..2 {
+            if period[axis] > 0.0 {
+                wrapped[axis] = floor_mod(v[axis], period[axis]);
+            }
+        }
+        // back to the integer lattice, to hash
+        let iu = (wrapped[0] + 0.5 * wrapped[1] + 0.5).floor();
+        let iv = (wrapped[1] + 0.5).floor();
+        let mut hash = floor_mod(iu, 289.0);
+        hash = floor_mod((hash * 51.0 + 2.0) * hash + iv, 289.0);
+        hash = floor_mod((hash * 34.0 + 10.0) * hash, 289.0);
+        let psi = hash * 0.07482 + alpha;
+        let g = [psi.cos(), psi.sin()];
+
+        let d = [x[0] - v[0], x[1] - v[1]];
+        let w = (0.8 - d[0] * d[0] - d[1] * d[1]).max(0.0);
+        let w2 = w * w;
+        let g_dot_d = g[0] * d[0] + g[1] * d[1];
+        noise += w2 * w2 * g_dot_d;
+        let dw = -8.0 * w2 * w * g_dot_d;
+        gradient[0] += w2 * w2 * g[0] + dw * d[0];
+        gradient[1] += w2 * w2 * g[1] + dw * d[1];
+    }
+    (10.9 * noise, gradient.map(|g| 10.9 * g))
+}
@@ -0,0 +1,28 @@
+// resolves the targets shader.wgsl `fs_oit` accumulated into, over the frame
+@group(0) @binding(0)
+var accum: texture_2d<f32>;
+@group(0) @binding(1)
+var revealage: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+// the unit square covers the screen
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(model.position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    // share of the background that shows through
+    let revealage = textureLoad(revealage, pixel, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+    let accum = textureLoad(accum, pixel, 0);
+    let color = accum.rgb / max(accum.a, 1e-5);
+    return vec4(color, 1.0 - revealage);
+}
@@ -0,0 +1,1521 @@
+use crate::age::Ages;
+use crate::backend::{self, Backend, Config, StepTimings, WorkGroupSizes};
+use crate::boundary::{Boundaries, Boundary, Edge, Emitter, FreeList};
+use crate::buffer_pool::{BufferPool, PooledBuffer};
+use crate::capabilities::{DeviceCaps, Svm, Variants};
+use crate::forces::{self, Force, ForcePrimitive};
+use crate::grid::Grid;
+use crate::histogram::{self, Histogram};
+use crate::ids::ParticleIds;
+use crate::kernel_cache::{self, KernelCache};
+use crate::material::Material;
+use crate::paddle::{self, Blades, Paddle};
+use crate::precision::{self, Precise};
+use crate::readback::{Completion, HostMirror};
+use crate::scene::{Scene, SceneEdit};
+use crate::sim::{Instance, SimParams};
+use crate::stats::ParticleStats;
+use crate::thermal::Thermal;
+use crate::trace::DeviceSpan;
+use crate::turbulence::{Surface, Turbulence};
+use crate::verify::GridCells;
+use crate::wind::Wind;
+use crate::{MAX_PARTICLES_PER_CELL, PARTICLE_RADIUS, PROGRAM_SOURCE, TIME_STEP};
+use opencl3 as cl;
+use opencl3::{kernel, types};
+use std::io;
+use std::mem::size_of;
+use std::ptr;
+
+/// Events that later commands have to wait for.
+///
+/// Keeps the raw handles next to the owning [`cl::event::Event`]s, so handing
+/// out a wait list doesn't allocate once both vectors have grown to size.
+#[derive(Default)]
+struct EventPool {
+    events: Vec<cl::event::Event>,
+    raw: Vec<types::cl_event>,
+}
+
+impl EventPool {
+    fn push(&mut self, event: cl::event::Event) {
+        self.raw.push(event.get());
+        self.events.push(event);
+    }
+
+    /// Drops all events but keeps the storage.
+    fn clear(&mut self) {
+        self.raw.clear();
+        self.events.clear();
+    }
+
+    fn replace(&mut self, event: cl::event::Event) {
+        self.clear();
+        self.push(event);
+    }
+
+    fn wait_list(&self) -> &[types::cl_event] {
+        &self.raw
+    }
+}
+
+/// Part of a step a command belongs to, see [`StepTimings`].
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Upload,
+    Integrate,
+    Grid,
+    Solve,
+    Readback,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Upload => "upload",
+            Stage::Integrate => "integrate",
+            Stage::Grid => "grid",
+            Stage::Solve => "solve",
+            Stage::Readback => "readback",
+        }
+    }
+}
+
+/// The commands of the current step, kept to read how long they took on the
+/// device once the step is done. The queue is created with profiling on.
+#[derive(Default)]
+struct Profile {
+    events: Vec<(Stage, &'static str, cl::event::Event)>,
+    /// The commands of the step whose read is in flight, done once it is.
+    finished: Vec<(Stage, &'static str, cl::event::Event)>,
+    /// The commands of the last step, see [`Backend::device_spans`].
+    spans: Vec<DeviceSpan>,
+}
+
+impl Profile {
+    /// Keeps a handle of its own to `event`, the command called `name`.
+    fn record(
+        &mut self,
+        stage: Stage,
+        name: &'static str,
+        event: &cl::event::Event,
+    ) -> cl::Result<()> {
+        unsafe { cl::event::retain_event(event.get()) }.map_err(cl::error_codes::ClError)?;
+        self.events
+            .push((stage, name, cl::event::Event::new(event.get())));
+        Ok(())
+    }
+
+    /// Sets the commands recorded so far aside as those of one step, for
+    /// [`timings`](Self::timings) once they are complete.
+    fn end_step(&mut self) {
+        self.finished.append(&mut self.events);
+    }
+
+    /// Device time per stage of the commands of the step set aside with
+    /// [`end_step`](Self::end_step), which have to be complete.
+    fn timings(&mut self) -> cl::Result<StepTimings> {
+        let mut timings = StepTimings::default();
+        self.spans.clear();
+        for (stage, name, event) in self.finished.drain(..) {
+            let start = event.profiling_command_start()?;
+            let end = event.profiling_command_end()?;
+            self.spans.push(DeviceSpan {
+                name,
+                category: stage.name(),
+                start,
+                end,
+            });
+            let seconds = (end - start) as f32 * 1e-9;
+            *match stage {
+                Stage::Upload => &mut timings.upload,
+                Stage::Integrate => &mut timings.integrate,
+                Stage::Grid => &mut timings.grid,
+                Stage::Solve => &mut timings.solve,
+                Stage::Readback => &mut timings.readback,
+            } += seconds;
+        }
+        Ok(timings)
+    }
+}
+
+/// The grid copied out of the buffers `sort_particles` fills, for
+/// `collide_particles` to read, see [`Config::grid_images`].
+struct GridImages {
+    counts: cl::memory::Image,
+    ids: cl::memory::Image,
+    /// Texels of `counts` and `ids` along each axis.
+    count_size: [usize; 2],
+    id_size: [usize; 2],
+}
+
+impl GridImages {
+    /// Sizes for a grid of `n_cells` squared with `n_per_cell` ids each: a
+    /// texel per cell, and a row of texels per cell.
+    fn sizes(n_cells: usize, n_per_cell: usize) -> ([usize; 2], [usize; 2]) {
+        ([n_cells, n_cells], [n_cells * n_per_cell, n_cells])
+    }
+
+    fn new(context: &cl::context::Context, grid: &Grid) -> cl::Result<Self> {
+        let (count_size, id_size) = Self::sizes(grid.n_cells() as usize, MAX_PARTICLES_PER_CELL);
+        let image = |size: [usize; 2], data_type| {
+            let format = types::cl_image_format {
+                image_channel_order: cl::memory::CL_R,
+                image_channel_data_type: data_type,
+            };
+            let desc = types::cl_image_desc {
+                image_type: cl::memory::CL_MEM_OBJECT_IMAGE2D,
+                image_width: size[0],
+                image_height: size[1],
+                image_depth: 1,
+                image_array_size: 1,
+                image_row_pitch: 0,
+                image_slice_pitch: 0,
+                num_mip_levels: 0,
+                num_samples: 0,
+                buffer: ptr::null_mut(),
+            };
+            unsafe {
+                cl::memory::Image::create(
+                    context,
+                    cl::memory::CL_MEM_READ_ONLY,
+                    &format,
+                    &desc,
+                    ptr::null_mut(),
+                )
+            }
+        };
+        Ok(Self {
+            counts: image(count_size, cl::memory::CL_UNSIGNED_INT32)?,
+            ids: image(id_size, cl::memory::CL_SIGNED_INT32)?,
+            count_size,
+            id_size,
+        })
+    }
+}
+
+pub struct OpenClState {
+    /// The read into `incoming` in flight, waited for before it is
+    /// dropped, so it comes first.
+    pending_read: Option<Completion>,
+    /// The particles as of the last read that arrived, what the host works on.
+    particles: HostMirror<Instance>,
+    /// Where the next read lands, swapped with `particles` once it has.
+    incoming: HostMirror<Instance>,
+    /// The host changed `particles` since the device last had them, so the
+    /// next step uploads them. Otherwise the device keeps its own.
+    dirty: bool,
+    particle_buffer: PooledBuffer<Instance>,
+    /// Where the buffers sized by the particle count come from, so they can
+    /// be handed back and reused when it changes.
+    buffer_pool: BufferPool,
+    count_per_cell: Vec<u32>,
+    count_buffer: cl::memory::Buffer<u32>,
+    cell_ids: Vec<i32>,
+    id_buffer: cl::memory::Buffer<i32>,
+    grid_images: Option<GridImages>,
+    grid: Grid,
+    /// Bound to every kernel that needs the grid, the step or sleeping.
+    _params_buffer: cl::memory::Buffer<SimParams>,
+
+    gravity: [f32; 2],
+    force_primitives: Vec<ForcePrimitive>,
+    /// `force_primitives` at `time`, the source of the pending force upload.
+    forces: Vec<Force>,
+    force_buffer: cl::memory::Buffer<Force>,
+    paddles: Vec<Paddle>,
+    /// `paddles` at `time`, the source of the pending paddle upload.
+    blades: Vec<Blades>,
+    blade_buffer: cl::memory::Buffer<Blades>,
+    /// Steps each particle has been slower than [`Config::sleep_speed`], only used on the device.
+    quiet_buffer: cl::memory::Buffer<u32>,
+    /// The particles in double precision, only used on the device and only
+    /// with [`Config::double_precision`] on devices that support it.
+    precise_buffer: Option<cl::memory::Buffer<Precise>>,
+    thermal: Thermal,
+    wind: Wind,
+    turbulence: Turbulence,
+    /// Updated on the host and uploaded every step while the turbulence is active.
+    surface: Surface,
+    surface_buffer: cl::memory::Buffer<f32>,
+    _region_buffer: cl::memory::Buffer<[f32; 4]>,
+    /// Updated on the host and uploaded every step while [`Thermal::is_active`].
+    temperatures: Vec<f32>,
+    temperature_buffer: cl::memory::Buffer<f32>,
+    _terrain_buffer: cl::memory::Buffer<f32>,
+    _distance_buffer: cl::memory::Buffer<f32>,
+    _sdf_obstacle_buffer: cl::memory::Buffer<u32>,
+    _material_buffer: cl::memory::Buffer<Material>,
+    time: f32,
+    boundaries: Boundaries,
+    free: FreeList,
+    emitter: Emitter,
+    ages: Ages,
+    ids: ParticleIds,
+
+    device: cl::device::Device,
+    _context: cl::context::Context,
+    queue: cl::command_queue::CommandQueue,
+    integrate_kernel: kernel::Kernel,
+    sort_kernel: kernel::Kernel,
+    collide_kernel: kernel::Kernel,
+    /// `order_cells`, only built with [`Config::deterministic`].
+    order_kernel: Option<kernel::Kernel>,
+    /// `sort_and_collide_particles` and its work-group size, only built when
+    /// the fused path is in use.
+    fused: Option<(kernel::Kernel, usize)>,
+    /// Launches of the other per-particle kernels.
+    dispatches: Dispatches,
+    active_events: EventPool,
+    profile: Profile,
+    /// Of the last step, from `profile`.
+    timings: StepTimings,
+    /// Reported with the `timings`.
+    work_groups: WorkGroupSizes,
+
+    reduce_kernel: kernel::Kernel,
+    reduce_partials_kernel: kernel::Kernel,
+    _partial_buffer: cl::memory::Buffer<ParticleStats>,
+    stats_buffer: cl::memory::Buffer<ParticleStats>,
+    reduce_work_size: usize,
+    reduce_groups: usize,
+    /// Target of the asynchronous stats read, must not move while `pending_stats` is set.
+    stats_readback: Vec<ParticleStats>,
+    pending_stats: Option<cl::event::Event>,
+    stats: ParticleStats,
+    histogram_kernel: kernel::Kernel,
+    histogram_buffer: cl::memory::Buffer<u32>,
+    /// Read with the stats, under the same rules.
+    histogram_readback: Vec<u32>,
+    /// Range of the histogram in flight, the fastest speed known when it started.
+    histogram_max: f32,
+    speeds: Histogram,
+}
+
+/// Bytes of idle buffers the [`BufferPool`] of a state holds on to.
+const MAX_IDLE_BUFFER_BYTES: usize = 64 << 20;
+
+/// Most work items a single launch of the per-particle kernels covers. Bigger
+/// particle counts are split into several launches, as some drivers reject
+/// or time out on huge global sizes long before the device limits say so.
+pub const MAX_DISPATCH: usize = 1 << 22;
+
+/// How the per-particle kernels are split into launches: global sizes are
+/// rounded up to a multiple of the work-group size, and no launch covers more
+/// than `max_chunk` work items. The kernels skip the ids past the particle
+/// count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dispatch {
+    pub local_size: usize,
+    pub max_chunk: usize,
+}
+
+impl Dispatch {
+    /// `max_chunk` is rounded down to a multiple of `local_size`, but covers at
+    /// least one work group.
+    pub fn new(local_size: usize, max_chunk: usize) -> Self {
+        let local_size = local_size.max(1);
+        Self {
+            local_size,
+            max_chunk: (max_chunk / local_size).max(1) * local_size,
+        }
+    }
+
+    /// Global offset and size of every launch over `n` work items.
+    pub fn chunks(&self, n: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let total = n.div_ceil(self.local_size) * self.local_size;
+        (0..total)
+            .step_by(self.max_chunk)
+            .map(move |offset| (offset, self.max_chunk.min(total - offset)))
+    }
+}
+
+/// The [`Dispatch`] of each per-particle kernel, in work groups the size
+/// that kernel prefers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dispatches {
+    integrate: Dispatch,
+    sort: Dispatch,
+    collide: Dispatch,
+}
+
+/// The largest work-group size `kernel` runs with on `device` that is a
+/// multiple of its preferred multiple, which is a power of two in practice.
+fn preferred_local_size(
+    kernel: &cl::kernel::Kernel,
+    device: &cl::device::Device,
+) -> cl::Result<usize> {
+    let limit = kernel.get_work_group_size(device.id())?;
+    let multiple = kernel.get_work_group_size_multiple(device.id())?.max(1);
+    Ok(match limit >= multiple {
+        true => limit / multiple * multiple,
+        false => limit,
+    })
+}
+
+/// Builds `sorting.ocl` with `options`, from the binary a previous run left in
+/// the [`KernelCache`] if there is one, storing it there otherwise.
+fn build_program(
+    context: &cl::context::Context,
+    device: &cl::device::Device,
+    options: &str,
+) -> Result<cl::program::Program, backend::Error> {
+    use cl::program::Program;
+
+    let cache = KernelCache::in_user_cache();
+    let identity = [
+        device.name(),
+        device.vendor(),
+        device.version(),
+        device.driver_version(),
+    ]
+    .into_iter()
+    .collect::<cl::Result<Vec<_>>>()?
+    .join("/");
+    let key = kernel_cache::key(&identity, PROGRAM_SOURCE, options);
+
+    if let Some(binary) = cache.as_ref().and_then(|cache| cache.load(&key)) {
+        match Program::create_and_build_from_binary(context, &[&binary], options) {
+            Ok(program) => {
+                log::info!("loaded the kernels from the cache, {key}");
+                return Ok(program);
+            }
+            Err(err) => log::warn!("could not load the cached kernels {key}, building them: {err}"),
+        }
+    }
+
+    let program = Program::create_and_build_from_source(context, PROGRAM_SOURCE, options)
+        .map_err(|log| format!("could not build the kernels:\n{log}"))?;
+    if let Some(cache) = &cache {
+        let stored = program
+            .get_binaries()
+            .map_err(|err| io::Error::other(err.to_string()))
+            .and_then(|binaries| match binaries.first() {
+                Some(binary) => cache.store(&key, binary),
+                None => Ok(()),
+            });
+        if let Err(err) = stored {
+            log::warn!(
+                "could not cache the kernels in {}: {err}",
+                cache.dir().display()
+            );
+        }
+    }
+    Ok(program)
+}
+
+/// Upper bound for the work-group size of the reduction kernels.
+const MAX_REDUCE_WORK_SIZE: usize = 256;
+/// Upper bound for the number of partial results of the first reduction pass.
+const MAX_REDUCE_GROUPS: usize = 64;
+
+impl OpenClState {
+    /// Runs on the first GPU, or the first CPU device if there is no GPU.
+    /// Fails if there is neither.
+    pub fn new(scene: &Scene, config: &Config) -> Result<Self, backend::Error> {
+        use cl::{
+            command_queue, context, device, kernel, memory,
+            types::{cl_float, cl_int, cl_uint},
+        };
+
+        let device_id = [
+            ("GPU", device::CL_DEVICE_TYPE_GPU),
+            ("CPU", device::CL_DEVICE_TYPE_CPU),
+        ]
+        .into_iter()
+        .find_map(|(kind, ty)| {
+            let id = device::get_all_devices(ty).ok()?.into_iter().next()?;
+            Some((kind, id))
+        });
+        let Some((kind, device_id)) = device_id else {
+            return Err("no OpenCL GPU or CPU device found".into());
+        };
+
+        let device = device::Device::new(device_id);
+        println!("OpenCL {kind} device: {:?}", device.name());
+
+        let context = context::Context::from_device(&device)?;
+
+        let queue = command_queue::CommandQueue::create_default_with_properties(
+            &context,
+            command_queue::CL_QUEUE_PROFILING_ENABLE,
+            device.queue_on_device_preferred_size()? as cl_uint,
+        )?;
+
+        let grid_size: cl_float = PARTICLE_RADIUS * 2.0;
+
+        let grid = Grid::new(grid_size).with_periodic(scene.boundaries.periodic());
+        let particles = HostMirror::new(&context, &queue, &scene.particles);
+        let incoming = HostMirror::new(&context, &queue, &scene.particles);
+
+        let count_per_cell = vec![0 as cl_uint; grid.cell_count()];
+        let cell_ids = vec![-1; grid.cell_count() * MAX_PARTICLES_PER_CELL];
+
+        let caps = DeviceCaps::new(
+            &device.extensions()?,
+            device.local_mem_size()?,
+            Svm::from_bits(device.svm_mem_capability()),
+        );
+        let caps = match device.image_support()? {
+            true => {
+                caps.with_image2d_max(device.image2d_max_width()?, device.image2d_max_height()?)
+            }
+            false => caps,
+        };
+        println!("OpenCL device capabilities: {caps}");
+        let fused_local_mem =
+            grid.cell_count() * size_of::<cl_uint>() + cell_ids.len() * size_of::<cl_int>();
+        // the ids are the larger of the two images
+        let (_, id_image_size) = GridImages::sizes(grid.n_cells() as usize, MAX_PARTICLES_PER_CELL);
+        let variants = Variants::select(
+            &caps,
+            (particles.len() <= config.fused_threshold && !config.deterministic)
+                .then_some(fused_local_mem),
+        )
+        .with_grid_images(&caps, config.grid_images, id_image_size)
+        .with_fp64(&caps, config.double_precision);
+        if config.grid_images && !variants.grid_images {
+            log::warn!("the device can't hold the grid in images, reading it from buffers");
+        }
+        if config.double_precision && !variants.fp64 {
+            log::warn!("the device lacks cl_khr_fp64, keeping the particles in single precision");
+        }
+        let options = variants.build_options();
+        log::info!("building the kernels with `{options}`");
+        let program = build_program(&context, &device, &options)?;
+
+        let integrate_kernel = kernel::Kernel::create(&program, "integrate_particles")?;
+        let sort_kernel = kernel::Kernel::create(&program, "sort_particles")?;
+        let collide_kernel = kernel::Kernel::create(&program, "collide_particles")?;
+        let order_kernel = match config.deterministic {
+            true => Some(kernel::Kernel::create(&program, "order_cells")?),
+            false => None,
+        };
+        let fused_kernel = match variants.fused {
+            true => Some(kernel::Kernel::create(
+                &program,
+                "sort_and_collide_particles",
+            )?),
+            false => None,
+        };
+        let reduce_kernel = kernel::Kernel::create(&program, "reduce_particles")?;
+        let reduce_partials_kernel = kernel::Kernel::create(&program, "reduce_partials")?;
+        let histogram_kernel = kernel::Kernel::create(&program, "histogram_speeds")?;
+
+        let count_buffer = unsafe {
+            memory::Buffer::<cl_uint>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE,
+                grid.cell_count(),
+                ptr::null_mut(),
+            )?
+        };
+
+        // left uninitialized, the first step uploads the particles
+        let mut buffer_pool = BufferPool::new(MAX_IDLE_BUFFER_BYTES);
+        let particle_buffer =
+            buffer_pool.acquire(&context, memory::CL_MEM_READ_WRITE, particles.len())?;
+
+        let id_buffer = unsafe {
+            memory::Buffer::<cl_int>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE,
+                cell_ids.len(),
+                ptr::null_mut(),
+            )?
+        };
+
+        let grid_images = match variants.grid_images {
+            true => Some(GridImages::new(&context, &grid)?),
+            false => None,
+        };
+
+        let mut quiet_steps = vec![0 as cl_uint; particles.len()];
+        let quiet_buffer = unsafe {
+            memory::Buffer::<cl_uint>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE | memory::CL_MEM_COPY_HOST_PTR,
+                quiet_steps.len(),
+                quiet_steps.as_mut_ptr().cast(),
+            )?
+        };
+
+        let precise_buffer = match variants.fp64 {
+            true => {
+                let mut precise = precision::track(&particles);
+                Some(unsafe {
+                    memory::Buffer::<Precise>::create(
+                        &context,
+                        memory::CL_MEM_READ_WRITE | memory::CL_MEM_COPY_HOST_PTR,
+                        precise.len(),
+                        precise.as_mut_ptr().cast(),
+                    )?
+                })
+            }
+            false => None,
+        };
+
+        let mut temperatures = vec![0 as cl_float; particles.len()];
+        let temperature_buffer = unsafe {
+            memory::Buffer::<cl_float>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                temperatures.len(),
+                temperatures.as_mut_ptr().cast(),
+            )?
+        };
+
+        // never empty, a zero sized buffer can't be created
+        let mut heights = match scene.terrain.is_empty() {
+            true => vec![0 as cl_float],
+            false => scene.terrain.heights.clone(),
+        };
+        let terrain_buffer = unsafe {
+            memory::Buffer::<cl_float>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                heights.len(),
+                heights.as_mut_ptr().cast(),
+            )?
+        };
+        let n_heights = match scene.terrain.is_empty() {
+            true => 0,
+            false => heights.len() as cl_uint,
+        };
+
+        let field = &scene.distance_field;
+        let mut distances = match field.is_empty() {
+            true => vec![0 as cl_float],
+            false => field.distances.clone(),
+        };
+        let distance_buffer = unsafe {
+            memory::Buffer::<cl_float>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                distances.len(),
+                distances.as_mut_ptr().cast(),
+            )?
+        };
+        let sdf_resolution = match field.is_empty() {
+            true => 0,
+            false => field.resolution as cl_uint,
+        };
+        let mut sdf_obstacles = match field.is_empty() {
+            true => vec![0 as cl_uint],
+            false => field.obstacles.clone(),
+        };
+        let sdf_obstacle_buffer = unsafe {
+            memory::Buffer::<cl_uint>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                sdf_obstacles.len(),
+                sdf_obstacles.as_mut_ptr().cast(),
+            )?
+        };
+        // never empty, the kernel falls back to the default past the end
+        let mut materials = field.materials.clone();
+        let n_materials = materials.len() as cl_uint;
+        if materials.is_empty() {
+            materials.push(Material::default());
+        }
+        let material_buffer = unsafe {
+            memory::Buffer::<Material>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                materials.len(),
+                materials.as_mut_ptr().cast(),
+            )?
+        };
+
+        let mut params = [SimParams::new(&grid, config, particles.len())];
+        let params_buffer = unsafe {
+            memory::Buffer::<SimParams>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                1,
+                params.as_mut_ptr().cast(),
+            )?
+        };
+
+        let force_buffer = unsafe {
+            memory::Buffer::<Force>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY,
+                scene.forces.len().max(1),
+                ptr::null_mut(),
+            )?
+        };
+
+        let blade_buffer = unsafe {
+            memory::Buffer::<Blades>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY,
+                scene.paddles.len().max(1),
+                ptr::null_mut(),
+            )?
+        };
+
+        let surface = Surface::default();
+        let surface_buffer = unsafe {
+            memory::Buffer::<cl_float>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY,
+                surface.heights.len(),
+                ptr::null_mut(),
+            )?
+        };
+        let mut regions = scene.turbulence.region_bounds();
+        let n_regions = regions.len() as cl_uint;
+        if regions.is_empty() {
+            regions.push([0.0; 4]);
+        }
+        let region_buffer = unsafe {
+            memory::Buffer::<[cl_float; 4]>::create(
+                &context,
+                memory::CL_MEM_READ_ONLY | memory::CL_MEM_COPY_HOST_PTR,
+                regions.len(),
+                regions.as_mut_ptr().cast(),
+            )?
+        };
+
+        // the arguments never change, so they are bound once here instead of every step
+        unsafe {
+            integrate_kernel.set_arg(0, &particle_buffer.buffer)?;
+            integrate_kernel.set_arg(1, &params_buffer)?;
+            integrate_kernel.set_arg(2, &force_buffer)?;
+            integrate_kernel.set_arg(3, &(scene.forces.len() as cl_uint))?;
+            integrate_kernel.set_arg(4, &scene.boundaries.open_mask())?;
+            integrate_kernel.set_arg(5, &quiet_buffer)?;
+            let (adhesion, range) = scene.boundaries.wall_params();
+            integrate_kernel.set_arg(6, &scene.boundaries.wall_mask())?;
+            integrate_kernel.set_arg(7, &adhesion)?;
+            integrate_kernel.set_arg(8, &range)?;
+            integrate_kernel.set_arg(9, &temperature_buffer)?;
+            integrate_kernel.set_arg(10, &scene.thermal.buoyancy)?;
+            integrate_kernel.set_arg(11, &terrain_buffer)?;
+            integrate_kernel.set_arg(12, &n_heights)?;
+            integrate_kernel.set_arg(13, &scene.gravity)?;
+            integrate_kernel.set_arg(14, &blade_buffer)?;
+            integrate_kernel.set_arg(15, &(scene.paddles.len() as cl_uint))?;
+            let (wavemakers, flaps) = scene.boundaries.wavemaker_masks();
+            integrate_kernel.set_arg(16, &wavemakers)?;
+            integrate_kernel.set_arg(17, &flaps)?;
+            integrate_kernel.set_arg(20, &distance_buffer)?;
+            integrate_kernel.set_arg(21, &sdf_resolution)?;
+            integrate_kernel.set_arg(22, &sdf_obstacle_buffer)?;
+            integrate_kernel.set_arg(23, &material_buffer)?;
+            integrate_kernel.set_arg(24, &n_materials)?;
+            integrate_kernel.set_arg(27, &surface_buffer)?;
+            integrate_kernel.set_arg(28, &region_buffer)?;
+            integrate_kernel.set_arg(29, &n_regions)?;
+            integrate_kernel.set_arg(30, &0.0f32)?;
+            if let Some(precise_buffer) = &precise_buffer {
+                integrate_kernel.set_arg(31, precise_buffer)?;
+            }
+
+            sort_kernel.set_arg(0, &count_buffer)?;
+            sort_kernel.set_arg(1, &id_buffer)?;
+            sort_kernel.set_arg(2, &particle_buffer.buffer)?;
+            sort_kernel.set_arg(3, &params_buffer)?;
+            if let Some(order_kernel) = &order_kernel {
+                order_kernel.set_arg(0, &count_buffer)?;
+                order_kernel.set_arg(1, &id_buffer)?;
+                order_kernel.set_arg(2, &particle_buffer.buffer)?;
+                order_kernel.set_arg(3, &params_buffer)?;
+            }
+
+            match &grid_images {
+                Some(images) => {
+                    collide_kernel.set_arg(0, &images.counts)?;
+                    collide_kernel.set_arg(1, &images.ids)?;
+                }
+                None => {
+                    collide_kernel.set_arg(0, &count_buffer)?;
+                    collide_kernel.set_arg(1, &id_buffer)?;
+                }
+            }
+            collide_kernel.set_arg(2, &particle_buffer.buffer)?;
+            collide_kernel.set_arg(3, &params_buffer)?;
+            collide_kernel.set_arg(4, &quiet_buffer)?;
+        }
+
+        let fused = if let Some(fused_kernel) = fused_kernel {
+            let work_size = fused_kernel
+                .get_work_group_size(device.id())?
+                .min(particles.len().max(1));
+
+            unsafe {
+                fused_kernel.set_arg(0, &particle_buffer.buffer)?;
+                fused_kernel.set_arg_local_buffer(1, grid.cell_count() * size_of::<cl_uint>())?;
+                fused_kernel.set_arg_local_buffer(2, cell_ids.len() * size_of::<cl_int>())?;
+                fused_kernel.set_arg(3, &params_buffer)?;
+                fused_kernel.set_arg(4, &quiet_buffer)?;
+            }
+
+            log::info!("using the fused sort/collide kernel with {work_size} work items");
+            Some((fused_kernel, work_size))
+        } else {
+            None
+        };
+
+        let dispatch = |kernel| -> cl::Result<Dispatch> {
+            Ok(Dispatch::new(
+                preferred_local_size(kernel, &device)?,
+                MAX_DISPATCH,
+            ))
+        };
+        let dispatches = Dispatches {
+            integrate: dispatch(&integrate_kernel)?,
+            sort: dispatch(&sort_kernel)?,
+            collide: dispatch(&collide_kernel)?,
+        };
+        let work_groups = WorkGroupSizes {
+            integrate: dispatches.integrate.local_size,
+            sort: dispatches.sort.local_size,
+            collide: dispatches.collide.local_size,
+            fused: fused.as_ref().map(|(_, size)| *size),
+        };
+        log::info!("work-group sizes: {work_groups:?}");
+
+        // the tree reduction needs a power of two work-group size
+        let reduce_limit = reduce_kernel
+            .get_work_group_size(device.id())?
+            .min(reduce_partials_kernel.get_work_group_size(device.id())?)
+            .min(MAX_REDUCE_WORK_SIZE);
+        let reduce_work_size = 1 << reduce_limit.ilog2();
+        let reduce_groups = particles
+            .len()
+            .div_ceil(reduce_work_size)
+            .clamp(1, MAX_REDUCE_GROUPS);
+
+        let partial_buffer = unsafe {
+            memory::Buffer::<ParticleStats>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE,
+                reduce_groups,
+                ptr::null_mut(),
+            )?
+        };
+
+        let stats_buffer = unsafe {
+            memory::Buffer::<ParticleStats>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE,
+                1,
+                ptr::null_mut(),
+            )?
+        };
+
+        let scratch_size = reduce_work_size * size_of::<ParticleStats>();
+        unsafe {
+            reduce_kernel.set_arg(0, &particle_buffer.buffer)?;
+            reduce_kernel.set_arg(1, &(particles.len() as cl_uint))?;
+            reduce_kernel.set_arg(2, &partial_buffer)?;
+            reduce_kernel.set_arg_local_buffer(3, scratch_size)?;
+
+            reduce_partials_kernel.set_arg(0, &partial_buffer)?;
+            reduce_partials_kernel.set_arg(1, &(reduce_groups as cl_uint))?;
+            reduce_partials_kernel.set_arg(2, &stats_buffer)?;
+            reduce_partials_kernel.set_arg_local_buffer(3, scratch_size)?;
+        }
+
+        let histogram_buffer = unsafe {
+            memory::Buffer::<cl_uint>::create(
+                &context,
+                memory::CL_MEM_READ_WRITE,
+                histogram::BINS,
+                ptr::null_mut(),
+            )?
+        };
+        unsafe {
+            histogram_kernel.set_arg(0, &particle_buffer.buffer)?;
+            histogram_kernel.set_arg(1, &(particles.len() as cl_uint))?;
+            histogram_kernel.set_arg(3, &histogram_buffer)?;
+        }
+
+        Ok(Self {
+            pending_read: None,
+            particles,
+            incoming,
+            dirty: true,
+            particle_buffer,
+            buffer_pool,
+            count_per_cell,
+            count_buffer,
+            cell_ids,
+            id_buffer,
+            grid_images,
+            grid,
+            _params_buffer: params_buffer,
+            gravity: scene.gravity,
+            force_primitives: scene.forces.clone(),
+            forces: Vec::with_capacity(scene.forces.len()),
+            force_buffer,
+            paddles: scene.paddles.clone(),
+            blades: Vec::with_capacity(scene.paddles.len()),
+            blade_buffer,
+            quiet_buffer,
+            precise_buffer,
+            thermal: scene.thermal.clone(),
+            wind: scene.wind,
+            turbulence: scene.turbulence.clone(),
+            surface,
+            surface_buffer,
+            _region_buffer: region_buffer,
+            temperatures,
+            temperature_buffer,
+            _terrain_buffer: terrain_buffer,
+            _distance_buffer: distance_buffer,
+            _sdf_obstacle_buffer: sdf_obstacle_buffer,
+            _material_buffer: material_buffer,
+            time: 0.0,
+            boundaries: scene.boundaries,
+            free: FreeList::from_particles(&scene.particles),
+            emitter: Emitter::default(),
+            ages: Ages::new(&scene.particles),
+            ids: ParticleIds::new(&scene.particles),
+            active_events: EventPool::default(),
+            profile: Profile::default(),
+            timings: StepTimings::default(),
+            work_groups,
+            device,
+            queue,
+            _context: context,
+            integrate_kernel,
+            sort_kernel,
+            order_kernel,
+            collide_kernel,
+            fused,
+            dispatches,
+            reduce_kernel,
+            reduce_partials_kernel,
+            _partial_buffer: partial_buffer,
+            stats_buffer,
+            reduce_work_size,
+            reduce_groups,
+            stats_readback: vec![ParticleStats::default()],
+            pending_stats: None,
+            stats: ParticleStats::default(),
+            histogram_kernel,
+            histogram_buffer,
+            histogram_readback: vec![0; histogram::BINS],
+            histogram_max: 0.0,
+            speeds: Histogram::new(0.0),
+        })
+    }
+
+    /// Applies `edit` and rebinds the integrate arguments it may have changed.
+    pub fn edit(&mut self, edit: &SceneEdit) -> Result<(), backend::Error> {
+        // the paddles are evaluated and uploaded every step, moving one
+        // takes nothing more
+        edit.apply_to(
+            &mut self.gravity,
+            &mut self.force_primitives,
+            &mut self.paddles,
+            &mut self.boundaries,
+        )?;
+
+        let (adhesion, range) = self.boundaries.wall_params();
+        unsafe {
+            if let SceneEdit::Damping(damping) = *edit {
+                self.integrate_kernel.set_arg(30, &damping)?;
+            }
+            self.integrate_kernel
+                .set_arg(4, &self.boundaries.open_mask())?;
+            self.integrate_kernel
+                .set_arg(6, &self.boundaries.wall_mask())?;
+            self.integrate_kernel.set_arg(7, &adhesion)?;
+            self.integrate_kernel.set_arg(8, &range)?;
+            self.integrate_kernel.set_arg(13, &self.gravity)?;
+            let (wavemakers, flaps) = self.boundaries.wavemaker_masks();
+            self.integrate_kernel.set_arg(16, &wavemakers)?;
+            self.integrate_kernel.set_arg(17, &flaps)?;
+        }
+        Ok(())
+    }
+
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.buffer_pool
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    /// Enqueues `kernel` over all particles after the currently active events,
+    /// in as many launches as its `dispatch` asks for. Each launch waits for
+    /// the one before, the event of the last is returned. All are profiled as
+    /// `stage` under `name`.
+    fn enqueue_kernel(
+        &mut self,
+        kernel: types::cl_kernel,
+        name: &'static str,
+        dispatch: Dispatch,
+        stage: Stage,
+    ) -> cl::Result<cl::event::Event> {
+        let mut last: Option<cl::event::Event> = None;
+        for (offset, size) in dispatch.chunks(self.particles.len().max(1)) {
+            let previous = last.as_ref().map(|event| [event.get()]);
+            let wait_list = match &previous {
+                Some(event) => &event[..],
+                None => self.active_events.wait_list(),
+            };
+            let event = unsafe {
+                self.queue.enqueue_nd_range_kernel(
+                    kernel,
+                    1,
+                    &offset,
+                    &size,
+                    &dispatch.local_size,
+                    wait_list,
+                )?
+            };
+            self.profile.record(stage, name, &event)?;
+            last = Some(event);
+        }
+        Ok(last.expect("at least one launch"))
+    }
+
+    /// Enqueues a step without waiting for the device. Only with an inlet
+    /// does it wait for the read of the step before, as spawning particles
+    /// changes them on the host.
+    pub fn step(&mut self) -> cl::Result<()> {
+        if self.has_inlets() {
+            self.wait_read()?;
+        }
+        self.enqueue_integrate()?;
+
+        if let Some((kernel, work_size)) = &self.fused {
+            return self.step_fused(kernel.get(), *work_size);
+        }
+
+        let counts = unsafe {
+            self.queue.enqueue_fill_buffer(
+                &mut self.count_buffer,
+                &[0],
+                0,
+                self.count_per_cell.len() * std::mem::size_of::<u32>(),
+                &[],
+            )?
+        };
+        self.profile.record(Stage::Grid, "clear counts", &counts)?;
+        self.active_events.push(counts);
+
+        let ids = unsafe {
+            self.queue.enqueue_fill_buffer(
+                &mut self.id_buffer,
+                &[-1],
+                0,
+                self.cell_ids.len() * std::mem::size_of::<i32>(),
+                &[],
+            )?
+        };
+        self.profile.record(Stage::Grid, "clear ids", &ids)?;
+        self.active_events.push(ids);
+
+        let sorting = self.enqueue_kernel(
+            self.sort_kernel.get(),
+            "sort_particles",
+            self.dispatches.sort,
+            Stage::Grid,
+        )?;
+        self.active_events.replace(sorting);
+
+        if let Some(order_kernel) = &self.order_kernel {
+            let cells = self.count_per_cell.len();
+            let ordering = unsafe {
+                self.queue.enqueue_nd_range_kernel(
+                    order_kernel.get(),
+                    1,
+                    ptr::null(),
+                    &cells,
+                    ptr::null(),
+                    self.active_events.wait_list(),
+                )?
+            };
+            self.profile.record(Stage::Grid, "order_cells", &ordering)?;
+            self.active_events.replace(ordering);
+        }
+
+        if let Some(images) = &mut self.grid_images {
+            let origin = [0; 3];
+            let count_region = [images.count_size[0], images.count_size[1], 1];
+            let id_region = [images.id_size[0], images.id_size[1], 1];
+            let wait_list = self.active_events.wait_list();
+            let counts = unsafe {
+                self.queue.enqueue_copy_buffer_to_image(
+                    &self.count_buffer,
+                    &mut images.counts,
+                    0,
+                    origin.as_ptr(),
+                    count_region.as_ptr(),
+                    wait_list,
+                )?
+            };
+            let ids = unsafe {
+                self.queue.enqueue_copy_buffer_to_image(
+                    &self.id_buffer,
+                    &mut images.ids,
+                    0,
+                    origin.as_ptr(),
+                    id_region.as_ptr(),
+                    wait_list,
+                )?
+            };
+            self.profile.record(Stage::Grid, "copy counts", &counts)?;
+            self.profile.record(Stage::Grid, "copy ids", &ids)?;
+            self.active_events.replace(counts);
+            self.active_events.push(ids);
+        }
+
+        let colliding = self.enqueue_kernel(
+            self.collide_kernel.get(),
+            "collide_particles",
+            self.dispatches.collide,
+            Stage::Solve,
+        )?;
+        self.active_events.replace(colliding);
+
+        self.enqueue_stats()
+    }
+
+    /// Spawns inflowing particles, uploads the particles, the forces and
+    /// paddles at the current time and the temperatures, then advances the particles by one time step.
+    fn enqueue_integrate(&mut self) -> cl::Result<()> {
+        let free = self.free.len();
+        self.emitter.emit(
+            &self.boundaries,
+            TIME_STEP,
+            &mut self.particles,
+            &mut self.free,
+        );
+        self.dirty |= self.free.len() != free;
+
+        if self.dirty {
+            let particles = unsafe {
+                self.queue.enqueue_write_buffer(
+                    &mut self.particle_buffer.buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &self.particles,
+                    &[],
+                )?
+            };
+            self.profile
+                .record(Stage::Upload, "write particles", &particles)?;
+            self.active_events.push(particles);
+            self.dirty = false;
+        }
+
+        forces::evaluate(&self.force_primitives, self.time, &mut self.forces);
+        if !self.forces.is_empty() {
+            let forces = unsafe {
+                self.queue.enqueue_write_buffer(
+                    &mut self.force_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &self.forces,
+                    &[],
+                )?
+            };
+            self.profile
+                .record(Stage::Upload, "write forces", &forces)?;
+            self.active_events.push(forces);
+        }
+
+        // the wavemakers and the wind change every step, unlike the other integrate arguments
+        let (offset, speed) = self.boundaries.wavemaker_motion(self.time);
+        unsafe {
+            self.integrate_kernel.set_arg(18, &offset)?;
+            self.integrate_kernel.set_arg(19, &speed)?;
+            self.integrate_kernel
+                .set_arg(25, &self.wind.params(self.time))?;
+            self.integrate_kernel
+                .set_arg(26, &self.turbulence.params(self.time))?;
+        }
+
+        paddle::evaluate(&self.paddles, self.time, &mut self.blades);
+        if !self.blades.is_empty() {
+            let blades = unsafe {
+                self.queue.enqueue_write_buffer(
+                    &mut self.blade_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &self.blades,
+                    &[],
+                )?
+            };
+            self.profile
+                .record(Stage::Upload, "write blades", &blades)?;
+            self.active_events.push(blades);
+        }
+
+        if self.thermal.is_active() {
+            self.thermal
+                .update(&self.particles, &mut self.temperatures, TIME_STEP);
+            let temperatures = unsafe {
+                self.queue.enqueue_write_buffer(
+                    &mut self.temperature_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &self.temperatures,
+                    &[],
+                )?
+            };
+            self.profile
+                .record(Stage::Upload, "write temperatures", &temperatures)?;
+            self.active_events.push(temperatures);
+        }
+
+        if self.turbulence.is_active() {
+            self.surface.update(&self.particles);
+            let surface = unsafe {
+                self.queue.enqueue_write_buffer(
+                    &mut self.surface_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &self.surface.heights,
+                    &[],
+                )?
+            };
+            self.profile
+                .record(Stage::Upload, "write surface", &surface)?;
+            self.active_events.push(surface);
+        }
+
+        let integrating = self.enqueue_kernel(
+            self.integrate_kernel.get(),
+            "integrate_particles",
+            self.dispatches.integrate,
+            Stage::Integrate,
+        )?;
+        self.active_events.replace(integrating);
+        self.time += TIME_STEP;
+
+        Ok(())
+    }
+
+    /// Sorting and collision of [`step`](Self::step) as a single launch of one work group.
+    fn step_fused(&mut self, kernel: types::cl_kernel, work_size: usize) -> cl::Result<()> {
+        let fused = unsafe {
+            self.queue.enqueue_nd_range_kernel(
+                kernel,
+                1,
+                ptr::null(),
+                &work_size,
+                &work_size,
+                self.active_events.wait_list(),
+            )?
+        };
+        self.profile
+            .record(Stage::Solve, "sort_and_collide_particles", &fused)?;
+        self.active_events.replace(fused);
+
+        self.enqueue_stats()
+    }
+
+    /// Reduces the particle state into [`ParticleStats`] and a [`Histogram`] of
+    /// the speeds on the device and starts reading the results back without
+    /// waiting for them. Skipped while the previous
+    /// result is still in flight.
+    fn enqueue_stats(&mut self) -> cl::Result<()> {
+        if self.pending_stats.is_some() {
+            return Ok(());
+        }
+
+        let global_size = self.reduce_work_size * self.reduce_groups;
+        let partials = unsafe {
+            self.queue.enqueue_nd_range_kernel(
+                self.reduce_kernel.get(),
+                1,
+                ptr::null(),
+                &global_size,
+                &self.reduce_work_size,
+                self.active_events.wait_list(),
+            )?
+        };
+
+        let reduced = unsafe {
+            self.queue.enqueue_nd_range_kernel(
+                self.reduce_partials_kernel.get(),
+                1,
+                ptr::null(),
+                &self.reduce_work_size,
+                &self.reduce_work_size,
+                &[partials.get()],
+            )?
+        };
+
+        let read = unsafe {
+            self.queue.enqueue_read_buffer(
+                &self.stats_buffer,
+                types::CL_NON_BLOCKING,
+                0,
+                &mut self.stats_readback,
+                &[reduced.get()],
+            )?
+        };
+
+        // the queue runs in order, once the bins are read the stats are too
+        self.histogram_max = self.stats.max_speed;
+        let zeroed = unsafe {
+            self.queue.enqueue_fill_buffer(
+                &mut self.histogram_buffer,
+                &[0],
+                0,
+                histogram::BINS * size_of::<u32>(),
+                &[read.get()],
+            )?
+        };
+        unsafe {
+            self.histogram_kernel.set_arg(2, &self.histogram_max)?;
+        }
+        let counted = unsafe {
+            self.queue.enqueue_nd_range_kernel(
+                self.histogram_kernel.get(),
+                1,
+                ptr::null(),
+                &global_size,
+                &self.reduce_work_size,
+                &[zeroed.get()],
+            )?
+        };
+        let read = unsafe {
+            self.queue.enqueue_read_buffer(
+                &self.histogram_buffer,
+                types::CL_NON_BLOCKING,
+                0,
+                &mut self.histogram_readback,
+                &[counted.get()],
+            )?
+        };
+        self.pending_stats = Some(read);
+
+        Ok(())
+    }
+
+    /// The most recent stats that finished reading back. Never blocks, so the
+    /// result may lag a few steps behind.
+    pub fn stats(&mut self) -> cl::Result<ParticleStats> {
+        if let Some(read) = &self.pending_stats {
+            if read.command_execution_status()?.0 == cl::event::CL_COMPLETE {
+                self.stats = self.stats_readback[0];
+                self.speeds.max = self.histogram_max;
+                self.speeds.bins.copy_from_slice(&self.histogram_readback);
+                self.pending_stats = None;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Enqueues reading the particles and, without the fused kernel, the
+    /// grid back after the step, without waiting for any of it. The
+    /// particles land in a second mirror, so [`particles`](Backend::particles)
+    /// keeps the last ones that arrived until
+    /// [`read_complete`](Self::read_complete) or [`wait_read`](Self::wait_read)
+    /// takes the new ones in. A read still in flight is waited for first.
+    pub fn read(&mut self) -> cl::Result<()> {
+        self.wait_read()?;
+        let wait_list = self.active_events.wait_list();
+
+        // the fused kernel keeps the cell lists in local memory
+        if self.fused.is_none() {
+            let read = unsafe {
+                self.queue.enqueue_read_buffer(
+                    &self.count_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &mut self.count_per_cell,
+                    wait_list,
+                )?
+            };
+            self.profile.record(Stage::Readback, "read counts", &read)?;
+
+            let read = unsafe {
+                self.queue.enqueue_read_buffer(
+                    &self.id_buffer,
+                    types::CL_NON_BLOCKING,
+                    0,
+                    &mut self.cell_ids,
+                    wait_list,
+                )?
+            };
+            self.profile.record(Stage::Readback, "read ids", &read)?;
+        }
+
+        // the queue runs in order, so this one finishes last
+        let read = unsafe {
+            self.queue.enqueue_read_buffer(
+                &self.particle_buffer.buffer,
+                types::CL_NON_BLOCKING,
+                0,
+                &mut self.incoming,
+                wait_list,
+            )?
+        };
+        self.profile
+            .record(Stage::Readback, "read particles", &read)?;
+        self.profile.end_step();
+        self.pending_read = Some(Completion::new(read)?);
+
+        self.active_events.clear();
+        Ok(())
+    }
+
+    /// Whether the last [`read`](Self::read) has arrived, taking it in if it
+    /// has. Never blocks.
+    pub fn read_complete(&mut self) -> cl::Result<bool> {
+        match &self.pending_read {
+            Some(read) if read.is_complete() => self.wait_read()?,
+            Some(_) => return Ok(false),
+            None => (),
+        }
+        Ok(true)
+    }
+
+    /// Blocks until the last [`read`](Self::read) has arrived and takes it in.
+    pub fn wait_read(&mut self) -> cl::Result<()> {
+        let Some(read) = self.pending_read.take() else {
+            return Ok(());
+        };
+        read.wait()?;
+        std::mem::swap(&mut self.particles, &mut self.incoming);
+        self.timings = StepTimings {
+            work_groups: Some(self.work_groups),
+            ..self.profile.timings()?
+        };
+        // particles past their lifetime are removed on the host, and on
+        // the device with the next step
+        self.dirty |= self
+            .ages
+            .update(&mut self.particles, &self.boundaries, TIME_STEP);
+        self.free.collect(&self.particles);
+        self.ids.update(&self.particles);
+        Ok(())
+    }
+
+    /// Whether particles flow in anywhere, and only ever appear on the host.
+    fn has_inlets(&self) -> bool {
+        Edge::ALL
+            .into_iter()
+            .any(|edge| matches!(self.boundaries.get(edge), Boundary::Inlet(_)))
+    }
+
+    /// Whether the particles are read back into pinned memory.
+    pub fn pinned_readback(&self) -> bool {
+        self.particles.is_pinned()
+    }
+}
+
+impl Backend for OpenClState {
+    fn name(&self) -> &'static str {
+        "opencl"
+    }
+
+    fn device(&self) -> Option<String> {
+        self.device.name().ok()
+    }
+
+    fn device_spans(&self) -> &[DeviceSpan] {
+        &self.profile.spans
+    }
+
+    fn step(&mut self) -> Result<(), backend::Error> {
+        OpenClState::step(self)?;
+        Ok(self.read()?)
+    }
+
+    fn read_complete(&mut self) -> Result<bool, backend::Error> {
+        Ok(OpenClState::read_complete(self)?)
+    }
+
+    fn wait_read(&mut self) -> Result<(), backend::Error> {
+        Ok(OpenClState::wait_read(self)?)
+    }
+
+    fn particles(&self) -> &[Instance] {
+        &self.particles
+    }
+
+    fn temperatures(&self) -> &[f32] {
+        &self.temperatures
+    }
+
+    fn ages(&self) -> &Ages {
+        &self.ages
+    }
+
+    fn ids(&self) -> &ParticleIds {
+        &self.ids
+    }
+
+    fn restore(&mut self, particles: &[Instance], time: f32) -> Result<(), backend::Error> {
+        if particles.len() != self.particles.len() {
+            return Err(format!(
+                "can't restore {} particles into {}",
+                particles.len(),
+                self.particles.len()
+            )
+            .into());
+        }
+        self.wait_read()?;
+        self.particles.copy_from_slice(particles);
+        self.dirty = true;
+        self.time = time;
+        let quiet = unsafe {
+            self.queue.enqueue_fill_buffer(
+                &mut self.quiet_buffer,
+                &[0],
+                0,
+                self.particles.len() * size_of::<u32>(),
+                &[],
+            )?
+        };
+        self.active_events.push(quiet);
+        if let Some(precise_buffer) = &mut self.precise_buffer {
+            let precise = precision::track(particles);
+            // blocking, as `precise` doesn't outlive this
+            unsafe {
+                self.queue.enqueue_write_buffer(
+                    precise_buffer,
+                    types::CL_BLOCKING,
+                    0,
+                    &precise,
+                    &[],
+                )?
+            };
+        }
+        self.free.collect(&self.particles);
+        self.ids.update(&self.particles);
+        Ok(())
+    }
+
+    fn timings(&self) -> Option<StepTimings> {
+        Some(self.timings)
+    }
+
+    fn grid_cells(&self) -> Option<GridCells<'_>> {
+        // the fused kernel keeps the grid in local memory, and the read of
+        // the grid may still be landing
+        let available = self.fused.is_none() && self.pending_read.is_none();
+        available.then_some(GridCells {
+            grid: self.grid,
+            n_per_cell: MAX_PARTICLES_PER_CELL as u32,
+            counts: &self.count_per_cell,
+            ids: &self.cell_ids,
+        })
+    }
+
+    fn edit(&mut self, edit: &SceneEdit) -> Result<(), backend::Error> {
+        OpenClState::edit(self, edit)
+    }
+
+    fn stats(&mut self) -> Result<ParticleStats, backend::Error> {
+        Ok(OpenClState::stats(self)?)
+    }
+
+    fn speed_histogram(&mut self) -> Result<Histogram, backend::Error> {
+        OpenClState::stats(self)?;
+        Ok(self.speeds.clone())
+    }
+}
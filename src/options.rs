@@ -0,0 +1,922 @@
+use crate::backend::{self, BackendKind};
+use crate::boundary::{Boundary, Edge, Inlet};
+use crate::damping::Damping;
+use crate::geometry::Polygon;
+use crate::groups::Group;
+use crate::material::Material;
+use crate::paddle::Paddle;
+use crate::phase::FluidBlock;
+use crate::probe::{Heatmap, Probe};
+use crate::relax::{self, RelaxParams};
+use crate::rewind;
+use crate::scene::Scene;
+use crate::sdf::DistanceField;
+use crate::sim::{Coloring, SortKey};
+use crate::terrain::Heightfield;
+use crate::theme::Theme;
+use crate::thermal::Heater;
+use crate::trigger::Trigger;
+use crate::turbulence::Turbulence;
+use crate::wind::Wind;
+use crate::TIME_STEP;
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+usage: pos-based-fluids [options]
+
+options:
+    --backend <opencl|cpu|wcsph>
+                              simulation backend (default: opencl, cpu in builds
+                              without the `opencl` feature)
+    --compare <opencl|cpu|wcsph>
+                              also run this backend from the same particles, drawn over
+                              the first in another color, and report the divergence every
+                              step
+    --compare-options <options>
+                              run the second backend with these space separated options on
+                              top of the others, such as another solver or viscosity; the
+                              backend of --compare if given, otherwise the first one or
+                              the --backend among them
+    --fused-threshold <n>     use the fused OpenCL kernel up to n particles (default: 1024)
+    --grid-images             read the grid from OpenCL images while colliding, compare
+                              the grid and solve times with --timings
+    --f64                     also keep the particles in double precision, to see
+                              whether drift comes from rounding (OpenCL needs fp64)
+    --deterministic           give every run of a scene the same result on the same device,
+                              at the cost of an extra pass over the grid (OpenCL)
+    --periodic <x|y|xy>       wrap the domain around along these axes
+    --boundary <edge>=<type>  set the boundary of the left, right, bottom or top edge
+                              to free, periodic, open, inlet[:<speed>] or
+                              wall[:<adhesion>[:<wetting>]] (repeatable)
+    --capacity <n>            reserve room for n particles, filled by inlets
+    --lifetime <seconds>      fade out and recycle particles from inlets after this long
+    --block <x0>,<y0>,<x1>,<y1>[=<phase>][:<sampling>]
+                              fill a rectangle with water, oil, snow or syrup (wcsph
+                              backend only) or a fluid of the given rest density, replacing the
+                              default particles (repeatable). A polygon of `;` separated
+                              <x>,<y> points works too. Particles sit on a `lattice`
+                              (default), a `jittered` one or at `poisson` disk samples
+    --paddle <x>,<y>,<length>,<angular velocity>[,<blades>]
+                              stir the fluid with blades turning around a point, two
+                              unless given (repeatable)
+    --gravity <x>,<y>         uniform acceleration on every particle (default: 0,0)
+    --wind <strength>,<scale>,<speed>[,<drag>]
+                              drag the particles along a breeze of tiling noise with this
+                              many cells across, churning at speed radians per second
+                              (default drag: 2 per second)
+    --turbulence <amplitude>,<scale>,<speed>,<depth>
+                              stir curl-noise eddies of this many cells across into the
+                              fluid, fading to 1/e at depth below the surface
+    --turbulence-region <x0>,<y0>,<x1>,<y1>
+                              only stir the eddies within this rectangle (repeatable)
+    --relax <steps>           let the fluid settle under gravity for this many damped steps
+                              before starting
+    --damping <rate>          take this share of the kinetic energy away every second while
+                              running, apart from viscosity and drag
+    --target-energy <e>       damp the fluid down to this kinetic energy per particle
+                              within half a second, and leave it alone below
+    --obstacle <x0>,<y0>;<x1>,<y1>;...
+                              a solid polygon the density estimates account for
+                              (repeatable)
+    --obstacle-sdf <n>        also collide the particles with the obstacles, through a
+                              signed distance field of n by n samples over the domain
+    --material restitution=<e>,friction=<f>,adhesion=<a>,range=<r>,wetting=<w>
+                              how particles bounce off, slide along and cling to the
+                              obstacle given last, wetting from -1 (beading) to 1
+                              (spreading) (with --obstacle-sdf, default: 0,0,0,0.05,0)
+    --terrain <h0>,<h1>,...   ground heights evenly spaced from the left to the right edge
+    --heater <x0>,<y0>,<x1>,<y1>=<temperature>
+                              set the temperature of particles in a rectangle relative
+                              to ambient, negative for a cooler (repeatable)
+    --trigger <name>=<x0>,<y0>,<x1>,<y1>
+                              report particles entering and leaving a rectangle
+                              (repeatable)
+    --probe <name>=<x>,<y> | <name>=<x0>,<y0>,<x1>,<y1>[:<samples>]
+                              sample density, pressure and velocity at a point or along
+                              a line every step (repeatable)
+    --group <name>[=<x0>,<y0>,<x1>,<y1>][:inlets][:color=<rrggbb>][:phase=<phase>]
+                              a named group of the particles starting in a rectangle
+                              and, with inlets, those coming out of inlets; drawn in its
+                              color and started as its phase if given (repeatable)
+    --probe-csv <path>        write the probe samples to a CSV file on exit
+    --report <path>           write a JSON report to a file on exit: the largest density error,
+                              the energy drift, the mean step time and its parts, and the
+                              particle count, energy and density error over time, with the
+                              backend, device and configuration
+    --trace <path>            write a timeline of the steps, the OpenCL commands and the
+                              render passes to a file on exit, for chrome://tracing or
+                              Perfetto
+    --measure <frames>        print the mean and percentiles of the frame and step times on
+                              exit, leaving out the first <frames> frames (driver warmup,
+                              kernel builds, surface setup)
+    --record <path>           write every frame's particles and colors to a file for
+                              the playback binary
+    --record-input <path>     write the commands sent from the window to a file, with the
+                              step each took effect at
+    --replay-input <path>     apply the commands of a file written with --record-input at
+                              their steps, also headless
+    --rewind <MiB>            memory for the particles of recent steps to rewind to
+                              (default: 256, 0 disables)
+    --timelapse <dir>         save every nth frame as a numbered PNG into a directory,
+                              with a manifest of the simulated times
+    --timelapse-every <n>     steps between timelapse images (default: 10)
+    --timelapse-size <pixels> width and height of the timelapse images (default: 512)
+    --buoyancy <a>            upward acceleration per degree above ambient (default: 1)
+    --color <velocity|phase|temperature|age>
+                              what the particle colors show (default: phase when there
+                              are several, otherwise velocity)
+    --dye                     color particles by a dye that can be painted with the mouse
+                              (left paints, right clears) and that inlets fill in
+    --mix <rate>              blend the colors of the phases into each other at this rate
+                              per second
+    --diffuse                 spawn foam, spray and bubble particles where the fluid is turbulent
+    --streamlines             trace streamlines through the flow (toggle with L)
+    --vorticity               show the vorticity behind the particles (toggle with V)
+    --heatmap <pressure|density-error>
+                              show the pressure or the density error behind the particles,
+                              over the vorticity (toggle with H, default: pressure)
+    --debug-window            open a second window showing the speed of the flow field
+    --split <options>         also run the scene with these space separated options on top
+                              of the others and show it next to the first, for comparing
+                              parameters frame by frame; commands and the mouse go to all
+                              of them (repeatable)
+    --oit                     blend overlapping particles independently of their draw order
+    --sort-by <y|speed>       draw the particles sorted on the GPU, higher or faster ones on top
+    --particle-scale <factor> draw the particles this many times their radius (default: 1)
+    --discs                   draw the particles as filled discs rather than rings
+                              (toggle with O)
+    --opaque                  draw the particles without transparency
+    --clip                    leave out the particles outside of the unit square
+    --present-mode <mode>     `vsync` (default), `mailbox` for vsync without waiting on it,
+                              or `immediate` for uncapped frames that may tear
+    --fps-cap <fps>           render at most this many frames per second
+    --aspect <mode>           how the view fits a window of another shape: `fit` (default)
+                              shows more of the world around it, `letterbox` bars instead,
+                              `fill` cuts it to cover the window and `stretch` distorts it
+    --pause-hidden            pause the simulation while the window is minimized or covered
+    --extrapolate             keep the particles moving by their velocity for up to a
+                              step when the simulation falls behind the display
+    --theme <name|path>       colors to draw with, `dark` (default), `light`, `contrast` or a
+                              theme file, see theme.rs
+    --background-image <path>[=<x0>,<y0>,<x1>,<y1>]
+                              draw a PNG image behind the simulation over this rectangle
+                              (default: the width of the domain, from the bottom left)
+    --plots                   plot energy (yellow), density error (red), particle count
+                              (green) and step time (blue) over the last frames
+                              (toggle with P)
+    --views <path>            file the view and its bookmarks are kept in across runs
+                              (default: pos-based-fluids.views)
+    --verify                  check the grid neighbors of a sample of particles against a
+                              k-d tree every frame and report any missed (slow)
+    --histograms              show histograms of the particle speeds and density errors
+    --timings                 show where the time of a frame goes, per step: upload (purple),
+                              integrate (green), grid (yellow), solve (red), readback (pink),
+                              then instances (gray), render (blue) and present (cyan), with a
+                              tick at 1/60 s (toggle with T)
+    --surface                 extract the free surface as polylines every frame
+    --script <path>           run a Rhai script that edits the scene while it runs
+                              (needs the `scripting` feature)
+    --viscosity <nu>          kinematic viscosity of the fluid, solved implicitly when
+                              too high for an explicit step (wcsph backend only); phases
+                              like syrup bring their own, and shift with the left mouse
+                              button paints syrup's on (right: none)
+    --viscosity-thinning <k>  divide the viscosity by e every 1/k degrees above ambient
+    --drag <linear>[,<quadratic>]
+                              slow every particle by this much per unit of velocity, and
+                              per unit of velocity and speed
+    --max-speed <v>           clamp velocities to this speed
+    --max-displacement <d>    clamp velocities to move particles at most this far per step
+    --brake                   split steps into smaller ones while clamping keeps triggering
+                              (cpu backend only)
+    --sleep-after <n>         let particles that stayed slow for n steps sleep, 0 disables (default: 0)
+    --headless                run without a window as fast as possible, printing progress
+                              (always on in builds without the `render` feature)
+    --steps <n>               stop after n steps
+    --stress                  double the particles until a step takes longer than the
+                              stress target and report the most that kept up, without
+                              a window
+    --stress-target <ms>      wall clock time per step for the stress test (default: the
+                              simulated time of a step, real time)
+
+keys:
+    WASD, arrow keys          pan the view
+    +, -                      zoom in and out
+    Home                      go back to the whole domain
+    Ctrl+1..9, 1..9           save the view to a bookmark, jump back to it
+    Space                     pause and resume
+    Backspace, comma          rewind a second or a step, pausing
+    period                    step forward while paused
+    I                         show the particle under the cursor in the title and mark it
+    O                         switch between rings and discs";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub backend: BackendKind,
+    /// Second backend to run alongside `backend`, see [`crate::compare`].
+    pub compare: Option<BackendKind>,
+    /// What the second backend runs with, `config` unless set.
+    pub compare_config: Option<backend::Config>,
+    pub config: backend::Config,
+    /// Applied on top of [`Scene::boundaries`](crate::scene::Scene::boundaries), in order.
+    pub boundaries: Vec<(Edge, Boundary)>,
+    /// See [`Scene::with_capacity`](crate::scene::Scene::with_capacity).
+    pub capacity: Option<usize>,
+    /// Sets [`Inlet::lifetime`](crate::boundary::Inlet::lifetime) of every inlet.
+    pub lifetime: Option<f32>,
+    /// Replace the default particles when not empty.
+    pub blocks: Vec<FluidBlock>,
+    /// Added to [`Scene::thermal`](crate::scene::Scene::thermal).
+    pub heaters: Vec<Heater>,
+    pub terrain: Option<Heightfield>,
+    pub gravity: Option<[f32; 2]>,
+    pub wind: Option<Wind>,
+    pub turbulence: Option<Turbulence>,
+    /// Added to [`Turbulence::regions`].
+    pub turbulence_regions: Vec<([f32; 2], [f32; 2])>,
+    /// Steps to [relax](crate::relax) the scene for before it starts.
+    pub relax: Option<u32>,
+    /// [Damping](crate::damping) of the kinetic energy while running.
+    pub damping: Damping,
+    /// Added to [`Scene::obstacles`](crate::scene::Scene::obstacles).
+    pub obstacles: Vec<Polygon>,
+    /// Material of each of the `obstacles`, in the same order.
+    pub obstacle_materials: Vec<Material>,
+    /// Samples a side of the [distance field](crate::sdf) the obstacles are
+    /// turned into, to collide with them.
+    pub obstacle_sdf: Option<u32>,
+    /// Added to [`Scene::paddles`](crate::scene::Scene::paddles).
+    pub paddles: Vec<Paddle>,
+    /// Added to [`Scene::triggers`](crate::scene::Scene::triggers).
+    pub triggers: Vec<Trigger>,
+    /// Added to [`Scene::probes`](crate::scene::Scene::probes).
+    pub probes: Vec<Probe>,
+    /// Added to [`Scene::groups`](crate::scene::Scene::groups).
+    pub groups: Vec<Group>,
+    /// Where to write the probe samples when the simulation stops.
+    pub probe_csv: Option<PathBuf>,
+    /// Where to write the [report](crate::report) when the simulation stops.
+    pub report: Option<PathBuf>,
+    /// Where to write the [trace](crate::trace) when the simulation stops.
+    pub trace: Option<PathBuf>,
+    /// Frames to leave out before [measuring](crate::measure) the frame and
+    /// step times, `None` to not measure them.
+    pub measure: Option<u64>,
+    /// [Recording](crate::recording) of every frame.
+    pub record: Option<PathBuf>,
+    /// [Log](crate::replay) of the commands from the window.
+    pub record_input: Option<PathBuf>,
+    /// [Log](crate::replay) of commands to play back.
+    pub replay_input: Option<PathBuf>,
+    /// Bytes for the [rewind buffer](crate::rewind), none if zero.
+    pub rewind_budget: usize,
+    /// Show the [histograms](crate::histogram).
+    pub histograms: bool,
+    /// [Check](crate::verify) the grid every frame.
+    pub verify: bool,
+    /// Directory for the [timelapse](crate::timelapse) images.
+    pub timelapse: Option<PathBuf>,
+    pub timelapse_every: u64,
+    pub timelapse_size: u32,
+    pub buoyancy: Option<f32>,
+    /// Overrides the default coloring, the dye and the color mix still take precedence.
+    pub coloring: Option<Coloring>,
+    /// Run a [`DiffuseSystem`](crate::diffuse::DiffuseSystem) next to the simulation.
+    pub diffuse: bool,
+    /// Run a [`DyeField`](crate::dye::DyeField) next to the simulation.
+    pub dye: bool,
+    /// Run a [`ColorMix`](crate::mixing::ColorMix) at this rate next to the simulation.
+    pub mix: Option<f32>,
+    /// Extract the surface with a [`SurfaceExtractor`](crate::surface::SurfaceExtractor) every frame.
+    pub surface: bool,
+    /// Start with [streamlines](crate::streamlines) on.
+    pub streamlines: bool,
+    /// Start with the [vorticity](crate::field::VelocityField::vorticity) shown.
+    pub vorticity: bool,
+    /// Start with this [heatmap](crate::probe::Probes::heatmap) shown.
+    pub heatmap: Option<Heatmap>,
+    /// Open a second window with the [speed](crate::field::VelocityField::speed)
+    /// of the flow.
+    pub debug_window: bool,
+    /// The simulations shown side by side with this one, each parsed from
+    /// the same arguments with those of its `--split` on top.
+    pub panes: Vec<Options>,
+    /// Blend the particles [order independently](crate::render::RenderState::set_oit).
+    pub oit: bool,
+    /// [Sort](crate::render::RenderState::set_sort_key) the particles by this before drawing.
+    pub sort_by: Option<SortKey>,
+    /// Drawn radius of the particles relative to their radius, see
+    /// [`RenderParams`](crate::render::RenderParams).
+    pub particle_scale: f32,
+    /// Draw the particles as filled discs.
+    pub discs: bool,
+    /// Draw the particles without transparency.
+    pub opaque: bool,
+    /// Leave out the particles outside of the unit square.
+    pub clip: bool,
+    /// PNG image to draw behind the simulation.
+    pub background_image: Option<PathBuf>,
+    /// World rectangle the background image covers, the width of the domain
+    /// by default.
+    pub background_rect: Option<([f32; 2], [f32; 2])>,
+    /// Colors around the particles and of the phases.
+    pub theme: Theme,
+    /// How frames are handed to the display.
+    pub present_mode: PresentMode,
+    /// How the view is fitted to the window.
+    pub aspect: AspectMode,
+    /// Frames per second to render at most.
+    pub fps_cap: Option<f32>,
+    /// Hold the simulation while the window is minimized or covered, instead
+    /// of only skipping the rendering.
+    pub pause_hidden: bool,
+    /// Draw the particles ahead of the latest state while the next one is
+    /// late, see [`Frame::instances_at`](crate::simulation::Frame::instances_at).
+    pub extrapolate: bool,
+    /// Start with the [plots](crate::plots) shown.
+    pub plots: bool,
+    /// Sidecar file of the [views](crate::views).
+    pub views: PathBuf,
+    /// Start with the [timings](crate::hud) shown.
+    pub timings: bool,
+    /// [Script](crate::script) attached to the scene.
+    pub script: Option<PathBuf>,
+    /// Run without a window, see [`crate::headless`].
+    pub headless: bool,
+    /// Steps after which the simulation stops, `None` to run until closed.
+    pub steps: Option<u64>,
+    /// Run the [stress test](crate::stress) instead.
+    pub stress: bool,
+    /// Seconds per step the stress test allows.
+    pub stress_target: f32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            backend: match cfg!(feature = "opencl") {
+                true => BackendKind::OpenCl,
+                false => BackendKind::Cpu,
+            },
+            compare: None,
+            compare_config: None,
+            config: backend::Config::default(),
+            boundaries: vec![],
+            capacity: None,
+            lifetime: None,
+            blocks: vec![],
+            heaters: vec![],
+            terrain: None,
+            gravity: None,
+            wind: None,
+            turbulence: None,
+            turbulence_regions: vec![],
+            relax: None,
+            damping: Damping::default(),
+            obstacles: vec![],
+            obstacle_materials: vec![],
+            obstacle_sdf: None,
+            paddles: vec![],
+            triggers: vec![],
+            probes: vec![],
+            groups: vec![],
+            probe_csv: None,
+            report: None,
+            trace: None,
+            measure: None,
+            record: None,
+            record_input: None,
+            replay_input: None,
+            rewind_budget: rewind::DEFAULT_BUDGET,
+            histograms: false,
+            verify: false,
+            timelapse: None,
+            timelapse_every: 10,
+            timelapse_size: 512,
+            buoyancy: None,
+            coloring: None,
+            diffuse: false,
+            dye: false,
+            mix: None,
+            surface: false,
+            streamlines: false,
+            vorticity: false,
+            heatmap: None,
+            debug_window: false,
+            panes: vec![],
+            oit: false,
+            sort_by: None,
+            particle_scale: 1.0,
+            discs: false,
+            opaque: false,
+            clip: false,
+            background_image: None,
+            background_rect: None,
+            theme: Theme::default(),
+            pause_hidden: false,
+            extrapolate: false,
+            present_mode: PresentMode::Vsync,
+            aspect: AspectMode::Fit,
+            fps_cap: None,
+            headless: !cfg!(feature = "render"),
+            steps: None,
+            stress: false,
+            stress_target: TIME_STEP,
+            plots: false,
+            views: "pos-based-fluids.views".into(),
+            timings: false,
+            script: None,
+        }
+    }
+}
+
+/// How the view is fitted to panes of another aspect ratio, see
+/// [`Camera::transform`](crate::render::Camera::transform).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AspectMode {
+    /// All of the view, centered, with more of the world around it.
+    #[default]
+    Fit,
+    /// All of the view, centered, with bars around it.
+    Letterbox,
+    /// The middle of the view, cut to cover the pane.
+    Fill,
+    /// All of the view and nothing else, stretched to the pane.
+    Stretch,
+}
+
+impl std::str::FromStr for AspectMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fit" => Ok(AspectMode::Fit),
+            "letterbox" => Ok(AspectMode::Letterbox),
+            "fill" => Ok(AspectMode::Fill),
+            "stretch" => Ok(AspectMode::Stretch),
+            _ => Err(format!(
+                "unknown aspect mode `{s}`, expected `fit`, `letterbox`, `fill` or `stretch`"
+            )),
+        }
+    }
+}
+
+/// When rendered frames are shown, falls back to [`Vsync`](Self::Vsync)
+/// where the display doesn't support the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Every frame, one per refresh.
+    Vsync,
+    /// The latest frame at every refresh, without holding rendering back.
+    Mailbox,
+    /// Right away, tearing if it has to.
+    Immediate,
+}
+
+impl std::str::FromStr for PresentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vsync" | "fifo" => Ok(PresentMode::Vsync),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => Err(format!(
+                "unknown present mode `{s}`, expected `vsync`, `mailbox` or `immediate`"
+            )),
+        }
+    }
+}
+
+impl Options {
+    /// The scene to simulate, with the overrides from the command line applied.
+    pub fn scene(&self) -> Result<Scene, String> {
+        let mut scene = match self.blocks.is_empty() {
+            true => Scene::default(),
+            false => self.blocks.iter().fold(Scene::new(vec![]), |scene, block| {
+                scene.with_block(block.clone())
+            }),
+        };
+        if scene.particles.is_empty() {
+            return Err("the blocks contain no particles".into());
+        }
+        for group in &self.groups {
+            scene = scene.with_group(group.clone())?;
+        }
+        self.theme.apply(&mut scene);
+        for &(edge, boundary) in &self.boundaries {
+            scene.boundaries.set(edge, boundary);
+        }
+        if let Some(lifetime) = self.lifetime {
+            for edge in Edge::ALL {
+                if let Boundary::Inlet(inlet) = scene.boundaries.get(edge) {
+                    let inlet = Inlet {
+                        lifetime: Some(lifetime),
+                        ..inlet
+                    };
+                    scene.boundaries.set(edge, Boundary::Inlet(inlet));
+                }
+            }
+        }
+        scene.boundaries.validate()?;
+        scene.thermal.heaters.extend_from_slice(&self.heaters);
+        if let Some(terrain) = &self.terrain {
+            scene.terrain = terrain.clone();
+        }
+        scene.obstacles.extend_from_slice(&self.obstacles);
+        scene
+            .obstacle_materials
+            .extend_from_slice(&self.obstacle_materials);
+        if let Some(resolution) = self.obstacle_sdf {
+            scene.distance_field = DistanceField::from_polygons(&scene.obstacles, resolution)
+                .with_materials(scene.obstacle_materials.clone());
+        }
+        scene.paddles.extend_from_slice(&self.paddles);
+        scene.triggers.extend_from_slice(&self.triggers);
+        scene.probes.extend_from_slice(&self.probes);
+        if let Some(gravity) = self.gravity {
+            scene.gravity = gravity;
+        }
+        if let Some(wind) = self.wind {
+            scene.wind = wind;
+        }
+        if let Some(turbulence) = &self.turbulence {
+            scene.turbulence = turbulence.clone();
+        }
+        scene
+            .turbulence
+            .regions
+            .extend_from_slice(&self.turbulence_regions);
+        if let Some(buoyancy) = self.buoyancy {
+            scene.thermal.buoyancy = buoyancy;
+        }
+        if let Some(capacity) = self.capacity {
+            scene = scene.with_capacity(capacity);
+        }
+        if let Some(steps) = self.relax {
+            let params = RelaxParams {
+                steps,
+                ..RelaxParams::default()
+            };
+            scene.particles = relax::relax(&scene, &params)
+                .map_err(|err| format!("could not relax the scene: {err}"))?;
+        }
+        Ok(scene)
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let all: Vec<String> = args.into_iter().collect();
+        let mut splits = vec![];
+        let mut compare_options = None;
+        let mut args = all.iter().cloned();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or(format!("missing value for {arg}\n\n{USAGE}"))
+            };
+
+            match arg.as_str() {
+                "--backend" => options.backend = value()?.parse()?,
+                "--compare" => options.compare = Some(value()?.parse()?),
+                "--compare-options" => compare_options = Some(value()?),
+                "--fused-threshold" => {
+                    options.config.fused_threshold = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --fused-threshold: {err}"))?
+                }
+                "--periodic" => {
+                    let edges: &[Edge] = match value()?.as_str() {
+                        "x" => &[Edge::Left, Edge::Right],
+                        "y" => &[Edge::Bottom, Edge::Top],
+                        "xy" => &Edge::ALL,
+                        other => {
+                            return Err(format!(
+                                "invalid --periodic `{other}`, expected `x`, `y` or `xy`"
+                            ))
+                        }
+                    };
+                    options
+                        .boundaries
+                        .extend(edges.iter().map(|&edge| (edge, Boundary::Periodic)));
+                }
+                "--boundary" => {
+                    let value = value()?;
+                    let (edge, boundary) = value.split_once('=').ok_or(format!(
+                        "invalid --boundary `{value}`, expected <edge>=<type>"
+                    ))?;
+                    options.boundaries.push((edge.parse()?, boundary.parse()?));
+                }
+                "--block" => options.blocks.push(value()?.parse()?),
+                "--terrain" => options.terrain = Some(value()?.parse()?),
+                "--gravity" => {
+                    let value = value()?;
+                    let invalid = || format!("invalid --gravity `{value}`, expected <x>,<y>");
+                    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+                    options.gravity = Some([
+                        x.trim().parse().map_err(|_| invalid())?,
+                        y.trim().parse().map_err(|_| invalid())?,
+                    ]);
+                }
+                "--damping" => {
+                    let rate: f32 = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --damping: {err}"))?;
+                    if rate < 0.0 {
+                        return Err(format!("invalid --damping {rate}, expected 0 or more"));
+                    }
+                    options.damping.rate = rate;
+                }
+                "--target-energy" => {
+                    let target: f32 = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --target-energy: {err}"))?;
+                    if target <= 0.0 {
+                        return Err(format!(
+                            "invalid --target-energy {target}, expected more than 0"
+                        ));
+                    }
+                    options.damping.target = Some(target);
+                }
+                "--relax" => {
+                    options.relax = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --relax: {err}"))?,
+                    )
+                }
+                "--obstacle" => {
+                    options.obstacles.push(value()?.parse()?);
+                    options.obstacle_materials.push(Material::default());
+                }
+                "--material" => {
+                    let material = value()?.parse()?;
+                    *options
+                        .obstacle_materials
+                        .last_mut()
+                        .ok_or("--material has to follow an --obstacle")? = material;
+                }
+                "--obstacle-sdf" => {
+                    let resolution = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --obstacle-sdf: {err}"))?;
+                    if resolution < 2 {
+                        return Err("invalid --obstacle-sdf, expected at least 2".into());
+                    }
+                    options.obstacle_sdf = Some(resolution);
+                }
+                "--paddle" => options.paddles.push(value()?.parse()?),
+                "--heater" => options.heaters.push(value()?.parse()?),
+                "--trigger" => options.triggers.push(value()?.parse()?),
+                "--probe" => options.probes.push(value()?.parse()?),
+                "--group" => options.groups.push(value()?.parse()?),
+                "--probe-csv" => options.probe_csv = Some(value()?.into()),
+                "--report" => options.report = Some(value()?.into()),
+                "--trace" => options.trace = Some(value()?.into()),
+                "--measure" => {
+                    options.measure = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --measure: {err}"))?,
+                    )
+                }
+                "--record" => options.record = Some(value()?.into()),
+                "--record-input" => options.record_input = Some(value()?.into()),
+                "--replay-input" => options.replay_input = Some(value()?.into()),
+                "--histograms" => options.histograms = true,
+                "--verify" => options.verify = true,
+                "--rewind" => {
+                    let mib: usize = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --rewind: {err}"))?;
+                    options.rewind_budget = mib << 20;
+                }
+                "--timelapse" => options.timelapse = Some(value()?.into()),
+                "--timelapse-every" => {
+                    options.timelapse_every = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --timelapse-every: {err}"))?
+                }
+                "--timelapse-size" => {
+                    options.timelapse_size = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --timelapse-size: {err}"))?
+                }
+                "--wind" => options.wind = Some(value()?.parse()?),
+                "--turbulence" => options.turbulence = Some(value()?.parse()?),
+                "--turbulence-region" => options
+                    .turbulence_regions
+                    .push(crate::scene::parse_rect(&value()?)?),
+                "--buoyancy" => {
+                    options.buoyancy = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --buoyancy: {err}"))?,
+                    )
+                }
+                "--color" => options.coloring = Some(value()?.parse()?),
+                "--diffuse" => options.diffuse = true,
+                "--dye" => options.dye = true,
+                "--mix" => {
+                    options.mix = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --mix: {err}"))?,
+                    )
+                }
+                "--surface" => options.surface = true,
+                "--streamlines" => options.streamlines = true,
+                "--vorticity" => options.vorticity = true,
+                "--heatmap" => options.heatmap = Some(value()?.parse()?),
+                "--debug-window" => options.debug_window = true,
+                "--split" => splits.push(value()?),
+                "--oit" => options.oit = true,
+                "--sort-by" => options.sort_by = Some(value()?.parse()?),
+                "--particle-scale" => {
+                    let scale: f32 = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --particle-scale: {err}"))?;
+                    if scale <= 0.0 {
+                        return Err(format!(
+                            "invalid --particle-scale {scale}, expected more than 0"
+                        ));
+                    }
+                    options.particle_scale = scale;
+                }
+                "--discs" => options.discs = true,
+                "--opaque" => options.opaque = true,
+                "--clip" => options.clip = true,
+                "--theme" => options.theme = Theme::load(&value()?)?,
+                "--pause-hidden" => options.pause_hidden = true,
+                "--extrapolate" => options.extrapolate = true,
+                "--present-mode" => options.present_mode = value()?.parse()?,
+                "--aspect" => options.aspect = value()?.parse()?,
+                "--fps-cap" => {
+                    let fps: f32 = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --fps-cap: {err}"))?;
+                    if fps <= 0.0 {
+                        return Err(format!("invalid --fps-cap {fps}, expected more than 0"));
+                    }
+                    options.fps_cap = Some(fps);
+                }
+                "--background-image" => {
+                    let value = value()?;
+                    match value.rsplit_once('=') {
+                        Some((path, rect)) => {
+                            options.background_image = Some(path.into());
+                            options.background_rect = Some(crate::scene::parse_rect(rect)?);
+                        }
+                        None => options.background_image = Some(value.into()),
+                    }
+                }
+                "--plots" => options.plots = true,
+                "--views" => options.views = value()?.into(),
+                "--timings" => options.timings = true,
+                "--script" => match cfg!(feature = "scripting") {
+                    true => options.script = Some(value()?.into()),
+                    false => {
+                        return Err("--script needs a build with the `scripting` feature".into())
+                    }
+                },
+                "--viscosity" => {
+                    options.config.viscosity = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --viscosity: {err}"))?
+                }
+                "--viscosity-thinning" => {
+                    options.config.viscosity_thinning = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --viscosity-thinning: {err}"))?
+                }
+                "--headless" => options.headless = true,
+                "--stress" => options.stress = true,
+                "--stress-target" => {
+                    let ms: f32 = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --stress-target: {err}"))?;
+                    if ms <= 0.0 {
+                        return Err(format!(
+                            "invalid --stress-target {ms}, expected more than 0"
+                        ));
+                    }
+                    options.stress_target = ms * 1e-3;
+                }
+                "--steps" => {
+                    options.steps = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --steps: {err}"))?,
+                    )
+                }
+                "--drag" => {
+                    let drag = value()?;
+                    let values = drag
+                        .split(',')
+                        .map(|x| x.trim().parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|err| format!("invalid --drag `{drag}`: {err}"))?;
+                    let (linear, quadratic) = match values[..] {
+                        [linear] => (linear, 0.0),
+                        [linear, quadratic] => (linear, quadratic),
+                        _ => {
+                            return Err(format!(
+                                "invalid --drag `{drag}`, expected <linear>[,<quadratic>]"
+                            ))
+                        }
+                    };
+                    if linear < 0.0 || quadratic < 0.0 {
+                        return Err(format!(
+                            "invalid --drag `{drag}`, the drag can't be negative"
+                        ));
+                    }
+                    options.config.linear_drag = linear;
+                    options.config.quadratic_drag = quadratic;
+                }
+                "--max-speed" => {
+                    options.config.max_speed = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --max-speed: {err}"))?
+                }
+                "--max-displacement" => {
+                    options.config.max_displacement = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --max-displacement: {err}"))?
+                }
+                "--brake" => options.config.brake = true,
+                "--grid-images" => options.config.grid_images = true,
+                "--f64" => options.config.double_precision = true,
+                "--deterministic" => options.config.deterministic = true,
+                "--sleep-after" => {
+                    options.config.sleep_after = value()?
+                        .parse()
+                        .map_err(|err| format!("invalid --sleep-after: {err}"))?
+                }
+                "--lifetime" => {
+                    options.lifetime = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --lifetime: {err}"))?,
+                    )
+                }
+                "--capacity" => {
+                    options.capacity = Some(
+                        value()?
+                            .parse()
+                            .map_err(|err| format!("invalid --capacity: {err}"))?,
+                    )
+                }
+                "-h" | "--help" => return Err(USAGE.into()),
+                _ => return Err(format!("unknown argument {arg}\n\n{USAGE}")),
+            }
+        }
+
+        if !splits.is_empty() && options.headless {
+            return Err("--split needs the window, it can't run headless".into());
+        }
+        for split in splits {
+            let mut pane = Self::parse_on_top(&all, &["--split"], &split)
+                .map_err(|err| format!("invalid --split `{split}`: {err}"))?;
+            if !pane.panes.is_empty() {
+                return Err(format!("invalid --split `{split}`, splits don't nest"));
+            }
+            // the first simulation writes the files
+            pane.probe_csv = None;
+            pane.report = None;
+            pane.trace = None;
+            pane.measure = None;
+            pane.record = None;
+            pane.record_input = None;
+            pane.timelapse = None;
+            options.panes.push(pane);
+        }
+        if let Some(extra) = compare_options {
+            let other = Self::parse_on_top(&all, &["--split", "--compare-options"], &extra)
+                .map_err(|err| format!("invalid --compare-options `{extra}`: {err}"))?;
+            options.compare = Some(other.compare.unwrap_or(other.backend));
+            options.compare_config = Some(other.config);
+        }
+        Ok(options)
+    }
+
+    /// Parses `args` again with the space separated options of `extra` on
+    /// top, leaving out the options in `skip` and their values.
+    fn parse_on_top(args: &[String], skip: &[&str], extra: &str) -> Result<Self, String> {
+        let mut base = vec![];
+        let mut args = args.iter().cloned();
+        while let Some(arg) = args.next() {
+            match skip.contains(&arg.as_str()) {
+                true => _ = args.next(),
+                false => base.push(arg),
+            }
+        }
+        Self::parse(
+            base.into_iter()
+                .chain(extra.split_whitespace().map(String::from)),
+        )
+    }
+}
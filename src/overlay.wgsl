@@ -0,0 +1,28 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// positions are already in clip space, the overlay ignores the camera
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(model.position, 0.0, 1.0);
+    // 0xAARRGGBB -> RGBA 0-1
+    let a = (model.color >> 24u);
+    let r = (model.color >> 16u) & 0xffu;
+    let g = (model.color >> 8u ) & 0xffu;
+    let b = (model.color       ) & 0xffu;
+    out.color = vec4(f32(r), f32(g), f32(b), f32(a)) / 255.0;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
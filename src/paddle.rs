@@ -0,0 +1,148 @@
+//! Rotating blades that stir the fluid, for mixer style scenes.
+
+use std::f32::consts::TAU;
+
+/// Blades of equal length spaced evenly around a hub, turning at a fixed
+/// angular velocity whatever the fluid does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paddle {
+    pub center: [f32; 2],
+    /// From the center to the tip of each blade.
+    pub length: f32,
+    /// Half the width of a blade.
+    pub thickness: f32,
+    pub blades: u32,
+    /// Radians per second, counter-clockwise for positive values.
+    pub angular_velocity: f32,
+}
+
+impl Paddle {
+    pub fn new(center: [f32; 2], length: f32, angular_velocity: f32) -> Self {
+        Self {
+            center,
+            length,
+            thickness: 0.01,
+            blades: 2,
+            angular_velocity,
+        }
+    }
+
+    pub fn with_blades(mut self, blades: u32) -> Self {
+        self.blades = blades.max(1);
+        self
+    }
+
+    /// The paddle as it stands at simulation time `time`.
+    pub fn at(&self, time: f32) -> Blades {
+        Blades {
+            center: self.center,
+            angle: self.angular_velocity * time,
+            angular_velocity: self.angular_velocity,
+            length: self.length,
+            thickness: self.thickness,
+            count: self.blades,
+            _pad: 0,
+        }
+    }
+}
+
+impl std::str::FromStr for Paddle {
+    type Err = String;
+
+    /// `<x>,<y>,<length>,<angular velocity>` optionally followed by `,<blades>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid paddle `{s}`: {err}"))?;
+        match values[..] {
+            [x, y, length, speed] if length > 0.0 => Ok(Paddle::new([x, y], length, speed)),
+            [x, y, length, speed, blades] if length > 0.0 && blades >= 1.0 => {
+                Ok(Paddle::new([x, y], length, speed).with_blades(blades as u32))
+            }
+            _ => Err(format!(
+                "invalid paddle `{s}`, expected <x>,<y>,<length>,<angular velocity>[,<blades>]"
+            )),
+        }
+    }
+}
+
+/// Evaluates every paddle at `time` into `out`, reusing its storage.
+pub fn evaluate(paddles: &[Paddle], time: f32, out: &mut Vec<Blades>) {
+    out.clear();
+    out.extend(paddles.iter().map(|p| p.at(time)));
+}
+
+/// A paddle at one point in time.
+///
+/// Mirrors `Blades` in `sorting.ocl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Blades {
+    pub center: [f32; 2],
+    /// Of the first blade, counter-clockwise from the x axis.
+    pub angle: f32,
+    pub angular_velocity: f32,
+    pub length: f32,
+    pub thickness: f32,
+    pub count: u32,
+    pub _pad: u32,
+}
+
+impl Blades {
+    /// Unit direction from the center along blade `i`.
+    fn direction(&self, i: u32) -> [f32; 2] {
+        let angle = self.angle + TAU * i as f32 / self.count as f32;
+        [angle.cos(), angle.sin()]
+    }
+
+    /// The center line of every blade, from the hub to the tip.
+    pub fn segments(&self) -> impl Iterator<Item = [[f32; 2]; 2]> + '_ {
+        (0..self.count).map(|i| {
+            let d = self.direction(i);
+            let tip = [
+                self.center[0] + d[0] * self.length,
+                self.center[1] + d[1] * self.length,
+            ];
+            [self.center, tip]
+        })
+    }
+
+    /// Pushes a particle inside a blade out to its surface and takes away
+    /// the part of its velocity, relative to the moving blade, that goes into
+    /// it. The blade hands its own velocity on to the particle this way.
+    /// Mirrors `collide_blades` in `sorting.ocl`.
+    pub fn collide(&self, pos: &mut [f32; 2], vel: &mut [f32; 2]) {
+        for i in 0..self.count {
+            let d = self.direction(i);
+            let rel = [pos[0] - self.center[0], pos[1] - self.center[1]];
+            let along = (rel[0] * d[0] + rel[1] * d[1]).clamp(0.0, self.length);
+            let closest = [self.center[0] + d[0] * along, self.center[1] + d[1] * along];
+            let offset = [pos[0] - closest[0], pos[1] - closest[1]];
+            let dist = (offset[0] * offset[0] + offset[1] * offset[1]).sqrt();
+            if dist >= self.thickness {
+                continue;
+            }
+
+            // a particle right on the center line leaves on the leading side
+            let normal = match dist > 1e-6 {
+                true => [offset[0] / dist, offset[1] / dist],
+                false => [
+                    -d[1] * self.angular_velocity.signum(),
+                    d[0] * self.angular_velocity.signum(),
+                ],
+            };
+            pos[0] = closest[0] + normal[0] * self.thickness;
+            pos[1] = closest[1] + normal[1] * self.thickness;
+
+            let r = [pos[0] - self.center[0], pos[1] - self.center[1]];
+            let wall = [-self.angular_velocity * r[1], self.angular_velocity * r[0]];
+            let into = (vel[0] - wall[0]) * normal[0] + (vel[1] - wall[1]) * normal[1];
+            if into < 0.0 {
+                vel[0] -= into * normal[0];
+                vel[1] -= into * normal[1];
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Immiscible fluids sharing the domain, and blocks of particles to fill it with.
+//!
+//! Every particle belongs to one phase for its whole life, looked up by slot in
+//! [`Scene::phase_ids`](crate::scene::Scene::phase_ids). The phase parameters
+//! are carried for the density solve; the current collision pass treats all
+//! phases alike, so for now phases only differ in how they are drawn. The
+//! [wcsph backend](crate::wcsph) also bonds the particles of
+//! [plastic](crate::plastic) phases such as snow, and uses the viscosity of
+//! phases that have one of their own, so water and syrup flow differently in
+//! the same solve.
+
+use crate::geometry::Polygon;
+use crate::plastic::Plastic;
+use crate::sampling::Sampling;
+use crate::sim::{self, Instance};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phase {
+    /// Relative to water.
+    pub rest_density: f32,
+    /// Tension at the interface to other phases, per unit length.
+    pub interface_tension: f32,
+    pub color: u32,
+    /// `None` for a fluid.
+    pub plastic: Option<Plastic>,
+    /// Kinematic [viscosity](crate::viscosity), `None` for
+    /// [`Config::viscosity`](crate::backend::Config::viscosity).
+    pub viscosity: Option<f32>,
+}
+
+impl Phase {
+    pub const WATER: Phase = Phase {
+        rest_density: 1.0,
+        interface_tension: 0.0,
+        color: sim::rgba_to_u32(40, 110, 255, 255),
+        plastic: None,
+        viscosity: None,
+    };
+
+    pub const OIL: Phase = Phase {
+        rest_density: 0.8,
+        interface_tension: 0.03,
+        color: sim::rgba_to_u32(230, 170, 40, 255),
+        plastic: None,
+        viscosity: None,
+    };
+
+    pub const SNOW: Phase = Phase {
+        rest_density: 0.4,
+        interface_tension: 0.0,
+        color: sim::rgba_to_u32(235, 240, 250, 255),
+        plastic: Some(Plastic::SNOW),
+        viscosity: None,
+    };
+
+    /// Thick enough to need the implicit viscosity solve.
+    pub const SYRUP: Phase = Phase {
+        rest_density: 1.3,
+        interface_tension: 0.0,
+        color: sim::rgba_to_u32(150, 70, 20, 255),
+        plastic: None,
+        viscosity: Some(0.5),
+    };
+}
+
+impl Default for Phase {
+    fn default() -> Self {
+        Phase::WATER
+    }
+}
+
+impl std::str::FromStr for Phase {
+    type Err = String;
+
+    /// `water`, `oil`, `snow`, `syrup`, or a rest density relative to water.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "water" => Ok(Phase::WATER),
+            "oil" => Ok(Phase::OIL),
+            "snow" => Ok(Phase::SNOW),
+            "syrup" => Ok(Phase::SYRUP),
+            _ => match s.parse::<f32>() {
+                Ok(rest_density) if rest_density > 0.0 => Ok(Phase {
+                    rest_density,
+                    ..Phase::OIL
+                }),
+                _ => Err(format!(
+                    "invalid phase `{s}`, expected water, oil, snow, syrup or a positive rest density"
+                )),
+            },
+        }
+    }
+}
+
+/// An axis aligned rectangle, or a polygon, filled with particles of one phase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FluidBlock {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    /// Only the part of the rectangle inside is filled, when set.
+    pub polygon: Option<Polygon>,
+    /// Distance between neighboring particles.
+    pub spacing: f32,
+    pub phase: Phase,
+    pub sampling: Sampling,
+}
+
+impl FluidBlock {
+    pub fn new(min: [f32; 2], max: [f32; 2], phase: Phase) -> Self {
+        Self {
+            min,
+            max,
+            polygon: None,
+            spacing: 0.02,
+            phase,
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Filled with particles of `radius` at the [rest spacing](rest_spacing)
+    /// of `phase`, so it starts out at rest density.
+    pub fn at_rest(min: [f32; 2], max: [f32; 2], radius: f32, phase: Phase) -> Self {
+        FluidBlock::new(min, max, phase).with_spacing(rest_spacing(radius, phase.rest_density))
+    }
+
+    /// The classic dam break: a column of water `width` wide and `height`
+    /// high in the bottom left corner of the domain, at rest.
+    pub fn dam_break(width: f32, height: f32, radius: f32) -> Self {
+        FluidBlock::at_rest([0.0, 0.0], [width, height], radius, Phase::WATER)
+    }
+
+    pub fn with_spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Filling the bounds of `polygon`, where they are inside it.
+    pub fn polygon(polygon: Polygon, phase: Phase) -> Self {
+        let (min, max) = polygon.bounds();
+        Self {
+            polygon: Some(polygon),
+            ..FluidBlock::new(min, max, phase)
+        }
+    }
+
+    /// Particles at rest, placed by the [sampling](crate::sampling). The
+    /// random samplings are seeded by `min`, so blocks in different places
+    /// look different but every run looks the same.
+    pub fn particles(&self) -> Vec<Instance> {
+        let seed = self.min[0].to_bits() ^ self.min[1].to_bits().rotate_left(16);
+        self.sampling
+            .sample(self.min, self.max, self.spacing, seed)
+            .into_iter()
+            .filter(|&pos| {
+                self.polygon
+                    .as_ref()
+                    .is_none_or(|polygon| polygon.contains(pos))
+            })
+            .map(|pos| Instance {
+                pos,
+                vel: [0.0, 0.0],
+            })
+            .collect()
+    }
+}
+
+/// Distance between particles of `radius` at `rest_density` relative to
+/// water. Water particles just touch, lighter phases spread out so there are
+/// fewer of them per area, as the particles all weigh the same.
+pub fn rest_spacing(radius: f32, rest_density: f32) -> f32 {
+    2.0 * radius / rest_density.sqrt()
+}
+
+impl std::str::FromStr for FluidBlock {
+    type Err = String;
+
+    /// `<x0>,<y0>,<x1>,<y1>` or a [polygon](Polygon), optionally followed by
+    /// `=<phase>`, then optionally by `:<sampling>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, sampling) = match s.split_once(':') {
+            Some((s, sampling)) => (s, sampling.parse()?),
+            None => (s, Sampling::default()),
+        };
+        let (shape, phase) = match s.split_once('=') {
+            Some((shape, phase)) => (shape, phase.parse()?),
+            None => (s, Phase::default()),
+        };
+
+        let block = match shape.contains(';') {
+            true => FluidBlock::polygon(shape.parse()?, phase),
+            false => {
+                let (min, max) = crate::scene::parse_rect(shape)?;
+                FluidBlock::new(min, max, phase)
+            }
+        };
+        Ok(FluidBlock { sampling, ..block })
+    }
+}
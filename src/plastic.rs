@@ -0,0 +1,159 @@
+//! Elastoplastic materials like snow: particles of a [`Phase`] with a
+//! [`Plastic`] material hold on to their neighbors with springy bonds that
+//! give way for good when stretched or squashed too far and snap when pulled
+//! apart.
+//!
+//! The bonds are position based distance constraints. Once a bond deforms
+//! beyond its yield strain, its rest length flows towards the deformed length,
+//! which is what lets snow pack and keep a dent. Bonds form whenever two
+//! particles of the same material come close, so snow sticks to itself and
+//! clumps back together.
+
+use crate::boundary;
+use crate::neighbors::CellList;
+use crate::phase::Phase;
+use crate::sim::Instance;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plastic {
+    /// Share of a bond's deformation corrected per step, up to 1.
+    pub stiffness: f32,
+    /// Relative change in length a bond takes back elastically.
+    pub yield_strain: f32,
+    /// Share of the deformation beyond yield that becomes permanent per step.
+    pub flow: f32,
+    /// Relative stretch at which a bond breaks.
+    pub break_strain: f32,
+    /// Particles closer than this form a bond.
+    pub bond_distance: f32,
+}
+
+impl Plastic {
+    pub const SNOW: Plastic = Plastic {
+        stiffness: 0.5,
+        yield_strain: 0.05,
+        flow: 0.3,
+        break_strain: 0.3,
+        bond_distance: 0.03,
+    };
+}
+
+/// Bonds per particle at most, so a clump squeezed together doesn't keep
+/// adding them.
+const MAX_BONDS: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bond {
+    pub a: u32,
+    pub b: u32,
+    pub rest: f32,
+}
+
+/// The bonds between the particles of plastic phases.
+pub struct Bonds {
+    /// Material per particle slot, `None` for fluid.
+    materials: Vec<Option<(u32, Plastic)>>,
+    bonds: Vec<Bond>,
+    pairs: HashSet<(u32, u32)>,
+    counts: Vec<u8>,
+}
+
+impl Bonds {
+    /// `phase_ids` index into `phases` per particle slot, like
+    /// [`Scene::phase_ids`](crate::scene::Scene::phase_ids).
+    pub fn new(phases: &[Phase], phase_ids: &[u32]) -> Self {
+        let materials = phase_ids
+            .iter()
+            .map(|&id| phases[id as usize].plastic.map(|m| (id, m)))
+            .collect();
+        Self {
+            materials,
+            bonds: vec![],
+            pairs: HashSet::new(),
+            counts: vec![0; phase_ids.len()],
+        }
+    }
+
+    /// Whether any particle is plastic.
+    pub fn is_active(&self) -> bool {
+        self.materials.iter().any(Option::is_some)
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    /// Bonds particles of the same material closer than its bond distance.
+    /// `cells` has to be built from `particles` with a radius of at least the
+    /// bond distance.
+    pub fn stick(&mut self, cells: &CellList, particles: &[Instance]) {
+        for (i, p) in particles.iter().enumerate() {
+            let Some((phase, material)) = self.materials[i] else {
+                continue;
+            };
+            if boundary::is_removed(p) || self.counts[i] >= MAX_BONDS {
+                continue;
+            }
+            cells.for_each_neighbor(particles, p.pos, |j, _, dist| {
+                let same = matches!(self.materials[j], Some((other, _)) if other == phase);
+                if j <= i || !same || dist >= material.bond_distance {
+                    return;
+                }
+                let full = self.counts[i] >= MAX_BONDS || self.counts[j] >= MAX_BONDS;
+                if full || !self.pairs.insert((i as u32, j as u32)) {
+                    return;
+                }
+                self.bonds.push(Bond {
+                    a: i as u32,
+                    b: j as u32,
+                    rest: dist.max(1e-4),
+                });
+                self.counts[i] += 1;
+                self.counts[j] += 1;
+            });
+        }
+    }
+
+    /// Yields, breaks and enforces the bonds after a step of `dt`, changing
+    /// the velocities along with the positions.
+    pub fn project(&mut self, particles: &mut [Instance], dt: f32) {
+        let materials = &self.materials;
+        let (pairs, counts) = (&mut self.pairs, &mut self.counts);
+        self.bonds.retain_mut(|bond| {
+            let (a, b) = (bond.a as usize, bond.b as usize);
+            let Some((_, material)) = materials[a] else {
+                return false;
+            };
+            let (pa, pb) = (particles[a], particles[b]);
+            let d = [pb.pos[0] - pa.pos[0], pb.pos[1] - pa.pos[1]];
+            let dist = (d[0] * d[0] + d[1] * d[1]).sqrt();
+            let strain = dist / bond.rest - 1.0;
+
+            let removed = boundary::is_removed(&pa) || boundary::is_removed(&pb);
+            if removed || strain > material.break_strain {
+                pairs.remove(&(bond.a, bond.b));
+                counts[a] -= 1;
+                counts[b] -= 1;
+                return false;
+            }
+            if strain.abs() > material.yield_strain {
+                let elastic = bond.rest * (1.0 + material.yield_strain * strain.signum());
+                bond.rest += material.flow * (dist - elastic);
+            }
+
+            if dist > 1e-6 {
+                let scale = 0.5 * material.stiffness * (dist - bond.rest) / dist;
+                let correction = [d[0] * scale, d[1] * scale];
+                for (id, sign) in [(a, 1.0), (b, -1.0)] {
+                    let p = &mut particles[id];
+                    p.pos[0] += sign * correction[0];
+                    p.pos[1] += sign * correction[1];
+                    p.vel[0] += sign * correction[0] / dt;
+                    p.vel[1] += sign * correction[1] / dt;
+                }
+            }
+            true
+        });
+    }
+}
@@ -0,0 +1,126 @@
+//! Scrolling plots of how the simulation evolves, drawn as lines in a corner
+//! of the window.
+
+use crate::sim::{self, OverlayVertex};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    KineticEnergy,
+    DensityError,
+    ParticleCount,
+    StepTime,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::KineticEnergy,
+        Metric::DensityError,
+        Metric::ParticleCount,
+        Metric::StepTime,
+    ];
+
+    pub fn color(self) -> u32 {
+        match self {
+            Metric::KineticEnergy => sim::rgba_to_u32(250, 200, 60, 255),
+            Metric::DensityError => sim::rgba_to_u32(240, 80, 80, 255),
+            Metric::ParticleCount => sim::rgba_to_u32(90, 210, 110, 255),
+            Metric::StepTime => sim::rgba_to_u32(80, 160, 250, 255),
+        }
+    }
+}
+
+/// One value per [`Metric`], as measured by the simulation thread for a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Metrics {
+    pub kinetic_energy: f32,
+    pub density_error: f32,
+    pub particle_count: f32,
+    /// Mean wall clock seconds per step.
+    pub step_time: f32,
+}
+
+impl Metrics {
+    pub fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::KineticEnergy => self.kinetic_energy,
+            Metric::DensityError => self.density_error,
+            Metric::ParticleCount => self.particle_count,
+            Metric::StepTime => self.step_time,
+        }
+    }
+}
+
+/// Frame outline color by default.
+pub const FRAME: u32 = sim::rgba_to_u32(255, 255, 255, 80);
+/// Screen rectangle of the first plot, in normalized device coordinates.
+const LEFT: f32 = -0.97;
+const WIDTH: f32 = 0.6;
+const TOP: f32 = 0.97;
+const HEIGHT: f32 = 0.16;
+const GAP: f32 = 0.04;
+
+/// The last `window` values of every [`Metric`], each plotted in its own box
+/// from zero to its largest value in the window, one above the other.
+pub struct Plots {
+    window: usize,
+    series: [VecDeque<f32>; 4],
+    /// Color of the frame outlines.
+    pub frame: u32,
+}
+
+impl Plots {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(2),
+            series: Default::default(),
+            frame: FRAME,
+        }
+    }
+
+    pub fn push(&mut self, metrics: &Metrics) {
+        for (metric, series) in Metric::ALL.into_iter().zip(&mut self.series) {
+            if series.len() == self.window {
+                series.pop_front();
+            }
+            series.push_back(metrics.get(metric));
+        }
+    }
+
+    pub fn series(&self, metric: Metric) -> &VecDeque<f32> {
+        &self.series[metric as usize]
+    }
+
+    /// Line segments of the frames and curves, two vertices each.
+    pub fn vertices(&self) -> Vec<OverlayVertex> {
+        let mut vertices = vec![];
+        let mut segment = |a: [f32; 2], b: [f32; 2], color: u32| {
+            vertices.push(OverlayVertex { pos: a, color });
+            vertices.push(OverlayVertex { pos: b, color });
+        };
+
+        for (i, metric) in Metric::ALL.into_iter().enumerate() {
+            let top = TOP - i as f32 * (HEIGHT + GAP);
+            let (left, right, bottom) = (LEFT, LEFT + WIDTH, top - HEIGHT);
+            segment([left, bottom], [right, bottom], self.frame);
+            segment([right, bottom], [right, top], self.frame);
+            segment([right, top], [left, top], self.frame);
+            segment([left, top], [left, bottom], self.frame);
+
+            let series = self.series(metric);
+            let max = series.iter().fold(0.0f32, |max, &v| max.max(v));
+            let point = |j: usize, v: f32| {
+                let x = left + WIDTH * j as f32 / (self.window - 1) as f32;
+                let y = match max > 0.0 {
+                    true => bottom + HEIGHT * (v / max).clamp(0.0, 1.0),
+                    false => bottom,
+                };
+                [x, y]
+            };
+            for (j, (&a, &b)) in series.iter().zip(series.iter().skip(1)).enumerate() {
+                segment(point(j, a), point(j + 1, b), metric.color());
+            }
+        }
+        vertices
+    }
+}
@@ -0,0 +1,177 @@
+//! Reading PNG images, such as photos of an experiment or level art to line
+//! the simulation up against.
+//!
+//! Covers what image editors and cameras write: gray, gray with alpha, RGB,
+//! RGBA and palette images with 8 or 16 bits per channel, not interlaced.
+//! Sixteen bit channels are cut down to eight. Writing is
+//! [`timelapse::write_png`](crate::timelapse::write_png).
+
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// 8 bit RGBA, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Colors packed as `0xAARRGGBB`, row by row from the bottom like the
+    /// domain.
+    pub fn packed(&self) -> Vec<u32> {
+        self.pixels
+            .chunks_exact(self.width as usize * 4)
+            .rev()
+            .flat_map(|row| row.chunks_exact(4))
+            .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+            .collect()
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+pub fn read_png(mut input: impl Read) -> io::Result<Image> {
+    let mut signature = [0; 8];
+    input.read_exact(&mut signature)?;
+    if &signature != b"\x89PNG\r\n\x1a\n" {
+        return Err(invalid("not a PNG image"));
+    }
+
+    let mut header = None;
+    let mut palette = vec![];
+    let mut transparency = vec![];
+    let mut data = vec![];
+    loop {
+        let mut head = [0; 8];
+        input.read_exact(&mut head)?;
+        let len = u32::from_be_bytes(head[..4].try_into().unwrap()) as usize;
+        let mut chunk = vec![0; len + 4];
+        input.read_exact(&mut chunk)?;
+        // crc left unchecked, the deflate stream has a checksum of its own
+        chunk.truncate(len);
+        match &head[4..] {
+            b"IHDR" if len == 13 => header = Some(chunk),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => data.extend(chunk),
+            b"IEND" => break,
+            _ => (),
+        }
+    }
+
+    let header = header.ok_or_else(|| invalid("PNG image without a header"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => return Err(invalid(format!("unknown PNG color type {color_type}"))),
+    };
+    match (depth, color_type) {
+        (8, _) | (16, 0 | 2 | 4 | 6) => (),
+        _ => {
+            return Err(invalid(format!(
+                "{depth} bit PNG images of color type {color_type} are not supported"
+            )))
+        }
+    }
+    if interlace != 0 {
+        return Err(invalid("interlaced PNG images are not supported"));
+    }
+
+    let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&data)
+        .map_err(|err| invalid(format!("corrupt PNG image data: {err:?}")))?;
+    let sample_size = depth as usize / 8;
+    let pixel_size = channels * sample_size;
+    let stride = width as usize * pixel_size;
+    if raw.len() != (stride + 1) * height as usize {
+        return Err(invalid("PNG image data does not match its size"));
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    let mut prev = vec![0; stride];
+    let mut row = vec![0; stride];
+    for line in raw.chunks_exact(stride + 1) {
+        unfilter(line[0], &line[1..], &prev, &mut row, pixel_size)?;
+        for pixel in row.chunks_exact(pixel_size) {
+            // the high byte of 16 bit samples
+            let sample = |i: usize| pixel[i * sample_size];
+            pixels.extend(match color_type {
+                0 => [sample(0), sample(0), sample(0), 255],
+                2 => [sample(0), sample(1), sample(2), 255],
+                3 => {
+                    let i = sample(0) as usize;
+                    let rgb = palette
+                        .get(i * 3..i * 3 + 3)
+                        .ok_or_else(|| invalid("PNG palette index out of range"))?;
+                    let alpha = transparency.get(i).copied().unwrap_or(255);
+                    [rgb[0], rgb[1], rgb[2], alpha]
+                }
+                4 => [sample(0), sample(0), sample(0), sample(1)],
+                _ => [sample(0), sample(1), sample(2), sample(3)],
+            });
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Undoes the `filter` of a row, given the unfiltered row above.
+fn unfilter(
+    filter: u8,
+    line: &[u8],
+    prev: &[u8],
+    row: &mut [u8],
+    pixel_size: usize,
+) -> io::Result<()> {
+    for i in 0..line.len() {
+        let left = if i >= pixel_size {
+            row[i - pixel_size]
+        } else {
+            0
+        };
+        let up = prev[i];
+        let up_left = if i >= pixel_size {
+            prev[i - pixel_size]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(invalid(format!("unknown PNG filter {filter}"))),
+        };
+        row[i] = line[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
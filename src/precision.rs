@@ -0,0 +1,70 @@
+//! Particle state in double precision, `--f64`, to tell whether drift in long
+//! runs comes from rounding.
+//!
+//! The particles themselves stay in single precision, as everything else
+//! reads them. Next to them, the backends keep positions and velocities in
+//! double precision, accumulate gravity, the forces and the motion in those,
+//! and round the particles from them after every step. Whatever changes a
+//! particle in single precision in between, emitting, collisions, boundaries
+//! or edits, is picked up because it no longer matches its rounded double,
+//! see [`Precise::sync`].
+
+use crate::grid::Grid;
+use crate::sim::Instance;
+
+/// Position and velocity of a particle, mirrored as `double4` in
+/// `sorting.ocl`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct Precise {
+    pub pos: [f64; 2],
+    pub vel: [f64; 2],
+}
+
+impl Precise {
+    pub fn of(p: &Instance) -> Self {
+        Self {
+            pos: p.pos.map(f64::from),
+            vel: p.vel.map(f64::from),
+        }
+    }
+
+    /// Takes every component of `p` that doesn't match its rounded double,
+    /// as something else changed it.
+    pub fn sync(&mut self, p: &Instance) {
+        let components = self.pos.iter_mut().zip(p.pos);
+        for (precise, single) in components.chain(self.vel.iter_mut().zip(p.vel)) {
+            // NaN never matches, which keeps removed particles removed
+            if *precise as f32 != single {
+                *precise = single as f64;
+            }
+        }
+    }
+
+    /// Adds `acceleration * dt` to the velocity and rounds it into `vel`.
+    pub fn kick(&mut self, vel: &mut [f32; 2], acceleration: [f32; 2], dt: f32) {
+        for ((precise, single), a) in self.vel.iter_mut().zip(vel).zip(acceleration) {
+            *precise += a as f64 * dt as f64;
+            *single = *precise as f32;
+        }
+    }
+
+    /// Moves by the velocity over `dt`, wrapping around the periodic edges of
+    /// `grid` like [`Grid::wrap_position`], and rounds the position into `pos`.
+    pub fn drift(&mut self, pos: &mut [f32; 2], dt: f32, grid: &Grid) {
+        let components = self.pos.iter_mut().zip(self.vel).zip(pos);
+        for (((precise, vel), single), periodic) in components.zip(grid.periodic()) {
+            *precise += vel * dt as f64;
+            if periodic {
+                let wrapped = *precise - precise.floor();
+                *precise = if wrapped >= 1.0 { 0.0 } else { wrapped };
+            }
+            *single = *precise as f32;
+        }
+    }
+}
+
+/// The double precision state of every particle, by index.
+pub fn track(particles: &[Instance]) -> Vec<Precise> {
+    particles.iter().map(Precise::of).collect()
+}
@@ -0,0 +1,337 @@
+//! Measurement points placed in a scene, sampled every step into time series
+//! for comparing a run against reference data.
+//!
+//! Density is the kernel weighted particle count around a point, relative to
+//! that inside a [`FluidBlock`](crate::phase::FluidBlock) at rest, so 1 in
+//! undisturbed fluid. The backends don't solve for a pressure, so the pressure
+//! is what a weakly compressible fluid at that density would have.
+//!
+//! The same samples taken over a whole grid make a [`Heatmap`] to show
+//! behind the particles, for finding where the pressure spikes.
+
+use crate::boundary::{self, Boundaries};
+use crate::field::ScalarGrid;
+use crate::ghost;
+use crate::neighbors::CellList;
+use crate::sim::Instance;
+use crate::solid::SolidParticles;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeParams {
+    /// Radius the samples average over.
+    pub radius: f32,
+    /// Particle spacing of the fluid at rest, where the density is 1.
+    pub rest_spacing: f32,
+    /// Pressure per unit of density above rest.
+    pub stiffness: f32,
+}
+
+impl Default for ProbeParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            rest_spacing: 0.02,
+            stiffness: 1.0,
+        }
+    }
+}
+
+/// What [`Probes::heatmap`] samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Heatmap {
+    /// [`Sample::pressure`], never negative.
+    #[default]
+    Pressure,
+    /// [`Sample::density`] relative to rest, below 0 where the fluid is
+    /// thinned out and above where it is compressed. 0 away from the fluid.
+    DensityError,
+}
+
+impl std::str::FromStr for Heatmap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pressure" => Ok(Heatmap::Pressure),
+            "density-error" => Ok(Heatmap::DensityError),
+            _ => Err(format!(
+                "unknown heatmap `{s}`, expected `pressure` or `density-error`"
+            )),
+        }
+    }
+}
+
+/// A single point, or evenly spaced points along a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub name: String,
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Probe {
+    pub fn point(name: impl Into<String>, pos: [f32; 2]) -> Self {
+        Self {
+            name: name.into(),
+            points: vec![pos],
+        }
+    }
+
+    /// `samples` points from `from` to `to`, both included.
+    pub fn line(name: impl Into<String>, from: [f32; 2], to: [f32; 2], samples: usize) -> Self {
+        let samples = samples.max(2);
+        let points = (0..samples)
+            .map(|i| {
+                let t = i as f32 / (samples - 1) as f32;
+                [
+                    from[0] + (to[0] - from[0]) * t,
+                    from[1] + (to[1] - from[1]) * t,
+                ]
+            })
+            .collect();
+        Self {
+            name: name.into(),
+            points,
+        }
+    }
+}
+
+impl std::str::FromStr for Probe {
+    type Err = String;
+
+    /// `<name>=<x>,<y>` for a point, `<name>=<x0>,<y0>,<x1>,<y1>[:<samples>]`
+    /// for a line, with 10 samples by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = "expected <name>=<x>,<y> or <name>=<x0>,<y0>,<x1>,<y1>[:<samples>]";
+        let (name, spec) = s
+            .split_once('=')
+            .ok_or(format!("invalid probe `{s}`, {expected}"))?;
+        let (coords, samples) = match spec.split_once(':') {
+            Some((coords, samples)) => (
+                coords,
+                samples
+                    .parse()
+                    .map_err(|err| format!("invalid probe samples `{samples}`: {err}"))?,
+            ),
+            None => (spec, 10),
+        };
+        let coords = coords
+            .split(',')
+            .map(|c| c.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid probe `{s}`: {err}"))?;
+
+        match coords[..] {
+            [x, y] if !spec.contains(':') => Ok(Probe::point(name, [x, y])),
+            [x0, y0, x1, y1] if samples >= 2 => Ok(Probe::line(name, [x0, y0], [x1, y1], samples)),
+            _ => Err(format!("invalid probe `{s}`, {expected}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sample {
+    pub density: f32,
+    pub pressure: f32,
+    /// Zero away from the fluid.
+    pub vel: [f32; 2],
+}
+
+/// The samples of every point of a probe at one time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub time: f32,
+    pub samples: Vec<Sample>,
+}
+
+pub struct Probes {
+    params: ProbeParams,
+    probes: Vec<Probe>,
+    /// Kernel weight summed over a lattice at rest, what a density of 1 is.
+    rest_weight: f32,
+    cells: CellList,
+    /// Obstacles counted in the density, see [`crate::solid`].
+    solids: Option<SolidParticles>,
+    /// The walls the samples see [ghosts](crate::ghost) behind.
+    boundaries: Boundaries,
+    /// One time series per probe.
+    series: Vec<Vec<Record>>,
+}
+
+impl Probes {
+    pub fn new(params: ProbeParams, probes: Vec<Probe>) -> Self {
+        Self {
+            cells: CellList::new(params.radius),
+            boundaries: Boundaries::default(),
+            series: vec![vec![]; probes.len()],
+            rest_weight: rest_weight(params.radius, params.rest_spacing),
+            solids: None,
+            params,
+            probes,
+        }
+    }
+
+    /// Counts ghosts behind the walls of `boundaries`, so the density doesn't
+    /// drop next to them.
+    pub fn with_boundaries(mut self, boundaries: Boundaries) -> Self {
+        self.boundaries = boundaries;
+        self
+    }
+
+    /// Counts the solid samples of obstacles in the density.
+    pub fn with_solids(mut self, solids: SolidParticles) -> Self {
+        self.solids = (!solids.is_empty()).then_some(solids);
+        self
+    }
+
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    /// Samples `particles` at every probe point and appends to the series.
+    pub fn record(&mut self, time: f32, particles: &[Instance]) {
+        self.cells.build(particles);
+        for (i, probe) in self.probes.iter().enumerate() {
+            let samples = probe
+                .points
+                .iter()
+                .map(|&pos| self.sample(particles, pos))
+                .collect();
+            self.series[i].push(Record { time, samples });
+        }
+    }
+
+    fn sample(&self, particles: &[Instance], pos: [f32; 2]) -> Sample {
+        let mut vel = [0.0; 2];
+        let mut weight = 0.0;
+        let mut add = |v: [f32; 2], dist: f32| {
+            let w = kernel(dist, self.params.radius);
+            vel[0] += v[0] * w;
+            vel[1] += v[1] * w;
+            weight += w;
+        };
+        self.cells
+            .for_each_neighbor(particles, pos, |id, _, dist| add(particles[id].vel, dist));
+        ghost::for_each_ghost(
+            &self.cells,
+            particles,
+            &self.boundaries,
+            pos,
+            |_, _, v, dist| add(v, dist),
+        );
+
+        let solid = self.solids.as_ref().map_or(0.0, |s| s.weight_at(pos));
+        let density = (weight + solid) / self.rest_weight;
+        Sample {
+            density,
+            pressure: self.params.stiffness * (density - 1.0).max(0.0),
+            vel: match weight > 0.0 {
+                true => [vel[0] / weight, vel[1] / weight],
+                false => [0.0; 2],
+            },
+        }
+    }
+
+    /// `heatmap` of `particles` at the centers of `resolution` by
+    /// `resolution` cells over the domain. Uses the probe radius but no
+    /// probe points.
+    pub fn heatmap(
+        &mut self,
+        particles: &[Instance],
+        heatmap: Heatmap,
+        resolution: u32,
+    ) -> ScalarGrid {
+        self.cells.build(particles);
+        let n = resolution as f32;
+        let mut values = Vec::with_capacity((resolution * resolution) as usize);
+        for y in 0..resolution {
+            for x in 0..resolution {
+                let pos = [(x as f32 + 0.5) / n, (y as f32 + 0.5) / n];
+                let sample = self.sample(particles, pos);
+                values.push(match heatmap {
+                    Heatmap::Pressure => sample.pressure,
+                    Heatmap::DensityError if sample.density > 0.0 => sample.density - 1.0,
+                    Heatmap::DensityError => 0.0,
+                });
+            }
+        }
+        ScalarGrid { resolution, values }
+    }
+
+    /// How far the particles are compressed beyond rest density on average, 0
+    /// for fluid at or below rest. Uses the probe radius but no probe points.
+    pub fn density_error(&mut self, particles: &[Instance]) -> f32 {
+        let errors = self.density_errors(particles);
+        match errors.len() {
+            0 => 0.0,
+            n => errors.iter().sum::<f32>() / n as f32,
+        }
+    }
+
+    /// The compression beyond rest density around every particle that hasn't
+    /// been removed, see [`density_error`](Self::density_error).
+    pub fn density_errors(&mut self, particles: &[Instance]) -> Vec<f32> {
+        self.cells.build(particles);
+        particles
+            .iter()
+            .filter(|p| !boundary::is_removed(p))
+            .map(|p| (self.sample(particles, p.pos).density - 1.0).max(0.0))
+            .collect()
+    }
+
+    /// Time series of the probe called `name`, `None` without such a probe.
+    pub fn series(&self, name: &str) -> Option<&[Record]> {
+        let i = self.probes.iter().position(|p| p.name == name)?;
+        Some(&self.series[i])
+    }
+
+    /// Every sample recorded so far, one row per probe point and time.
+    pub fn write_csv(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "probe,point,x,y,time,density,pressure,vx,vy")?;
+        for (probe, series) in self.probes.iter().zip(&self.series) {
+            for record in series {
+                for (i, (pos, s)) in probe.points.iter().zip(&record.samples).enumerate() {
+                    writeln!(
+                        out,
+                        "{},{i},{},{},{},{},{},{},{}",
+                        probe.name,
+                        pos[0],
+                        pos[1],
+                        record.time,
+                        s.density,
+                        s.pressure,
+                        s.vel[0],
+                        s.vel[1]
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sum of [`kernel`] over a square lattice of `spacing` around one of its
+/// points, what a density of 1 is.
+pub(crate) fn rest_weight(radius: f32, spacing: f32) -> f32 {
+    let reach = (radius / spacing) as i32;
+    (-reach..=reach)
+        .flat_map(|y| (-reach..=reach).map(move |x| (x, y)))
+        .map(|(x, y)| kernel((x as f32).hypot(y as f32) * spacing, radius))
+        .sum()
+}
+
+/// Smooth falloff from 1 at the center to 0 at `radius`.
+pub(crate) fn kernel(dist: f32, radius: f32) -> f32 {
+    let falloff = (1.0 - dist * dist / (radius * radius)).max(0.0);
+    falloff * falloff * falloff
+}
+
+/// Derivative of [`kernel`] by `dist`, never positive.
+pub(crate) fn kernel_slope(dist: f32, radius: f32) -> f32 {
+    let falloff = (1.0 - dist * dist / (radius * radius)).max(0.0);
+    -6.0 * dist / (radius * radius) * falloff * falloff
+}
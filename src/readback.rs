@@ -0,0 +1,195 @@
+//! Getting the particles back to the host without stalling on every copy.
+//!
+//! The host copy of the particles is a [`HostMirror`], a buffer allocated
+//! with `CL_MEM_ALLOC_HOST_PTR` that stays mapped for as long as it lives.
+//! Most drivers back those with page-locked memory the device copies into
+//! directly, instead of staging the transfer through a pinned buffer of
+//! their own first. Drivers that can't map one get an ordinary `Vec`, which
+//! works the same, only slower.
+//!
+//! Reads into it are enqueued without waiting and tracked by a
+//! [`Completion`], which the driver flags from its own thread once the copy
+//! is done. The host only waits when it actually needs the new positions.
+//! The OpenCL backend keeps two mirrors, reading into one while the host
+//! works on the other, the last that arrived, and swaps them once the read
+//! is done.
+
+use opencl3 as cl;
+use opencl3::memory::{Buffer, ClMem};
+use opencl3::types::{cl_command_queue, cl_event, cl_int};
+use std::ffi::c_void;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A buffer kept mapped for reading and writing, with the queue to unmap it
+/// on when dropped.
+pub struct Pinned<T> {
+    buffer: Buffer<T>,
+    data: *mut T,
+    len: usize,
+    /// Retained, so the mirror can outlive the queue it came from.
+    queue: cl_command_queue,
+}
+
+impl<T: Copy> Pinned<T> {
+    /// A pinned copy of `data`, mapped on `queue`.
+    pub fn new(
+        context: &cl::context::Context,
+        queue: &cl::command_queue::CommandQueue,
+        data: &[T],
+    ) -> cl::Result<Self> {
+        let buffer = unsafe {
+            Buffer::<T>::create(
+                context,
+                cl::memory::CL_MEM_READ_WRITE | cl::memory::CL_MEM_ALLOC_HOST_PTR,
+                data.len(),
+                ptr::null_mut(),
+            )?
+        };
+        let mut mapped = ptr::null_mut();
+        unsafe {
+            queue.enqueue_map_buffer(
+                &buffer,
+                cl::types::CL_BLOCKING,
+                cl::memory::CL_MAP_READ | cl::memory::CL_MAP_WRITE,
+                0,
+                std::mem::size_of_val(data),
+                &mut mapped,
+                &[],
+            )?;
+            cl::command_queue::retain_command_queue(queue.get())
+                .map_err(cl::error_codes::ClError)?;
+        }
+        let pinned = Self {
+            buffer,
+            data: mapped.cast(),
+            len: data.len(),
+            queue: queue.get(),
+        };
+        unsafe { std::slice::from_raw_parts_mut(pinned.data, pinned.len) }.copy_from_slice(data);
+        Ok(pinned)
+    }
+}
+
+impl<T> Drop for Pinned<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let unmapped = cl::command_queue::enqueue_unmap_mem_object(
+                self.queue,
+                self.buffer.get(),
+                self.data.cast(),
+                0,
+                ptr::null(),
+            );
+            if let Ok(event) = unmapped {
+                let _ = cl::event::release_event(event);
+            }
+            // the buffer is released after the unmap, however long it takes
+            let _ = cl::command_queue::release_command_queue(self.queue);
+        }
+    }
+}
+
+/// The host copy of device data, pinned if the driver allows.
+pub enum HostMirror<T> {
+    Pinned(Pinned<T>),
+    Pageable(Vec<T>),
+}
+
+impl<T: Copy> HostMirror<T> {
+    pub fn new(
+        context: &cl::context::Context,
+        queue: &cl::command_queue::CommandQueue,
+        data: &[T],
+    ) -> Self {
+        if data.is_empty() {
+            return Self::Pageable(Vec::new());
+        }
+        match Pinned::new(context, queue, data) {
+            Ok(pinned) => Self::Pinned(pinned),
+            Err(err) => {
+                log::warn!(
+                    "could not map pinned host memory ({err}), reading back into pageable memory"
+                );
+                Self::Pageable(data.to_vec())
+            }
+        }
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        matches!(self, Self::Pinned(_))
+    }
+}
+
+impl<T> Deref for HostMirror<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Pinned(pinned) => unsafe { std::slice::from_raw_parts(pinned.data, pinned.len) },
+            Self::Pageable(data) => data,
+        }
+    }
+}
+
+impl<T> DerefMut for HostMirror<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self {
+            Self::Pinned(pinned) => unsafe {
+                std::slice::from_raw_parts_mut(pinned.data, pinned.len)
+            },
+            Self::Pageable(data) => data,
+        }
+    }
+}
+
+/// A command in flight and whether it has finished, set by the driver
+/// through an event callback, so checking doesn't call into OpenCL.
+/// Dropping it waits for the command, as it may still be writing to memory
+/// about to be freed.
+pub struct Completion {
+    event: cl::event::Event,
+    done: Arc<AtomicBool>,
+}
+
+impl Completion {
+    pub fn new(event: cl::event::Event) -> cl::Result<Self> {
+        let done = Arc::new(AtomicBool::new(false));
+        let user_data = Arc::into_raw(done.clone()) as *mut c_void;
+        if let Err(err) = event.set_callback(cl::event::CL_COMPLETE, notify, user_data) {
+            // never called, so the reference is ours to drop
+            drop(unsafe { Arc::from_raw(user_data as *const AtomicBool) });
+            return Err(err);
+        }
+        Ok(Self { event, done })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    pub fn wait(&self) -> cl::Result<()> {
+        if !self.is_complete() {
+            self.event.wait()?;
+        }
+        Ok(())
+    }
+
+    pub fn event(&self) -> &cl::event::Event {
+        &self.event
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        let _ = self.wait();
+    }
+}
+
+/// Called once the command finished or failed, either way it no longer runs.
+extern "C" fn notify(_event: cl_event, _status: cl_int, user_data: *mut c_void) {
+    let done = unsafe { Arc::from_raw(user_data as *const AtomicBool) };
+    done.store(true, Ordering::Release);
+}
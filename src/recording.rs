@@ -0,0 +1,165 @@
+//! Particle streams written while simulating and played back later, see
+//! `src/bin/playback.rs`, without running a backend again.
+//!
+//! A recording is a header followed by one record per frame the simulation
+//! thread publishes: the step, the particle count, then the particles, their
+//! colors and their [ids](crate::ids), with `u32::MAX` for removed particles.
+//! Everything is little endian.
+
+use crate::sim::Instance;
+use crate::TIME_STEP;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"PBFR";
+const VERSION: u32 = 2;
+/// Stands in for the id of a removed particle.
+const NO_ID: u32 = u32::MAX;
+
+/// The particles of one frame as they were drawn.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedFrame {
+    pub step: u64,
+    pub particles: Vec<Instance>,
+    pub colors: Vec<u32>,
+    /// Stable id per particle, `None` for removed ones.
+    pub ids: Vec<Option<u32>>,
+}
+
+impl RecordedFrame {
+    /// Simulated seconds since the start.
+    pub fn time(&self) -> f32 {
+        self.step as f32 * TIME_STEP
+    }
+}
+
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        Ok(Self { out })
+    }
+
+    /// Appends a frame. `colors` and `ids` have one entry per particle.
+    pub fn write(
+        &mut self,
+        step: u64,
+        particles: &[Instance],
+        colors: &[u32],
+        ids: &[Option<u32>],
+    ) -> io::Result<()> {
+        assert_eq!(particles.len(), colors.len(), "one color per particle");
+        assert_eq!(particles.len(), ids.len(), "one id per particle");
+        self.out.write_all(&step.to_le_bytes())?;
+        self.out
+            .write_all(&(particles.len() as u32).to_le_bytes())?;
+        for p in particles {
+            for x in p.pos.iter().chain(&p.vel) {
+                self.out.write_all(&x.to_le_bytes())?;
+            }
+        }
+        for c in colors {
+            self.out.write_all(&c.to_le_bytes())?;
+        }
+        for id in ids {
+            self.out.write_all(&id.unwrap_or(NO_ID).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Reads the frames of a recording in order.
+pub struct Playback<R: Read> {
+    input: R,
+}
+
+impl Playback<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Playback<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a particle recording",
+            ));
+        }
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported recording version {version}, expected {VERSION}"),
+            ));
+        }
+        Ok(Self { input })
+    }
+
+    /// The next frame, `None` at the end of the recording.
+    pub fn next_frame(&mut self) -> io::Result<Option<RecordedFrame>> {
+        let mut step = [0; 8];
+        match self.input.read_exact(&mut step) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let count = read_u32(&mut self.input)? as usize;
+        let mut particles = Vec::with_capacity(count);
+        for _ in 0..count {
+            let [px, py, vx, vy] = [(); 4].map(|_| read_f32(&mut self.input));
+            particles.push(Instance {
+                pos: [px?, py?],
+                vel: [vx?, vy?],
+            });
+        }
+        let colors = (0..count)
+            .map(|_| read_u32(&mut self.input))
+            .collect::<io::Result<_>>()?;
+        let ids = (0..count)
+            .map(|_| read_u32(&mut self.input).map(|id| (id != NO_ID).then_some(id)))
+            .collect::<io::Result<_>>()?;
+        Ok(Some(RecordedFrame {
+            step: u64::from_le_bytes(step),
+            particles,
+            colors,
+            ids,
+        }))
+    }
+
+    /// Every remaining frame.
+    pub fn read_all(&mut self) -> io::Result<Vec<RecordedFrame>> {
+        let mut frames = vec![];
+        while let Some(frame) = self.next_frame()? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(input: &mut impl Read) -> io::Result<f32> {
+    read_u32(input).map(f32::from_bits)
+}
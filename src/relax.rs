@@ -0,0 +1,59 @@
+//! Settling a scene into hydrostatic equilibrium before it starts, so a tank
+//! doesn't begin with the fluid slumping under its own weight.
+//!
+//! The scene is run on the [CPU backend](crate::cpu) with heavily damped
+//! velocities, without anything that would stir it up or add particles: no
+//! forces, paddles or inlets, and wavemakers held still as walls. Whatever
+//! state the solver comes to rest in becomes the start of the simulation.
+
+use crate::backend::{Backend, Config, Error};
+use crate::boundary::{Boundary, Edge, Wall};
+use crate::cpu::CpuState;
+use crate::scene::Scene;
+use crate::sim::Instance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelaxParams {
+    pub steps: u32,
+    /// Fraction of the velocity taken away after every step.
+    pub damping: f32,
+}
+
+impl Default for RelaxParams {
+    fn default() -> Self {
+        Self {
+            steps: 200,
+            damping: 0.1,
+        }
+    }
+}
+
+/// The particles of `scene` after relaxing it, at rest.
+pub fn relax(scene: &Scene, params: &RelaxParams) -> Result<Vec<Instance>, Error> {
+    let mut settling = scene.clone();
+    settling.forces.clear();
+    settling.paddles.clear();
+    for edge in Edge::ALL {
+        match settling.boundaries.get(edge) {
+            Boundary::Inlet(_) => settling.boundaries.set(edge, Boundary::Free),
+            Boundary::Wavemaker(_) => settling
+                .boundaries
+                .set(edge, Boundary::Wall(Wall::default())),
+            _ => {}
+        }
+    }
+
+    let config = Config {
+        // sleeping would freeze particles before they have settled
+        sleep_after: 0,
+        ..Config::default()
+    };
+    let mut cpu = CpuState::new(&settling, &config);
+    let keep = 1.0 - params.damping.clamp(0.0, 1.0);
+    for _ in 0..params.steps {
+        cpu.step()?;
+        cpu.damp(keep);
+    }
+    cpu.damp(0.0);
+    Ok(cpu.particles().to_vec())
+}
@@ -1,11 +1,17 @@
 use glam::{Mat4, Vec3};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use std::iter;
 use std::mem::size_of;
-use winit::event_loop::ControlFlow;
-use winit::{event::*, event_loop::EventLoop, window};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use winit::{event::*, window};
 
+use crate::options::AspectMode;
+use crate::png::Image;
+use crate::sim::{DiffuseInstance, Instance, OverlayVertex, SimParams, SortKey};
+use crate::theme::{self, Theme};
+use crate::trace::DeviceSpan;
 use crate::wgpu_utils as utils;
-use crate::{PARTICLE_COUNT, PARTICLE_RADIUS};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -27,11 +33,80 @@ impl utils::VertexDescription for Vertex {
     }
 }
 
+/// How the particles are drawn, a uniform next to the camera and the
+/// [`SimParams`] so changing it takes a buffer write rather than new
+/// pipelines. Mirrored by `RenderParams` in `shader.wgsl`.
 #[repr(C)]
-#[derive(Clone, Default, Debug, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Instance {
-    pub pos: [f32; 2],
-    pub vel: [f32; 2],
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderParams {
+    /// Drawn radius relative to [`SimParams::particle_radius`].
+    pub radius_scale: f32,
+    /// Inner edge of the ring a particle is drawn as relative to its
+    /// radius, 0 for filled discs.
+    pub ring_inner: f32,
+    /// [`OPAQUE`](Self::OPAQUE) and [`CLIP`](Self::CLIP).
+    pub flags: u32,
+    pub _pad: u32,
+    /// Min x, min y, max x and max y of the world, see [`CLIP`](Self::CLIP).
+    pub bounds: [f32; 4],
+}
+
+impl Default for RenderParams {
+    fn default() -> Self {
+        Self {
+            radius_scale: 1.0,
+            ring_inner: Self::RING_INNER,
+            flags: 0,
+            _pad: 0,
+            bounds: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl RenderParams {
+    /// Ignore the alpha of the particle colors.
+    pub const OPAQUE: u32 = 1;
+    /// Leave out the particles outside of [`bounds`](Self::bounds).
+    pub const CLIP: u32 = 2;
+    /// [`ring_inner`](Self::ring_inner) particles are drawn with by default.
+    pub const RING_INNER: f32 = 0.95;
+
+    /// Drawn as filled discs rather than rings.
+    pub fn discs(&self) -> bool {
+        self.ring_inner == 0.0
+    }
+
+    pub fn set_discs(&mut self, discs: bool) {
+        self.ring_inner = if discs { 0.0 } else { Self::RING_INNER };
+    }
+
+    pub fn set_flag(&mut self, flag: u32, enabled: bool) {
+        match enabled {
+            true => self.flags |= flag,
+            false => self.flags &= !flag,
+        }
+    }
+}
+
+impl utils::VertexDescription for OverlayVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 2]>() as _,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
 }
 
 impl utils::VertexDescription for Instance {
@@ -56,8 +131,50 @@ impl utils::VertexDescription for Instance {
     }
 }
 
-pub const fn rgba_to_u32(r: u8, g: u8, b: u8, _a: u8) -> u32 {
-    (r as u32) << 16 | (g as u32) << 8 | (b as u32) << 2
+#[repr(C)]
+#[derive(Clone, Default, Debug, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceColor {
+    pub rgba: u32,
+}
+
+impl utils::VertexDescription for InstanceColor {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceColor>() as _,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 4,
+                format: wgpu::VertexFormat::Uint32,
+            }],
+        }
+    }
+}
+
+impl utils::VertexDescription for DiffuseInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DiffuseInstance>() as _,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 2]>() as _,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 3]>() as _,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
 }
 
 const SQUARE_VERT: &[Vertex] = &[
@@ -69,55 +186,1276 @@ const SQUARE_VERT: &[Vertex] = &[
     Vertex { pos: [-1f32, 1f32] },
 ];
 
-const SQUARE_INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
+const SQUARE_INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
+
+/// Format of [offscreen](RenderState::offscreen) rendering, sRGB like the
+/// window surfaces usually are, so the colors come out the same.
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Draws `particles` in `colors` at the radius of `sim` with `params` into a
+/// `width` by `height`
+/// image of the unit square, with the default theme and nothing else shown,
+/// 8 bit RGBA row by row from the top. The same state comes out the same on
+/// the same adapter, for golden-image tests of the shaders. `None` without
+/// a GPU adapter.
+pub async fn render_offscreen(
+    particles: &[Instance],
+    colors: &[u32],
+    sim: &SimParams,
+    params: &RenderParams,
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    let mut state = RenderState::offscreen(width, height).await?;
+    state.update_params(sim);
+    state.set_render_params(*params);
+    state.update_instances(particles);
+    state.update_colors(colors);
+    state.update();
+    Some(state.capture())
+}
+
+/// RGBA 0-1 of a packed color, as the shaders take them.
+fn unpack(color: u32) -> [f32; 4] {
+    let [a, r, g, b] = color.to_be_bytes();
+    [r, g, b, a].map(|c| c as f32 / 255.0)
+}
+
+/// The surface is sRGB, so the clear color has to be given in linear RGB.
+fn clear_color(color: u32) -> wgpu::Color {
+    let [r, g, b, a] = theme::to_linear(color).map(f64::from);
+    wgpu::Color { r, g, b, a }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    aspect: f32,
+    mode: AspectMode,
+    left: f32,
+    right: f32,
+    top: f32,
+    bottom: f32,
+}
+
+impl Camera {
+    /// Share of the view a key press pans by.
+    const PAN_STEP: f32 = 0.05;
+    /// Factor a key press zooms by.
+    const ZOOM_STEP: f32 = 1.25;
+
+    /// Looking at the unit square, the domain of the default scene.
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            aspect,
+            mode: AspectMode::Fit,
+            left: 0.0,
+            right: 1.0,
+            bottom: 0.0,
+            top: 1.0,
+        }
+    }
+
+    /// Moves the view by `dx` and `dy` times its size.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let (dx, dy) = (dx * (self.right - self.left), dy * (self.top - self.bottom));
+        self.left += dx;
+        self.right += dx;
+        self.bottom += dy;
+        self.top += dy;
+    }
+
+    /// Zooms in by `factor` around the center of the view, out below one.
+    pub fn zoom(&mut self, factor: f32) {
+        let center = [
+            (self.left + self.right) / 2.0,
+            (self.bottom + self.top) / 2.0,
+        ];
+        let half = [
+            (self.right - self.left) / 2.0 / factor,
+            (self.top - self.bottom) / 2.0 / factor,
+        ];
+        self.left = center[0] - half[0];
+        self.right = center[0] + half[0];
+        self.bottom = center[1] - half[1];
+        self.top = center[1] + half[1];
+    }
+
+    /// Back to the view it started with.
+    pub fn reset(&mut self) {
+        *self = Self {
+            mode: self.mode,
+            ..Self::new(self.aspect)
+        };
+    }
+
+    pub fn mode(&self) -> AspectMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: AspectMode) {
+        self.mode = mode;
+    }
+
+    /// Width over height of the viewport it draws into.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    /// Min and max corner of the view, before fitting it to the aspect ratio.
+    pub fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        ([self.left, self.bottom], [self.right, self.top])
+    }
+
+    pub fn set_bounds(&mut self, (min, max): ([f32; 2], [f32; 2])) {
+        [self.left, self.bottom] = min;
+        [self.right, self.top] = max;
+    }
+
+    /// What the view shows, fitted to the aspect ratio by the
+    /// [mode](Self::set_mode) around its center.
+    pub fn transform(&self) -> WorldTransform {
+        let (width, height) = (self.right - self.left, self.top - self.bottom);
+        // whether the viewport is wider than the view
+        let wider = self.aspect > width / height;
+        let (width, height) = match self.mode {
+            AspectMode::Stretch => (width, height),
+            AspectMode::Fit | AspectMode::Letterbox if wider => (height * self.aspect, height),
+            AspectMode::Fit | AspectMode::Letterbox => (width, width / self.aspect),
+            AspectMode::Fill if wider => (width, width / self.aspect),
+            AspectMode::Fill => (height * self.aspect, height),
+        };
+        let center = [
+            (self.left + self.right) / 2.0,
+            (self.bottom + self.top) / 2.0,
+        ];
+        WorldTransform::new(
+            [center[0] - width / 2.0, center[1] - height / 2.0],
+            [center[0] + width / 2.0, center[1] + height / 2.0],
+        )
+    }
+
+    /// Offset from the top left and size of the part of a pane of `size`
+    /// pixels it draws into, all of it but with
+    /// [`Letterbox`](AspectMode::Letterbox), where it is as wide or as high as
+    /// the view.
+    pub fn viewport(&self, size: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        if self.mode != AspectMode::Letterbox {
+            return ([0.0, 0.0], size);
+        }
+        let view = (self.right - self.left) / (self.top - self.bottom);
+        let inner = match size[0] / size[1] > view {
+            true => [size[1] * view, size[1]],
+            false => [size[0], size[0] / view],
+        };
+        let offset = [(size[0] - inner[0]) / 2.0, (size[1] - inner[1]) / 2.0];
+        (offset, inner)
+    }
+
+    /// World position under `cursor`, given in pixels from the top left of a
+    /// viewport of `size` pixels.
+    pub fn to_world(&self, cursor: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+        self.transform().to_world(cursor, size)
+    }
+
+    /// Like [`raw`](Self::raw), but only the `pixels` squared around `cursor`
+    /// fill the clip space, for [picking](RenderState::pick).
+    pub fn pick_raw(&self, cursor: [f32; 2], size: [f32; 2], pixels: f32) -> [f32; 16] {
+        self.transform().around(cursor, size, pixels).raw()
+    }
+
+    pub fn raw(&self) -> [f32; 16] {
+        self.transform().raw()
+    }
+}
+
+/// Maps a rectangle of the world onto the clip space of a viewport, and
+/// back. Everything that goes between the two takes it from the
+/// [`Camera`]: the shaders, picking, the mouse and world-anchored parts of
+/// the overlay, so none of them can disagree on where something is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldTransform {
+    /// World position at the bottom left of the viewport.
+    pub min: [f32; 2],
+    /// World position at the top right of the viewport.
+    pub max: [f32; 2],
+}
+
+impl WorldTransform {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self { min, max }
+    }
+
+    /// Clip space position of `world`.
+    pub fn to_clip(&self, world: [f32; 2]) -> [f32; 2] {
+        [0, 1].map(|i| 2.0 * (world[i] - self.min[i]) / (self.max[i] - self.min[i]) - 1.0)
+    }
+
+    /// World position at `clip` in clip space.
+    pub fn from_clip(&self, clip: [f32; 2]) -> [f32; 2] {
+        [0, 1].map(|i| self.min[i] + (clip[i] + 1.0) / 2.0 * (self.max[i] - self.min[i]))
+    }
+
+    /// World position under `cursor`, given in pixels from the top left of a
+    /// viewport of `size` pixels.
+    pub fn to_world(&self, cursor: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+        self.from_clip(pixel_to_clip(cursor, size))
+    }
+
+    /// The part of the world the `pixels` squared around `cursor` show.
+    pub fn around(&self, cursor: [f32; 2], size: [f32; 2], pixels: f32) -> Self {
+        let center = self.to_world(cursor, size);
+        let half = [0, 1].map(|i| (self.max[i] - self.min[i]) * pixels / size[i] / 2.0);
+        Self::new(
+            [center[0] - half[0], center[1] - half[1]],
+            [center[0] + half[0], center[1] + half[1]],
+        )
+    }
+
+    /// As a column-major matrix for the shaders, world positions at depth 0
+    /// map to depth 1.
+    pub fn raw(&self) -> [f32; 16] {
+        let view = Mat4::look_at_rh(
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let [left, bottom] = self.min;
+        let [right, top] = self.max;
+        let proj = Mat4::orthographic_rh(left, right, bottom, top, 0.0, 1.0);
+        (proj * view).to_cols_array()
+    }
+}
+
+/// Clip space position of `cursor`, given in pixels from the top left of a
+/// viewport of `size` pixels.
+fn pixel_to_clip(cursor: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+    [
+        2.0 * cursor[0] / size[0] - 1.0,
+        1.0 - 2.0 * cursor[1] / size[1],
+    ]
+}
+
+/// A texture of packed colors stretched over a rectangle of the world, see
+/// [`RenderState::update_background`] and
+/// [`RenderState::set_background_image`].
+struct Background {
+    texture: wgpu::Texture,
+    sampler: wgpu::Sampler,
+    rect_buffer: wgpu::Buffer,
+    bind_group: utils::BindGroup,
+    size: (u32, u32),
+    visible: bool,
+}
+
+impl Background {
+    fn create_texture(device: &wgpu::Device, size: (u32, u32)) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Background Texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // `0xAARRGGBB` in little endian
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    /// Covers the unit domain until [moved](Self::set_rect).
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = Self::create_texture(device, (1, 1));
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Background Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let rect_buffer =
+            utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
+                .label("background_rect_buffer")
+                .data(&[[0f32, 0.0, 1.0, 1.0]])
+                .build(device);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = Self::bind_group(&view, &sampler, &rect_buffer).build(device);
+
+        Self {
+            texture,
+            sampler,
+            rect_buffer,
+            bind_group,
+            size: (1, 1),
+            visible: false,
+        }
+    }
+
+    fn bind_group<'a>(
+        view: &'a wgpu::TextureView,
+        sampler: &'a wgpu::Sampler,
+        rect_buffer: &'a wgpu::Buffer,
+    ) -> utils::BindGroupBuilder<'a> {
+        utils::BindGroupBuilder::default()
+            .label("background_bind_group")
+            .texture(view, wgpu::ShaderStages::FRAGMENT)
+            .sampler(sampler, wgpu::ShaderStages::FRAGMENT)
+            .uniform_buffer(rect_buffer, wgpu::ShaderStages::VERTEX)
+    }
+
+    fn set_rect(&self, queue: &wgpu::Queue, min: [f32; 2], max: [f32; 2]) {
+        queue.write_buffer(
+            &self.rect_buffer,
+            0,
+            bytemuck::cast_slice(&[min[0], min[1], max[0], max[1]]),
+        );
+    }
+
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: (u32, u32),
+        colors: &[u32],
+    ) {
+        if size != self.size {
+            self.texture = Self::create_texture(device, size);
+            let view = self
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            self.bind_group.group = Self::bind_group(&view, &self.sampler, &self.rect_buffer)
+                .rebuild(device, &self.bind_group.layout);
+            self.size = size;
+        }
+
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            bytemuck::cast_slice(colors),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.0 * 4),
+                rows_per_image: None,
+            },
+            self.texture.size(),
+        );
+    }
+}
+
+/// Targets and pipelines of the [order independent](RenderState::set_oit)
+/// particle pass, the targets sized like the surface.
+/// Measures how long the GPU spends on a frame with timestamp queries, on
+/// devices that have them, and on each of its passes between
+/// [marks](Self::mark). The result of a frame comes back a few frames
+/// later, frames rendered while one is still on its way are not measured.
+struct GpuTimer {
+    queries: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    /// Nanoseconds per tick.
+    period: f32,
+    /// Whether the frame being encoded is measured.
+    measuring: bool,
+    /// Frames begun so far.
+    frame: u64,
+    /// Frame being measured and the names of its passes, one per timestamp
+    /// after the first.
+    passes: (u64, Vec<&'static str>),
+    /// Hears back once `readback` is mapped, while a result is on its way.
+    mapped: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    /// Seconds of the last frame measured.
+    last: Option<f32>,
+    /// Passes of the last frame measured, until [taken](RenderState::take_gpu_spans).
+    spans: Option<(u64, Vec<DeviceSpan>)>,
+}
+
+impl GpuTimer {
+    const QUERIES: u32 = 8;
+    const SIZE: u64 = Self::QUERIES as u64 * size_of::<u64>() as u64;
+
+    fn new(context: &utils::WGPUContext) -> Option<Self> {
+        let device = &context.device;
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let queries = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Frame Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERIES,
+        });
+        let resolve = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Timestamp Resolve Buffer"),
+            size: Self::SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Timestamp Readback Buffer"),
+            size: Self::SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            queries,
+            resolve,
+            readback,
+            period: context.queue.get_timestamp_period(),
+            measuring: false,
+            frame: 0,
+            passes: (0, vec![]),
+            mapped: None,
+            last: None,
+            spans: None,
+        })
+    }
+
+    /// Picks up a finished measurement and starts measuring this frame if
+    /// none is on its way.
+    fn begin(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(mapped) = &self.mapped {
+            device.poll(wgpu::Maintain::Poll);
+            match mapped.try_recv() {
+                Ok(Ok(())) => {
+                    {
+                        let range = self.readback.slice(..).get_mapped_range();
+                        let ticks: &[u64] = bytemuck::cast_slice(&range);
+                        let ns = |tick: u64| (tick as f64 * self.period as f64) as u64;
+                        let (frame, names) = &self.passes;
+                        let spans = names
+                            .iter()
+                            .zip(ticks.windows(2))
+                            .map(|(&name, ticks)| DeviceSpan {
+                                name,
+                                category: "render",
+                                start: ns(ticks[0]),
+                                end: ns(ticks[1].max(ticks[0])),
+                            })
+                            .collect::<Vec<_>>();
+                        let total = ticks[names.len()].saturating_sub(ticks[0]);
+                        self.last = Some(total as f32 * self.period * 1e-9);
+                        self.spans = Some((*frame, spans));
+                    }
+                    self.readback.unmap();
+                    self.mapped = None;
+                }
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => self.mapped = None,
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+        }
+        self.frame += 1;
+        self.measuring = self.mapped.is_none();
+        if self.measuring {
+            self.passes = (self.frame, vec![]);
+            encoder.write_timestamp(&self.queries, 0);
+        }
+    }
+
+    /// Ends the pass `name` that ran since the last mark, or since the frame
+    /// began.
+    fn mark(&mut self, encoder: &mut wgpu::CommandEncoder, name: &'static str) {
+        let names = &mut self.passes.1;
+        if self.measuring && names.len() + 1 < Self::QUERIES as usize {
+            names.push(name);
+            encoder.write_timestamp(&self.queries, names.len() as u32);
+        }
+    }
+
+    fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.measuring {
+            let count = self.passes.1.len() as u32 + 1;
+            encoder.resolve_query_set(&self.queries, 0..count, &self.resolve, 0);
+            encoder.copy_buffer_to_buffer(&self.resolve, 0, &self.readback, 0, Self::SIZE);
+        }
+    }
+
+    /// Reads the timestamps back once the frame was submitted.
+    fn submitted(&mut self) {
+        if !std::mem::take(&mut self.measuring) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.mapped = Some(receiver);
+    }
+}
+
+/// Finds the particle under the cursor by drawing the particles' indices
+/// into a small target around it and reading that back, rather than looking
+/// through all of them on the host. Like the [`GpuTimer`], the answer comes
+/// back a few frames later, and a pick asked for while one is on its way waits
+/// for it.
+struct Picker {
+    target: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    camera_buffer: wgpu::Buffer,
+    /// The pick camera with the parameters, bound like the camera.
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Cursor in pixels of the next pick, if one was asked for.
+    requested: Option<[f32; 2]>,
+    /// Whether the frame being encoded picks.
+    picking: bool,
+    /// Hears back once `readback` is mapped, while a pick is on its way.
+    mapped: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    /// Of the last pick, until taken.
+    result: Option<Option<u32>>,
+}
+
+impl Picker {
+    /// Pixels across the target, how close the cursor has to be to a particle.
+    const SIZE: u32 = 16;
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+    /// Bytes per row of the readback, copies need whole blocks of 256.
+    const ROW: u32 = {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        (Self::SIZE * 4).div_ceil(align) * align
+    };
+
+    fn new(
+        context: &utils::WGPUContext,
+        camera_bind_group: &utils::BindGroup,
+        params_buffer: &wgpu::Buffer,
+        render_params_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let device = &context.device;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Pick Target"),
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: (Self::ROW * Self::SIZE) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_buffer =
+            utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
+                .label("pick_camera_buffer")
+                .data(&[Mat4::IDENTITY.to_cols_array()])
+                .build(device);
+        let bind_group = utils::BindGroupBuilder::default()
+            .label("pick_bind_group")
+            .uniform_buffer(&camera_buffer, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(params_buffer, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(render_params_buffer, wgpu::ShaderStages::VERTEX_FRAGMENT)
+            .rebuild(device, &camera_bind_group.layout);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let vertex = utils::ShaderModule::from(&shader)
+            .entry("vs_pick")
+            .vertex::<Vertex>()
+            .instance::<Instance>()
+            .instance::<InstanceColor>();
+        // no blending, the particle drawn last is the one on top
+        let fragment = utils::ShaderModule::from(&shader)
+            .entry("fs_pick")
+            .fragment()
+            .color_target(wgpu::ColorTargetState {
+                format: Self::FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            });
+        let pipeline = utils::RenderPipelineBuilder::default()
+            .label("Pick Pipeline")
+            .vertex_stage(&vertex)
+            .fragment_stage(&fragment)
+            .bind(camera_bind_group)
+            .build(device);
+
+        Self {
+            target,
+            view,
+            readback,
+            camera_buffer,
+            bind_group,
+            pipeline,
+            requested: None,
+            picking: false,
+            mapped: None,
+            result: None,
+        }
+    }
+
+    /// Picks up a finished pick and, if one was asked for and none is on its
+    /// way, sets up the pick camera for this frame.
+    fn begin(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        transform: &WorldTransform,
+        size: [f32; 2],
+    ) {
+        if let Some(mapped) = &self.mapped {
+            device.poll(wgpu::Maintain::Poll);
+            match mapped.try_recv() {
+                Ok(Ok(())) => {
+                    {
+                        let range = self.readback.slice(..).get_mapped_range();
+                        let texels: &[u32] = bytemuck::cast_slice(&range);
+                        let stride = (Self::ROW / 4) as usize;
+                        self.result = Some(nearest_hit(texels, stride, Self::SIZE as usize));
+                    }
+                    self.readback.unmap();
+                    self.mapped = None;
+                }
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => self.mapped = None,
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+        }
+        self.picking = self.mapped.is_none() && self.requested.is_some();
+        if let Some(cursor) = self.requested.filter(|_| self.picking) {
+            let raw = transform.around(cursor, size, Self::SIZE as f32).raw();
+            queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[raw]));
+            self.requested = None;
+        }
+    }
+
+    /// Draws the particles of `state` unsorted, so the indices are those they
+    /// were uploaded with, and copies the target out.
+    fn pick(&self, encoder: &mut wgpu::CommandEncoder, state: &RenderState) {
+        if !self.picking {
+            return;
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, state.vertex_buffer.slice(..));
+            let pane = &state.panes[0];
+            render_pass.set_vertex_buffer(1, pane.instance_buffer.buffer.slice(..));
+            render_pass.set_vertex_buffer(2, pane.color_buffer.buffer.slice(..));
+            render_pass.set_index_buffer(state.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(
+                0..SQUARE_INDICES.len() as u32,
+                0,
+                0..pane.instance_buffer.len() as u32,
+            );
+        }
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(Self::ROW),
+                    rows_per_image: None,
+                },
+            },
+            self.target.size(),
+        );
+    }
+
+    /// Reads the target back once the frame was submitted.
+    fn submitted(&mut self) {
+        if !std::mem::take(&mut self.picking) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.mapped = Some(receiver);
+    }
+}
+
+/// Index of the particle closest to the center of a `size` squared pick
+/// target, read back as `texels` with `stride` of them per row, each holding
+/// an index plus one or zero where there is none.
+pub fn nearest_hit(texels: &[u32], stride: usize, size: usize) -> Option<u32> {
+    let center = (size as f32 - 1.0) / 2.0;
+    (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let index = texels.get(y * stride + x)?.checked_sub(1)?;
+            let distance = (x as f32 - center).powi(2) + (y as f32 - center).powi(2);
+            Some((distance, index))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, index)| index)
+}
+
+struct Oit {
+    accum: wgpu::TextureView,
+    revealage: wgpu::TextureView,
+    bind_group: utils::BindGroup,
+    size: (u32, u32),
+    pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Oit {
+    const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+    fn create_target(
+        device: &wgpu::Device,
+        label: &str,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0.max(1),
+                    height: size.1.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn bind_group<'a>(
+        accum: &'a wgpu::TextureView,
+        revealage: &'a wgpu::TextureView,
+    ) -> utils::BindGroupBuilder<'a> {
+        utils::BindGroupBuilder::default()
+            .label("oit_bind_group")
+            .texture(accum, wgpu::ShaderStages::FRAGMENT)
+            .texture(revealage, wgpu::ShaderStages::FRAGMENT)
+    }
+
+    fn new(context: &utils::WGPUContext, camera_bind_group: &utils::BindGroup) -> Self {
+        let device = &context.device;
+        let size = (context.config.width, context.config.height);
+        let accum = Self::create_target(device, "OIT Accumulation", Self::ACCUM_FORMAT, size);
+        let revealage = Self::create_target(device, "OIT Revealage", Self::REVEALAGE_FORMAT, size);
+        let bind_group = Self::bind_group(&accum, &revealage).build(device);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let vertex = utils::ShaderModule::from(&shader)
+            .entry("vs_main")
+            .vertex::<Vertex>()
+            .instance::<Instance>()
+            .instance::<InstanceColor>();
+        // accumulation sums up, revealage multiplies by one minus each alpha
+        let fragment = utils::ShaderModule::from(&shader)
+            .entry("fs_oit")
+            .fragment()
+            .color_target(wgpu::ColorTargetState {
+                format: Self::ACCUM_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .color_target(wgpu::ColorTargetState {
+                format: Self::REVEALAGE_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                write_mask: wgpu::ColorWrites::RED,
+            });
+        let pipeline = utils::RenderPipelineBuilder::default()
+            .label("OIT Pipeline")
+            .vertex_stage(&vertex)
+            .fragment_stage(&fragment)
+            .bind(camera_bind_group)
+            .build(device);
+
+        let composite_shader = device.create_shader_module(wgpu::include_wgsl!("oit.wgsl"));
+        let composite_vertex = utils::ShaderModule::from(&composite_shader)
+            .entry("vs_main")
+            .vertex::<Vertex>();
+        let composite_fragment = utils::ShaderModule::from(&composite_shader)
+            .entry("fs_main")
+            .fragment()
+            .color_target(wgpu::ColorTargetState {
+                format: context.config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            });
+        let composite_pipeline = utils::RenderPipelineBuilder::default()
+            .label("OIT Composite Pipeline")
+            .vertex_stage(&composite_vertex)
+            .fragment_stage(&composite_fragment)
+            .bind(&bind_group)
+            .build(device);
+
+        Self {
+            accum,
+            revealage,
+            bind_group,
+            size,
+            pipeline,
+            composite_pipeline,
+        }
+    }
+
+    /// Recreates the targets if the surface changed size.
+    fn fit(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if size == self.size {
+            return;
+        }
+        self.accum = Self::create_target(device, "OIT Accumulation", Self::ACCUM_FORMAT, size);
+        self.revealage = Self::create_target(device, "OIT Revealage", Self::REVEALAGE_FORMAT, size);
+        self.bind_group.group =
+            Self::bind_group(&self.accum, &self.revealage).rebuild(device, &self.bind_group.layout);
+        self.size = size;
+    }
+}
+
+/// Mirrors `SortParams` in `sort.wgsl`, one per dispatch.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SortParams {
+    n_instances: u32,
+    key: u32,
+    j: u32,
+    k: u32,
+}
+
+/// Sorts copies of the instances and their colors on the GPU, see
+/// [`RenderState::set_sort_key`].
+struct Sorter {
+    key: SortKey,
+    fill_keys_pipeline: wgpu::ComputePipeline,
+    bitonic_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    bind_group: utils::BindGroup,
+    /// Instances sorted for, rounded up to a power of two.
+    capacity: usize,
+    keys: wgpu::Buffer,
+    instances: wgpu::Buffer,
+    colors: wgpu::Buffer,
+    params: wgpu::Buffer,
+    /// Bytes between the params of two dispatches.
+    params_stride: u64,
+    params_data: Vec<u8>,
+    /// Sizes of the unsorted buffers the bind group reads from, they are only
+    /// ever replaced by bigger ones.
+    sources: (u64, u64),
+    /// Whether `instances` and `colors` hold this frame's particles.
+    sorted: bool,
+}
+
+impl Sorter {
+    const WORKGROUP_SIZE: usize = 256;
+
+    fn new(
+        device: &wgpu::Device,
+        key: SortKey,
+        instances: &wgpu::Buffer,
+        colors: &wgpu::Buffer,
+    ) -> Self {
+        let params_stride = (size_of::<SortParams>() as u64)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let capacity = 1;
+        let (keys, sorted_instances, sorted_colors, params) =
+            Self::create_buffers(device, capacity, params_stride);
+        let bind_group = Self::bind_group(
+            instances,
+            colors,
+            &keys,
+            &sorted_instances,
+            &sorted_colors,
+            &params,
+        )
+        .build(device);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("sort.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sort Pipeline Layout"),
+            bind_group_layouts: &[&bind_group.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+
+        Self {
+            key,
+            fill_keys_pipeline: pipeline("fill_keys"),
+            bitonic_pipeline: pipeline("bitonic"),
+            scatter_pipeline: pipeline("scatter"),
+            bind_group,
+            capacity,
+            keys,
+            instances: sorted_instances,
+            colors: sorted_colors,
+            params,
+            params_stride,
+            params_data: vec![],
+            sources: (instances.size(), colors.size()),
+            sorted: false,
+        }
+    }
+
+    /// Dispatches a sort of `capacity` keys takes, one bitonic merge step
+    /// each plus filling the keys and scattering.
+    fn dispatches(capacity: usize) -> usize {
+        let stages = capacity.trailing_zeros() as usize;
+        stages * (stages + 1) / 2 + 2
+    }
+
+    fn create_buffers(
+        device: &wgpu::Device,
+        capacity: usize,
+        params_stride: u64,
+    ) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Buffer, wgpu::Buffer) {
+        let buffer = |label, usage, size| {
+            utils::BufferBuilder::new(usage)
+                .label(label)
+                .size(size as _)
+                .build(device)
+        };
+        let storage = wgpu::BufferUsages::STORAGE;
+        let drawn = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX;
+        (
+            buffer("Sort Keys", storage, capacity * size_of::<[u32; 2]>()),
+            buffer("Sorted Instances", drawn, capacity * size_of::<Instance>()),
+            buffer("Sorted Colors", drawn, capacity * size_of::<u32>()),
+            buffer(
+                "Sort Params",
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                Self::dispatches(capacity) * params_stride as usize,
+            ),
+        )
+    }
+
+    fn bind_group<'a>(
+        instances: &'a wgpu::Buffer,
+        colors: &'a wgpu::Buffer,
+        keys: &'a wgpu::Buffer,
+        sorted_instances: &'a wgpu::Buffer,
+        sorted_colors: &'a wgpu::Buffer,
+        params: &'a wgpu::Buffer,
+    ) -> utils::BindGroupBuilder<'a> {
+        let stage = wgpu::ShaderStages::COMPUTE;
+        utils::BindGroupBuilder::default()
+            .label("sort_bind_group")
+            .storage_buffer(instances, true, stage)
+            .storage_buffer(colors, true, stage)
+            .storage_buffer(keys, false, stage)
+            .storage_buffer(sorted_instances, false, stage)
+            .storage_buffer(sorted_colors, false, stage)
+            .dynamic_uniform_buffer(
+                params,
+                wgpu::BufferSize::new(size_of::<SortParams>() as u64).unwrap(),
+                stage,
+            )
+    }
+
+    /// Records sorting the first `n` of `instances` and `colors` into
+    /// `encoder`. Leaves [`sorted`](Self::sorted) unset if there are more
+    /// than one dispatch can cover.
+    fn sort(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &wgpu::Buffer,
+        colors: &wgpu::Buffer,
+        n: usize,
+    ) {
+        let capacity = n.next_power_of_two();
+        let workgroups = capacity.div_ceil(Self::WORKGROUP_SIZE) as u32;
+        self.sorted = n > 0 && workgroups <= device.limits().max_compute_workgroups_per_dimension;
+        if !self.sorted {
+            return;
+        }
+
+        let sources = (instances.size(), colors.size());
+        let grown = capacity > self.capacity;
+        if grown {
+            (self.keys, self.instances, self.colors, self.params) =
+                Self::create_buffers(device, capacity, self.params_stride);
+            self.capacity = capacity;
+        }
+        if grown || sources != self.sources {
+            self.bind_group.group = Self::bind_group(
+                instances,
+                colors,
+                &self.keys,
+                &self.instances,
+                &self.colors,
+                &self.params,
+            )
+            .rebuild(device, &self.bind_group.layout);
+            self.sources = sources;
+        }
+
+        // the whole capacity is sorted, keys past `n` are padding
+        let mut steps = vec![(0, 0)];
+        let mut k = 2;
+        while k <= self.capacity {
+            let mut j = k / 2;
+            while j > 0 {
+                steps.push((j, k));
+                j /= 2;
+            }
+            k *= 2;
+        }
+        self.params_data.clear();
+        for &(j, k) in &steps {
+            let params = SortParams {
+                n_instances: n as u32,
+                key: self.key as u32,
+                j: j as u32,
+                k: k as u32,
+            };
+            self.params_data.extend(bytemuck::bytes_of(&params));
+            self.params_data.resize(
+                self.params_data
+                    .len()
+                    .next_multiple_of(self.params_stride as usize),
+                0,
+            );
+        }
+        queue.write_buffer(&self.params, 0, &self.params_data);
+
+        let workgroups = self.capacity.div_ceil(Self::WORKGROUP_SIZE) as u32;
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Sort Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.fill_keys_pipeline);
+        pass.set_bind_group(0, &self.bind_group.group, &[0]);
+        pass.dispatch_workgroups(workgroups, 1, 1);
+        pass.set_pipeline(&self.bitonic_pipeline);
+        for i in 1..steps.len() {
+            let offset = (i as u64 * self.params_stride) as u32;
+            pass.set_bind_group(0, &self.bind_group.group, &[offset]);
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        pass.set_pipeline(&self.scatter_pipeline);
+        pass.set_bind_group(0, &self.bind_group.group, &[0]);
+        pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
-pub struct Camera {
-    aspect: f32,
-    left: f32,
-    right: f32,
-    top: f32,
-    bottom: f32,
+/// What one simulation draws, see [`RenderState::set_panes`]: its
+/// particles, foam, streamlines and background. The camera, the obstacles
+/// and the overlay are shared by all panes.
+pub struct Pane {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    instance_buffer: utils::MirroredBuffer<Instance>,
+    color_buffer: utils::MirroredBuffer<u32>,
+    /// Drawn on top of the fluid.
+    diffuse_buffer: utils::MirroredBuffer<DiffuseInstance>,
+    /// Stretched over the domain, behind the particles.
+    background: Background,
+    streamline_buffer: utils::MirroredBuffer<Vertex>,
+    /// Scratch for flattening the streamlines into segments.
+    streamline_vertices: Vec<Vertex>,
 }
 
-impl Camera {
-    pub fn raw(&self) -> [f32; 16] {
-        let view = Mat4::look_at_rh(
-            Vec3::new(0.0, 0.0, 1.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
-        );
+impl Pane {
+    fn new(context: &utils::WGPUContext) -> Self {
+        let device = &context.device;
+        // storage too, for the sorter to read
+        let drawn = wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE;
+        Self {
+            device: context.device.clone(),
+            queue: context.queue.clone(),
+            instance_buffer: utils::MirroredBuffer::new(device, "Instance Buffer", drawn, 1),
+            color_buffer: utils::MirroredBuffer::new(device, "Color Buffer", drawn, 1),
+            diffuse_buffer: utils::MirroredBuffer::new(
+                device,
+                "Diffuse Buffer",
+                wgpu::BufferUsages::VERTEX,
+                1,
+            ),
+            background: Background::new(device),
+            streamline_buffer: utils::MirroredBuffer::new(
+                device,
+                "Streamline Buffer",
+                wgpu::BufferUsages::VERTEX,
+                1,
+            ),
+            streamline_vertices: vec![],
+        }
+    }
 
-        let ar = self.aspect;
+    /// Uploads the particles to draw, only writing the parts that changed since the last call.
+    /// Needs a matching [`update_colors`](Self::update_colors) whenever the count grows.
+    pub fn update_instances(&mut self, instances: &[Instance]) {
+        self.instance_buffer
+            .update(&self.device, &self.queue, instances);
+    }
 
-        let bounds = if self.aspect >= 1.0 {
-            [self.left * ar, self.right * ar, self.bottom, self.top]
-        } else {
-            [self.left, self.right, self.bottom / ar, self.top / ar]
-        };
+    /// Uploads one packed color (see [`rgba_to_u32`]) per instance.
+    pub fn update_colors(&mut self, colors: &[u32]) {
+        self.color_buffer.update(&self.device, &self.queue, colors);
+    }
 
-        let proj = Mat4::orthographic_rh(bounds[0], bounds[1], bounds[2], bounds[3], 0.0, 1.0);
+    /// Uploads the foam, spray and bubble particles to draw over the fluid.
+    pub fn update_diffuse(&mut self, diffuse: &[DiffuseInstance]) {
+        self.diffuse_buffer
+            .update(&self.device, &self.queue, diffuse);
+    }
 
-        (proj * view).to_cols_array()
+    /// Shows `colors`, packed as by [`rgba_to_u32`] for a `resolution` squared
+    /// grid row by row from the bottom, stretched over the domain behind the
+    /// particles. Hidden again by passing no colors.
+    pub fn update_background(&mut self, resolution: u32, colors: &[u32]) {
+        self.background.visible = !colors.is_empty();
+        if self.background.visible {
+            debug_assert_eq!(colors.len(), (resolution * resolution) as usize);
+            self.background
+                .update(&self.device, &self.queue, (resolution, resolution), colors);
+        }
+    }
+
+    /// Uploads the streamlines to draw, see [`crate::streamlines`].
+    pub fn update_streamlines(&mut self, lines: &[Vec<[f32; 2]>]) {
+        self.streamline_vertices.clear();
+        for line in lines {
+            for segment in line.windows(2) {
+                self.streamline_vertices.push(Vertex { pos: segment[0] });
+                self.streamline_vertices.push(Vertex { pos: segment[1] });
+            }
+        }
+        self.streamline_buffer
+            .update(&self.device, &self.queue, &self.streamline_vertices);
     }
 }
 
-pub struct RenderState<'a> {
-    pub context: utils::WGPUContext<'a>,
+pub struct RenderState {
+    pub context: utils::WGPUContext,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub instances: Vec<Instance>,
     pub camera: Camera,
     pub camera_buffer: wgpu::Buffer,
+    /// Bound next to the camera, see [`update_params`](Self::update_params).
+    pub params_buffer: wgpu::Buffer,
+    params: SimParams,
+    /// Bound after the [`params_buffer`](Self::params_buffer), see
+    /// [`set_render_params`](Self::set_render_params).
+    pub render_params_buffer: wgpu::Buffer,
+    render_params: RenderParams,
     pub camera_bind_group: utils::BindGroup,
 
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
-    pub instance_buffer: wgpu::Buffer,
+    /// At least one, side by side, see [`set_panes`](Self::set_panes).
+    panes: Vec<Pane>,
+
+    /// Draws the [`DiffuseInstance`]s on top of the fluid.
+    pub diffuse_pipeline: wgpu::RenderPipeline,
+
+    /// Draws [`background`](Self::update_background) stretched over the domain, behind the particles.
+    pub background_pipeline: wgpu::RenderPipeline,
+    /// Drawn behind the background, see [`set_background_image`](Self::set_background_image).
+    image: Background,
+
+    /// Draws line segments, two vertices each, on top of everything else.
+    pub lines_pipeline: wgpu::RenderPipeline,
+    lines_color_buffer: wgpu::Buffer,
+    lines_bind_group: utils::BindGroup,
+    pub obstacle_buffer: utils::MirroredBuffer<Vertex>,
+
+    /// Draws [`OverlayVertex`] line segments in screen space, last.
+    pub overlay_pipeline: wgpu::RenderPipeline,
+    pub overlay_buffer: utils::MirroredBuffer<OverlayVertex>,
+
+    /// Blends the particles order independently instead, when set.
+    oit: Option<Oit>,
+    /// Sorts the particles before drawing them, when set.
+    sorter: Option<Sorter>,
+    /// See [`set_theme`](Self::set_theme).
+    clear: wgpu::Color,
+    /// See [`gpu_time`](Self::gpu_time).
+    timer: Option<GpuTimer>,
+    /// See [`present_time`](Self::present_time).
+    present_time: f32,
+    /// See [`pick`](Self::pick).
+    picker: Picker,
 }
 
-impl<'a> RenderState<'a> {
-    pub async fn new(window: &'a window::Window) -> RenderState<'a> {
-        let context = utils::WGPUContext::from_window(&window).await;
+impl RenderState {
+    pub async fn new(window: Arc<window::Window>) -> RenderState {
+        Self::with_context(utils::WGPUContext::from_window(window).await)
+    }
+
+    /// Draws into a window another application owns, see
+    /// [`WGPUContext::from_raw`](utils::WGPUContext::from_raw).
+    ///
+    /// # Safety
+    ///
+    /// The window behind `handle` has to stay alive for as long as the
+    /// renderer.
+    pub async unsafe fn from_raw(
+        handle: &(impl HasRawWindowHandle + HasRawDisplayHandle),
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> RenderState {
+        Self::with_context(utils::WGPUContext::from_raw(handle, size).await)
+    }
+
+    /// Draws into a texture of [`OFFSCREEN_FORMAT`] `width` by `height` pixels
+    /// large rather than a window, read back with [`capture`](Self::capture).
+    /// `None` without a GPU adapter.
+    pub async fn offscreen(width: u32, height: u32) -> Option<RenderState> {
+        let size = winit::dpi::PhysicalSize { width, height };
+        let context = utils::WGPUContext::offscreen(size, OFFSCREEN_FORMAT).await?;
+        Some(Self::with_context(context))
+    }
+
+    /// Draws into another window with the same device, with a camera of its
+    /// own and nothing uploaded yet.
+    pub fn for_window(&self, window: Arc<window::Window>) -> RenderState {
+        Self::with_context(self.context.for_window(window))
+    }
+
+    fn with_context(context: utils::WGPUContext) -> RenderState {
         let device = &context.device;
         let config = &context.config;
 
@@ -126,23 +1464,71 @@ impl<'a> RenderState<'a> {
         let vertex = utils::ShaderModule::from(&shader)
             .entry("vs_main")
             .vertex::<Vertex>()
-            .instance::<Instance>();
+            .instance::<Instance>()
+            .instance::<InstanceColor>();
+
+        let color_target = wgpu::ColorTargetState {
+            format: config.format,
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        };
 
         let fragment = utils::ShaderModule::from(&shader)
             .entry("fs_main")
             .fragment()
-            .color_target(wgpu::ColorTargetState {
-                format: config.format,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::SrcAlpha,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                    alpha: wgpu::BlendComponent::OVER,
-                }),
-                write_mask: wgpu::ColorWrites::ALL,
-            });
+            .color_target(color_target.clone());
+
+        let diffuse_shader = device.create_shader_module(wgpu::include_wgsl!("diffuse.wgsl"));
+
+        let diffuse_vertex = utils::ShaderModule::from(&diffuse_shader)
+            .entry("vs_main")
+            .vertex::<Vertex>()
+            .instance::<DiffuseInstance>();
+
+        let diffuse_fragment = utils::ShaderModule::from(&diffuse_shader)
+            .entry("fs_main")
+            .fragment()
+            .color_target(color_target.clone());
+
+        let background_shader = device.create_shader_module(wgpu::include_wgsl!("background.wgsl"));
+
+        let background_vertex = utils::ShaderModule::from(&background_shader)
+            .entry("vs_main")
+            .vertex::<Vertex>();
+
+        let background_fragment = utils::ShaderModule::from(&background_shader)
+            .entry("fs_main")
+            .fragment()
+            .color_target(color_target.clone());
+
+        let lines_shader = device.create_shader_module(wgpu::include_wgsl!("lines.wgsl"));
+
+        let lines_vertex = utils::ShaderModule::from(&lines_shader)
+            .entry("vs_main")
+            .vertex::<Vertex>();
+
+        let lines_fragment = utils::ShaderModule::from(&lines_shader)
+            .entry("fs_main")
+            .fragment()
+            .color_target(color_target.clone());
+
+        let overlay_shader = device.create_shader_module(wgpu::include_wgsl!("overlay.wgsl"));
+
+        let overlay_vertex = utils::ShaderModule::from(&overlay_shader)
+            .entry("vs_main")
+            .vertex::<OverlayVertex>();
+
+        let overlay_fragment = utils::ShaderModule::from(&overlay_shader)
+            .entry("fs_main")
+            .fragment()
+            .color_target(color_target);
 
         let vertex_buffer = utils::BufferBuilder::vertex()
             .label("Vertex Buffer")
@@ -154,21 +1540,13 @@ impl<'a> RenderState<'a> {
             .data(SQUARE_INDICES)
             .build(device);
 
-        let instances = vec![Instance::default(); PARTICLE_COUNT];
+        let pane = Pane::new(&context);
+        let obstacle_buffer =
+            utils::MirroredBuffer::new(device, "Obstacle Buffer", wgpu::BufferUsages::VERTEX, 1);
+        let overlay_buffer =
+            utils::MirroredBuffer::new(device, "Overlay Buffer", wgpu::BufferUsages::VERTEX, 1);
 
-        let instance_buffer = utils::BufferBuilder::vertex()
-            .label("Instance Buffer")
-            .usage(wgpu::BufferUsages::COPY_DST)
-            .data(instances.as_slice())
-            .build(&context.device);
-
-        let camera = Camera {
-            aspect: config.width as f32 / config.height as f32,
-            left: 0.0,
-            right: 1.0,
-            bottom: 0.0,
-            top: 1.0,
-        };
+        let camera = Camera::new(config.width as f32 / config.height as f32);
 
         let camera_buffer =
             utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
@@ -176,9 +1554,25 @@ impl<'a> RenderState<'a> {
                 .data(&[camera.raw()])
                 .build(device);
 
+        let params = SimParams::default();
+        let params_buffer =
+            utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
+                .label("params_buffer")
+                .data(&[params])
+                .build(device);
+
+        let render_params = RenderParams::default();
+        let render_params_buffer =
+            utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
+                .label("render_params_buffer")
+                .data(&[render_params])
+                .build(device);
+
         let camera_bind_group = utils::BindGroupBuilder::default()
             .label("camera_bind_group")
             .uniform_buffer(&camera_buffer, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(&params_buffer, wgpu::ShaderStages::VERTEX)
+            .uniform_buffer(&render_params_buffer, wgpu::ShaderStages::VERTEX_FRAGMENT)
             .build(device);
 
         let render_pipeline = utils::RenderPipelineBuilder::default()
@@ -187,27 +1581,289 @@ impl<'a> RenderState<'a> {
             .bind(&camera_bind_group)
             .build(device);
 
+        let diffuse_pipeline = utils::RenderPipelineBuilder::default()
+            .label("Diffuse Pipeline")
+            .vertex_stage(&diffuse_vertex)
+            .fragment_stage(&diffuse_fragment)
+            .bind(&camera_bind_group)
+            .build(device);
+
+        let image = Background::new(device);
+        let background_pipeline = utils::RenderPipelineBuilder::default()
+            .label("Background Pipeline")
+            .vertex_stage(&background_vertex)
+            .fragment_stage(&background_fragment)
+            .bind(&camera_bind_group)
+            .bind(&pane.background.bind_group)
+            .build(device);
+
+        let theme = Theme::default();
+        let lines_color_buffer =
+            utils::BufferBuilder::new(wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST)
+                .label("lines_color_buffer")
+                .data(&[unpack(theme.lines)])
+                .build(device);
+        let lines_bind_group = utils::BindGroupBuilder::default()
+            .label("lines_bind_group")
+            .uniform_buffer(&lines_color_buffer, wgpu::ShaderStages::FRAGMENT)
+            .build(device);
+
+        let lines_pipeline = utils::RenderPipelineBuilder::default()
+            .label("Lines Pipeline")
+            .vertex_stage(&lines_vertex)
+            .fragment_stage(&lines_fragment)
+            .bind(&camera_bind_group)
+            .bind(&lines_bind_group)
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .build(device);
+
+        let overlay_pipeline = utils::RenderPipelineBuilder::default()
+            .label("Overlay Pipeline")
+            .vertex_stage(&overlay_vertex)
+            .fragment_stage(&overlay_fragment)
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .build(device);
+
+        let timer = GpuTimer::new(&context);
+        let picker = Picker::new(
+            &context,
+            &camera_bind_group,
+            &params_buffer,
+            &render_params_buffer,
+        );
         Self {
             context,
             render_pipeline,
-            instances,
             camera,
             camera_buffer,
+            params_buffer,
+            params,
+            render_params_buffer,
+            render_params,
             camera_bind_group,
             vertex_buffer,
             index_buffer,
-            instance_buffer,
+            panes: vec![pane],
+            diffuse_pipeline,
+            background_pipeline,
+            image,
+            lines_pipeline,
+            lines_color_buffer,
+            lines_bind_group,
+            obstacle_buffer,
+            overlay_pipeline,
+            overlay_buffer,
+            oit: None,
+            sorter: None,
+            clear: clear_color(theme.clear),
+            timer,
+            present_time: 0.0,
+            picker,
+        }
+    }
+
+    /// Takes the clear and line colors of `theme`. The palette goes into the
+    /// scene and the overlay color into the [plots](crate::plots) instead.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.clear = clear_color(theme.clear);
+        self.context.queue.write_buffer(
+            &self.lines_color_buffer,
+            0,
+            bytemuck::cast_slice(&[unpack(theme.lines)]),
+        );
+    }
+
+    /// Draws the particles sorted by `key`, so the ones with larger keys
+    /// end up on top of the others, like fast foam over the slower bulk.
+    /// The sort runs on the GPU every frame, a bitonic sort over the count
+    /// rounded up to a power of two. Unsorted again with `None`.
+    pub fn set_sort_key(&mut self, key: Option<SortKey>) {
+        self.sorter = key.map(|key| {
+            Sorter::new(
+                &self.context.device,
+                key,
+                &self.panes[0].instance_buffer.buffer,
+                &self.panes[0].color_buffer.buffer,
+            )
+        });
+    }
+
+    /// Switches to weighted blended order independent transparency for the
+    /// particles: they are accumulated into two offscreen targets and then
+    /// composited over the background in one go, so overlapping particles
+    /// come out the same whatever order they are drawn in. Costs two extra
+    /// passes and the targets.
+    pub fn set_oit(&mut self, enabled: bool) {
+        self.oit = match enabled {
+            true => Some(Oit::new(&self.context, &self.camera_bind_group)),
+            false => None,
+        };
+    }
+
+    /// Seconds the GPU took for a recent frame, from the first pass to the
+    /// last. `None` until the first measurement is back, and on devices
+    /// without timestamp queries.
+    pub fn gpu_time(&self) -> Option<f32> {
+        self.timer.as_ref().and_then(|timer| timer.last)
+    }
+
+    /// The passes of the last frame measured and which frame it was, once
+    /// after it comes back. See [`gpu_time`](Self::gpu_time).
+    pub fn take_gpu_spans(&mut self) -> Option<(u64, Vec<DeviceSpan>)> {
+        self.timer.as_mut().and_then(|timer| timer.spans.take())
+    }
+
+    /// Seconds the last frame spent waiting for a surface texture and
+    /// handing it back to the display, which includes waiting on vsync.
+    pub fn present_time(&self) -> f32 {
+        self.present_time
+    }
+
+    /// Asks for the particle under a cursor position reported by the window,
+    /// found on the GPU with a later frame, see [`take_pick`](Self::take_pick).
+    /// A newer request replaces one that hasn't started yet. With several
+    /// panes, the particle of the first at the same spot of its pane.
+    pub fn pick(&mut self, cursor: winit::dpi::PhysicalPosition<f64>) {
+        self.picker.requested = Some(self.in_viewport(cursor));
+    }
+
+    /// The answer to the last [`pick`](Self::pick) once it is back, the
+    /// index of the instance under the cursor, or `Some(None)` where there
+    /// was none.
+    pub fn take_pick(&mut self) -> Option<Option<u32>> {
+        self.picker.result.take()
+    }
+
+    /// Moves the camera with the keyboard: WASD or the arrow keys pan, plus
+    /// and minus zoom and Home goes back to the start. Returns whether
+    /// `event` was used up.
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        use winit::keyboard::{Key, NamedKey};
+
+        let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    logical_key,
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        else {
+            return false;
+        };
+        let step = Camera::PAN_STEP;
+        match logical_key {
+            Key::Named(NamedKey::ArrowLeft) => self.camera.pan(-step, 0.0),
+            Key::Named(NamedKey::ArrowRight) => self.camera.pan(step, 0.0),
+            Key::Named(NamedKey::ArrowUp) => self.camera.pan(0.0, step),
+            Key::Named(NamedKey::ArrowDown) => self.camera.pan(0.0, -step),
+            Key::Named(NamedKey::Home) => self.camera.reset(),
+            Key::Character(key) => match key.to_lowercase().as_str() {
+                "a" => self.camera.pan(-step, 0.0),
+                "d" => self.camera.pan(step, 0.0),
+                "w" => self.camera.pan(0.0, step),
+                "s" => self.camera.pan(0.0, -step),
+                // + shares a key with = on most layouts
+                "+" | "=" => self.camera.zoom(Camera::ZOOM_STEP),
+                "-" => self.camera.zoom(1.0 / Camera::ZOOM_STEP),
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// World position under a cursor position reported by the window, in
+    /// whichever pane it is over.
+    pub fn to_world(&self, cursor: winit::dpi::PhysicalPosition<f64>) -> [f32; 2] {
+        let (_, size) = self.viewport(0);
+        self.camera
+            .transform()
+            .to_world(self.in_viewport(cursor), size)
+    }
+
+    /// The world to clip space mapping of every pane as of the last
+    /// [`update`](Self::update).
+    pub fn transform(&self) -> WorldTransform {
+        self.camera.transform()
+    }
+
+    /// Where `world` is drawn in the first pane, in the clip space of the
+    /// whole window the [overlay](Self::update_overlay) is drawn in.
+    pub fn world_to_overlay(&self, world: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.transform().to_clip(world);
+        let (offset, size) = self.viewport(0);
+        let config = &self.context.config;
+        let pixel = [
+            offset[0] + (x + 1.0) / 2.0 * size[0],
+            offset[1] + (1.0 - y) / 2.0 * size[1],
+        ];
+        [
+            2.0 * pixel[0] / config.width as f32 - 1.0,
+            1.0 - 2.0 * pixel[1] / config.height as f32,
+        ]
+    }
+
+    /// How the view is fitted to the panes from the next
+    /// [`update`](Self::update) on.
+    pub fn set_aspect_mode(&mut self, mode: AspectMode) {
+        self.camera.set_mode(mode);
+    }
+
+    /// Splits the window into `count` panes side by side, at least one, each
+    /// drawing a simulation of its own under the same camera. The first pane
+    /// draws what the `update_` methods upload, the others what is uploaded
+    /// to them through [`pane_mut`](Self::pane_mut). Only the first pane is
+    /// [sorted](Self::set_sort_key) and [picked](Self::pick) from.
+    pub fn set_panes(&mut self, count: usize) {
+        let count = count.max(1);
+        self.panes.truncate(count);
+        while self.panes.len() < count {
+            self.panes.push(Pane::new(&self.context));
         }
     }
 
-    pub fn input(&mut self, _event: &WindowEvent) -> bool {
-        false
+    pub fn pane_count(&self) -> usize {
+        self.panes.len()
+    }
+
+    /// Pane `pane` from the left, to upload what it draws.
+    pub fn pane_mut(&mut self, pane: usize) -> &mut Pane {
+        &mut self.panes[pane]
+    }
+
+    /// Width and height of every pane in pixels.
+    fn pane_size(&self) -> [f32; 2] {
+        let config = &self.context.config;
+        [
+            config.width as f32 / self.panes.len() as f32,
+            config.height as f32,
+        ]
+    }
+
+    /// Offset from the top left of the window and size of what pane `pane`
+    /// draws into, in pixels, see [`Camera::viewport`].
+    fn viewport(&self, pane: usize) -> ([f32; 2], [f32; 2]) {
+        let pane_size = self.pane_size();
+        let (offset, size) = self.camera.viewport(pane_size);
+        ([offset[0] + pane as f32 * pane_size[0], offset[1]], size)
+    }
+
+    /// A cursor position reported by the window relative to the viewport
+    /// of the pane it is over.
+    fn in_viewport(&self, cursor: winit::dpi::PhysicalPosition<f64>) -> [f32; 2] {
+        let width = self.pane_size()[0];
+        let (offset, _) = self.viewport(0);
+        [
+            (cursor.x as f32).rem_euclid(width) - offset[0],
+            cursor.y as f32 - offset[1],
+        ]
     }
 
     pub fn update(&mut self) {
-        let width = self.context.config.width as f32;
-        let height = self.context.config.height as f32;
-        self.camera.aspect = width / height;
+        let (_, [width, height]) = self.viewport(0);
+        self.camera.set_aspect(width / height);
         self.context.queue.write_buffer(
             &self.camera_buffer,
             0,
@@ -215,38 +1871,177 @@ impl<'a> RenderState<'a> {
         );
     }
 
-    pub fn update_instances(&mut self, instances: &[Instance]) {
+    /// Uploads the parameters the simulation runs with, the same the OpenCL
+    /// kernels get.
+    pub fn update_params(&mut self, params: &SimParams) {
+        self.params = *params;
         self.context
             .queue
-            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+            .write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[*params]));
+    }
+
+    pub fn params(&self) -> SimParams {
+        self.params
+    }
+
+    pub fn render_params(&self) -> RenderParams {
+        self.render_params
+    }
+
+    /// Changes how the particles are drawn from the next frame on.
+    pub fn set_render_params(&mut self, params: RenderParams) {
+        self.render_params = params;
+        self.context.queue.write_buffer(
+            &self.render_params_buffer,
+            0,
+            bytemuck::cast_slice(&[params]),
+        );
+    }
+
+    /// Uploads the particles to draw, only writing the parts that changed since the last call.
+    /// Needs a matching [`update_colors`](Self::update_colors) whenever the count grows.
+    pub fn update_instances(&mut self, instances: &[Instance]) {
+        self.panes[0].update_instances(instances);
+    }
+
+    /// Uploads one packed color (see [`rgba_to_u32`]) per instance.
+    pub fn update_colors(&mut self, colors: &[u32]) {
+        self.panes[0].update_colors(colors);
+    }
+
+    /// Uploads the foam, spray and bubble particles to draw over the fluid.
+    pub fn update_diffuse(&mut self, diffuse: &[DiffuseInstance]) {
+        self.panes[0].update_diffuse(diffuse);
+    }
+
+    /// Shows `colors`, packed as by [`rgba_to_u32`] for a `resolution` squared
+    /// grid row by row from the bottom, stretched over the domain behind the
+    /// particles. Hidden again by passing no colors.
+    pub fn update_background(&mut self, resolution: u32, colors: &[u32]) {
+        self.panes[0].update_background(resolution, colors);
+    }
+
+    /// Shows `image` behind everything else, stretched over the world
+    /// rectangle from `min` to `max`, such as a photo of the experiment being
+    /// simulated or level art to place obstacles by.
+    pub fn set_background_image(
+        &mut self,
+        image: &Image,
+        min: [f32; 2],
+        max: [f32; 2],
+    ) -> Result<(), String> {
+        let limit = self.context.device.limits().max_texture_dimension_2d;
+        if image.width > limit || image.height > limit {
+            return Err(format!(
+                "background image of {}x{} pixels is larger than the {limit} pixels the device takes",
+                image.width, image.height
+            ));
+        }
+        self.image.visible = true;
+        self.image.set_rect(&self.context.queue, min, max);
+        self.image.update(
+            &self.context.device,
+            &self.context.queue,
+            (image.width, image.height),
+            &image.packed(),
+        );
+        Ok(())
+    }
+
+    /// Uploads the streamlines to draw, see [`crate::streamlines`].
+    pub fn update_streamlines(&mut self, lines: &[Vec<[f32; 2]>]) {
+        self.panes[0].update_streamlines(lines);
     }
 
+    /// Uploads the outlines of moving obstacles, such as the
+    /// [paddle](crate::paddle) blades, one segment each.
+    pub fn update_obstacles(&mut self, segments: &[[[f32; 2]; 2]]) {
+        let vertices = segments
+            .iter()
+            .flatten()
+            .map(|&pos| Vertex { pos })
+            .collect::<Vec<_>>();
+        self.obstacle_buffer
+            .update(&self.context.device, &self.context.queue, &vertices);
+    }
+
+    /// Uploads line segments to draw over everything, in clip space, such as
+    /// the [plots](crate::plots). Hidden again by passing no vertices.
+    pub fn update_overlay(&mut self, vertices: &[OverlayVertex]) {
+        self.overlay_buffer
+            .update(&self.context.device, &self.context.queue, vertices);
+    }
+
+    /// Does nothing while [hidden](utils::WGPUContext::is_hidden), rather
+    /// than waiting on a surface texture that may never come.
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.context.surface.get_current_texture()?;
+        let Some(surface) = &self.context.surface else {
+            return Ok(());
+        };
+        if self.context.is_hidden() {
+            return Ok(());
+        }
+        let acquiring = Instant::now();
+        let output = surface.get_current_texture()?;
+        let acquired = acquiring.elapsed();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        let encoder = self.encode(&view);
+        self.submit(encoder);
+        let presenting = Instant::now();
+        output.present();
+        self.present_time = (acquired + presenting.elapsed()).as_secs_f32();
+
+        Ok(())
+    }
+
+    /// Records everything drawn in a frame into `view`, which is as large
+    /// as the surface.
+    fn encode(&mut self, view: &wgpu::TextureView) -> wgpu::CommandEncoder {
         let mut encoder =
             self.context
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("Render Encoder"),
                 });
+        if let Some(timer) = &mut self.timer {
+            timer.begin(&self.context.device, &mut encoder);
+        }
+        self.picker.begin(
+            &self.context.device,
+            &self.context.queue,
+            &self.camera.transform(),
+            self.viewport(0).1,
+        );
+        self.picker.pick(&mut encoder, self);
+        if let Some(timer) = &mut self.timer {
+            timer.mark(&mut encoder, "pick");
+        }
+
+        if let Some(sorter) = &mut self.sorter {
+            sorter.sort(
+                &self.context.device,
+                &self.context.queue,
+                &mut encoder,
+                &self.panes[0].instance_buffer.buffer,
+                &self.panes[0].color_buffer.buffer,
+                self.panes[0].instance_buffer.len(),
+            );
+            if let Some(timer) = &mut self.timer {
+                timer.mark(&mut encoder, "sort");
+            }
+        }
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: (40f32 / 255f32).powf(2.2).into(),
-                            g: (44f32 / 255f32).powf(2.2).into(),
-                            b: (52f32 / 255f32).powf(2.2).into(),
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -255,18 +2050,258 @@ impl<'a> RenderState<'a> {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group.group, &[]);
+            for (i, pane) in self.panes.iter().enumerate() {
+                self.set_viewport(&mut render_pass, Some(i));
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                render_pass
+                    .set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                for background in [&self.image, &pane.background] {
+                    if background.visible {
+                        render_pass.set_pipeline(&self.background_pipeline);
+                        render_pass.set_bind_group(1, &background.bind_group.group, &[]);
+                        render_pass.draw_indexed(0..SQUARE_INDICES.len() as u32, 0, 0..1);
+                    }
+                }
+
+                if self.oit.is_none() {
+                    render_pass.set_pipeline(&self.render_pipeline);
+                    self.draw_particles(&mut render_pass, i);
+                    self.draw_over(&mut render_pass, pane);
+                }
+            }
+            if self.oit.is_none() {
+                self.set_viewport(&mut render_pass, None);
+                self.draw_overlay(&mut render_pass);
+            }
+        }
+        if let Some(timer) = &mut self.timer {
+            timer.mark(&mut encoder, "main pass");
+        }
+
+        if let Some(oit) = &mut self.oit {
+            let config = &self.context.config;
+            oit.fit(&self.context.device, (config.width, config.height));
+        }
+        if let Some(oit) = &self.oit {
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("OIT Pass"),
+                    color_attachments: &[
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &oit.accum,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        }),
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &oit.revealage,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        }),
+                    ],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                render_pass.set_pipeline(&oit.pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group.group, &[]);
+                for i in 0..self.panes.len() {
+                    self.set_viewport(&mut render_pass, Some(i));
+                    self.draw_particles(&mut render_pass, i);
+                }
+            }
+            if let Some(timer) = &mut self.timer {
+                timer.mark(&mut encoder, "OIT pass");
+            }
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("OIT Composite Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&oit.composite_pipeline);
+            render_pass.set_bind_group(0, &oit.bind_group.group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..SQUARE_INDICES.len() as u32, 0, 0..1);
+
+            render_pass.set_bind_group(0, &self.camera_bind_group.group, &[]);
+            for (i, pane) in self.panes.iter().enumerate() {
+                self.set_viewport(&mut render_pass, Some(i));
+                self.draw_over(&mut render_pass, pane);
+            }
+            self.set_viewport(&mut render_pass, None);
+            self.draw_overlay(&mut render_pass);
+            drop(render_pass);
+            if let Some(timer) = &mut self.timer {
+                timer.mark(&mut encoder, "OIT composite pass");
+            }
+        }
 
-            render_pass.draw_indexed(0..SQUARE_INDICES.len() as u32, 0, 0..PARTICLE_COUNT as _);
+        if let Some(timer) = &mut self.timer {
+            timer.end(&mut encoder);
         }
+        encoder
+    }
+
+    /// Draws a frame into a texture as large as the surface and reads it
+    /// back, 8 bit RGBA row by row from the top. Waits for the GPU.
+    pub fn capture(&mut self) -> Vec<u8> {
+        let config = &self.context.config;
+        let (width, height) = (config.width, config.height);
+        let texture = self
+            .context
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Capture Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // rows of a copy are padded to a multiple of 256 bytes
+        let row = width * 4;
+        let padded =
+            row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = self.context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback Buffer"),
+            size: padded as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.encode(&view);
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.submit(encoder);
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+        self.context.device.poll(wgpu::Maintain::Wait);
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded as usize)
+            .flat_map(|padded| &padded[..row as usize])
+            .copied()
+            .collect();
+        readback.unmap();
+        pixels
+    }
 
+    fn submit(&mut self, encoder: wgpu::CommandEncoder) {
         self.context.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        if let Some(timer) = &mut self.timer {
+            timer.submitted();
+        }
+        self.picker.submitted();
+    }
 
-        Ok(())
+    /// Limits drawing to the [viewport](Self::viewport) of pane `pane`, or
+    /// to the whole window with `None`.
+    fn set_viewport(&self, render_pass: &mut wgpu::RenderPass, pane: Option<usize>) {
+        let config = &self.context.config;
+        let (offset, size) = match pane {
+            Some(i) => self.viewport(i),
+            None => ([0.0, 0.0], [config.width as f32, config.height as f32]),
+        };
+        render_pass.set_viewport(offset[0], offset[1], size[0], size[1], 0.0, 1.0);
+    }
+
+    /// Draws the particles of pane `pane`, sorted if it is the first.
+    fn draw_particles<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, pane: usize) {
+        let buffers = &self.panes[pane];
+        let (instances, colors) = match &self.sorter {
+            Some(sorter) if sorter.sorted && pane == 0 => (&sorter.instances, &sorter.colors),
+            _ => (
+                &buffers.instance_buffer.buffer,
+                &buffers.color_buffer.buffer,
+            ),
+        };
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instances.slice(..));
+        render_pass.set_vertex_buffer(2, colors.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(
+            0..SQUARE_INDICES.len() as u32,
+            0,
+            0..buffers.instance_buffer.len() as u32,
+        );
+    }
+
+    /// Draws everything of `pane` that goes on top of its particles, with the
+    /// camera bound.
+    fn draw_over<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, pane: &'a Pane) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        if !pane.diffuse_buffer.is_empty() {
+            render_pass.set_pipeline(&self.diffuse_pipeline);
+            render_pass.set_vertex_buffer(1, pane.diffuse_buffer.buffer.slice(..));
+            render_pass.draw_indexed(
+                0..SQUARE_INDICES.len() as u32,
+                0,
+                0..pane.diffuse_buffer.len() as u32,
+            );
+        }
+
+        if !pane.streamline_buffer.is_empty() {
+            render_pass.set_pipeline(&self.lines_pipeline);
+            render_pass.set_bind_group(1, &self.lines_bind_group.group, &[]);
+            render_pass.set_vertex_buffer(0, pane.streamline_buffer.buffer.slice(..));
+            render_pass.draw(0..pane.streamline_buffer.len() as u32, 0..1);
+        }
+
+        if !self.obstacle_buffer.is_empty() {
+            render_pass.set_pipeline(&self.lines_pipeline);
+            render_pass.set_bind_group(1, &self.lines_bind_group.group, &[]);
+            render_pass.set_vertex_buffer(0, self.obstacle_buffer.buffer.slice(..));
+            render_pass.draw(0..self.obstacle_buffer.len() as u32, 0..1);
+        }
+    }
+
+    /// Draws the overlay, over the whole window.
+    fn draw_overlay<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if !self.overlay_buffer.is_empty() {
+            render_pass.set_pipeline(&self.overlay_pipeline);
+            render_pass.set_vertex_buffer(0, self.overlay_buffer.buffer.slice(..));
+            render_pass.draw(0..self.overlay_buffer.len() as u32, 0..1);
+        }
     }
 }
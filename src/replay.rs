@@ -0,0 +1,136 @@
+//! Interaction logs: the [commands](Command) the window sent the simulation,
+//! each with the step it took effect at. Written with `--record-input` and
+//! played back against a fresh simulation with `--replay-input`, also
+//! headless, for demos and for reproducing what an interaction did.
+//!
+//! A log has one command per line, after the step:
+//!
+//! ```text
+//! 120 paint 0.5 0.25 0.05 1    # x, y, radius, dye value
+//! 200 paint-viscosity 0.3 0.2 0.05 0.5    # x, y, radius, viscosity
+//! 300 toggle streamlines
+//! ```
+//!
+//! Pausing and stepping are left out, the steps already say when things
+//! happened. A log of a session that [rewound](crate::rewind) has the steps
+//! after the rewind twice, which replays what was done the second time.
+
+use crate::simulation::Command;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub struct InputRecorder<W: Write> {
+    out: W,
+}
+
+impl InputRecorder<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> InputRecorder<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Writes a line for `command` as applied before `step`, flushed right
+    /// away so a crash doesn't lose it.
+    pub fn write(&mut self, step: u64, command: &Command) -> io::Result<()> {
+        let line = match *command {
+            Command::Paint { pos, radius, value } => {
+                format!("paint {} {} {radius} {value}", pos[0], pos[1])
+            }
+            Command::PaintViscosity {
+                pos,
+                radius,
+                viscosity,
+            } => format!("paint-viscosity {} {} {radius} {viscosity}", pos[0], pos[1]),
+            Command::ToggleStreamlines => "toggle streamlines".into(),
+            Command::ToggleVorticity => "toggle vorticity".into(),
+            Command::ToggleHeatmap => "toggle heatmap".into(),
+            Command::TogglePlots => "toggle plots".into(),
+            Command::SetPaused(_) | Command::Rewind(_) | Command::StepForward => return Ok(()),
+        };
+        writeln!(self.out, "{step} {line}")?;
+        self.out.flush()
+    }
+}
+
+/// The commands of a log still to come, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    commands: VecDeque<(u64, Command)>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?
+            .parse()
+            .map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Takes the commands to apply before `step`, including any from before
+    /// that were missed.
+    pub fn due(&mut self, step: u64) -> Vec<Command> {
+        let mut due = vec![];
+        while let Some(&(at, command)) = self.commands.front() {
+            if at > step {
+                break;
+            }
+            due.push(command);
+            self.commands.pop_front();
+        }
+        due
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl std::str::FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut commands = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |err: &str| format!("line {}: {err} in `{line}`", i + 1);
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let step = words[0]
+                .parse::<u64>()
+                .map_err(|_| invalid("expected a step first"))?;
+            let number = |s: &str| s.parse::<f32>().map_err(|_| invalid("invalid number"));
+            let command = match words[1..] {
+                ["paint", x, y, radius, value] => Command::Paint {
+                    pos: [number(x)?, number(y)?],
+                    radius: number(radius)?,
+                    value: number(value)?,
+                },
+                ["paint-viscosity", x, y, radius, viscosity] => Command::PaintViscosity {
+                    pos: [number(x)?, number(y)?],
+                    radius: number(radius)?,
+                    viscosity: number(viscosity)?,
+                },
+                ["toggle", "streamlines"] => Command::ToggleStreamlines,
+                ["toggle", "vorticity"] => Command::ToggleVorticity,
+                ["toggle", "heatmap"] => Command::ToggleHeatmap,
+                ["toggle", "plots"] => Command::TogglePlots,
+                _ => return Err(invalid("unknown command")),
+            };
+            commands.push((step, command));
+        }
+        // a log edited by hand may be out of order
+        commands.sort_by_key(|&(step, _)| step);
+        Ok(Self {
+            commands: commands.into(),
+        })
+    }
+}
@@ -0,0 +1,228 @@
+//! A summary of a whole run, for tracking accuracy and speed across commits.
+//!
+//! The simulation thread hands every frame to a [`Report`] when run with
+//! `--report`, and writes it out as JSON once it stops: how compressed the
+//! fluid got at worst, how far the kinetic energy drifted from the start,
+//! the mean time of a step and of each of its parts, and the particle count,
+//! energy and density error every [`SAMPLE_EVERY`] steps. Along with them go
+//! the backend, the device it ran on and the [`Config`], so two reports say
+//! whether they are comparable at all.
+//!
+//! There is no serde to lean on, so the JSON is written by hand. Numbers
+//! that aren't finite come out as `null`.
+
+use crate::backend::{Config, StepTimings};
+use crate::stats::ParticleStats;
+use std::io::{self, Write};
+
+/// Steps between the [samples](Report::samples) over time.
+pub const SAMPLE_EVERY: u64 = 10;
+
+/// The state of the fluid at one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub step: u64,
+    pub particle_count: u32,
+    pub kinetic_energy: f32,
+    pub density_error: f32,
+}
+
+/// What a report says it was measured with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    /// [`Backend::name`](crate::backend::Backend::name) of the backend.
+    pub backend: &'static str,
+    /// See [`Backend::device`](crate::backend::Backend::device).
+    pub device: Option<String>,
+    pub config: Config,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub samples: Vec<Sample>,
+    pub max_density_error: f32,
+    /// Kinetic energy of the first frame, what the drift is relative to.
+    pub initial_energy: Option<f32>,
+    pub final_energy: f32,
+    pub steps: u64,
+    /// Wall clock seconds of all steps.
+    pub step_time: f64,
+    /// Summed over the steps that had timings, see [`timed_steps`](Self::timed_steps).
+    timings: StepTimings,
+    timed_steps: u64,
+}
+
+impl Report {
+    /// Takes in a frame after `steps` more steps, up to `step`, that took
+    /// `step_time` seconds, with `timings` summed over them if the backend
+    /// keeps track.
+    pub fn record(
+        &mut self,
+        step: u64,
+        steps: u32,
+        stats: &ParticleStats,
+        density_error: f32,
+        step_time: f32,
+        timings: Option<&StepTimings>,
+    ) {
+        let sample = Sample {
+            step,
+            particle_count: stats.count,
+            kinetic_energy: stats.kinetic_energy,
+            density_error,
+        };
+        let due = self
+            .samples
+            .last()
+            .is_none_or(|last| step >= last.step + SAMPLE_EVERY);
+        if due {
+            self.samples.push(sample);
+        }
+        self.max_density_error = self.max_density_error.max(density_error);
+        self.initial_energy.get_or_insert(stats.kinetic_energy);
+        self.final_energy = stats.kinetic_energy;
+        self.steps += steps as u64;
+        self.step_time += step_time as f64;
+        if let Some(timings) = timings {
+            self.timings.add(timings);
+            self.timed_steps += steps as u64;
+        }
+    }
+
+    /// Change of the kinetic energy since the first frame, relative to it.
+    pub fn energy_drift(&self) -> f32 {
+        match self.initial_energy {
+            Some(initial) if initial > 0.0 => (self.final_energy - initial) / initial,
+            _ => 0.0,
+        }
+    }
+
+    /// Mean seconds of a step.
+    pub fn mean_step_time(&self) -> f32 {
+        match self.steps {
+            0 => 0.0,
+            n => (self.step_time / n as f64) as f32,
+        }
+    }
+
+    /// Mean seconds of each part of a step, `None` if the backend kept no
+    /// track of them.
+    pub fn mean_timings(&self) -> Option<StepTimings> {
+        (self.timed_steps > 0).then(|| {
+            let mut timings = self.timings;
+            timings.scale(1.0 / self.timed_steps as f32);
+            timings
+        })
+    }
+
+    pub fn write_json(&self, mut out: impl Write, metadata: &Metadata) -> io::Result<()> {
+        let config = &metadata.config;
+        writeln!(out, "{{")?;
+        writeln!(
+            out,
+            "  \"version\": {},",
+            json_string(env!("CARGO_PKG_VERSION"))
+        )?;
+        writeln!(out, "  \"backend\": {},", json_string(metadata.backend))?;
+        let device = metadata
+            .device
+            .as_deref()
+            .map_or("null".into(), json_string);
+        writeln!(out, "  \"device\": {device},")?;
+        writeln!(out, "  \"config\": {{")?;
+        writeln!(out, "    \"fused_threshold\": {},", config.fused_threshold)?;
+        writeln!(out, "    \"sleep_speed\": {},", number(config.sleep_speed))?;
+        writeln!(out, "    \"sleep_after\": {},", config.sleep_after)?;
+        writeln!(out, "    \"viscosity\": {},", number(config.viscosity))?;
+        writeln!(
+            out,
+            "    \"viscosity_thinning\": {},",
+            number(config.viscosity_thinning)
+        )?;
+        writeln!(out, "    \"linear_drag\": {},", number(config.linear_drag))?;
+        writeln!(
+            out,
+            "    \"quadratic_drag\": {},",
+            number(config.quadratic_drag)
+        )?;
+        writeln!(out, "    \"max_speed\": {},", number(config.max_speed))?;
+        writeln!(
+            out,
+            "    \"max_displacement\": {},",
+            number(config.max_displacement)
+        )?;
+        writeln!(out, "    \"brake\": {},", config.brake)?;
+        writeln!(out, "    \"grid_images\": {},", config.grid_images)?;
+        writeln!(
+            out,
+            "    \"double_precision\": {},",
+            config.double_precision
+        )?;
+        writeln!(out, "    \"deterministic\": {}", config.deterministic)?;
+        writeln!(out, "  }},")?;
+        writeln!(out, "  \"steps\": {},", self.steps)?;
+        writeln!(
+            out,
+            "  \"max_density_error\": {},",
+            number(self.max_density_error)
+        )?;
+        writeln!(
+            out,
+            "  \"initial_energy\": {},",
+            number(self.initial_energy.unwrap_or(0.0))
+        )?;
+        writeln!(out, "  \"final_energy\": {},", number(self.final_energy))?;
+        writeln!(out, "  \"energy_drift\": {},", number(self.energy_drift()))?;
+        writeln!(out, "  \"step_time\": {},", number(self.mean_step_time()))?;
+        match self.mean_timings() {
+            Some(t) => writeln!(
+                out,
+                "  \"timings\": {{\"upload\": {}, \"integrate\": {}, \"grid\": {}, \
+                 \"solve\": {}, \"readback\": {}}},",
+                number(t.upload),
+                number(t.integrate),
+                number(t.grid),
+                number(t.solve),
+                number(t.readback),
+            )?,
+            None => writeln!(out, "  \"timings\": null,")?,
+        }
+        writeln!(out, "  \"samples\": [")?;
+        for (i, s) in self.samples.iter().enumerate() {
+            let comma = if i + 1 < self.samples.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    {{\"step\": {}, \"particle_count\": {}, \"kinetic_energy\": {}, \
+                 \"density_error\": {}}}{comma}",
+                s.step,
+                s.particle_count,
+                number(s.kinetic_energy),
+                number(s.density_error),
+            )?;
+        }
+        writeln!(out, "  ]")?;
+        writeln!(out, "}}")
+    }
+}
+
+fn number(x: f32) -> String {
+    match x.is_finite() {
+        true => x.to_string(),
+        false => "null".into(),
+    }
+}
+
+/// `s` as a JSON string, quoted and escaped.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,88 @@
+//! The particles of the most recent steps, kept to go back to when something
+//! goes wrong: Backspace rewinds a second, `,` a single step, and `.` steps
+//! forward again from there while paused.
+//!
+//! Only the particles are rewound. Dye, probes, triggers and the like carry
+//! on from where they were, and so does the time of the scene's forces and
+//! paddles unless the backend [restores](crate::backend::Backend::restore) it.
+
+use crate::sim::Instance;
+use std::collections::VecDeque;
+use std::mem::size_of_val;
+
+/// Default for `--rewind`, in bytes.
+pub const DEFAULT_BUDGET: usize = 256 << 20;
+
+/// States taken after a step, oldest first, no more than the budget allows.
+#[derive(Debug, Clone, Default)]
+pub struct RewindBuffer {
+    budget: usize,
+    states: VecDeque<(u64, Vec<Instance>)>,
+}
+
+impl RewindBuffer {
+    /// Keeps up to `budget` bytes of particles.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            states: VecDeque::new(),
+        }
+    }
+
+    /// Keeps `particles` as the state after `step`, dropping the oldest
+    /// states to make room. Nothing is kept if a single state is over the
+    /// budget.
+    pub fn push(&mut self, step: u64, particles: &[Instance]) {
+        let size = size_of_val(particles);
+        if size > self.budget {
+            self.states.clear();
+            return;
+        }
+        // reuse the storage of the states dropped
+        let mut storage = vec![];
+        while self.bytes() + size > self.budget {
+            match self.states.pop_front() {
+                Some((_, particles)) => storage = particles,
+                None => break,
+            }
+        }
+        storage.clear();
+        storage.extend_from_slice(particles);
+        self.states.push_back((step, storage));
+    }
+
+    /// Goes back `steps` from the newest state, or as far as there are
+    /// states, dropping everything newer. Returns the state that is now the
+    /// newest, with its step.
+    pub fn rewind(&mut self, steps: u64) -> Option<(u64, &[Instance])> {
+        let newest = self.states.back()?.0;
+        let target = newest.saturating_sub(steps);
+        while self.states.len() > 1 && self.states.back().is_some_and(|&(at, _)| at > target) {
+            self.states.pop_back();
+        }
+        self.states
+            .back()
+            .map(|(step, particles)| (*step, particles.as_slice()))
+    }
+
+    /// Steps of the oldest and newest state.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((self.states.front()?.0, self.states.back()?.0))
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Memory taken by the states kept.
+    pub fn bytes(&self) -> usize {
+        self.states
+            .iter()
+            .map(|(_, particles)| size_of_val(particles.as_slice()))
+            .sum()
+    }
+}
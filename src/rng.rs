@@ -0,0 +1,82 @@
+//! Seedable random numbers for everything on the host that needs them, from
+//! [sampling](crate::sampling) blocks to spawning [diffuse](crate::diffuse)
+//! particles.
+//!
+//! The generator is O'Neill's PCG32 (XSH RR): 64 bits of state, 32 bit
+//! outputs, and 2^63 independent streams per seed. The same seed and stream
+//! always give the same numbers. Work that is split up, such as placing
+//! particles in parallel, takes a stream per item with
+//! [`with_stream`](Rng::with_stream) or [`split`](Rng::split)s off a
+//! generator per worker, so the result doesn't depend on the order the items
+//! are done in.
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    /// Selects the stream, always odd.
+    increment: u64,
+}
+
+impl Rng {
+    /// Stream 0 of `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    /// Stream `stream` of `seed`. Only the lower 63 bits of `stream` count.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// A generator on a stream of its own, seeded from this one.
+    pub fn split(&mut self) -> Self {
+        let seed = self.next_u64();
+        let stream = self.next_u64();
+        Self::with_stream(seed, stream)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// Uniform in `[0, 1)`, in steps of 2^-24.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Uniform in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+
+    /// Uniform below `n` without bias, after Lemire. Zero if `n` is.
+    pub fn below(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let mut product = self.next_u32() as u64 * n as u64;
+        if (product as u32) < n {
+            let threshold = n.wrapping_neg() % n;
+            while (product as u32) < threshold {
+                product = self.next_u32() as u64 * n as u64;
+            }
+        }
+        (product >> 32) as u32
+    }
+}
@@ -0,0 +1,150 @@
+//! Where the particles of a [block](crate::phase::FluidBlock) start out.
+//!
+//! The lattice is the densest and the most regular, which shows as artifacts
+//! along its rows until the particles have moved about. A jittered grid
+//! shakes every lattice point a little, Poisson disk sampling places the
+//! particles at random but never closer than a minimum distance, giving blue
+//! noise. Both start out without overlaps and are the same for the same seed.
+
+use crate::rng::Rng;
+use std::f32::consts::{SQRT_2, TAU};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sampling {
+    #[default]
+    Lattice,
+    Jittered,
+    Poisson,
+}
+
+impl std::str::FromStr for Sampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lattice" => Ok(Sampling::Lattice),
+            "jittered" => Ok(Sampling::Jittered),
+            "poisson" => Ok(Sampling::Poisson),
+            _ => Err(format!(
+                "invalid sampling `{s}`, expected lattice, jittered or poisson"
+            )),
+        }
+    }
+}
+
+/// How far jittered points move from their lattice point at most along each
+/// axis, as a share of the spacing. Keeps neighbors at least half a spacing
+/// apart.
+pub const JITTER: f32 = 0.25;
+/// Smallest distance between Poisson disk samples, as a share of the
+/// spacing. Random packings are looser than the lattice, this about makes up
+/// for it so blocks come out at the same density either way.
+pub const POISSON_DISTANCE: f32 = 0.8;
+/// Candidates tried around a sample before giving up on it.
+const ATTEMPTS: u32 = 30;
+
+impl Sampling {
+    /// Points between `min` and `max` about `spacing` apart.
+    pub fn sample(self, min: [f32; 2], max: [f32; 2], spacing: f32, seed: u32) -> Vec<[f32; 2]> {
+        match self {
+            Sampling::Lattice => lattice(min, max, spacing),
+            Sampling::Jittered => jittered(min, max, spacing, seed),
+            Sampling::Poisson => poisson_disk(min, max, spacing * POISSON_DISTANCE, seed),
+        }
+    }
+}
+
+/// A square lattice, starting half a spacing in from `min`.
+pub fn lattice(min: [f32; 2], max: [f32; 2], spacing: f32) -> Vec<[f32; 2]> {
+    let count = |axis: usize| ((max[axis] - min[axis]) / spacing).max(0.0) as usize;
+    let [nx, ny] = [count(0), count(1)];
+
+    (0..nx * ny)
+        .map(|i| {
+            [
+                min[0] + ((i % nx) as f32 + 0.5) * spacing,
+                min[1] + ((i / nx) as f32 + 0.5) * spacing,
+            ]
+        })
+        .collect()
+}
+
+/// The [`lattice`] with every point moved by up to [`JITTER`] spacings.
+/// Every point has a stream of its own, so they could be moved in any order.
+pub fn jittered(min: [f32; 2], max: [f32; 2], spacing: f32, seed: u32) -> Vec<[f32; 2]> {
+    let mut points = lattice(min, max, spacing);
+    let jitter = JITTER * spacing;
+    for (i, p) in points.iter_mut().enumerate() {
+        let mut random = Rng::with_stream(seed.into(), i as u64);
+        p[0] += random.range(-jitter, jitter);
+        p[1] += random.range(-jitter, jitter);
+    }
+    points
+}
+
+/// Bridson's Poisson disk sampling: random points between `min` and `max`,
+/// none closer to another than `distance`, packed until there is no room
+/// left.
+pub fn poisson_disk(min: [f32; 2], max: [f32; 2], distance: f32, seed: u32) -> Vec<[f32; 2]> {
+    let size = [max[0] - min[0], max[1] - min[1]];
+    if size[0] <= 0.0 || size[1] <= 0.0 || distance <= 0.0 {
+        return vec![];
+    }
+    // a cell holds one point at most
+    let cell = distance / SQRT_2;
+    let [nx, ny] = [0, 1].map(|axis| ((size[axis] / cell).ceil() as usize).max(1));
+    let cell_of = |p: [f32; 2]| {
+        let x = (((p[0] - min[0]) / cell) as usize).min(nx - 1);
+        let y = (((p[1] - min[1]) / cell) as usize).min(ny - 1);
+        (x, y)
+    };
+    let mut grid = vec![None; nx * ny];
+    let mut points: Vec<[f32; 2]> = vec![];
+    let mut active = vec![];
+    let mut random = Rng::new(seed.into());
+
+    let first = [
+        min[0] + random.next_f32() * size[0],
+        min[1] + random.next_f32() * size[1],
+    ];
+    let add = |p: [f32; 2],
+               points: &mut Vec<[f32; 2]>,
+               grid: &mut [Option<usize>],
+               active: &mut Vec<usize>| {
+        let (x, y) = cell_of(p);
+        grid[y * nx + x] = Some(points.len());
+        active.push(points.len());
+        points.push(p);
+    };
+    add(first, &mut points, &mut grid, &mut active);
+
+    while !active.is_empty() {
+        let slot = random.below(active.len() as u32) as usize;
+        let center = points[active[slot]];
+        let found = (0..ATTEMPTS).find_map(|_| {
+            let angle = random.next_f32() * TAU;
+            let r = distance * (1.0 + random.next_f32());
+            let p = [center[0] + r * angle.cos(), center[1] + r * angle.sin()];
+            let inside = (min[0]..max[0]).contains(&p[0]) && (min[1]..max[1]).contains(&p[1]);
+            if !inside {
+                return None;
+            }
+            let (x, y) = cell_of(p);
+            let near = (y.saturating_sub(2)..(y + 3).min(ny))
+                .flat_map(|y| (x.saturating_sub(2)..(x + 3).min(nx)).map(move |x| (x, y)))
+                .filter_map(|(x, y)| grid[y * nx + x])
+                .any(|i| {
+                    let d = [points[i][0] - p[0], points[i][1] - p[1]];
+                    d[0] * d[0] + d[1] * d[1] < distance * distance
+                });
+            (!near).then_some(p)
+        });
+        match found {
+            Some(p) => add(p, &mut points, &mut grid, &mut active),
+            None => {
+                active.swap_remove(slot);
+            }
+        }
+    }
+    points
+}
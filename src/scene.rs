@@ -0,0 +1,310 @@
+//! Everything a backend starts from: the particles and what acts on them.
+
+use crate::boundary::{self, Boundaries, Boundary, Edge};
+use crate::forces::ForcePrimitive;
+use crate::geometry::Polygon;
+use crate::groups::{Group, MAX_GROUPS};
+use crate::initial_particles;
+use crate::material::Material;
+use crate::paddle::Paddle;
+use crate::phase::{FluidBlock, Phase};
+use crate::probe::Probe;
+use crate::sdf::DistanceField;
+use crate::sim::Instance;
+use crate::terrain::Heightfield;
+use crate::thermal::Thermal;
+use crate::trigger::Trigger;
+use crate::turbulence::Turbulence;
+use crate::wind::Wind;
+
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub particles: Vec<Instance>,
+    /// Uniform acceleration on every particle.
+    pub gravity: [f32; 2],
+    pub forces: Vec<ForcePrimitive>,
+    pub paddles: Vec<Paddle>,
+    /// Solid outlines, sampled into [`SolidParticles`](crate::solid::SolidParticles)
+    /// for the density estimates.
+    pub obstacles: Vec<Polygon>,
+    /// Material of each of the `obstacles`, in the same order.
+    pub obstacle_materials: Vec<Material>,
+    pub boundaries: Boundaries,
+    /// Every phase in the scene, the first is the one inlets fill with.
+    pub phases: Vec<Phase>,
+    /// Index into `phases` per particle slot.
+    pub phase_ids: Vec<u32>,
+    /// Named subsets of the particles, see [`crate::groups`].
+    pub groups: Vec<Group>,
+    /// Bit `i` set for the particles in `groups[i]`, per particle slot.
+    pub group_masks: Vec<u32>,
+    pub thermal: Thermal,
+    /// Ambient breeze the particles are dragged along by, see [`crate::wind`].
+    pub wind: Wind,
+    /// Eddies stirred in below the surface, see [`crate::turbulence`].
+    pub turbulence: Turbulence,
+    pub terrain: Heightfield,
+    /// Obstacles the particles collide with, see [`crate::sdf`].
+    pub distance_field: DistanceField,
+    /// Regions reporting particles going in and out, see [`crate::trigger`].
+    pub triggers: Vec<Trigger>,
+    /// Points sampled every step, see [`crate::probe`].
+    pub probes: Vec<Probe>,
+}
+
+impl Scene {
+    pub fn new(particles: Vec<Instance>) -> Self {
+        Self {
+            phase_ids: vec![0; particles.len()],
+            group_masks: vec![0; particles.len()],
+            particles,
+            gravity: [0.0, 0.0],
+            forces: vec![],
+            paddles: vec![],
+            obstacles: vec![],
+            obstacle_materials: vec![],
+            boundaries: Boundaries::default(),
+            phases: vec![Phase::default()],
+            groups: vec![],
+            thermal: Thermal::default(),
+            wind: Wind::default(),
+            turbulence: Turbulence::default(),
+            terrain: Heightfield::default(),
+            distance_field: DistanceField::default(),
+            triggers: vec![],
+            probes: vec![],
+        }
+    }
+
+    pub fn with_gravity(mut self, gravity: [f32; 2]) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    /// Applies `edit` to the scene, see [`SceneEdit::apply_to`].
+    pub fn apply(&mut self, edit: &SceneEdit) -> Result<(), String> {
+        edit.apply_to(
+            &mut self.gravity,
+            &mut self.forces,
+            &mut self.paddles,
+            &mut self.boundaries,
+        )
+    }
+
+    pub fn with_paddle(mut self, paddle: Paddle) -> Self {
+        self.paddles.push(paddle);
+        self
+    }
+
+    pub fn with_obstacle(self, obstacle: Polygon) -> Self {
+        self.with_obstacle_material(obstacle, Material::default())
+    }
+
+    pub fn with_obstacle_material(mut self, obstacle: Polygon, material: Material) -> Self {
+        self.obstacles.push(obstacle);
+        self.obstacle_materials.push(material);
+        self
+    }
+
+    pub fn with_trigger(mut self, trigger: Trigger) -> Self {
+        self.triggers.push(trigger);
+        self
+    }
+
+    pub fn with_probe(mut self, probe: Probe) -> Self {
+        self.probes.push(probe);
+        self
+    }
+
+    pub fn with_force(mut self, force: ForcePrimitive) -> Self {
+        self.forces.push(force);
+        self
+    }
+
+    /// Pads the particles with removed ones up to `capacity`, as room for inlets to fill.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        if capacity > self.particles.len() {
+            self.particles.resize(capacity, boundary::REMOVED);
+            self.phase_ids.resize(capacity, 0);
+            self.group_masks.resize(capacity, 0);
+        }
+        self
+    }
+
+    /// Adds the particles of `block`, and its phase unless the scene already has it.
+    pub fn with_block(mut self, block: FluidBlock) -> Self {
+        let phase = self.phase_index(block.phase);
+        let particles = block.particles();
+        self.phase_ids
+            .resize(self.phase_ids.len() + particles.len(), phase);
+        self.group_masks
+            .resize(self.group_masks.len() + particles.len(), 0);
+        self.particles.extend(particles);
+        self
+    }
+
+    /// Adds `group` with the particles in its region, which take on its phase
+    /// if it has one.
+    pub fn with_group(mut self, group: Group) -> Result<Self, String> {
+        if self.groups.len() >= MAX_GROUPS {
+            return Err(format!("at most {MAX_GROUPS} groups are supported"));
+        }
+        let bit = 1 << self.groups.len();
+        let phase = group.phase.map(|phase| self.phase_index(phase));
+        for (i, p) in self.particles.iter().enumerate() {
+            if boundary::is_removed(p) || !group.contains(p.pos) {
+                continue;
+            }
+            self.group_masks[i] |= bit;
+            if let Some(phase) = phase {
+                self.phase_ids[i] = phase;
+            }
+        }
+        self.groups.push(group);
+        Ok(self)
+    }
+
+    /// Index of `phase` in `phases`, added unless the scene already has it.
+    fn phase_index(&mut self, phase: Phase) -> u32 {
+        let i = match self.phases.iter().position(|&p| p == phase) {
+            Some(i) => i,
+            None => {
+                self.phases.push(phase);
+                self.phases.len() - 1
+            }
+        };
+        i as u32
+    }
+
+    /// Colors of the phases, removed particles are transparent.
+    pub fn phase_colors(&self, particles: &[Instance]) -> Vec<u32> {
+        particles
+            .iter()
+            .zip(&self.phase_ids)
+            .map(|(p, &phase)| match boundary::is_removed(p) {
+                true => 0,
+                false => self.phases[phase as usize].color,
+            })
+            .collect()
+    }
+
+    pub fn with_boundaries(mut self, boundaries: Boundaries) -> Self {
+        self.boundaries = boundaries;
+        self
+    }
+
+    pub fn with_terrain(mut self, terrain: Heightfield) -> Self {
+        self.terrain = terrain;
+        self
+    }
+
+    pub fn with_distance_field(mut self, field: DistanceField) -> Self {
+        self.distance_field = field;
+        self
+    }
+}
+
+/// A change to a running scene, applied by [`Backend::edit`](crate::backend::Backend::edit).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SceneEdit {
+    Gravity([f32; 2]),
+    /// Moves the force primitive at this index in [`Scene::forces`].
+    MoveForce {
+        index: usize,
+        center: [f32; 2],
+    },
+    ForceStrength {
+        index: usize,
+        strength: f32,
+    },
+    /// Moves the paddle at this index in [`Scene::paddles`], which keeps
+    /// turning around its new center.
+    MovePaddle {
+        index: usize,
+        center: [f32; 2],
+    },
+    /// Periodic edges can't be added or removed while running, the grid
+    /// depends on them.
+    Boundary(Edge, Boundary),
+    /// Sets the [viscosity](crate::viscosity) of the particles within
+    /// `radius` of `center`, for the backends that solve for it.
+    PaintViscosity {
+        center: [f32; 2],
+        radius: f32,
+        viscosity: f32,
+    },
+    /// Drag on every particle on top of
+    /// [`Config::linear_drag`](crate::backend::Config::linear_drag), see
+    /// [`crate::damping`].
+    Damping(f32),
+}
+
+impl SceneEdit {
+    /// Applies the edit to the parts of a scene it touches, leaving them
+    /// unchanged when it is invalid.
+    pub fn apply_to(
+        &self,
+        gravity: &mut [f32; 2],
+        forces: &mut [ForcePrimitive],
+        paddles: &mut [Paddle],
+        boundaries: &mut Boundaries,
+    ) -> Result<(), String> {
+        fn force(
+            forces: &mut [ForcePrimitive],
+            index: usize,
+        ) -> Result<&mut ForcePrimitive, String> {
+            let count = forces.len();
+            forces
+                .get_mut(index)
+                .ok_or(format!("no force primitive {index}, the scene has {count}"))
+        }
+
+        match *self {
+            SceneEdit::Gravity(g) => *gravity = g,
+            SceneEdit::MoveForce { index, center } => force(forces, index)?.center = center,
+            SceneEdit::ForceStrength { index, strength } => {
+                force(forces, index)?.strength = strength
+            }
+            SceneEdit::MovePaddle { index, center } => {
+                let count = paddles.len();
+                paddles
+                    .get_mut(index)
+                    .ok_or(format!("no paddle {index}, the scene has {count}"))?
+                    .center = center
+            }
+            SceneEdit::Boundary(edge, boundary) => {
+                let mut edited = *boundaries;
+                edited.set(edge, boundary);
+                edited.validate()?;
+                if edited.periodic() != boundaries.periodic() {
+                    return Err("periodic edges can't change while running".into());
+                }
+                *boundaries = edited;
+            }
+            // belong to the backends, not the scene
+            SceneEdit::PaintViscosity { .. } | SceneEdit::Damping(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Parses `<x0>,<y0>,<x1>,<y1>` into the min and max corner of a rectangle.
+pub(crate) fn parse_rect(s: &str) -> Result<([f32; 2], [f32; 2]), String> {
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid rectangle `{s}`: {err}"))?;
+    match coords[..] {
+        [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(([x0, y0], [x1, y1])),
+        _ => Err(format!(
+            "invalid rectangle `{s}`, expected <x0>,<y0>,<x1>,<y1> with x0 < x1 and y0 < y1"
+        )),
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new(initial_particles())
+    }
+}
@@ -0,0 +1,187 @@
+//! Scenes driven by [Rhai](https://rhai.rs) scripts, for demos that change
+//! over time without recompiling.
+//!
+//! The top level of a script runs once when it is loaded, then its
+//! `update(time)` function, if it has one, runs before every step with the
+//! simulated time in seconds. Both can call
+//!
+//! - `gravity(x, y)` to set the uniform acceleration,
+//! - `move_force(index, x, y)` to move a force primitive,
+//! - `force_strength(index, strength)` to change its strength,
+//! - `move_obstacle(index, x, y)` to move the center of a paddle, the
+//!   obstacles that can move,
+//! - `boundary(edge, type)` to change an edge, with the edges and types of
+//!   `--boundary`, so `boundary("left", "inlet:0.5")` starts an emitter.
+//! - `paint_viscosity(x, y, radius, viscosity)` to set the viscosity of the
+//!   particles around a point.
+//!
+//! A function `on_trigger(name, entered, left, inside)` is called whenever
+//! particles go in or out of one of the scene's [triggers](crate::trigger),
+//! and can call the same functions.
+//!
+//! ```rhai
+//! gravity(0.0, -9.81);
+//!
+//! fn update(time) {
+//!     move_force(0, 0.5 + 0.3 * (time * 2.0).sin(), 0.5);
+//!     if time > 5.0 {
+//!         boundary("top", "inlet:0.5");
+//!     }
+//! }
+//! ```
+
+use crate::backend;
+use crate::boundary::{Boundary, Edge};
+use crate::scene::SceneEdit;
+use crate::trigger::{Trigger, TriggerCounts};
+use rhai::{CallFnOptions, Engine, EvalAltResult, FuncArgs, Scope, AST};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    has_update: bool,
+    has_on_trigger: bool,
+    /// Edits requested by the script since they were last taken.
+    edits: Arc<Mutex<Vec<SceneEdit>>>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, backend::Error> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read script {}: {err}", path.display()))?;
+        Self::new(&source)
+    }
+
+    /// Compiles `source` and runs its top level.
+    pub fn new(source: &str) -> Result<Self, backend::Error> {
+        let edits = Arc::new(Mutex::new(vec![]));
+        let mut engine = Engine::new();
+
+        let push = {
+            let edits = edits.clone();
+            move |edit| edits.lock().unwrap().push(edit)
+        };
+        let index =
+            |index: i64| usize::try_from(index).map_err(|_| format!("invalid index {index}"));
+
+        engine.register_fn("gravity", {
+            let push = push.clone();
+            move |x: f64, y: f64| push(SceneEdit::Gravity([x as f32, y as f32]))
+        });
+        engine.register_fn("move_force", {
+            let push = push.clone();
+            move |i: i64, x: f64, y: f64| -> Result<(), Box<EvalAltResult>> {
+                push(SceneEdit::MoveForce {
+                    index: index(i)?,
+                    center: [x as f32, y as f32],
+                });
+                Ok(())
+            }
+        });
+        engine.register_fn("force_strength", {
+            let push = push.clone();
+            move |i: i64, strength: f64| -> Result<(), Box<EvalAltResult>> {
+                push(SceneEdit::ForceStrength {
+                    index: index(i)?,
+                    strength: strength as f32,
+                });
+                Ok(())
+            }
+        });
+        engine.register_fn("move_obstacle", {
+            let push = push.clone();
+            move |i: i64, x: f64, y: f64| -> Result<(), Box<EvalAltResult>> {
+                push(SceneEdit::MovePaddle {
+                    index: index(i)?,
+                    center: [x as f32, y as f32],
+                });
+                Ok(())
+            }
+        });
+        engine.register_fn("paint_viscosity", {
+            let push = push.clone();
+            move |x: f64, y: f64, radius: f64, viscosity: f64| {
+                push(SceneEdit::PaintViscosity {
+                    center: [x as f32, y as f32],
+                    radius: radius as f32,
+                    viscosity: viscosity as f32,
+                })
+            }
+        });
+        engine.register_fn(
+            "boundary",
+            move |edge: &str, boundary: &str| -> Result<(), Box<EvalAltResult>> {
+                let edge: Edge = edge.parse()?;
+                let boundary: Boundary = boundary.parse()?;
+                push(SceneEdit::Boundary(edge, boundary));
+                Ok(())
+            },
+        );
+
+        let ast = engine
+            .compile(source)
+            .map_err(|err| format!("invalid script: {err}"))?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|err| format!("script failed: {err}"))?;
+        let has_fn = |name: &str, params: usize| {
+            ast.iter_functions()
+                .any(|f| f.name == name && f.params.len() == params)
+        };
+        let has_update = has_fn("update", 1);
+        let has_on_trigger = has_fn("on_trigger", 4);
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            has_update,
+            has_on_trigger,
+            edits,
+        })
+    }
+
+    /// Runs `update(time)` and returns the edits requested since the last
+    /// call, including those of the top level on the first one.
+    pub fn update(&mut self, time: f32) -> Result<Vec<SceneEdit>, backend::Error> {
+        if self.has_update {
+            self.call("update", (time as f64,))
+                .map_err(|err| format!("script failed at {time:.2}s: {err}"))?;
+        }
+        Ok(std::mem::take(&mut *self.edits.lock().unwrap()))
+    }
+
+    /// Runs `on_trigger` for a trigger that particles went in or out of, its
+    /// edits are returned by the next [`update`](Self::update).
+    pub fn trigger(
+        &mut self,
+        trigger: &Trigger,
+        counts: &TriggerCounts,
+    ) -> Result<(), backend::Error> {
+        if self.has_on_trigger {
+            let args = (
+                trigger.name.clone(),
+                counts.entered as i64,
+                counts.left as i64,
+                counts.inside as i64,
+            );
+            self.call("on_trigger", args)
+                .map_err(|err| format!("script failed in on_trigger: {err}"))?;
+        }
+        Ok(())
+    }
+
+    /// Calls the script function `name`, ignoring whatever it returns.
+    /// Unlike [`Engine::call_fn`] this doesn't run the top level again, it
+    /// ran once in [`new`](Self::new).
+    fn call(&mut self, name: &str, args: impl FuncArgs) -> Result<(), Box<EvalAltResult>> {
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine
+            .call_fn_with_options::<rhai::Dynamic>(options, &mut self.scope, &self.ast, name, args)
+            .map(drop)
+    }
+}
@@ -0,0 +1,172 @@
+//! Obstacles the particles collide with, as a signed distance field.
+//!
+//! Any number of closed polygons are sampled on a grid over the unit square,
+//! negative inside and positive outside. Inside is decided by the even-odd
+//! rule over all the polygons together, so a polygon within another cuts a
+//! hole out of it, and a polygon within that hole fills it again. Particles
+//! that end up inside are pushed back out along the gradient of the field,
+//! the same on the host and on the device, at the cost of one lookup per
+//! particle however many edges the outlines have. How they bounce off and
+//! cling to each obstacle is up to its [`Material`].
+
+use crate::geometry::Polygon;
+use crate::material::Material;
+
+/// Distances at `resolution` squared evenly spaced points, from the corner
+/// at the origin to the one at `[1, 1]`, row by row from the bottom, bilinearly
+/// interpolated in between. Fewer than two points a side mean there is no
+/// field.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DistanceField {
+    pub resolution: u32,
+    pub distances: Vec<f32>,
+    /// Index of the polygon closest to each point.
+    pub obstacles: Vec<u32>,
+    /// Material of each polygon, the default for those past the end.
+    pub materials: Vec<Material>,
+}
+
+impl DistanceField {
+    pub fn from_polygons(polygons: &[Polygon], resolution: u32) -> Self {
+        let step = 1.0 / (resolution.max(2) - 1) as f32;
+        let (distances, obstacles) = (0..resolution * resolution)
+            .map(|i| {
+                let p = [
+                    (i % resolution) as f32 * step,
+                    (i / resolution) as f32 * step,
+                ];
+                let (distance, closest) = polygons
+                    .iter()
+                    .enumerate()
+                    .map(|(id, poly)| {
+                        let distance = poly
+                            .edges()
+                            .map(|(a, b)| segment_distance(p, a, b))
+                            .fold(f32::INFINITY, f32::min);
+                        (distance, id as u32)
+                    })
+                    .fold((f32::INFINITY, 0), |a, b| if b.0 < a.0 { b } else { a });
+                let inside = polygons.iter().filter(|poly| poly.contains(p)).count() % 2 == 1;
+                let distance = match inside {
+                    true => -distance,
+                    false => distance,
+                };
+                (distance, closest)
+            })
+            .unzip();
+        Self {
+            resolution,
+            distances,
+            obstacles,
+            materials: vec![],
+        }
+    }
+
+    /// With `materials[i]` for the `i`th polygon.
+    pub fn with_materials(mut self, materials: Vec<Material>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolution < 2
+    }
+
+    /// Cell below `p` and how far into it `p` is, clamped to the unit square.
+    fn cell(&self, p: [f32; 2]) -> ([usize; 2], [f32; 2]) {
+        let last = (self.resolution - 1) as f32;
+        let t = p.map(|x| x.clamp(0.0, 1.0) * last);
+        let i = t.map(|t| (t as usize).min(self.resolution as usize - 2));
+        (i, [t[0] - i[0] as f32, t[1] - i[1] as f32])
+    }
+
+    /// The four corners of a cell, bottom left, bottom right, top left and
+    /// top right.
+    fn corners(&self, [x, y]: [usize; 2]) -> [f32; 4] {
+        let row = self.resolution as usize;
+        let at = |x, y| self.distances[y * row + x];
+        [at(x, y), at(x + 1, y), at(x, y + 1), at(x + 1, y + 1)]
+    }
+
+    /// Signed distance at `p`, `None` without a field.
+    pub fn distance_at(&self, p: [f32; 2]) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+        let (i, [fx, fy]) = self.cell(p);
+        let [d00, d10, d01, d11] = self.corners(i);
+        let bottom = d00 + (d10 - d00) * fx;
+        let top = d01 + (d11 - d01) * fx;
+        Some(bottom + (top - bottom) * fy)
+    }
+
+    /// Direction the distance grows fastest in at `p`, pointing out of the
+    /// obstacles. `None` without a field or where it is flat.
+    pub fn normal_at(&self, p: [f32; 2]) -> Option<[f32; 2]> {
+        if self.is_empty() {
+            return None;
+        }
+        let (i, [fx, fy]) = self.cell(p);
+        let [d00, d10, d01, d11] = self.corners(i);
+        let gx = (d10 - d00) * (1.0 - fy) + (d11 - d01) * fy;
+        let gy = (d01 - d00) * (1.0 - fx) + (d11 - d10) * fx;
+        let len = (gx * gx + gy * gy).sqrt();
+        (len > 0.0).then(|| [gx / len, gy / len])
+    }
+
+    /// Material of the obstacle closest to the point nearest to `p`.
+    pub fn material_at(&self, p: [f32; 2]) -> Material {
+        if self.is_empty() {
+            return Material::default();
+        }
+        let last = self.resolution - 1;
+        let [x, y] = p.map(|x| ((x.clamp(0.0, 1.0) * last as f32 + 0.5) as u32).min(last));
+        let obstacle = self.obstacles[(y * self.resolution + x) as usize];
+        self.materials
+            .get(obstacle as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Moves a particle inside an obstacle out along the normal by its
+    /// distance and lets the obstacle's material [respond](Material::respond).
+    /// Mirrors `collide_sdf` in `sorting.ocl`.
+    pub fn collide(&self, pos: &mut [f32; 2], vel: &mut [f32; 2]) {
+        let Some(distance) = self.distance_at(*pos) else {
+            return;
+        };
+        if distance >= 0.0 {
+            return;
+        }
+        let Some(normal) = self.normal_at(*pos) else {
+            return;
+        };
+
+        pos[0] -= distance * normal[0];
+        pos[1] -= distance * normal[1];
+        self.material_at(*pos).respond(vel, normal);
+    }
+
+    /// Acceleration pulling a particle near an obstacle towards it, by the
+    /// [adhesion](Material::adhesion) of the obstacle's material.
+    /// Mirrors `sdf_adhesion` in `sorting.ocl`.
+    pub fn adhesion(&self, pos: [f32; 2]) -> [f32; 2] {
+        let (Some(distance), Some(normal)) = (self.distance_at(pos), self.normal_at(pos)) else {
+            return [0.0, 0.0];
+        };
+        self.material_at(pos).adhesion(distance, normal)
+    }
+}
+
+/// Distance from `p` to the segment from `a` to `b`.
+fn segment_distance(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [p[0] - a[0], p[1] - a[1]];
+    let len_sq = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = match len_sq > 0.0 {
+        true => ((ap[0] * ab[0] + ap[1] * ab[1]) / len_sq).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+    let d = [ap[0] - ab[0] * t, ap[1] - ab[1] * t];
+    (d[0] * d[0] + d[1] * d[1]).sqrt()
+}
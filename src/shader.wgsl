@@ -1,45 +1,153 @@
-struct CameraUniform {
-    transform: mat4x4<f32>,
-}
-
-@group(0) @binding(0)
-var<uniform> camera: CameraUniform;
-
-struct VertexInput {
-    @location(0) position: vec2<f32>,
-};
-
-struct InstanceInput {
-    @location(2) position: vec2<f32>,
-    @location(3) velocity: vec2<f32>,
-}
-
-struct VertexOutput {
-    @builtin(position) position: vec4<f32>,
-    @location(1) local_pos: vec2<f32>,
-    @location(0) color: vec3<f32>,
-};
-
-@vertex
-fn vs_main(
-    model: VertexInput,
-    instance: InstanceInput,
-) -> VertexOutput {
-    var out: VertexOutput;
-    let pos = instance.position + model.position * 0.5;
-
-    out.local_pos = model.position;
-    out.position = camera.transform * vec4<f32>(pos, 0.0, 1.0);
-    out.color = vec3<f32>(instance.velocity, 1.0);
-
-    return out;
-}
-
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let center = in.local_pos;
-    let dist = center.x * center.x + center.y * center.y;
-    let outer_alpha = smoothstep(0.0, 0.01, 1.0 - dist);
-    let inner_alpha = smoothstep(0.01, 0.0, 0.90 - dist);
-    return vec4(in.color, outer_alpha * inner_alpha);
-}
\ No newline at end of file
+struct CameraUniform {
+    transform: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// mirrors `SimParams` in sim.rs, shared with sorting.ocl
+struct SimParams {
+    particle_radius: f32,
+    time_step: f32,
+    n_cells: u32,
+    n_per_cell: u32,
+    periodic: u32,
+    sleep_speed: f32,
+    sleep_after: u32,
+    n_particles: u32,
+    max_speed: f32,
+    linear_drag: f32,
+    quadratic_drag: f32,
+    _pad: u32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: SimParams;
+
+// mirrors `RenderParams` in render.rs
+struct RenderParams {
+    radius_scale: f32,
+    ring_inner: f32,
+    flags: u32,
+    _pad: u32,
+    // min x, min y, max x, max y of the world
+    bounds: vec4<f32>,
+}
+
+@group(0) @binding(2)
+var<uniform> render: RenderParams;
+
+// mirror `RenderParams::OPAQUE` and `RenderParams::CLIP`
+const OPAQUE: u32 = 1u;
+const CLIP: u32 = 2u;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+};
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) velocity: vec2<f32>,
+    @location(4) color: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(1) local_pos: vec2<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// clip space position of `corner` of the quad of a particle at `center`
+fn place(center: vec2<f32>, corner: vec2<f32>) -> vec4<f32> {
+    let outside = any(center < render.bounds.xy) || any(center > render.bounds.zw);
+    if (render.flags & CLIP) != 0u && outside {
+        // every corner in the same point out of view, nothing is drawn
+        return vec4(2.0, 2.0, 2.0, 1.0);
+    }
+    let pos = center + corner * params.particle_radius * render.radius_scale;
+    return camera.transform * vec4<f32>(pos, 0.0, 1.0);
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.local_pos = model.position;
+    out.position = place(instance.position, model.position);
+    // 0xAARRGGBB -> RGBA 0-1
+    let a = (instance.color >> 24u);
+    let r = (instance.color >> 16u) & 0xffu;
+    let g = (instance.color >> 8u ) & 0xffu;
+    let b = (instance.color       ) & 0xffu;
+    out.color = vec4(f32(r), f32(g), f32(b), f32(a)) / 255.0;
+    if (render.flags & OPAQUE) != 0u {
+        out.color.a = 1.0;
+    }
+
+    return out;
+}
+
+// how much of the fragment the particle covers, a ring from
+// `render.ring_inner` out, a disc without an inner edge
+fn coverage(local_pos: vec2<f32>) -> f32 {
+    let dist = length(local_pos);
+    // the edges fade over one pixel on screen, however far the camera zooms
+    let pixel = fwidth(dist);
+    let outer_alpha = 1.0 - smoothstep(1.0 - pixel, 1.0, dist);
+    let inner_alpha = smoothstep(render.ring_inner - pixel, render.ring_inner, dist);
+    return outer_alpha * inner_alpha;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color.rgb, in.color.a * coverage(in.local_pos));
+}
+
+struct OitOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+}
+
+// weighted blended order independent transparency (McGuire and Bavoil 2013),
+// composited by oit.wgsl. Everything is at the same depth, so the weight only
+// favors the more opaque fragments.
+@fragment
+fn fs_oit(in: VertexOutput) -> OitOutput {
+    let alpha = in.color.a * coverage(in.local_pos);
+    let weight = clamp(pow(min(1.0, alpha * 10.0) + 0.01, 3.0) * 1e3, 1e-2, 3e3);
+    var out: OitOutput;
+    out.accum = vec4(in.color.rgb * alpha, alpha) * weight;
+    out.revealage = alpha;
+    return out;
+}
+
+struct PickOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) local_pos: vec2<f32>,
+    @location(1) @interpolate(flat) index: u32,
+}
+
+// draws the index of each particle plus one, zero is nothing, see `Picker`
+// in render.rs
+@vertex
+fn vs_pick(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) index: u32,
+) -> PickOutput {
+    var out: PickOutput;
+    out.position = place(instance.position, model.position);
+    out.local_pos = model.position;
+    out.index = index + 1u;
+    return out;
+}
+
+@fragment
+fn fs_pick(in: PickOutput) -> @location(0) u32 {
+    if length(in.local_pos) > 1.0 {
+        discard;
+    }
+    return in.index;
+}
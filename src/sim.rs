@@ -0,0 +1,194 @@
+//! The particle data the simulation hands to whatever shows it, and the
+//! colors it is shown in. Nothing here needs the `render` feature, so the
+//! simulation builds without a window or GPU.
+
+use crate::backend::Config;
+use crate::grid::Grid;
+use crate::scene::Scene;
+use crate::stability;
+use crate::{MAX_PARTICLES_PER_CELL, PARTICLE_RADIUS, TIME_STEP};
+
+#[repr(C)]
+#[derive(Clone, Default, Debug, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub pos: [f32; 2],
+    pub vel: [f32; 2],
+}
+
+/// What particles are sorted by before drawing, larger keys on top. Mirrored
+/// by the `KEY_` constants in `sort.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Higher particles over lower ones.
+    Y = 0,
+    /// Faster particles, foam and spray, over slower ones.
+    Speed = 1,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "y" => Ok(SortKey::Y),
+            "speed" => Ok(SortKey::Speed),
+            _ => Err(format!("unknown sort key `{s}`, expected `y` or `speed`")),
+        }
+    }
+}
+
+/// Parameters shared by the OpenCL kernels and the shaders, uploaded as one
+/// buffer to both so they can't disagree. Mirrored by `SimParams` in
+/// `sorting.ocl` and `shader.wgsl`, padded to 48 bytes as WGSL uniforms need
+/// a multiple of 16.
+#[repr(C)]
+#[derive(Clone, Debug, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SimParams {
+    /// Radius particles collide at and are drawn with.
+    pub particle_radius: f32,
+    pub time_step: f32,
+    /// Cells per axis of the [`Grid`].
+    pub n_cells: u32,
+    /// Particle slots per grid cell.
+    pub n_per_cell: u32,
+    /// [`Grid::periodic_mask`].
+    pub periodic: u32,
+    /// [`Config::sleep_speed`](crate::backend::Config::sleep_speed).
+    pub sleep_speed: f32,
+    /// [`Config::sleep_after`](crate::backend::Config::sleep_after).
+    pub sleep_after: u32,
+    /// Particle slots, launches can be rounded up past it.
+    pub n_particles: u32,
+    /// [`stability::speed_limit`] of a step.
+    pub max_speed: f32,
+    /// [`Config::linear_drag`](crate::backend::Config::linear_drag).
+    pub linear_drag: f32,
+    /// [`Config::quadratic_drag`](crate::backend::Config::quadratic_drag).
+    pub quadratic_drag: f32,
+    pub _pad: u32,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self::new(&Grid::new(PARTICLE_RADIUS * 2.0), &Config::default(), 0)
+    }
+}
+
+impl SimParams {
+    /// What the backends run `scene` with.
+    pub fn for_scene(scene: &Scene, config: &Config) -> Self {
+        let grid = Grid::new(PARTICLE_RADIUS * 2.0).with_periodic(scene.boundaries.periodic());
+        Self::new(&grid, config, scene.particles.len())
+    }
+
+    pub fn new(grid: &Grid, config: &Config, n_particles: usize) -> Self {
+        Self {
+            particle_radius: PARTICLE_RADIUS,
+            time_step: TIME_STEP,
+            n_cells: grid.n_cells(),
+            n_per_cell: MAX_PARTICLES_PER_CELL as u32,
+            periodic: grid.periodic_mask(),
+            sleep_speed: config.sleep_speed,
+            sleep_after: config.sleep_after,
+            n_particles: n_particles as u32,
+            max_speed: stability::speed_limit(config, TIME_STEP),
+            linear_drag: config.linear_drag,
+            quadratic_drag: config.quadratic_drag,
+            _pad: 0,
+        }
+    }
+}
+
+/// A foam, spray or bubble particle as drawn by `diffuse.wgsl`, see [`crate::diffuse`].
+#[repr(C)]
+#[derive(Clone, Default, Debug, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DiffuseInstance {
+    pub pos: [f32; 2],
+    pub size: f32,
+    pub alpha: f32,
+}
+
+/// A colored vertex in clip space, drawn by `overlay.wgsl` on top of the scene.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    pub pos: [f32; 2],
+    /// Packed as by [`rgba_to_u32`].
+    pub color: u32,
+}
+
+/// Packs a color as `0xAARRGGBB`, the layout `shader.wgsl` unpacks.
+pub const fn rgba_to_u32(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    (a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+/// The default coloring: red and green follow the velocity, blue is saturated.
+pub fn velocity_color(p: &Instance) -> u32 {
+    let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0) as u8;
+    rgba_to_u32(channel(p.vel[0]), channel(p.vel[1]), 255, 255)
+}
+
+/// What the particle colors show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coloring {
+    /// See [`velocity_color`].
+    Velocity,
+    /// The color of each particle's [phase](crate::phase::Phase).
+    Phase,
+    /// [`colormap`] of the [temperature](crate::thermal), centered on ambient.
+    Temperature,
+    /// [`colormap`] of the [age](crate::age), fresh particles dark.
+    Age,
+}
+
+impl std::str::FromStr for Coloring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "velocity" => Ok(Coloring::Velocity),
+            "phase" => Ok(Coloring::Phase),
+            "temperature" => Ok(Coloring::Temperature),
+            "age" => Ok(Coloring::Age),
+            _ => Err(format!(
+                "unknown coloring `{s}`, expected `velocity`, `phase`, `temperature` or `age`"
+            )),
+        }
+    }
+}
+
+/// Maps `t` in `[0, 1]` to a perceptually ordered color, dark blue through
+/// green to yellow. Values outside are clamped.
+pub fn colormap(t: f32) -> u32 {
+    const STOPS: [[f32; 3]; 5] = [
+        [68.0, 1.0, 84.0],
+        [59.0, 82.0, 139.0],
+        [33.0, 145.0, 140.0],
+        [94.0, 201.0, 98.0],
+        [253.0, 231.0, 37.0],
+    ];
+
+    let x = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let f = x - i as f32;
+    let [r, g, b] = [0, 1, 2].map(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * f) as u8);
+    rgba_to_u32(r, g, b, 255)
+}
+
+/// [`colormap`] of `t` in `[0, 1]`, fading to transparent towards 0, for
+/// showing where a quantity peaks over whatever is behind.
+pub fn heat(t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    colormap(t) & 0x00ff_ffff | ((t * 200.0) as u32) << 24
+}
+
+/// Maps `t` in `[-1, 1]` to blue for negative and red for positive values,
+/// fading to transparent at 0. Values outside are clamped.
+pub fn diverging(t: f32) -> u32 {
+    let t = t.clamp(-1.0, 1.0);
+    let alpha = (t.abs() * 255.0) as u8;
+    match t >= 0.0 {
+        true => rgba_to_u32(220, 60, 50, alpha),
+        false => rgba_to_u32(50, 100, 230, alpha),
+    }
+}
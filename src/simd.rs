@@ -0,0 +1,244 @@
+//! The [wcsph](crate::wcsph) density and pressure loops and the collision
+//! pass of the [cpu](crate::cpu) backend, 8 neighbors at a time, with the
+//! `simd` feature.
+//!
+//! [`Lanes`] copies the particles into the order of a [`CellList`] with one
+//! array per component, so the particles of a row of neighboring cells sit
+//! next to each other and load straight into 8-wide lanes. The tail of a row
+//! is padded with particles infinitely far away. The sums are the same as the scalar loops
+//! up to rounding; ghosts and solid samples at the boundaries are still
+//! added one at a time. [`Slots`] does the same for the fixed cell slots of
+//! the cpu backend, whose collisions come out exactly as without it.
+
+use crate::grid::Grid;
+use crate::neighbors::CellList;
+use crate::sim::Instance;
+use crate::wcsph::WcsphParams;
+use std::ops::Range;
+use wide::{f32x8, CmpGt, CmpLe, CmpLt, CmpNe};
+
+/// Particles per lane.
+pub const LANES: usize = 8;
+
+/// The particles sorted by cell, one array per component.
+#[derive(Debug, Clone, Default)]
+pub struct Lanes {
+    pos_x: Vec<f32>,
+    pos_y: Vec<f32>,
+    vel_x: Vec<f32>,
+    vel_y: Vec<f32>,
+    densities: Vec<f32>,
+    /// Pressure over density squared.
+    pressure_terms: Vec<f32>,
+}
+
+impl Lanes {
+    /// Positions and velocities of `particles` in the order `cells` was
+    /// last built in.
+    pub fn gather(&mut self, cells: &CellList, particles: &[Instance]) {
+        let sorted = cells.sorted();
+        let component = |array: &mut Vec<f32>, get: fn(&Instance) -> f32| {
+            array.clear();
+            array.extend(sorted.iter().map(|&id| get(&particles[id as usize])));
+        };
+        component(&mut self.pos_x, |p| p.pos[0]);
+        component(&mut self.pos_y, |p| p.pos[1]);
+        component(&mut self.vel_x, |p| p.vel[0]);
+        component(&mut self.vel_y, |p| p.vel[1]);
+    }
+
+    /// Densities and pressures by particle, in the same order as
+    /// [`gather`](Self::gather).
+    pub fn gather_pressures(&mut self, cells: &CellList, densities: &[f32], pressures: &[f32]) {
+        let sorted = cells.sorted().iter().map(|&id| id as usize);
+        self.densities.clear();
+        self.densities
+            .extend(sorted.clone().map(|id| densities[id]));
+        self.pressure_terms.clear();
+        self.pressure_terms
+            .extend(sorted.map(|id| pressures[id] / (densities[id] * densities[id])));
+    }
+
+    /// [`kernel`](crate::probe) weight summed over the particles within the
+    /// radius of `cells` around `pos`.
+    pub fn weight_at(&self, cells: &CellList, pos: [f32; 2]) -> f32 {
+        let inv_radius_sq = f32x8::splat(1.0 / (cells.radius() * cells.radius()));
+        let mut sum = f32x8::ZERO;
+        for range in cells.neighbor_rows(pos) {
+            for at in range.clone().step_by(LANES) {
+                let dx = load(&self.pos_x, at, range.end, f32::INFINITY) - pos[0];
+                let dy = load(&self.pos_y, at, range.end, f32::INFINITY) - pos[1];
+                let falloff =
+                    (f32x8::ONE - (dx * dx + dy * dy) * inv_radius_sq).fast_max(f32x8::ZERO);
+                sum += falloff * falloff * falloff;
+            }
+        }
+        sum.reduce_add()
+    }
+
+    /// Pressure and artificial viscosity acceleration of particle `p` with
+    /// `density` and pressure term `own` from its neighbors, with every
+    /// particle of `mass`.
+    pub fn pressure_acceleration(
+        &self,
+        cells: &CellList,
+        params: &WcsphParams,
+        mass: f32,
+        p: &Instance,
+        density: f32,
+        own: f32,
+    ) -> [f32; 2] {
+        let h = params.radius;
+        let radius_sq = f32x8::splat(h * h);
+        // `-kernel_slope(dist) / dist`, without the falloff
+        let slope = f32x8::splat(6.0 / (h * h));
+        let viscosity = f32x8::splat(params.viscosity * params.speed_of_sound * h);
+        let softening = f32x8::splat(0.01 * h * h);
+        let (mut acc_x, mut acc_y) = (f32x8::ZERO, f32x8::ZERO);
+        for range in cells.neighbor_rows(p.pos) {
+            for at in range.clone().step_by(LANES) {
+                let lane = |array: &[f32], fill: f32| load(array, at, range.end, fill);
+                // `d` points from the particle to its neighbor
+                let dx = lane(&self.pos_x, f32::INFINITY) - p.pos[0];
+                let dy = lane(&self.pos_y, f32::INFINITY) - p.pos[1];
+                let dist_sq = dx * dx + dy * dy;
+                // leaves out the particle itself, like the scalar loop
+                let near = dist_sq.cmp_lt(radius_sq) & dist_sq.cmp_gt(f32x8::splat(1e-12));
+
+                let approach = (lane(&self.vel_x, 0.0) - p.vel[0]) * dx
+                    + (lane(&self.vel_y, 0.0) - p.vel[1]) * dy;
+                let mean_density = (lane(&self.densities, 1.0) + density) * 0.5;
+                let viscous = approach.cmp_lt(f32x8::ZERO).blend(
+                    viscosity * -approach / (mean_density * (dist_sq + softening)),
+                    f32x8::ZERO,
+                );
+                let scale = (lane(&self.pressure_terms, 0.0) + own + viscous) * mass;
+
+                let falloff = (f32x8::ONE - dist_sq / radius_sq).fast_max(f32x8::ZERO);
+                let push = scale * slope * falloff * falloff;
+                acc_x -= near.blend(push * dx, f32x8::ZERO);
+                acc_y -= near.blend(push * dy, f32x8::ZERO);
+            }
+        }
+        [acc_x.reduce_add(), acc_y.reduce_add()]
+    }
+}
+
+/// The particles of the [cpu backend](crate::cpu::CpuState) in the slots of
+/// its grid, one array per component. Every cell has the same number of
+/// slots and the cells of a row are next to each other, so a row of
+/// neighboring cells loads straight into lanes. Empty slots hold particles
+/// infinitely far away.
+#[derive(Debug, Clone, Default)]
+pub struct Slots {
+    pos_x: Vec<f32>,
+    pos_y: Vec<f32>,
+    /// 1 for the particles asleep, 0 for the others.
+    asleep: Vec<f32>,
+    ids: Vec<i32>,
+}
+
+impl Slots {
+    /// The particles with their ids in `cell_ids`, -1 in the empty slots.
+    pub fn gather(
+        &mut self,
+        cell_ids: &[i32],
+        particles: &[Instance],
+        asleep: impl Fn(usize) -> bool,
+    ) {
+        let slot = |id: i32| usize::try_from(id).ok();
+        let component = |array: &mut Vec<f32>, get: &dyn Fn(usize) -> f32, empty: f32| {
+            array.clear();
+            array.extend(cell_ids.iter().map(|&id| slot(id).map_or(empty, get)));
+        };
+        component(&mut self.pos_x, &|id| particles[id].pos[0], f32::INFINITY);
+        component(&mut self.pos_y, &|id| particles[id].pos[1], f32::INFINITY);
+        component(&mut self.asleep, &|id| asleep(id) as u32 as f32, 0.0);
+        self.ids.clear();
+        self.ids.extend_from_slice(cell_ids);
+    }
+
+    /// Whether particle `id` at `pos` in `cell` is within `radius` of any
+    /// other in the cells around, leaving out the others asleep if it is
+    /// `asleep` too. `n_per_cell` is the number of slots of a cell.
+    #[allow(clippy::too_many_arguments)]
+    pub fn touches(
+        &self,
+        grid: &Grid,
+        n_per_cell: usize,
+        cell: u32,
+        id: usize,
+        pos: [f32; 2],
+        radius: f32,
+        asleep: bool,
+    ) -> bool {
+        let own_slots = cell as usize * n_per_cell..(cell as usize + 1) * n_per_cell;
+        let own = own_slots
+            .into_iter()
+            .find(|&slot| self.ids[slot] == id as i32);
+        let radius_sq = f32x8::splat(radius * radius);
+        let [periodic_x, periodic_y] = grid.periodic();
+        let wrap = |d: f32x8, periodic: bool| if periodic { d - d.round() } else { d };
+        // the others asleep too are left out, none are 2
+        let skip = if asleep {
+            f32x8::ONE
+        } else {
+            f32x8::splat(2.0)
+        };
+
+        rows(grid, cell).any(|cells| {
+            let end = (cells.end as usize) * n_per_cell;
+            (cells.start as usize * n_per_cell..end)
+                .step_by(LANES)
+                .any(|at| {
+                    // matches `Grid::wrap_delta`, so exactly the same pairs touch
+                    let dx = wrap(
+                        load(&self.pos_x, at, end, f32::INFINITY) - pos[0],
+                        periodic_x,
+                    );
+                    let dy = wrap(
+                        load(&self.pos_y, at, end, f32::INFINITY) - pos[1],
+                        periodic_y,
+                    );
+                    let near = (dx * dx + dy * dy).cmp_le(radius_sq)
+                        & load(&self.asleep, at, end, 0.0).cmp_ne(skip);
+                    let mut hits = near.move_mask();
+                    if let Some(own) = own.filter(|own| (at..at + LANES).contains(own)) {
+                        hits &= !(1 << (own - at));
+                    }
+                    hits != 0
+                })
+        })
+    }
+}
+
+/// The [neighbors](Grid::neighbors) of `cell` as runs of cells next to each
+/// other, split where a periodic axis wraps around.
+fn rows(grid: &Grid, cell: u32) -> impl Iterator<Item = Range<u32>> {
+    // at most one per neighbor
+    let mut runs: [Range<u32>; 9] = std::array::from_fn(|_| 0..0);
+    let mut count = 0usize;
+    for neighbor in grid.neighbors(cell) {
+        match count.checked_sub(1).map(|last| &mut runs[last]) {
+            Some(run) if run.end == neighbor => run.end += 1,
+            _ => {
+                runs[count] = neighbor..neighbor + 1;
+                count += 1;
+            }
+        }
+    }
+    runs.into_iter().take(count)
+}
+
+/// The [`LANES`] values from `at`, with `fill` from `end` on.
+#[inline]
+fn load(values: &[f32], at: usize, end: usize, fill: f32) -> f32x8 {
+    if at + LANES <= end {
+        let lane: [f32; LANES] = values[at..at + LANES].try_into().unwrap();
+        return f32x8::new(lane);
+    }
+    let mut lane = [fill; LANES];
+    let count = end - at;
+    lane[..count].copy_from_slice(&values[at..end]);
+    f32x8::new(lane)
+}
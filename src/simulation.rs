@@ -0,0 +1,1044 @@
+//! Drives a backend (or a comparison of two) at a fixed rate on its own thread.
+
+use crate::backend::{self, Backend, StepTimings};
+use crate::boundary;
+use crate::compare::Comparison;
+use crate::damping::Damping;
+use crate::diffuse::{DiffuseParams, DiffuseSystem};
+use crate::dye::{DyeField, DyeParams};
+use crate::field::{ScalarGrid, VelocityField};
+use crate::groups::Groups;
+use crate::histogram::{self, Histogram, Histograms};
+use crate::ids::ParticleIds;
+use crate::kdtree::KdTree;
+use crate::measure::Measurement;
+use crate::mixing::{ColorMix, MixParams};
+use crate::options::Options;
+use crate::plots::Metrics;
+use crate::probe::{Heatmap, ProbeParams, Probes};
+use crate::recording::Recorder;
+use crate::replay::{InputRecorder, Replay};
+use crate::report::{Metadata, Report};
+use crate::rewind::RewindBuffer;
+use crate::scene::{Scene, SceneEdit};
+use crate::sim::{self, Coloring, DiffuseInstance, Instance, SimParams};
+use crate::solid::SolidParticles;
+use crate::stats::ParticleStats;
+use crate::streamlines::{self, StreamlineParams};
+use crate::surface::{Polyline, SurfaceExtractor, SurfaceParams};
+use crate::timelapse::Timelapse;
+use crate::timestep::{self, FixedTimestep};
+use crate::trace::{DeviceSpan, SharedTrace, Trace, Track};
+use crate::trigger::Triggers;
+use crate::verify::{self, GridCells};
+use crate::TIME_STEP;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a [paused](Command::SetPaused) simulation thread checks for commands.
+const PAUSED_POLL: Duration = Duration::from_millis(50);
+/// Cells across the [heatmap](Probes::heatmap).
+const HEATMAP_RESOLUTION: u32 = 64;
+
+pub enum Simulation {
+    Single(Box<dyn Backend>),
+    Compare(Comparison),
+}
+
+impl Simulation {
+    pub fn new(scene: &Scene, options: &Options) -> Result<Self, backend::Error> {
+        let backend = options.backend.create(scene, &options.config)?;
+
+        Ok(match options.compare {
+            Some(other) => {
+                let config = options.compare_config.as_ref().unwrap_or(&options.config);
+                Simulation::Compare(Comparison::new(backend, other.create(scene, config)?))
+            }
+            None => Simulation::Single(backend),
+        })
+    }
+
+    pub fn step(&mut self) -> Result<(), backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.step(),
+            Simulation::Compare(comparison) => {
+                let divergence = comparison.step()?;
+                let previous = comparison.history.iter().rev().nth(1);
+                if previous.is_none_or(|p| p.max != divergence.max) {
+                    println!("{divergence}");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// See [`Backend::read_complete`], both backends when comparing.
+    pub fn read_complete(&mut self) -> Result<bool, backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.read_complete(),
+            // a comparison waits for both to compare them
+            Simulation::Compare(_) => Ok(true),
+        }
+    }
+
+    /// See [`Backend::wait_read`].
+    pub fn wait_read(&mut self) -> Result<(), backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.wait_read(),
+            Simulation::Compare(_) => Ok(()),
+        }
+    }
+
+    pub fn instances(&self) -> Vec<Instance> {
+        match self {
+            Simulation::Single(backend) => backend.particles().to_vec(),
+            Simulation::Compare(comparison) => comparison.instances(),
+        }
+    }
+
+    /// Particles of the primary backend.
+    pub fn particles(&self) -> &[Instance] {
+        match self {
+            Simulation::Single(backend) => backend.particles(),
+            Simulation::Compare(comparison) => comparison.a.particles(),
+        }
+    }
+
+    /// Temperatures of the primary backend.
+    pub fn temperatures(&self) -> &[f32] {
+        match self {
+            Simulation::Single(backend) => backend.temperatures(),
+            Simulation::Compare(comparison) => comparison.a.temperatures(),
+        }
+    }
+
+    /// Stable ids of the particles of the primary backend.
+    pub fn ids(&self) -> &ParticleIds {
+        match self {
+            Simulation::Single(backend) => backend.ids(),
+            Simulation::Compare(comparison) => comparison.a.ids(),
+        }
+    }
+
+    /// Timings of the last step of the primary backend.
+    pub fn timings(&self) -> Option<StepTimings> {
+        match self {
+            Simulation::Single(backend) => backend.timings(),
+            Simulation::Compare(comparison) => comparison.a.timings(),
+        }
+    }
+
+    /// Stats of the primary backend.
+    pub fn stats(&mut self) -> Result<ParticleStats, backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.stats(),
+            Simulation::Compare(comparison) => comparison.a.stats(),
+        }
+    }
+
+    /// Goes back to `particles`, as taken from [`instances`](Self::instances)
+    /// after step `step`.
+    pub fn restore(&mut self, particles: &[Instance], step: u64) -> Result<(), backend::Error> {
+        let time = step as f32 * TIME_STEP;
+        match self {
+            Simulation::Single(backend) => backend.restore(particles, time),
+            Simulation::Compare(comparison) => {
+                let (a, b) =
+                    particles.split_at(comparison.a.particles().len().min(particles.len()));
+                comparison.a.restore(a, time)?;
+                comparison.b.restore(b, time)
+            }
+        }
+    }
+
+    /// What the primary backend is and runs on, for the [report](crate::report).
+    pub fn metadata(&self, config: &backend::Config) -> Metadata {
+        let backend = match self {
+            Simulation::Single(backend) => backend,
+            Simulation::Compare(comparison) => &comparison.a,
+        };
+        Metadata {
+            backend: backend.name(),
+            device: backend.device(),
+            config: config.clone(),
+        }
+    }
+
+    /// Device commands of the last step of the primary backend, for the
+    /// [trace](crate::trace).
+    pub fn device_spans(&self) -> &[DeviceSpan] {
+        match self {
+            Simulation::Single(backend) => backend.device_spans(),
+            Simulation::Compare(comparison) => comparison.a.device_spans(),
+        }
+    }
+
+    /// Grid of the primary backend, if it keeps one.
+    pub fn grid_cells(&self) -> Option<GridCells<'_>> {
+        match self {
+            Simulation::Single(backend) => backend.grid_cells(),
+            Simulation::Compare(comparison) => comparison.a.grid_cells(),
+        }
+    }
+
+    /// Speed histogram of the primary backend.
+    pub fn speed_histogram(&mut self) -> Result<Histogram, backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.speed_histogram(),
+            Simulation::Compare(comparison) => comparison.a.speed_histogram(),
+        }
+    }
+
+    /// Applies `edit` to every backend.
+    pub fn edit(&mut self, edit: &SceneEdit) -> Result<(), backend::Error> {
+        match self {
+            Simulation::Single(backend) => backend.edit(edit),
+            Simulation::Compare(comparison) => {
+                comparison.a.edit(edit)?;
+                comparison.b.edit(edit)
+            }
+        }
+    }
+
+    pub fn colors(&self) -> Vec<u32> {
+        match self {
+            Simulation::Single(backend) => backend
+                .particles()
+                .iter()
+                .map(|p| match boundary::is_removed(p) {
+                    true => 0,
+                    false => sim::velocity_color(p),
+                })
+                .collect(),
+            Simulation::Compare(comparison) => comparison.colors(),
+        }
+    }
+}
+
+/// Requests from the render thread, handled before the next step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Sets the [dye](crate::dye) within `radius` of `pos` to `value`.
+    Paint {
+        pos: [f32; 2],
+        radius: f32,
+        value: f32,
+    },
+    /// Sets the [viscosity](crate::viscosity) within `radius` of `pos`.
+    PaintViscosity {
+        pos: [f32; 2],
+        radius: f32,
+        viscosity: f32,
+    },
+    /// Starts or stops tracing [streamlines](crate::streamlines).
+    ToggleStreamlines,
+    /// Starts or stops computing the [vorticity](VelocityField::vorticity).
+    ToggleVorticity,
+    /// Starts or stops sampling the [heatmap](Probes::heatmap).
+    ToggleHeatmap,
+    /// Starts or stops measuring the [`Metrics`] for the plots.
+    TogglePlots,
+    /// Holds the simulation, or lets it go on without catching up.
+    SetPaused(bool),
+    /// Pauses and goes back this many steps, see [`crate::rewind`].
+    Rewind(u64),
+    /// Runs a single step while paused.
+    StepForward,
+}
+
+/// A [`Simulation`] driven by the loop of an embedding application instead of
+/// a [`SimThread`]: every [`tick`](Self::tick) runs as many fixed steps as the
+/// time passed asks for, carrying the rest over to the next tick.
+pub struct TickedSimulation {
+    pub sim: Simulation,
+    timestep: FixedTimestep,
+}
+
+impl TickedSimulation {
+    pub fn new(sim: Simulation) -> Self {
+        Self {
+            sim,
+            timestep: FixedTimestep::new(TIME_STEP),
+        }
+    }
+
+    /// Advances by `real_dt` seconds of wall clock time and returns how many
+    /// seconds were simulated, a whole number of steps. Falls behind instead
+    /// of running more than [`MAX_STEPS_PER_FRAME`](crate::timestep::MAX_STEPS_PER_FRAME)
+    /// steps, negative or NaN times count as zero. The
+    /// [particles](Simulation::particles) show the last step when it returns.
+    pub fn tick(&mut self, real_dt: f32) -> Result<f32, backend::Error> {
+        let elapsed = Duration::try_from_secs_f32(real_dt.max(0.0)).unwrap_or(Duration::ZERO);
+        let steps = self.timestep.accumulate(elapsed);
+        for _ in 0..steps {
+            self.sim.step()?;
+        }
+        if steps > 0 {
+            // nothing else waits for the read, the next tick may not step
+            self.sim.wait_read()?;
+        }
+        Ok(steps as f32 * self.timestep.dt())
+    }
+
+    /// How far the time carried over is into the next step, in `[0, 1)`, to
+    /// [interpolate](crate::timestep::interpolate) positions for rendering.
+    pub fn alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+}
+
+/// The two most recent simulation states, as published by the [`SimThread`].
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub step: u64,
+    pub previous: Vec<Instance>,
+    pub current: Vec<Instance>,
+    /// Stable [ids](crate::ids) of `current`.
+    pub ids: Vec<Option<u32>>,
+    /// What the backend runs with, for the shaders.
+    pub params: SimParams,
+    pub colors: Vec<u32>,
+    /// Foam, spray and bubbles at `current`, empty unless enabled in the [`Options`].
+    pub diffuse: Vec<DiffuseInstance>,
+    /// Free surface of the primary backend at `current`, empty unless enabled
+    /// in the [`Options`].
+    pub surface: Vec<Polyline>,
+    /// Center lines of the paddle blades at `current`.
+    pub blades: Vec<[[f32; 2]; 2]>,
+    /// Streamlines of the primary backend at `current`, empty unless toggled on.
+    pub streamlines: Vec<Vec<[f32; 2]>>,
+    /// Vorticity of the primary backend at `current`, unless toggled off.
+    pub vorticity: Option<ScalarGrid>,
+    /// The [heatmap](Probes::heatmap) of the primary backend at `current`
+    /// and what it shows, unless toggled off.
+    pub heatmap: Option<(Heatmap, ScalarGrid)>,
+    /// The [speed](VelocityField::speed) for the debug window, if there is one.
+    pub speed: Option<ScalarGrid>,
+    /// Measured over the steps since the last frame, while the plots are on.
+    pub metrics: Option<Metrics>,
+    /// Averaged over the steps since the last frame, if the backend keeps track.
+    pub timings: Option<StepTimings>,
+    pub stats: ParticleStats,
+    /// Of `current`, with `--histograms`.
+    pub histograms: Option<Histograms>,
+    /// When `current` was produced.
+    pub time: Instant,
+}
+
+impl Frame {
+    /// Interpolation factor between `previous` and `current` at `now`, assuming
+    /// the next state arrives one time step after this one.
+    pub fn alpha(&self, now: Instant) -> f32 {
+        (now.saturating_duration_since(self.time).as_secs_f32() / TIME_STEP).min(1.0)
+    }
+
+    /// Seconds the next state is overdue at `now`, up to one time step, to
+    /// [extrapolate](crate::timestep::extrapolate) `current` by while the
+    /// simulation can't keep up with the display.
+    pub fn overdue(&self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.time).as_secs_f32();
+        (elapsed - TIME_STEP).clamp(0.0, TIME_STEP)
+    }
+
+    /// The particles to draw at `now`, between `previous` and `current` and
+    /// past `current` by [`overdue`](Self::overdue) with `extrapolate`.
+    pub fn instances_at(&self, now: Instant, extrapolate: bool) -> Vec<Instance> {
+        match self.overdue(now) {
+            overdue if extrapolate && overdue > 0.0 => {
+                timestep::extrapolate(&self.current, overdue)
+            }
+            _ => timestep::interpolate(&self.previous, &self.current, self.alpha(now)),
+        }
+    }
+}
+
+/// A single slot holding the newest value. Writing replaces whatever the
+/// reader hasn't picked up yet, so a slow reader never holds up the writer.
+#[derive(Debug, Default)]
+pub struct Mailbox<T> {
+    slot: Mutex<Option<T>>,
+}
+
+impl<T> Mailbox<T> {
+    pub fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+        }
+    }
+
+    pub fn put(&self, value: T) {
+        *self.slot.lock().unwrap() = Some(value);
+    }
+
+    pub fn take(&self) -> Option<T> {
+        self.slot.lock().unwrap().take()
+    }
+}
+
+/// The visualizations derived from the [`VelocityField`], each toggled at runtime.
+struct FlowViews {
+    field: VelocityField,
+    streamline_params: StreamlineParams,
+    streamlines: bool,
+    vorticity: bool,
+    /// For the debug window, never toggled.
+    speed: bool,
+}
+
+impl FlowViews {
+    fn new(options: &Options) -> Self {
+        Self {
+            field: VelocityField::new(64, 0.05),
+            streamline_params: StreamlineParams::default(),
+            streamlines: options.streamlines,
+            vorticity: options.vorticity,
+            speed: options.debug_window,
+        }
+    }
+
+    /// Interpolates the velocity field, if any of the views is on.
+    fn build(&mut self, particles: &[Instance]) {
+        if self.streamlines || self.vorticity || self.speed {
+            self.field.build(particles);
+        }
+    }
+
+    fn streamlines(&self) -> Vec<Vec<[f32; 2]>> {
+        match self.streamlines {
+            true => streamlines::trace(&self.field, &self.streamline_params),
+            false => vec![],
+        }
+    }
+
+    fn vorticity(&self) -> Option<ScalarGrid> {
+        self.vorticity.then(|| self.field.vorticity())
+    }
+
+    fn speed(&self) -> Option<ScalarGrid> {
+        self.speed.then(|| self.field.speed())
+    }
+}
+
+pub struct SimThread {
+    mailbox: Arc<Mailbox<Frame>>,
+    stop: Arc<AtomicBool>,
+    commands: mpsc::Sender<Command>,
+    handle: Option<thread::JoinHandle<Result<(), backend::Error>>>,
+    trace: Option<SharedTrace>,
+}
+
+impl SimThread {
+    /// Creates the simulation on a new thread and starts stepping it.
+    pub fn spawn(options: Options) -> Self {
+        let mailbox = Arc::new(Mailbox::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let (commands, receiver) = mpsc::channel();
+        let trace = options.trace.as_ref().map(|_| Trace::shared());
+
+        let handle = thread::Builder::new()
+            .name("simulation".into())
+            .spawn({
+                let mailbox = mailbox.clone();
+                let stop = stop.clone();
+                let trace = trace.clone();
+                move || Self::run(options, &mailbox, &stop, &receiver, trace.as_ref())
+            })
+            .expect("could not spawn simulation thread");
+
+        Self {
+            mailbox,
+            stop,
+            commands,
+            handle: Some(handle),
+            trace,
+        }
+    }
+
+    /// The timeline the thread writes out with `--trace`, for the window to
+    /// add its render passes to.
+    pub fn trace(&self) -> Option<SharedTrace> {
+        self.trace.clone()
+    }
+
+    fn run(
+        options: Options,
+        mailbox: &Mailbox<Frame>,
+        stop: &AtomicBool,
+        commands: &mpsc::Receiver<Command>,
+        trace: Option<&SharedTrace>,
+    ) -> Result<(), backend::Error> {
+        // only scripts edit the scene
+        #[cfg_attr(not(feature = "scripting"), allow(unused_mut))]
+        let mut scene = options.scene()?;
+        let mut sim = Simulation::new(&scene, &options)?;
+        #[cfg(feature = "scripting")]
+        let mut script = match &options.script {
+            Some(path) => Some(crate::script::Script::load(path)?),
+            None => None,
+        };
+        let mut mix = options.mix.map(|rate| {
+            let params = MixParams {
+                rate,
+                ..MixParams::default()
+            };
+            ColorMix::new(params, &scene)
+        });
+        let mut dye = options
+            .dye
+            .then(|| DyeField::new(DyeParams::default(), sim.particles()));
+        let mut diffuse = options
+            .diffuse
+            .then(|| DiffuseSystem::new(DiffuseParams::default()));
+        let mut surface = options
+            .surface
+            .then(|| SurfaceExtractor::new(SurfaceParams::default()));
+        let mut groups = (!scene.groups.is_empty()).then(|| Groups::new(&scene, sim.particles()));
+        let mut triggers = Triggers::new(scene.triggers.clone(), sim.particles());
+        triggers.on_change(|trigger, counts| {
+            println!(
+                "{}: {} entered, {} left, {} inside",
+                trigger.name, counts.entered, counts.left, counts.inside
+            )
+        });
+        let probe_params = ProbeParams::default();
+        let solids = SolidParticles::sample(
+            &scene.obstacles,
+            probe_params.rest_spacing,
+            probe_params.radius,
+            probe_params.rest_spacing,
+        );
+        let mut probes = Probes::new(probe_params, scene.probes.clone())
+            .with_boundaries(scene.boundaries)
+            .with_solids(solids);
+        let mut flow = FlowViews::new(&options);
+        // the drag the damping last set
+        let mut drag = 0.0;
+        let heatmap_kind = options.heatmap.unwrap_or_default();
+        let mut heatmap = options.heatmap.is_some();
+        let mut plots = options.plots;
+        let mut paused = false;
+        let mut timestep = FixedTimestep::new(TIME_STEP);
+        let mut step = 0;
+        let mut rewind =
+            (options.rewind_budget > 0).then(|| RewindBuffer::new(options.rewind_budget));
+        // steps asked for with `StepForward` while paused
+        let mut single_steps = 0;
+        // a frame is due to show where a rewind went, even without steps
+        let mut rewound = false;
+        let mut recorder = match &options.record {
+            Some(path) => Some(
+                Recorder::create(path)
+                    .map_err(|err| format!("could not create {}: {err}", path.display()))?,
+            ),
+            None => None,
+        };
+        let mut input_recorder = match &options.record_input {
+            Some(path) => Some(
+                InputRecorder::create(path)
+                    .map_err(|err| format!("could not create {}: {err}", path.display()))?,
+            ),
+            None => None,
+        };
+        let mut replay = match &options.replay_input {
+            Some(path) => Some(Replay::load(path)?),
+            None => None,
+        };
+        let mut report = options.report.as_ref().map(|_| Report::default());
+        let mut measurement = options.measure.map(Measurement::new);
+        // published so far, what the measurement warms up over
+        let mut frames = 0;
+        let mut timelapse = match &options.timelapse {
+            Some(dir) => Some(
+                Timelapse::create(dir, options.timelapse_every, options.timelapse_size)
+                    .map_err(|err| format!("could not create {}: {err}", dir.display()))?,
+            ),
+            None => None,
+        };
+
+        if options.verify && sim.grid_cells().is_none() {
+            eprintln!(
+                "--verify needs a backend that keeps its grid, not wcsph or the fused OpenCL kernel"
+            );
+        }
+        let params = SimParams::for_scene(&scene, &options.config);
+        let current = sim.instances();
+        flow.build(sim.particles());
+        let frame = Frame {
+            step,
+            previous: current.clone(),
+            current,
+            ids: sim.ids().ids().to_vec(),
+            params,
+            colors: Self::colors(
+                &sim,
+                &scene,
+                options.coloring,
+                dye.as_ref(),
+                mix.as_ref(),
+                groups.as_ref(),
+            ),
+            diffuse: vec![],
+            surface: surface
+                .as_mut()
+                .map_or(vec![], |surface| surface.extract(sim.particles())),
+            blades: Self::blades(&scene, 0.0),
+            streamlines: flow.streamlines(),
+            vorticity: flow.vorticity(),
+            heatmap: heatmap.then(|| {
+                let grid = probes.heatmap(sim.particles(), heatmap_kind, HEATMAP_RESOLUTION);
+                (heatmap_kind, grid)
+            }),
+            speed: flow.speed(),
+            metrics: None,
+            timings: sim.timings(),
+            stats: sim.stats()?,
+            histograms: match options.histograms {
+                true => Some(Self::histograms(&mut sim, &mut probes)?),
+                false => None,
+            },
+            time: Instant::now(),
+        };
+        Self::damp(&mut sim, &options.damping, &frame.stats, &mut drag)?;
+        if let Some(recorder) = &mut recorder {
+            recorder.write(frame.step, &frame.current, &frame.colors, &frame.ids)?;
+        }
+        if let Some(timelapse) = &mut timelapse {
+            timelapse.capture(frame.step, &frame.current, &frame.colors)?;
+        }
+        if let Some(rewind) = &mut rewind {
+            rewind.push(frame.step, &frame.current);
+        }
+        mailbox.put(frame);
+
+        while !stop.load(Ordering::Relaxed) {
+            for command in commands.try_iter() {
+                if let Some(input_recorder) = &mut input_recorder {
+                    input_recorder.write(step, &command)?;
+                }
+                match command {
+                    Command::Rewind(steps) => {
+                        let Some((at, particles)) = rewind.as_mut().and_then(|r| r.rewind(steps))
+                        else {
+                            continue;
+                        };
+                        sim.restore(particles, at)?;
+                        step = at;
+                        paused = true;
+                        rewound = true;
+                    }
+                    Command::StepForward => single_steps += 1,
+                    command => Self::apply(
+                        command,
+                        &mut sim,
+                        &mut dye,
+                        &mut flow,
+                        &mut heatmap,
+                        &mut plots,
+                        &mut paused,
+                    )?,
+                }
+            }
+            let steps = if paused {
+                timestep.reset();
+                if single_steps == 0 && !rewound {
+                    thread::sleep(PAUSED_POLL);
+                    continue;
+                }
+                std::mem::take(&mut single_steps)
+            } else if options.headless {
+                // without a window to keep up with, there is no point in waiting
+                1
+            } else {
+                timestep.advance(Instant::now())
+            };
+            let steps = match options.steps {
+                Some(last) if step >= last => break,
+                Some(last) => steps.min(u32::try_from(last - step).unwrap_or(u32::MAX)),
+                None => steps,
+            };
+            if steps == 0 && !rewound {
+                thread::sleep(timestep.until_next_step());
+                continue;
+            }
+            rewound = false;
+
+            let mut previous = vec![];
+            let mut step_time = 0.0;
+            let mut timings: Option<StepTimings> = None;
+            for _ in 0..steps {
+                previous = sim.instances();
+                if let Some(replay) = &mut replay {
+                    for command in replay.due(step) {
+                        Self::apply(
+                            command,
+                            &mut sim,
+                            &mut dye,
+                            &mut flow,
+                            &mut heatmap,
+                            &mut plots,
+                            &mut paused,
+                        )?;
+                    }
+                }
+                #[cfg(feature = "scripting")]
+                if let Some(script) = &mut script {
+                    for edit in script.update(step as f32 * TIME_STEP)? {
+                        scene.apply(&edit)?;
+                        sim.edit(&edit)?;
+                    }
+                }
+                let started = Instant::now();
+                sim.step()?;
+                let elapsed = started.elapsed().as_secs_f32();
+                step_time += elapsed;
+                if let Some(measurement) = &mut measurement {
+                    measurement.record(frames, elapsed);
+                }
+                if let Some(trace) = trace {
+                    let now = Instant::now();
+                    let mut trace = trace.lock().unwrap();
+                    trace.add_host("step", started, now, step);
+                    trace.add_device(Track::Simulation, sim.device_spans(), step, now);
+                }
+                // what follows every step on the host waits for its positions,
+                // otherwise the read lands while the thread goes on and the
+                // frame shows the last step that arrived
+                let follows_steps = rewind.is_some()
+                    || trace.is_some()
+                    || !probes.is_empty()
+                    || !triggers.is_empty()
+                    || diffuse.is_some()
+                    || dye.is_some()
+                    || mix.is_some()
+                    || groups.is_some();
+                if follows_steps {
+                    sim.wait_read()?;
+                }
+                if let Some(last) = sim.timings() {
+                    timings.get_or_insert_with(StepTimings::default).add(&last);
+                }
+                if let Some(diffuse) = &mut diffuse {
+                    diffuse.step(sim.particles(), TIME_STEP);
+                }
+                if let Some(dye) = &mut dye {
+                    dye.step(sim.particles(), &scene.boundaries, TIME_STEP);
+                }
+                if let Some(mix) = &mut mix {
+                    mix.step(sim.particles(), TIME_STEP);
+                }
+                if let Some(groups) = &mut groups {
+                    groups.update(sim.particles());
+                }
+                if !triggers.is_empty() {
+                    triggers.update(sim.particles());
+                    #[cfg(feature = "scripting")]
+                    if let Some(script) = &mut script {
+                        for (trigger, counts) in triggers.triggers().iter().zip(triggers.counts()) {
+                            if counts.changed() {
+                                script.trigger(trigger, counts)?;
+                            }
+                        }
+                    }
+                }
+                step += 1;
+                if !probes.is_empty() {
+                    probes.record(step as f32 * TIME_STEP, sim.particles());
+                }
+                if let Some(rewind) = &mut rewind {
+                    rewind.push(step, &sim.instances());
+                }
+            }
+
+            // at most one step behind, as every read waits for the one before
+            let shown = match sim.read_complete()? {
+                true => step,
+                false => step - 1,
+            };
+            flow.build(sim.particles());
+            if options.verify {
+                Self::verify(&sim, shown, params.particle_radius);
+            }
+            let stats = sim.stats()?;
+            Self::damp(&mut sim, &options.damping, &stats, &mut drag)?;
+            let density_error = match plots || report.is_some() {
+                true => probes.density_error(sim.particles()),
+                false => 0.0,
+            };
+            if let Some(report) = &mut report {
+                report.record(
+                    step,
+                    steps,
+                    &stats,
+                    density_error,
+                    step_time,
+                    timings.as_ref(),
+                );
+            }
+            let metrics = plots.then(|| Metrics {
+                kinetic_energy: stats.kinetic_energy,
+                density_error,
+                particle_count: stats.count as f32,
+                step_time: step_time / steps.max(1) as f32,
+            });
+            let frame = Frame {
+                step: shown,
+                previous,
+                current: sim.instances(),
+                ids: sim.ids().ids().to_vec(),
+                params,
+                colors: Self::colors(
+                    &sim,
+                    &scene,
+                    options.coloring,
+                    dye.as_ref(),
+                    mix.as_ref(),
+                    groups.as_ref(),
+                ),
+                diffuse: diffuse.as_ref().map_or(vec![], DiffuseSystem::instances),
+                surface: surface
+                    .as_mut()
+                    .map_or(vec![], |surface| surface.extract(sim.particles())),
+                blades: Self::blades(&scene, shown as f32 * TIME_STEP),
+                streamlines: flow.streamlines(),
+                vorticity: flow.vorticity(),
+                heatmap: heatmap.then(|| {
+                    let grid = probes.heatmap(sim.particles(), heatmap_kind, HEATMAP_RESOLUTION);
+                    (heatmap_kind, grid)
+                }),
+                speed: flow.speed(),
+                metrics,
+                timings: timings.map(|mut timings| {
+                    timings.scale(1.0 / steps as f32);
+                    timings
+                }),
+                stats,
+                histograms: match options.histograms {
+                    true => Some(Self::histograms(&mut sim, &mut probes)?),
+                    false => None,
+                },
+                time: Instant::now(),
+            };
+            if let Some(recorder) = &mut recorder {
+                recorder.write(frame.step, &frame.current, &frame.colors, &frame.ids)?;
+            }
+            if let Some(timelapse) = &mut timelapse {
+                timelapse.capture(frame.step, &frame.current, &frame.colors)?;
+            }
+            mailbox.put(frame);
+            frames += 1;
+        }
+
+        if let Some(measurement) = &measurement {
+            match measurement.summary() {
+                Some(summary) => println!("steps: {summary}"),
+                None => println!(
+                    "steps: none measured after {} warmup frames",
+                    measurement.warmup
+                ),
+            }
+        }
+        if let Some(recorder) = &mut recorder {
+            recorder.flush()?;
+        }
+        if let Some(timelapse) = &mut timelapse {
+            timelapse.flush()?;
+        }
+
+        if let Some(path) = &options.probe_csv {
+            let file = std::fs::File::create(path)
+                .map_err(|err| format!("could not create {}: {err}", path.display()))?;
+            probes.write_csv(std::io::BufWriter::new(file))?;
+        }
+        if let (Some(path), Some(report)) = (&options.report, &report) {
+            let file = std::fs::File::create(path)
+                .map_err(|err| format!("could not create {}: {err}", path.display()))?;
+            report.write_json(
+                std::io::BufWriter::new(file),
+                &sim.metadata(&options.config),
+            )?;
+        }
+        if let (Some(path), Some(trace)) = (&options.trace, trace) {
+            let file = std::fs::File::create(path)
+                .map_err(|err| format!("could not create {}: {err}", path.display()))?;
+            trace
+                .lock()
+                .unwrap()
+                .write_json(std::io::BufWriter::new(file))?;
+        }
+        Ok(())
+    }
+
+    /// Carries out a command from the window or a [replay](crate::replay).
+    fn apply(
+        command: Command,
+        sim: &mut Simulation,
+        dye: &mut Option<DyeField>,
+        flow: &mut FlowViews,
+        heatmap: &mut bool,
+        plots: &mut bool,
+        paused: &mut bool,
+    ) -> Result<(), backend::Error> {
+        match command {
+            Command::Paint { pos, radius, value } => {
+                if let Some(dye) = dye {
+                    dye.paint(sim.particles(), pos, radius, value);
+                }
+            }
+            Command::PaintViscosity {
+                pos,
+                radius,
+                viscosity,
+            } => sim.edit(&SceneEdit::PaintViscosity {
+                center: pos,
+                radius,
+                viscosity,
+            })?,
+            Command::ToggleStreamlines => flow.streamlines = !flow.streamlines,
+            Command::ToggleVorticity => flow.vorticity = !flow.vorticity,
+            Command::ToggleHeatmap => *heatmap = !*heatmap,
+            Command::TogglePlots => *plots = !*plots,
+            Command::SetPaused(pause) => *paused = pause,
+            // need the simulation itself, the thread loop takes care of these
+            Command::Rewind(_) | Command::StepForward => {}
+        }
+        Ok(())
+    }
+
+    /// Passes the drag `damping` asks for at `stats` on to the backends, if
+    /// it changed from the last one.
+    fn damp(
+        sim: &mut Simulation,
+        damping: &Damping,
+        stats: &ParticleStats,
+        last: &mut f32,
+    ) -> Result<(), backend::Error> {
+        if !damping.is_active() {
+            return Ok(());
+        }
+        let drag = damping.drag(stats);
+        if drag != *last {
+            sim.edit(&SceneEdit::Damping(drag))?;
+            *last = drag;
+        }
+        Ok(())
+    }
+
+    /// Reports the neighbors the grid missed for a sample of particles.
+    fn verify(sim: &Simulation, step: u64, radius: f32) {
+        let Some(cells) = sim.grid_cells() else {
+            return;
+        };
+        let particles = sim.particles();
+        let tree = KdTree::from_particles(particles);
+        let sample = verify::sample(particles.len(), step as u32);
+        for missed in verify::check(particles, &cells, &tree, sample, radius) {
+            println!("step {step}: {missed}");
+        }
+    }
+
+    fn histograms(sim: &mut Simulation, probes: &mut Probes) -> Result<Histograms, backend::Error> {
+        Ok(Histograms {
+            speed: sim.speed_histogram()?,
+            density_error: Histogram::from_values(
+                histogram::DENSITY_ERROR_RANGE,
+                probes.density_errors(sim.particles()),
+            ),
+        })
+    }
+
+    fn blades(scene: &Scene, time: f32) -> Vec<[[f32; 2]; 2]> {
+        scene
+            .paddles
+            .iter()
+            .flat_map(|paddle| paddle.at(time).segments().collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// The dye if there is one, then the color mix, otherwise `coloring`, by
+    /// default the phases if there are several, faded out towards the end of each particle's
+    /// lifetime. Groups with a color paint over that. When comparing
+    /// backends, the colors tell the backends apart instead.
+    fn colors(
+        sim: &Simulation,
+        scene: &Scene,
+        coloring: Option<Coloring>,
+        dye: Option<&DyeField>,
+        mix: Option<&ColorMix>,
+        groups: Option<&Groups>,
+    ) -> Vec<u32> {
+        let Simulation::Single(backend) = sim else {
+            return sim.colors();
+        };
+
+        let coloring = coloring.unwrap_or(match scene.phases.len() > 1 {
+            true => Coloring::Phase,
+            false => Coloring::Velocity,
+        });
+        let particles = backend.particles();
+        let mut colors = match (dye, mix, coloring) {
+            (Some(dye), _, _) => dye.colors(particles),
+            (None, Some(mix), _) => mix.colors(particles),
+            (None, None, Coloring::Velocity) => sim.colors(),
+            (None, None, Coloring::Phase) => scene.phase_colors(particles),
+            (None, None, Coloring::Temperature) => {
+                scene.thermal.colors(particles, backend.temperatures())
+            }
+            (None, None, Coloring::Age) => backend.ages().colors(particles),
+        };
+        if let Some(groups) = groups {
+            groups.apply_colors(&mut colors);
+        }
+        backend.ages().fade_colors(&mut colors);
+        colors
+    }
+
+    /// Queues `command` for the simulation thread.
+    pub fn send(&self, command: Command) {
+        // if the thread is gone, `check` reports why
+        let _ = self.commands.send(command);
+    }
+
+    /// The newest frame since the last call, if there is one.
+    pub fn latest(&self) -> Option<Frame> {
+        self.mailbox.take()
+    }
+
+    /// Whether the thread has stopped, after [`Options::steps`] or an error.
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_none_or(|handle| handle.is_finished())
+    }
+
+    /// Waits for the thread to stop on its own.
+    pub fn join(mut self) -> Result<(), backend::Error> {
+        match self.handle.take().map(|handle| handle.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("simulation thread panicked".into()),
+            None => Ok(()),
+        }
+    }
+
+    /// If the thread has stopped on its own, joins it and returns why.
+    pub fn check(&mut self) -> Result<(), backend::Error> {
+        match self.handle.take_if(|handle| handle.is_finished()) {
+            Some(handle) => match handle.join() {
+                Ok(Ok(())) => Err("simulation thread stopped".into()),
+                Ok(Err(err)) => Err(err),
+                Err(_) => Err("simulation thread panicked".into()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SimThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
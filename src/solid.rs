@@ -0,0 +1,96 @@
+//! Static particles sampled along obstacle outlines, standing in for the
+//! solid in kernel sums, after Akinci et al. 2012, "Versatile rigid-fluid
+//! coupling for incompressible SPH".
+//!
+//! The samples sit on the outline only, so each one counts for as much
+//! fluid as the gap it covers: its volume is the inverse of the kernel sum
+//! over the samples around it, which evens out uneven spacing and corners.
+//! That volume is of a band around the outline, half of which is on the
+//! fluid side, so a sample counts for half of it.
+
+use crate::geometry::Polygon;
+use crate::neighbors::CellList;
+use crate::probe::{kernel, rest_weight};
+use crate::sim::Instance;
+
+pub struct SolidParticles {
+    /// Standing still, as a neighbor search over them wants [`Instance`]s.
+    particles: Vec<Instance>,
+    /// How many fluid particles at rest each sample counts as.
+    volumes: Vec<f32>,
+    cells: CellList,
+}
+
+impl SolidParticles {
+    /// Samples the outlines every `spacing`, with the volumes for kernel sums
+    /// over `radius` in fluid of rest spacing `rest_spacing`.
+    pub fn sample(polygons: &[Polygon], spacing: f32, radius: f32, rest_spacing: f32) -> Self {
+        let mut particles = vec![];
+        for polygon in polygons {
+            for (a, b) in polygon.edges() {
+                let d = [b[0] - a[0], b[1] - a[1]];
+                let len = (d[0] * d[0] + d[1] * d[1]).sqrt();
+                let n = (len / spacing).ceil().max(1.0) as usize;
+                // the end point is the start of the next edge
+                particles.extend((0..n).map(|i| {
+                    let t = i as f32 / n as f32;
+                    Instance {
+                        pos: [a[0] + d[0] * t, a[1] + d[1] * t],
+                        vel: [0.0; 2],
+                    }
+                }));
+            }
+        }
+
+        let mut cells = CellList::new(radius);
+        cells.build(&particles);
+        let rest = rest_weight(radius, rest_spacing);
+        let volumes = particles
+            .iter()
+            .map(|p| {
+                let mut sum = 0.0;
+                cells
+                    .for_each_neighbor(&particles, p.pos, |_, _, dist| sum += kernel(dist, radius));
+                0.5 * rest / sum
+            })
+            .collect();
+
+        Self {
+            particles,
+            volumes,
+            cells,
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = [f32; 2]> + '_ {
+        self.particles.iter().map(|p| p.pos)
+    }
+
+    pub fn volumes(&self) -> &[f32] {
+        &self.volumes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Calls `f` with every sample within the radius around `pos`, its offset
+    /// from `pos`, its distance and its volume.
+    pub fn for_each_near(&self, pos: [f32; 2], mut f: impl FnMut([f32; 2], f32, f32)) {
+        self.cells
+            .for_each_neighbor(&self.particles, pos, |id, d, dist| {
+                f(d, dist, self.volumes[id])
+            });
+    }
+
+    /// Kernel sum of the samples around `pos`, weighted by their volumes, in
+    /// the units of a kernel sum over fluid particles.
+    pub fn weight_at(&self, pos: [f32; 2]) -> f32 {
+        let mut weight = 0.0;
+        self.cells
+            .for_each_neighbor(&self.particles, pos, |id, _, dist| {
+                weight += kernel(dist, self.cells.radius()) * self.volumes[id];
+            });
+        weight
+    }
+}
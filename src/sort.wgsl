@@ -0,0 +1,91 @@
+// sorts the particles by a key before they are drawn, so the ones with the
+// larger key end up on top: keys pairs each with its index, a bitonic sort
+// orders the pairs and scatter copies the particles over in that order.
+
+struct Instance {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+}
+
+struct SortParams {
+    n_instances: u32,
+    // what to sort by, as `SortKey` in sim.rs
+    key: u32,
+    // the bitonic merge step, for `bitonic`
+    j: u32,
+    k: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> instances: array<Instance>;
+@group(0) @binding(1)
+var<storage, read> colors: array<u32>;
+@group(0) @binding(2)
+var<storage, read_write> keys: array<vec2<u32>>;
+@group(0) @binding(3)
+var<storage, read_write> sorted_instances: array<Instance>;
+@group(0) @binding(4)
+var<storage, read_write> sorted_colors: array<u32>;
+@group(0) @binding(5)
+var<uniform> params: SortParams;
+
+const KEY_Y: u32 = 0u;
+const KEY_SPEED: u32 = 1u;
+
+// maps floats to integers that sort the same way
+fn ordered(x: f32) -> u32 {
+    let bits = bitcast<u32>(x);
+    if (bits & 0x80000000u) != 0u {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+@compute @workgroup_size(256)
+fn fill_keys(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= arrayLength(&keys) {
+        return;
+    }
+    // padding sorts last
+    var key = 0xffffffffu;
+    if i < params.n_instances {
+        let instance = instances[i];
+        switch params.key {
+            case KEY_SPEED: {
+                key = ordered(length(instance.velocity));
+            }
+            default: {
+                key = ordered(instance.position.y);
+            }
+        }
+    }
+    keys[i] = vec2(key, i);
+}
+
+@compute @workgroup_size(256)
+fn bitonic(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let l = i ^ params.j;
+    if i >= arrayLength(&keys) || l <= i {
+        return;
+    }
+    let a = keys[i];
+    let b = keys[l];
+    let ascending = (i & params.k) == 0u;
+    if (a.x > b.x) == ascending {
+        keys[i] = b;
+        keys[l] = a;
+    }
+}
+
+@compute @workgroup_size(256)
+fn scatter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.n_instances {
+        return;
+    }
+    let source = keys[i].y;
+    sorted_instances[i] = instances[source];
+    sorted_colors[i] = colors[source];
+}
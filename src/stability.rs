@@ -0,0 +1,103 @@
+//! Guards that keep bad parameter choices from blowing the simulation up.
+//!
+//! Velocities are clamped every step to [`Config::max_speed`], and to the
+//! speed that moves a particle [`Config::max_displacement`] within the step.
+//! With [`Config::brake`] on, a [`Brake`] watches how often that happens and
+//! splits the steps into smaller ones while the clamping keeps kicking in,
+//! easing off again once things have calmed down.
+//!
+//! Before that, [`drag`] damps them, for calming a demo down or standing in
+//! for the air slowing spray.
+
+use crate::backend::Config;
+
+/// Fastest a particle may move in a step of `dt`, infinite without limits.
+pub fn speed_limit(config: &Config, dt: f32) -> f32 {
+    config.max_speed.min(config.max_displacement / dt)
+}
+
+/// Acceleration of the drag on a particle with `vel` over a step of `dt`,
+/// slowing it by `linear + quadratic * speed` per unit of velocity. Taken
+/// implicitly, so however strong the drag, it stops a particle rather than
+/// turning it around. Mirrors `drag_acceleration` in `sorting.ocl`.
+pub fn drag(vel: [f32; 2], linear: f32, quadratic: f32, dt: f32) -> [f32; 2] {
+    let rate = linear + quadratic * vel[0].hypot(vel[1]);
+    if rate <= 0.0 {
+        return [0.0, 0.0];
+    }
+    let scale = rate / (1.0 + rate * dt);
+    [-scale * vel[0], -scale * vel[1]]
+}
+
+/// Scales `vel` down to `limit` if it is faster. Returns whether it was.
+pub fn clamp_velocity(vel: &mut [f32; 2], limit: f32) -> bool {
+    let speed_sq = vel[0] * vel[0] + vel[1] * vel[1];
+    if speed_sq <= limit * limit {
+        return false;
+    }
+    let scale = limit / speed_sq.sqrt();
+    vel[0] *= scale;
+    vel[1] *= scale;
+    true
+}
+
+/// Halves the step while clamping keeps triggering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brake {
+    /// Share of the particles that has to be clamped for a step to count.
+    pub trigger: f32,
+    /// Counting steps in a row until the step is halved.
+    pub strikes: u32,
+    /// Steps in a row without counting until the step is doubled again.
+    pub calm: u32,
+    /// Smallest step as a fraction of the full one.
+    pub max_substeps: u32,
+    substeps: u32,
+    strike_count: u32,
+    calm_count: u32,
+}
+
+impl Default for Brake {
+    fn default() -> Self {
+        Self {
+            trigger: 0.01,
+            strikes: 3,
+            calm: 120,
+            max_substeps: 16,
+            substeps: 1,
+            strike_count: 0,
+            calm_count: 0,
+        }
+    }
+}
+
+impl Brake {
+    /// Steps to split the next one into.
+    pub fn substeps(&self) -> u32 {
+        self.substeps
+    }
+
+    /// Takes note of a step in which `clamped` of `active` particles were
+    /// clamped. Returns the new number of substeps if it changed.
+    pub fn record(&mut self, clamped: usize, active: usize) -> Option<u32> {
+        let counts = clamped > 0 && clamped as f32 >= self.trigger * active as f32;
+        if counts {
+            self.calm_count = 0;
+            self.strike_count += 1;
+            if self.strike_count >= self.strikes && self.substeps < self.max_substeps {
+                self.strike_count = 0;
+                self.substeps = (self.substeps * 2).min(self.max_substeps);
+                return Some(self.substeps);
+            }
+        } else {
+            self.strike_count = 0;
+            self.calm_count += 1;
+            if self.calm_count >= self.calm && self.substeps > 1 {
+                self.calm_count = 0;
+                self.substeps /= 2;
+                return Some(self.substeps);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,50 @@
+use crate::boundary;
+use crate::sim::Instance;
+
+/// Whole-simulation reductions over the particle velocities.
+///
+/// Mirrors `ParticleStats` in `sorting.ocl`, where the OpenCL backend computes it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ParticleStats {
+    pub max_speed: f32,
+    /// `Σ ½|v|²`, assuming unit mass.
+    pub kinetic_energy: f32,
+    pub speed_sum: f32,
+    pub count: u32,
+}
+
+impl ParticleStats {
+    /// Reduces over the particles that haven't been [removed](boundary::REMOVED).
+    pub fn from_particles(particles: &[Instance]) -> Self {
+        particles.iter().fold(Self::default(), |acc, p| {
+            if boundary::is_removed(p) {
+                return acc;
+            }
+            let speed_sq = p.vel[0] * p.vel[0] + p.vel[1] * p.vel[1];
+            acc.combine(&Self {
+                max_speed: speed_sq.sqrt(),
+                kinetic_energy: 0.5 * speed_sq,
+                speed_sum: speed_sq.sqrt(),
+                count: 1,
+            })
+        })
+    }
+
+    pub fn combine(&self, other: &Self) -> Self {
+        Self {
+            max_speed: self.max_speed.max(other.max_speed),
+            kinetic_energy: self.kinetic_energy + other.kinetic_energy,
+            speed_sum: self.speed_sum + other.speed_sum,
+            count: self.count + other.count,
+        }
+    }
+
+    pub fn mean_speed(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.speed_sum / self.count as f32
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Curves that follow the flow, traced through a [`VelocityField`].
+
+use crate::field::VelocityField;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamlineParams {
+    /// Seeds per axis, on a regular lattice over the domain.
+    pub seeds: u32,
+    /// Length of one integration step.
+    pub step: f32,
+    /// Steps in each direction from the seed.
+    pub max_steps: u32,
+    /// Tracing stops where the flow is slower than this.
+    pub min_speed: f32,
+}
+
+impl Default for StreamlineParams {
+    fn default() -> Self {
+        Self {
+            seeds: 16,
+            step: 0.005,
+            max_steps: 100,
+            min_speed: 1e-3,
+        }
+    }
+}
+
+/// Traces a streamline from every seed that lies in the fluid, both upstream
+/// and downstream. The points of each line are ordered along the flow.
+pub fn trace(field: &VelocityField, params: &StreamlineParams) -> Vec<Vec<[f32; 2]>> {
+    let mut lines = vec![];
+    for y in 0..params.seeds {
+        for x in 0..params.seeds {
+            let seed = [
+                (x as f32 + 0.5) / params.seeds as f32,
+                (y as f32 + 0.5) / params.seeds as f32,
+            ];
+
+            let mut line = follow(field, params, seed, -1.0);
+            line.reverse();
+            line.pop();
+            line.extend(follow(field, params, seed, 1.0));
+            if line.len() >= 2 {
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+/// Flow direction at `pos`, scaled by `sign`, or `None` where tracing stops.
+fn direction(
+    field: &VelocityField,
+    params: &StreamlineParams,
+    pos: [f32; 2],
+    sign: f32,
+) -> Option<[f32; 2]> {
+    let [vx, vy] = field.sample(pos)?;
+    let speed = (vx * vx + vy * vy).sqrt();
+    (speed >= params.min_speed).then(|| [sign * vx / speed, sign * vy / speed])
+}
+
+/// Midpoint steps of fixed length from `start`, including `start` itself.
+fn follow(
+    field: &VelocityField,
+    params: &StreamlineParams,
+    start: [f32; 2],
+    sign: f32,
+) -> Vec<[f32; 2]> {
+    let mut points = vec![start];
+    let mut pos = start;
+    for _ in 0..params.max_steps {
+        let Some(d) = direction(field, params, pos, sign) else {
+            break;
+        };
+        let half = [
+            pos[0] + d[0] * params.step * 0.5,
+            pos[1] + d[1] * params.step * 0.5,
+        ];
+        let Some(d) = direction(field, params, half, sign) else {
+            break;
+        };
+        pos = [pos[0] + d[0] * params.step, pos[1] + d[1] * params.step];
+        points.push(pos);
+    }
+    points
+}
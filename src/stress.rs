@@ -0,0 +1,117 @@
+//! Finding how many particles the current device and settings can simulate
+//! in real time. Started with `--stress`, the particle count doubles until a
+//! step takes longer than `--stress-target`, by default the simulated time of
+//! a step, then the largest count that kept up is reported.
+//!
+//! Every round fills the lower half of the domain with water at a finer
+//! spacing, with all other options applied as usual, and times the steps
+//! without a window, including reading the particles back like the
+//! simulation thread does for every frame.
+
+use crate::backend;
+use crate::options::Options;
+use crate::phase::{FluidBlock, Phase};
+use crate::simulation::Simulation;
+use std::time::Instant;
+
+/// Particles of the first round, about.
+pub const START: usize = 1024;
+/// Stops doubling beyond this many particles even if still fast enough.
+pub const LIMIT: usize = 1 << 24;
+/// Steps run before timing, for caches and lazily built kernels.
+const WARMUP_STEPS: u32 = 10;
+/// Steps averaged over per round.
+const TIMED_STEPS: u32 = 60;
+
+/// Outcome of one round.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Round {
+    pub particles: usize,
+    /// Mean wall clock seconds per step.
+    pub step_time: f32,
+}
+
+/// Measures rounds of `measure(count)` with doubling counts from `start`
+/// until one is slower than `target` seconds per step or the count passes
+/// `limit`. Returns every round measured, the last one within the target
+/// is the result.
+pub fn search<E>(
+    start: usize,
+    limit: usize,
+    target: f32,
+    mut measure: impl FnMut(usize) -> Result<Round, E>,
+) -> Result<Vec<Round>, E> {
+    let mut rounds = vec![];
+    let mut count = start.max(1);
+    while count <= limit {
+        let round = measure(count)?;
+        rounds.push(round);
+        if round.step_time > target {
+            break;
+        }
+        count *= 2;
+    }
+    Ok(rounds)
+}
+
+/// The largest count of `rounds` within `target`.
+pub fn best(rounds: &[Round], target: f32) -> Option<Round> {
+    rounds
+        .iter()
+        .filter(|round| round.step_time <= target)
+        .max_by_key(|round| round.particles)
+        .copied()
+}
+
+/// Runs the rounds, printing each, then the result.
+pub fn run(options: Options) -> Result<(), backend::Error> {
+    let target = options.stress_target;
+    println!("stress test, target {:.2}ms per step", target * 1e3);
+    let rounds = search(START, LIMIT, target, |count| {
+        let round = measure(&options, count)?;
+        println!(
+            "{:>9} particles | {:.3}ms per step",
+            round.particles,
+            round.step_time * 1e3
+        );
+        Ok::<_, backend::Error>(round)
+    })?;
+    match best(&rounds, target) {
+        Some(round) => println!(
+            "up to {} particles in real time ({:.3}ms per step)",
+            round.particles,
+            round.step_time * 1e3
+        ),
+        None => println!("even {START} particles are too slow for the target"),
+    }
+    Ok(())
+}
+
+/// Times the steps of a scene with about `count` particles.
+fn measure(options: &Options, count: usize) -> Result<Round, backend::Error> {
+    let (min, max) = ([0.0, 0.0], [1.0, 0.5]);
+    let area = (max[0] - min[0]) * (max[1] - min[1]);
+    let block = FluidBlock::new(min, max, Phase::WATER).with_spacing((area / count as f32).sqrt());
+    let options = Options {
+        blocks: vec![block],
+        ..options.clone()
+    };
+    let scene = options.scene()?;
+    let mut sim = Simulation::new(&scene, &options)?;
+
+    for _ in 0..WARMUP_STEPS {
+        sim.step()?;
+        sim.wait_read()?;
+        sim.instances();
+    }
+    let started = Instant::now();
+    for _ in 0..TIMED_STEPS {
+        sim.step()?;
+        sim.wait_read()?;
+        sim.instances();
+    }
+    Ok(Round {
+        particles: scene.particles.len(),
+        step_time: started.elapsed().as_secs_f32() / TIMED_STEPS as f32,
+    })
+}
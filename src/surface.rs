@@ -0,0 +1,240 @@
+//! Free surface extraction with marching squares.
+//!
+//! The particles are splatted into a density field sampled on a regular grid,
+//! and the iso-contour of that field is traced into ordered polylines.
+
+use crate::neighbors::CellList;
+use crate::sim::Instance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurfaceParams {
+    /// Field samples per axis.
+    pub resolution: u32,
+    /// Radius of a particle's contribution to the field.
+    pub radius: f32,
+    /// Field value the surface is traced at. A lone particle peaks at 1.
+    pub iso: f32,
+}
+
+impl Default for SurfaceParams {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            radius: 0.03,
+            iso: 0.5,
+        }
+    }
+}
+
+/// A piece of the surface in world coordinates, with the fluid on its left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<[f32; 2]>,
+    /// Whether the last point connects back to the first. Open polylines end
+    /// where the fluid touches the edge of the sampled area.
+    pub closed: bool,
+}
+
+const NONE: u32 = u32::MAX;
+
+/// Segments per marching squares case as pairs of cell edges (bottom, right,
+/// top, left), not yet oriented. Saddles (5 and 10) are resolved separately.
+const CASES: [&[(u8, u8)]; 16] = [
+    &[],
+    &[(3, 0)],
+    &[(0, 1)],
+    &[(3, 1)],
+    &[(1, 2)],
+    &[],
+    &[(0, 2)],
+    &[(3, 2)],
+    &[(2, 3)],
+    &[(0, 2)],
+    &[],
+    &[(1, 2)],
+    &[(1, 3)],
+    &[(0, 1)],
+    &[(3, 0)],
+    &[],
+];
+
+pub struct SurfaceExtractor {
+    params: SurfaceParams,
+    cells: CellList,
+    field: Vec<f32>,
+    /// Crossing position per sample grid edge.
+    points: Vec<[f32; 2]>,
+    /// Edge the contour continues to from each edge.
+    next: Vec<u32>,
+    incoming: Vec<bool>,
+}
+
+impl SurfaceExtractor {
+    pub fn new(params: SurfaceParams) -> Self {
+        let n = params.resolution as usize;
+        let edges = 2 * n * n;
+        Self {
+            cells: CellList::new(params.radius),
+            params,
+            field: vec![0.0; n * n],
+            points: vec![[0.0; 2]; edges],
+            next: vec![NONE; edges],
+            incoming: vec![false; edges],
+        }
+    }
+
+    /// World position of sample `(x, y)`. Samples sit at cell centers, so all
+    /// of them are inside the domain.
+    fn sample_pos(&self, x: usize, y: usize) -> [f32; 2] {
+        let n = self.params.resolution as f32;
+        [(x as f32 + 0.5) / n, (y as f32 + 0.5) / n]
+    }
+
+    fn sample(&self, x: usize, y: usize) -> f32 {
+        self.field[x + y * self.params.resolution as usize]
+    }
+
+    /// Id of the edge from sample `(x, y)` to `(x + 1, y)`.
+    fn horizontal_edge(&self, x: usize, y: usize) -> usize {
+        x + y * self.params.resolution as usize
+    }
+
+    /// Id of the edge from sample `(x, y)` to `(x, y + 1)`.
+    fn vertical_edge(&self, x: usize, y: usize) -> usize {
+        let n = self.params.resolution as usize;
+        n * n + x + y * n
+    }
+
+    /// The surface of `particles`, as ordered polylines.
+    pub fn extract(&mut self, particles: &[Instance]) -> Vec<Polyline> {
+        self.splat(particles);
+        self.next.iter_mut().for_each(|n| *n = NONE);
+        self.incoming.iter_mut().for_each(|i| *i = false);
+
+        let n = self.params.resolution as usize;
+        for y in 0..n.saturating_sub(1) {
+            for x in 0..n.saturating_sub(1) {
+                self.march(x, y);
+            }
+        }
+
+        self.trace()
+    }
+
+    fn splat(&mut self, particles: &[Instance]) {
+        self.cells.build(particles);
+        let n = self.params.resolution as usize;
+        let radius_sq = self.params.radius * self.params.radius;
+
+        for y in 0..n {
+            for x in 0..n {
+                let mut density = 0.0;
+                self.cells
+                    .for_each_neighbor(particles, self.sample_pos(x, y), |_, _, dist| {
+                        let falloff = 1.0 - dist * dist / radius_sq;
+                        density += falloff * falloff * falloff;
+                    });
+                self.field[x + y * n] = density;
+            }
+        }
+    }
+
+    /// Adds the segments of the cell with sample `(x, y)` in its bottom left corner.
+    fn march(&mut self, x: usize, y: usize) {
+        let iso = self.params.iso;
+        // bottom left, bottom right, top right, top left
+        let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+        let values = corners.map(|(cx, cy)| self.sample(cx, cy));
+        let inside = values.map(|v| v >= iso);
+        let case = inside
+            .iter()
+            .enumerate()
+            .fold(0, |case, (i, &inside)| case | (inside as usize) << i);
+
+        let center_inside = values.iter().sum::<f32>() / 4.0 >= iso;
+        let segments: &[(u8, u8)] = match (case, center_inside) {
+            // the inside corners connect through the center
+            (5, true) | (10, false) => &[(0, 1), (2, 3)],
+            (5, false) | (10, true) => &[(3, 0), (1, 2)],
+            _ => CASES[case],
+        };
+
+        let edge_ids = [
+            self.horizontal_edge(x, y),
+            self.vertical_edge(x + 1, y),
+            self.horizontal_edge(x, y + 1),
+            self.vertical_edge(x, y),
+        ];
+        let edge_corners = [(0, 1), (1, 2), (3, 2), (0, 3)];
+
+        let corner_pos = corners.map(|(cx, cy)| self.sample_pos(cx, cy));
+        for &(a, b) in segments {
+            let [pa, pb] = [a, b].map(|e| {
+                let (c0, c1) = edge_corners[e as usize];
+                let t = ((iso - values[c0]) / (values[c1] - values[c0])).clamp(0.0, 1.0);
+                let [p0, p1] = [corner_pos[c0], corner_pos[c1]];
+                [p0[0] + (p1[0] - p0[0]) * t, p0[1] + (p1[1] - p0[1]) * t]
+            });
+
+            // orient the segment so the fluid is on its left, judged by the
+            // corner furthest from it
+            let side =
+                |c: [f32; 2]| (pb[0] - pa[0]) * (c[1] - pa[1]) - (pb[1] - pa[1]) * (c[0] - pa[0]);
+            let furthest = (0..4)
+                .max_by(|&i, &j| {
+                    side(corner_pos[i])
+                        .abs()
+                        .total_cmp(&side(corner_pos[j]).abs())
+                })
+                .unwrap();
+            let (from, to) = match (side(corner_pos[furthest]) > 0.0) == inside[furthest] {
+                true => ((a, pa), (b, pb)),
+                false => ((b, pb), (a, pa)),
+            };
+
+            let from_id = edge_ids[from.0 as usize];
+            let to_id = edge_ids[to.0 as usize];
+            self.points[from_id] = from.1;
+            self.points[to_id] = to.1;
+            self.next[from_id] = to_id as u32;
+            self.incoming[to_id] = true;
+        }
+    }
+
+    /// Follows the links between edges, open chains first, then the loops that remain.
+    fn trace(&mut self) -> Vec<Polyline> {
+        let mut polylines = vec![];
+        let mut visited = vec![false; self.next.len()];
+
+        let starts = (0..self.next.len())
+            .filter(|&e| self.next[e] != NONE && !self.incoming[e])
+            .chain(0..self.next.len())
+            .collect::<Vec<_>>();
+
+        for start in starts {
+            if visited[start] || self.next[start] == NONE {
+                continue;
+            }
+
+            let mut points = vec![];
+            let mut edge = start as u32;
+            let mut closed = false;
+            loop {
+                if edge == NONE {
+                    break;
+                }
+                if visited[edge as usize] {
+                    closed = edge as usize == start;
+                    break;
+                }
+                visited[edge as usize] = true;
+                points.push(self.points[edge as usize]);
+                edge = self.next[edge as usize];
+            }
+
+            polylines.push(Polyline { points, closed });
+        }
+
+        polylines
+    }
+}
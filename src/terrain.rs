@@ -0,0 +1,78 @@
+//! Ground described by a handful of heights across the domain.
+
+/// Heights at evenly spaced x positions, from the left edge to the right edge,
+/// linearly interpolated in between. Particles below the ground are pushed back
+/// on top of it. Fewer than two heights mean there is no terrain.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Heightfield {
+    pub heights: Vec<f32>,
+}
+
+impl Heightfield {
+    pub fn new(heights: Vec<f32>) -> Self {
+        Self { heights }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heights.len() < 2
+    }
+
+    /// Segment below `x` and how far along it `x` is.
+    fn segment(&self, x: f32) -> (usize, f32) {
+        let t = x.clamp(0.0, 1.0) * (self.heights.len() - 1) as f32;
+        let i = (t as usize).min(self.heights.len() - 2);
+        (i, t - i as f32)
+    }
+
+    /// Ground height at `x`, `None` without terrain.
+    pub fn height_at(&self, x: f32) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+        let (i, f) = self.segment(x);
+        Some(self.heights[i] + (self.heights[i + 1] - self.heights[i]) * f)
+    }
+
+    /// Moves a particle below the ground straight up onto it and removes the
+    /// part of its velocity going into the ground.
+    /// Mirrors `collide_terrain` in `sorting.ocl`.
+    pub fn collide(&self, pos: &mut [f32; 2], vel: &mut [f32; 2]) {
+        let Some(height) = self.height_at(pos[0]) else {
+            return;
+        };
+        if pos[1] >= height {
+            return;
+        }
+
+        let (i, _) = self.segment(pos[0]);
+        let slope = (self.heights[i + 1] - self.heights[i]) * (self.heights.len() - 1) as f32;
+        let len = (slope * slope + 1.0).sqrt();
+        let normal = [-slope / len, 1.0 / len];
+
+        pos[1] = height;
+        let into = vel[0] * normal[0] + vel[1] * normal[1];
+        if into < 0.0 {
+            vel[0] -= into * normal[0];
+            vel[1] -= into * normal[1];
+        }
+    }
+}
+
+impl std::str::FromStr for Heightfield {
+    type Err = String;
+
+    /// Comma separated heights, at least two.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let heights = s
+            .split(',')
+            .map(|h| h.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid terrain `{s}`: {err}"))?;
+        match heights.len() >= 2 {
+            true => Ok(Heightfield::new(heights)),
+            false => Err(format!(
+                "invalid terrain `{s}`, expected at least two heights"
+            )),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Colors of everything around the particles, picked by name or loaded from a
+//! theme file with `--theme`.
+//!
+//! A theme file has one `key = value` per line, `#` starts a comment. Colors
+//! are `rrggbb` or `aarrggbb` in hex, as sRGB:
+//!
+//! ```text
+//! base = light           # built-in theme to start from, `dark` by default
+//! clear = f5f5f0         # behind everything
+//! palette = 1e5ac8, c87814, 788296
+//! lines = 96000000       # streamlines and obstacle outlines
+//! overlay = 5a000000     # the frames of the plots
+//! ```
+//!
+//! The palette recolors the phases of the scene in order, starting over once
+//! it runs out. Empty, the phases keep their own colors.
+
+use crate::scene::Scene;
+use crate::sim;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub clear: u32,
+    pub palette: Vec<u32>,
+    pub lines: u32,
+    pub overlay: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub const BUILT_IN: [&'static str; 3] = ["dark", "light", "contrast"];
+
+    pub fn dark() -> Self {
+        Self {
+            clear: sim::rgba_to_u32(40, 44, 52, 255),
+            palette: vec![],
+            lines: sim::rgba_to_u32(255, 255, 255, 153),
+            overlay: sim::rgba_to_u32(255, 255, 255, 80),
+        }
+    }
+
+    /// For printing and projectors.
+    pub fn light() -> Self {
+        Self {
+            clear: sim::rgba_to_u32(245, 245, 240, 255),
+            palette: vec![
+                sim::rgba_to_u32(30, 90, 200, 255),
+                sim::rgba_to_u32(200, 120, 20, 255),
+                sim::rgba_to_u32(120, 130, 150, 255),
+            ],
+            lines: sim::rgba_to_u32(0, 0, 0, 150),
+            overlay: sim::rgba_to_u32(0, 0, 0, 90),
+        }
+    }
+
+    /// Bright phases on black.
+    pub fn contrast() -> Self {
+        Self {
+            clear: sim::rgba_to_u32(0, 0, 0, 255),
+            palette: vec![
+                sim::rgba_to_u32(0, 200, 255, 255),
+                sim::rgba_to_u32(255, 220, 0, 255),
+                sim::rgba_to_u32(255, 255, 255, 255),
+            ],
+            lines: sim::rgba_to_u32(255, 80, 200, 220),
+            overlay: sim::rgba_to_u32(255, 255, 255, 160),
+        }
+    }
+
+    pub fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "contrast" => Some(Theme::contrast()),
+            _ => None,
+        }
+    }
+
+    /// A built-in theme by name, or else a theme file.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        if let Some(theme) = Theme::built_in(name_or_path) {
+            return Ok(theme);
+        }
+        let path = Path::new(name_or_path);
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            format!(
+                "could not read theme {} ({err}), the built-in ones are {}",
+                path.display(),
+                Theme::BUILT_IN.join(", ")
+            )
+        })?;
+        source.parse()
+    }
+
+    /// Gives the phases of `scene` the colors of the palette.
+    pub fn apply(&self, scene: &mut Scene) {
+        for (phase, &color) in scene.phases.iter_mut().zip(self.palette.iter().cycle()) {
+            phase.color = color;
+        }
+    }
+}
+
+/// `rrggbb` or `aarrggbb`, opaque without alpha.
+pub fn parse_color(s: &str) -> Result<u32, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let invalid = || format!("invalid color `{s}`, expected rrggbb or aarrggbb");
+    let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+    match hex.len() {
+        6 => Ok(0xff00_0000 | value),
+        8 => Ok(value),
+        _ => Err(invalid()),
+    }
+}
+
+/// Linear RGB and alpha of a packed sRGB color, as shaders blend in.
+pub fn to_linear(color: u32) -> [f32; 4] {
+    let [a, r, g, b] = color.to_be_bytes();
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        match c <= 0.04045 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        }
+    };
+    [linear(r), linear(g), linear(b), a as f32 / 255.0]
+}
+
+impl std::str::FromStr for Theme {
+    type Err = String;
+
+    /// The contents of a theme file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut theme = Theme::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |err: String| format!("line {} of the theme: {err}", i + 1);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected <key> = <value>, got `{line}`")))?;
+            let value = value.trim();
+            match key.trim() {
+                "base" => {
+                    theme = Theme::built_in(value).ok_or_else(|| {
+                        invalid(format!(
+                            "unknown theme `{value}`, expected one of {}",
+                            Theme::BUILT_IN.join(", ")
+                        ))
+                    })?
+                }
+                "clear" => theme.clear = parse_color(value).map_err(invalid)?,
+                "palette" => {
+                    theme.palette = value
+                        .split(',')
+                        .filter(|color| !color.trim().is_empty())
+                        .map(parse_color)
+                        .collect::<Result<_, _>>()
+                        .map_err(invalid)?
+                }
+                "lines" => theme.lines = parse_color(value).map_err(invalid)?,
+                "overlay" => theme.overlay = parse_color(value).map_err(invalid)?,
+                key => {
+                    return Err(invalid(format!(
+                        "unknown key `{key}`, expected base, clear, palette, lines or overlay"
+                    )))
+                }
+            }
+        }
+        Ok(theme)
+    }
+}
@@ -0,0 +1,109 @@
+//! Per-particle temperature, set by heaters and coolers and driving buoyancy.
+//!
+//! Temperatures are relative to the ambient temperature. They are updated on
+//! the host from the particle positions and uploaded next to the particles, the
+//! backends only turn them into buoyancy while integrating.
+
+use crate::boundary;
+use crate::sim::{self, Instance};
+
+/// A rectangle that sets the temperature of every particle inside it.
+/// Negative temperatures make a cooler.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heater {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub temperature: f32,
+}
+
+impl Heater {
+    pub fn contains(&self, pos: [f32; 2]) -> bool {
+        (self.min[0]..self.max[0]).contains(&pos[0]) && (self.min[1]..self.max[1]).contains(&pos[1])
+    }
+}
+
+impl std::str::FromStr for Heater {
+    type Err = String;
+
+    /// `<x0>,<y0>,<x1>,<y1>=<temperature>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rect, temperature) = s.split_once('=').ok_or(format!(
+            "invalid heater `{s}`, expected <x0>,<y0>,<x1>,<y1>=<temperature>"
+        ))?;
+        let (min, max) = crate::scene::parse_rect(rect)?;
+        let temperature = temperature
+            .parse()
+            .map_err(|err| format!("invalid heater temperature `{temperature}`: {err}"))?;
+        Ok(Heater {
+            min,
+            max,
+            temperature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermal {
+    pub heaters: Vec<Heater>,
+    /// Upward acceleration per degree above ambient.
+    pub buoyancy: f32,
+    /// Fraction of the difference to ambient lost per second outside of heaters.
+    pub cooling: f32,
+}
+
+impl Default for Thermal {
+    fn default() -> Self {
+        Self {
+            heaters: vec![],
+            buoyancy: 1.0,
+            cooling: 0.1,
+        }
+    }
+}
+
+impl Thermal {
+    /// Without heaters every particle stays at ambient temperature.
+    pub fn is_active(&self) -> bool {
+        !self.heaters.is_empty()
+    }
+
+    /// Sets the temperature of particles inside a heater, lets the others cool
+    /// off for `dt`. Removed particles are reset to ambient, ready for an inlet
+    /// to reuse their slot.
+    pub fn update(&self, particles: &[Instance], temperatures: &mut [f32], dt: f32) {
+        let keep = 1.0 - (self.cooling * dt).min(1.0);
+        for (p, temperature) in particles.iter().zip(temperatures) {
+            if boundary::is_removed(p) {
+                *temperature = 0.0;
+                continue;
+            }
+            // later heaters win where they overlap
+            *temperature = match self.heaters.iter().rev().find(|h| h.contains(p.pos)) {
+                Some(heater) => heater.temperature,
+                None => *temperature * keep,
+            };
+        }
+    }
+
+    /// The largest temperature difference to ambient any heater produces.
+    pub fn range(&self) -> f32 {
+        self.heaters
+            .iter()
+            .map(|h| h.temperature.abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// [`sim::colormap`] of the temperatures, centered on ambient. Removed
+    /// particles are transparent.
+    pub fn colors(&self, particles: &[Instance], temperatures: &[f32]) -> Vec<u32> {
+        let range = self.range().max(f32::EPSILON);
+        particles
+            .iter()
+            .zip(temperatures)
+            .map(|(p, &t)| match boundary::is_removed(p) {
+                true => 0,
+                false => sim::colormap(0.5 + 0.5 * t / range),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,193 @@
+//! Every nth frame as a numbered PNG, for figures and flipbooks.
+//!
+//! The frames are drawn on the CPU, so they look the same with or without a
+//! window: the unit domain fills a square image, with every particle a disc
+//! in its color on a dark background. Next to the images, `manifest.csv` has
+//! the step and simulated time of every one.
+//!
+//! There is no image crate to lean on, so the PNGs are written by hand with
+//! uncompressed deflate blocks. They are bigger than they need to be, any
+//! image tool shrinks them.
+
+use crate::boundary;
+use crate::sim::Instance;
+use crate::TIME_STEP;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const BACKGROUND: [u8; 3] = [16, 16, 24];
+
+pub struct Timelapse {
+    dir: PathBuf,
+    /// Steps between images.
+    every: u64,
+    /// Width and height of the images in pixels.
+    size: u32,
+    /// Radius of the particle discs in domain units.
+    pub radius: f32,
+    frames: u32,
+    manifest: BufWriter<File>,
+    pixels: Vec<u8>,
+}
+
+impl Timelapse {
+    /// Creates `dir` if needed. Images already in it are overwritten.
+    pub fn create(dir: &Path, every: u64, size: u32) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut manifest = BufWriter::new(File::create(dir.join("manifest.csv"))?);
+        writeln!(manifest, "frame,step,time")?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            every: every.max(1),
+            size,
+            radius: 0.005,
+            frames: 0,
+            manifest,
+            pixels: vec![],
+        })
+    }
+
+    /// Where image `frame` goes.
+    pub fn path(&self, frame: u32) -> PathBuf {
+        self.dir.join(format!("frame_{frame:05}.png"))
+    }
+
+    /// Saves the frame of `step` if it is one of every nth.
+    pub fn capture(&mut self, step: u64, particles: &[Instance], colors: &[u32]) -> io::Result<()> {
+        if !step.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        self.draw(particles, colors);
+        let path = self.path(self.frames);
+        let mut out = BufWriter::new(File::create(&path)?);
+        write_png(&mut out, self.size, self.size, &self.pixels)?;
+        out.flush()?;
+        writeln!(
+            self.manifest,
+            "{},{step},{}",
+            self.frames,
+            step as f32 * TIME_STEP
+        )?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.manifest.flush()
+    }
+
+    fn draw(&mut self, particles: &[Instance], colors: &[u32]) {
+        let size = self.size as usize;
+        self.pixels.clear();
+        self.pixels.extend(
+            BACKGROUND
+                .iter()
+                .chain(&[255])
+                .cycle()
+                .take(size * size * 4),
+        );
+
+        let scale = size as f32;
+        let radius = (self.radius * scale).max(0.5);
+        for (p, &color) in particles.iter().zip(colors) {
+            if boundary::is_removed(p) {
+                continue;
+            }
+            // images go from the top row down, the domain from the bottom up
+            let center = [p.pos[0] * scale, (1.0 - p.pos[1]) * scale];
+            let [a, r, g, b] = color.to_be_bytes();
+            let x0 = (center[0] - radius).floor().max(0.0) as usize;
+            let y0 = (center[1] - radius).floor().max(0.0) as usize;
+            let x1 = ((center[0] + radius).ceil().max(0.0) as usize).min(size);
+            let y1 = ((center[1] + radius).ceil().max(0.0) as usize).min(size);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let dx = x as f32 + 0.5 - center[0];
+                    let dy = y as f32 + 0.5 - center[1];
+                    // one pixel of antialiasing at the rim
+                    let cover = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+                    let alpha = cover * a as f32 / 255.0;
+                    if alpha <= 0.0 {
+                        continue;
+                    }
+                    let pixel = &mut self.pixels[(y * size + x) * 4..][..3];
+                    for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+                        let blended = *channel as f32 * (1.0 - alpha) + value as f32 * alpha;
+                        *channel = blended.round() as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes 8 bit RGBA `pixels`, row by row from the top, as a PNG.
+pub fn write_png(mut out: impl Write, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize * 4);
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = vec![];
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, standard filters, not interlaced
+    header.extend([8, 6, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header)?;
+
+    // every row starts with its filter, none
+    let mut rows = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks_exact(width as usize * 4) {
+        rows.push(0);
+        rows.extend(row);
+    }
+    // zlib stream of stored deflate blocks
+    let mut data = vec![0x78, 0x01];
+    let blocks = rows.chunks(u16::MAX as usize);
+    let count = blocks.len();
+    for (i, block) in blocks.enumerate() {
+        let len = block.len() as u16;
+        data.push((i + 1 == count) as u8);
+        data.extend(len.to_le_bytes());
+        data.extend((!len).to_le_bytes());
+        data.extend(block);
+    }
+    if count == 0 {
+        data.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    data.extend(adler32(&rows).to_be_bytes());
+    write_chunk(&mut out, b"IDAT", &data)?;
+
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,150 @@
+//! Fixed rate simulation stepping, decoupled from the display refresh rate.
+
+use crate::boundary;
+use crate::sim::Instance;
+use std::time::{Duration, Instant};
+
+/// Steps run for a single frame at most. If the simulation falls further behind
+/// than this, the remaining time is dropped instead of trying to catch up.
+pub const MAX_STEPS_PER_FRAME: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    dt: Duration,
+    accumulator: Duration,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32) -> Self {
+        Self {
+            dt: Duration::from_secs_f32(dt),
+            accumulator: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt.as_secs_f32()
+    }
+
+    /// Adds the wall-clock time since the previous call and returns how many
+    /// simulation steps are due. The first call only starts the clock.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = match self.last {
+            Some(last) => now.saturating_duration_since(last),
+            None => Duration::ZERO,
+        };
+        self.last = Some(now);
+        self.accumulate(elapsed)
+    }
+
+    /// Adds `elapsed` and returns how many simulation steps are due, for
+    /// callers that measure the time themselves.
+    pub fn accumulate(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.dt {
+            self.accumulator -= self.dt;
+            steps += 1;
+        }
+
+        if steps > MAX_STEPS_PER_FRAME {
+            log::warn!("simulation is {steps} steps behind, skipping ahead");
+            steps = MAX_STEPS_PER_FRAME;
+        }
+
+        steps
+    }
+
+    /// Forgets the time since the last call, for after a pause that should
+    /// not be caught up on.
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.last = None;
+    }
+
+    /// Time left until the next step is due, as of the last [`advance`](Self::advance).
+    pub fn until_next_step(&self) -> Duration {
+        self.dt.saturating_sub(self.accumulator)
+    }
+
+    /// How far the current time is between the last two simulation states, in `[0, 1)`.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.dt.as_secs_f32()
+    }
+}
+
+/// Spaces frames out to at most a given rate.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / fps as f64),
+            next: None,
+        }
+    }
+
+    /// Whether a frame is due at `now`, starting the wait for the next one if
+    /// it is. Frames keep to the rate on average, but a stall isn't made up
+    /// for with a burst.
+    pub fn due(&mut self, now: Instant) -> bool {
+        if self.next.is_some_and(|next| now < next) {
+            return false;
+        }
+        let next = self.next.map(|next| next + self.interval);
+        self.next = Some(match next {
+            Some(next) if next > now => next,
+            _ => now + self.interval,
+        });
+        true
+    }
+
+    /// When the next frame is due.
+    pub fn next(&self) -> Option<Instant> {
+        self.next
+    }
+}
+
+/// Moves the particles on by their velocity over `seconds`, to keep them going
+/// for rendering while the next state is late. [Removed](boundary::REMOVED)
+/// particles stay removed.
+pub fn extrapolate(current: &[Instance], seconds: f32) -> Vec<Instance> {
+    current
+        .iter()
+        .map(|c| match boundary::is_removed(c) {
+            true => *c,
+            false => Instance {
+                pos: [c.pos[0] + c.vel[0] * seconds, c.pos[1] + c.vel[1] * seconds],
+                vel: c.vel,
+            },
+        })
+        .collect()
+}
+
+/// Blends positions between two simulation states for rendering.
+///
+/// Particles that only exist in `current`, or were [removed](boundary::REMOVED)
+/// in `previous`, are taken as is.
+pub fn interpolate(previous: &[Instance], current: &[Instance], alpha: f32) -> Vec<Instance> {
+    current
+        .iter()
+        .enumerate()
+        .map(|(i, c)| match previous.get(i) {
+            Some(p) if !boundary::is_removed(p) => Instance {
+                pos: [
+                    p.pos[0] + (c.pos[0] - p.pos[0]) * alpha,
+                    p.pos[1] + (c.pos[1] - p.pos[1]) * alpha,
+                ],
+                vel: c.vel,
+            },
+            _ => *c,
+        })
+        .collect()
+}
@@ -0,0 +1,161 @@
+//! A timeline of what the host and the devices did, for `chrome://tracing`
+//! or Perfetto, written with `--trace`.
+//!
+//! Every step of the simulation thread is a span on the host track, and
+//! the commands the OpenCL backend [profiled](crate::backend::Backend::device_spans)
+//! for it are spans on a track of their own, as are the passes of every
+//! frame the window measured with timestamp queries. Gaps on the device
+//! tracks are the device waiting on the host or on the other device.
+//!
+//! Each device has a clock of its own. A track is lined up with the host
+//! clock when its first spans come in, taking the last of them to have just
+//! ended, so the device tracks are only roughly in line with the host and
+//! each other: good for spotting bubbles and serialization, not for
+//! measuring the latency between them.
+
+use crate::report::json_string;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A command that ran on a device, in nanoseconds of its clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceSpan {
+    pub name: &'static str,
+    /// What part of a step or frame it belongs to.
+    pub category: &'static str,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Where a span goes in the timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    /// The steps of the simulation thread.
+    Host,
+    /// The commands of the OpenCL backend.
+    Simulation,
+    /// The passes of the window.
+    Render,
+}
+
+impl Track {
+    const ALL: [Track; 3] = [Track::Host, Track::Simulation, Track::Render];
+
+    fn id(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Track::Host => "simulation thread",
+            Track::Simulation => "OpenCL",
+            Track::Render => "wgpu",
+        }
+    }
+}
+
+/// One span of the timeline, in microseconds since the trace started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub name: &'static str,
+    pub category: &'static str,
+    pub track: Track,
+    pub start: f64,
+    pub duration: f64,
+    /// The step or frame it belongs to.
+    pub frame: u64,
+}
+
+/// Shared by the simulation thread and the window.
+pub type SharedTrace = Arc<Mutex<Trace>>;
+
+#[derive(Debug)]
+pub struct Trace {
+    started: Instant,
+    pub events: Vec<Event>,
+    /// Microseconds to add to device microseconds per track, once lined up.
+    offsets: [Option<f64>; 3],
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl Trace {
+    /// An empty trace with everything relative to `started`.
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            events: vec![],
+            offsets: [None; 3],
+        }
+    }
+
+    pub fn shared() -> SharedTrace {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Adds a span on the host from `start` to `end`.
+    pub fn add_host(&mut self, name: &'static str, start: Instant, end: Instant, frame: u64) {
+        let micros = |t: Instant| t.saturating_duration_since(self.started).as_secs_f64() * 1e6;
+        self.events.push(Event {
+            name,
+            category: "host",
+            track: Track::Host,
+            start: micros(start),
+            duration: micros(end) - micros(start),
+            frame,
+        });
+    }
+
+    /// Adds `spans` of the device on `track` for step or frame `frame`,
+    /// which have finished by `now`.
+    pub fn add_device(&mut self, track: Track, spans: &[DeviceSpan], frame: u64, now: Instant) {
+        let Some(last) = spans.iter().map(|span| span.end).max() else {
+            return;
+        };
+        let now = now.saturating_duration_since(self.started).as_secs_f64() * 1e6;
+        let offset = *self.offsets[track.id()].get_or_insert(now - last as f64 * 1e-3);
+        for span in spans {
+            self.events.push(Event {
+                name: span.name,
+                category: span.category,
+                track,
+                start: span.start as f64 * 1e-3 + offset,
+                duration: span.end.saturating_sub(span.start) as f64 * 1e-3,
+                frame,
+            });
+        }
+    }
+
+    /// Writes the trace in the Trace Event Format, with a name for every track.
+    pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+        let names = Track::ALL.into_iter().map(|track| {
+            format!(
+                "{{\"name\": \"thread_name\", \"ph\": \"M\", \"pid\": 1, \"tid\": {}, \
+                 \"args\": {{\"name\": {}}}}}",
+                track.id(),
+                json_string(track.name()),
+            )
+        });
+        let spans = self.events.iter().map(|event| {
+            format!(
+                "{{\"name\": {}, \"cat\": {}, \"ph\": \"X\", \"ts\": {:.3}, \"dur\": {:.3}, \
+                 \"pid\": 1, \"tid\": {}, \"args\": {{\"frame\": {}}}}}",
+                json_string(event.name),
+                json_string(event.category),
+                event.start,
+                event.duration.max(0.0),
+                event.track.id(),
+                event.frame,
+            )
+        });
+        let lines = names.chain(spans).collect::<Vec<_>>();
+        writeln!(out, "{{\"displayTimeUnit\": \"ms\", \"traceEvents\": [")?;
+        writeln!(out, "{}", lines.join(",\n"))?;
+        writeln!(out, "]}}")
+    }
+}
@@ -0,0 +1,150 @@
+//! Rectangles that report particles entering and leaving them, for scenes
+//! that react to where the fluid goes and for checking where it ended up.
+
+use crate::boundary;
+use crate::sim::Instance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub name: String,
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Trigger {
+    pub fn contains(&self, pos: [f32; 2]) -> bool {
+        (self.min[0]..self.max[0]).contains(&pos[0]) && (self.min[1]..self.max[1]).contains(&pos[1])
+    }
+}
+
+impl std::str::FromStr for Trigger {
+    type Err = String;
+
+    /// `<name>=<x0>,<y0>,<x1>,<y1>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rect) = s.split_once('=').ok_or(format!(
+            "invalid trigger `{s}`, expected <name>=<x0>,<y0>,<x1>,<y1>"
+        ))?;
+        let (min, max) = crate::scene::parse_rect(rect)?;
+        Ok(Trigger {
+            name: name.into(),
+            min,
+            max,
+        })
+    }
+}
+
+/// What happened in one trigger during an [`update`](Triggers::update).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TriggerCounts {
+    pub entered: usize,
+    pub left: usize,
+    /// Particles inside after the update.
+    pub inside: usize,
+}
+
+impl TriggerCounts {
+    pub fn changed(&self) -> bool {
+        self.entered > 0 || self.left > 0
+    }
+}
+
+type Callback = Box<dyn FnMut(&Trigger, &TriggerCounts) + Send>;
+
+/// Tracks which particles are inside which [`Trigger`]. Removed particles are
+/// never inside, so a particle being removed leaves its trigger.
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    /// Per trigger, whether each particle was inside it at the last update.
+    inside: Vec<Vec<bool>>,
+    counts: Vec<TriggerCounts>,
+    /// Particles that weren't removed at the last update.
+    active: usize,
+    callbacks: Vec<Callback>,
+}
+
+impl Triggers {
+    /// Starts out with the particles inside each trigger counted as inside,
+    /// without them having entered.
+    pub fn new(triggers: Vec<Trigger>, particles: &[Instance]) -> Self {
+        let mut this = Self {
+            inside: vec![vec![]; triggers.len()],
+            counts: vec![TriggerCounts::default(); triggers.len()],
+            active: 0,
+            triggers,
+            callbacks: vec![],
+        };
+        this.update(particles);
+        this.counts.iter_mut().for_each(|c| {
+            c.entered = 0;
+            c.left = 0;
+        });
+        this
+    }
+
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    /// Calls `callback` with a trigger and its counts after every update that
+    /// moved particles into or out of it.
+    pub fn on_change(&mut self, callback: impl FnMut(&Trigger, &TriggerCounts) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Counts the particles that entered and left each trigger since the last
+    /// update, then calls the callbacks.
+    pub fn update(&mut self, particles: &[Instance]) -> &[TriggerCounts] {
+        self.active = particles
+            .iter()
+            .filter(|p| !boundary::is_removed(p))
+            .count();
+
+        for (t, trigger) in self.triggers.iter().enumerate() {
+            let was_inside = &mut self.inside[t];
+            was_inside.resize(particles.len(), false);
+
+            let mut counts = TriggerCounts::default();
+            for (p, was) in particles.iter().zip(was_inside.iter_mut()) {
+                let is = !boundary::is_removed(p) && trigger.contains(p.pos);
+                match (*was, is) {
+                    (false, true) => counts.entered += 1,
+                    (true, false) => counts.left += 1,
+                    _ => {}
+                }
+                counts.inside += is as usize;
+                *was = is;
+            }
+
+            self.counts[t] = counts;
+        }
+
+        for (trigger, counts) in self.triggers.iter().zip(&self.counts) {
+            if counts.changed() {
+                for callback in &mut self.callbacks {
+                    callback(trigger, counts);
+                }
+            }
+        }
+        &self.counts
+    }
+
+    /// Counts of the last update, in the order of [`triggers`](Self::triggers).
+    pub fn counts(&self) -> &[TriggerCounts] {
+        &self.counts
+    }
+
+    /// Share of the particles that aren't removed inside the trigger called
+    /// `name` at the last update, `None` without such a trigger.
+    pub fn fraction(&self, name: &str) -> Option<f32> {
+        let t = self.triggers.iter().position(|t| t.name == name)?;
+        Some(match self.active {
+            0 => 0.0,
+            n => self.counts[t].inside as f32 / n as f32,
+        })
+    }
+}
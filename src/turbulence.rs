@@ -0,0 +1,156 @@
+//! Small eddies stirred back into the fluid, which the grid is too coarse to
+//! keep on its own.
+//!
+//! The eddies are the curl of [tiling noise](crate::noise::simplex), a swirl
+//! that pushes no fluid together or apart, applied as an acceleration. They
+//! are strongest at the free surface and fade exponentially with depth below
+//! it, so the splashing top churns while the bulk stays calm. The surface
+//! is the highest particle in each of [`COLUMNS`] columns, taken from the
+//! particles at the start of every step. Regions limit the turbulence to
+//! parts of the domain, anywhere if there are none.
+
+use crate::boundary;
+use crate::noise;
+use crate::sim::Instance;
+
+/// Columns the surface is tracked in. Mirrors `SURFACE_COLUMNS` in
+/// `sorting.ocl`.
+pub const COLUMNS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turbulence {
+    /// Acceleration per unit curl right at the surface, none at all if 0.
+    pub amplitude: f32,
+    /// Noise cells across the domain, rounded to an even number to tile.
+    pub scale: f32,
+    /// How fast the eddies churn, in radians per second.
+    pub speed: f32,
+    /// Depth at which the amplitude has fallen to `1/e`.
+    pub depth: f32,
+    /// Rectangles as min and max corners the turbulence is limited to.
+    pub regions: Vec<([f32; 2], [f32; 2])>,
+}
+
+impl Default for Turbulence {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.0,
+            scale: 16.0,
+            speed: 2.0,
+            depth: 0.05,
+            regions: vec![],
+        }
+    }
+}
+
+impl Turbulence {
+    pub fn is_active(&self) -> bool {
+        self.amplitude != 0.0
+    }
+
+    /// What the integrate kernel gets as `turbulence`: the amplitude, the
+    /// noise cells, the rotation of the gradients at `time` and the depth.
+    pub fn params(&self, time: f32) -> [f32; 4] {
+        match self.is_active() {
+            true => [
+                self.amplitude,
+                noise::tiling_period(self.scale),
+                self.speed * time,
+                self.depth,
+            ],
+            false => [0.0; 4],
+        }
+    }
+
+    /// The regions as `(x0, y0, x1, y1)`, for the device.
+    pub fn region_bounds(&self) -> Vec<[f32; 4]> {
+        self.regions
+            .iter()
+            .map(|(min, max)| [min[0], min[1], max[0], max[1]])
+            .collect()
+    }
+
+    /// Acceleration on a particle at `pos` at `time`.
+    /// Mirrors `turbulence_acceleration` in `sorting.ocl`.
+    pub fn acceleration(&self, pos: [f32; 2], surface: &Surface, time: f32) -> [f32; 2] {
+        let [amplitude, cells, alpha, depth] = self.params(time);
+        if amplitude == 0.0 {
+            return [0.0, 0.0];
+        }
+        let inside = |(min, max): &([f32; 2], [f32; 2])| {
+            (min[0]..max[0]).contains(&pos[0]) && (min[1]..max[1]).contains(&pos[1])
+        };
+        if !self.regions.is_empty() && !self.regions.iter().any(inside) {
+            return [0.0, 0.0];
+        }
+
+        let below = (surface.height_at(pos[0]) - pos[1]).max(0.0);
+        let falloff = (-below / depth).exp();
+        let (_, gradient) = noise::simplex(pos.map(|x| x * cells), [cells, cells], alpha);
+        let scale = amplitude * falloff;
+        [scale * gradient[1], -scale * gradient[0]]
+    }
+}
+
+/// Height of the highest particle in each column, 0 for empty ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Surface {
+    pub heights: Vec<f32>,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            heights: vec![0.0; COLUMNS],
+        }
+    }
+}
+
+impl Surface {
+    pub fn update(&mut self, particles: &[Instance]) {
+        self.heights.fill(f32::NEG_INFINITY);
+        for p in particles.iter().filter(|p| !boundary::is_removed(p)) {
+            let height = &mut self.heights[column(p.pos[0])];
+            *height = height.max(p.pos[1]);
+        }
+        for height in &mut self.heights {
+            if *height == f32::NEG_INFINITY {
+                *height = 0.0;
+            }
+        }
+    }
+
+    pub fn height_at(&self, x: f32) -> f32 {
+        self.heights[column(x)]
+    }
+}
+
+fn column(x: f32) -> usize {
+    ((x.clamp(0.0, 1.0) * COLUMNS as f32) as usize).min(COLUMNS - 1)
+}
+
+impl std::str::FromStr for Turbulence {
+    type Err = String;
+
+    /// `<amplitude>,<scale>,<speed>,<depth>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|x| x.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid turbulence `{s}`: {err}"))?;
+        match values[..] {
+            [amplitude, scale, speed, depth] if scale > 0.0 && depth > 0.0 => Ok(Turbulence {
+                amplitude,
+                scale,
+                speed,
+                depth,
+                regions: vec![],
+            }),
+            _ => Err(format!(
+                "invalid turbulence `{s}`, expected <amplitude>,<scale>,<speed>,<depth> \
+                 with a positive scale and depth"
+            )),
+        }
+    }
+}
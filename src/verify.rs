@@ -0,0 +1,130 @@
+//! Slow checks of a backend's grid, started with `--verify`: every frame, the
+//! neighbors the collisions see through the grid are compared against a
+//! [k-d tree](crate::kdtree) for a random sample of particles, and any
+//! neighbor the grid missed is reported, such as those dropped from cells
+//! with more than `MAX_PARTICLES_PER_CELL` particles.
+
+use crate::grid::Grid;
+use crate::kdtree::KdTree;
+use crate::rng::Rng;
+use crate::sim::Instance;
+use std::fmt;
+
+/// Particles checked per frame.
+pub const SAMPLE: usize = 64;
+
+/// The grid of a backend as of its last step: `counts` per cell and
+/// `n_per_cell` slots of `ids` per cell, like `sort_particles` leaves them.
+#[derive(Debug, Clone, Copy)]
+pub struct GridCells<'a> {
+    pub grid: Grid,
+    pub n_per_cell: u32,
+    pub counts: &'a [u32],
+    pub ids: &'a [i32],
+}
+
+impl GridCells<'_> {
+    /// Particles within `radius` of particle `id` that the collisions reach
+    /// through the grid, in ascending order.
+    pub fn neighbors(&self, particles: &[Instance], id: usize, radius: f32) -> Vec<usize> {
+        let Some(own_cell) = self.grid.cell_index(particles[id].pos) else {
+            return vec![];
+        };
+        let mut found = vec![];
+        for cell in self.grid.neighbors(own_cell) {
+            let count = self.counts[cell as usize].min(self.n_per_cell);
+            let start = (cell * self.n_per_cell) as usize;
+            for &other in &self.ids[start..start + count as usize] {
+                let other = other as usize;
+                let [dx, dy] = self
+                    .grid
+                    .wrap_delta(particles[id].pos, particles[other].pos);
+                if other != id && dx * dx + dy * dy <= radius * radius {
+                    found.push(other);
+                }
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+}
+
+/// Neighbors of `particle` that the grid doesn't hand the collisions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Missed {
+    pub particle: usize,
+    pub neighbors: Vec<usize>,
+}
+
+impl fmt::Display for Missed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let neighbors = self
+            .neighbors
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "particle {} misses neighbors {}",
+            self.particle,
+            neighbors.join(", ")
+        )
+    }
+}
+
+/// Compares the grid neighbors of every particle in `sample` with those
+/// `tree` finds within `radius`, looking across periodic edges as well.
+pub fn check(
+    particles: &[Instance],
+    cells: &GridCells,
+    tree: &KdTree,
+    sample: impl IntoIterator<Item = usize>,
+    radius: f32,
+) -> Vec<Missed> {
+    let shifts = |periodic: bool| match periodic {
+        true => vec![-1.0, 0.0, 1.0],
+        false => vec![0.0],
+    };
+    let [periodic_x, periodic_y] = cells.grid.periodic();
+
+    sample
+        .into_iter()
+        .filter_map(|id| {
+            let pos = particles[id].pos;
+            let mut expected = vec![];
+            for dy in shifts(periodic_y) {
+                for dx in shifts(periodic_x) {
+                    expected.extend(tree.within([pos[0] + dx, pos[1] + dy], radius));
+                }
+            }
+            expected.sort_unstable();
+            expected.dedup();
+            expected.retain(|&other| other != id);
+
+            let found = cells.neighbors(particles, id, radius);
+            let neighbors = expected
+                .into_iter()
+                .filter(|other| found.binary_search(other).is_err())
+                .collect::<Vec<_>>();
+            (!neighbors.is_empty()).then_some(Missed {
+                particle: id,
+                neighbors,
+            })
+        })
+        .collect()
+}
+
+/// Up to [`SAMPLE`] distinct particle indices below `count`, different for
+/// every `seed`.
+pub fn sample(count: usize, seed: u32) -> Vec<usize> {
+    if count <= SAMPLE {
+        return (0..count).collect();
+    }
+    let mut random = Rng::new(seed.into());
+    let mut ids = (0..SAMPLE)
+        .map(|_| random.below(count as u32) as usize)
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
@@ -0,0 +1,101 @@
+//! The camera view and numbered bookmarks of it, kept in a sidecar file so
+//! the same region comes back up in the next run.
+//!
+//! Ctrl+1 to Ctrl+9 save the view to a bookmark, 1 to 9 jump back to it. The
+//! file has one `key = x0, y0, x1, y1` rectangle of the world per line, `#`
+//! starts a comment:
+//!
+//! ```text
+//! view = 0, 0, 1, 1      # the view on exit, restored on startup
+//! 1 = 0.2, 0.1, 0.5, 0.4
+//! ```
+
+use crate::scene;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Bookmarks, saved with Ctrl and a number.
+pub const BOOKMARKS: usize = 9;
+
+/// Min and max corner of what the camera shows.
+pub type Rect = ([f32; 2], [f32; 2]);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Views {
+    /// Where the camera was on exit.
+    pub current: Option<Rect>,
+    /// Bookmark `n` at `n - 1`.
+    pub bookmarks: [Option<Rect>; BOOKMARKS],
+}
+
+impl Views {
+    /// Reads the views from `path`, none if there is no such file yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => source
+                .parse()
+                .map_err(|err| format!("{}: {err}", path.display())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Views::default()),
+            Err(err) => Err(format!("could not read {}: {err}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Bookmark `n`, counting from one.
+    pub fn bookmark(&self, n: usize) -> Option<Rect> {
+        self.bookmarks.get(n.checked_sub(1)?).copied().flatten()
+    }
+
+    pub fn set_bookmark(&mut self, n: usize, rect: Rect) {
+        if let Some(bookmark) = n.checked_sub(1).and_then(|i| self.bookmarks.get_mut(i)) {
+            *bookmark = Some(rect);
+        }
+    }
+}
+
+impl std::str::FromStr for Views {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut views = Views::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |err: String| format!("line {}: {err}", i + 1);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected <key> = <rect>, got `{line}`")))?;
+            let rect = scene::parse_rect(value).map_err(invalid)?;
+            match key.trim() {
+                "view" => views.current = Some(rect),
+                key => match key.parse::<usize>() {
+                    Ok(n @ 1..=BOOKMARKS) => views.set_bookmark(n, rect),
+                    _ => {
+                        return Err(invalid(format!(
+                            "unknown key `{key}`, expected view or 1 to {BOOKMARKS}"
+                        )))
+                    }
+                },
+            }
+        }
+        Ok(views)
+    }
+}
+
+impl fmt::Display for Views {
+    /// The contents of a views file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let current = self.current.map(|rect| ("view".to_string(), rect));
+        let bookmarks = (1..=BOOKMARKS).filter_map(|n| Some((n.to_string(), self.bookmark(n)?)));
+        for (key, (min, max)) in current.into_iter().chain(bookmarks) {
+            writeln!(f, "{key} = {}, {}, {}, {}", min[0], min[1], max[0], max[1])?;
+        }
+        Ok(())
+    }
+}
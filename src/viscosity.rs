@@ -0,0 +1,210 @@
+//! Viscosity as velocity diffusion between neighbors, for fluids from water
+//! to honey and lava.
+//!
+//! Each particle's velocity moves towards the kernel weighted average of its
+//! neighbors'. For the kernel of [`crate::probe`] that difference is about
+//! `radius² / 20` times the Laplacian of the velocity, so a kinematic viscosity
+//! `ν` over a step `dt` takes a share `c = 20 ν dt / radius²` of it. Up to
+//! `c = 1` a single explicit pass does, like XSPH. Beyond that the explicit
+//! pass overshoots and blows up, so the diffusion is solved implicitly
+//! instead, with Gauss-Seidel iterations over the neighborhoods.
+//!
+//! Every particle has a viscosity of its own, taken from its
+//! [phase](crate::phase::Phase::viscosity), painted on while running and
+//! thinned by its temperature. Two neighbors diffuse into each other with the
+//! mean of their shares, which keeps the exchange symmetric, so a syrup
+//! stays thick next to water without dragging the water along any more than
+//! the water drags it.
+
+use crate::boundary;
+use crate::neighbors::CellList;
+use crate::phase::Phase;
+use crate::probe::kernel;
+use crate::sim::Instance;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viscosity {
+    /// Kinematic viscosity of the particles without one of their own, 0 for
+    /// none.
+    pub viscosity: f32,
+    /// Kinematic viscosity per particle slot. Slots past the end, such as
+    /// particles that came out of an inlet later, take
+    /// [`viscosity`](Self::viscosity).
+    pub viscosities: Vec<f32>,
+    /// The viscosity is divided by `e` every `1 / thinning` degrees above
+    /// ambient, 0 for none.
+    pub thinning: f32,
+    /// Gauss-Seidel iterations of the implicit solve.
+    pub iterations: u32,
+    /// Neighbors of every particle with their normalized kernel weights,
+    /// `neighbors[start[i]..start[i + 1]]` for particle `i`.
+    neighbors: Vec<(u32, f32)>,
+    start: Vec<u32>,
+    /// Velocities before the solve.
+    initial: Vec<[f32; 2]>,
+    /// Share of every particle in the current step.
+    shares: Vec<f32>,
+}
+
+impl Viscosity {
+    pub fn new(viscosity: f32) -> Self {
+        Self {
+            viscosity,
+            viscosities: vec![],
+            thinning: 0.0,
+            iterations: 20,
+            neighbors: vec![],
+            start: vec![],
+            initial: vec![],
+            shares: vec![],
+        }
+    }
+
+    /// Particles take the viscosity of their phase, if it has one.
+    /// `phase_ids` index into `phases` per particle slot, like
+    /// [`Scene::phase_ids`](crate::scene::Scene::phase_ids).
+    pub fn with_phases(mut self, phases: &[Phase], phase_ids: &[u32]) -> Self {
+        self.viscosities = phase_ids
+            .iter()
+            .map(|&id| phases[id as usize].viscosity.unwrap_or(self.viscosity))
+            .collect();
+        self
+    }
+
+    pub fn with_thinning(mut self, thinning: f32) -> Self {
+        self.thinning = thinning;
+        self
+    }
+
+    /// Viscosity of the particle in slot `i` at `temperature` above ambient.
+    pub fn at(&self, i: usize, temperature: f32) -> f32 {
+        let viscosity = self.viscosities.get(i).copied().unwrap_or(self.viscosity);
+        viscosity * (-self.thinning * temperature).exp()
+    }
+
+    /// Sets the viscosity of the particles within `radius` of `center`.
+    pub fn paint(&mut self, particles: &[Instance], center: [f32; 2], radius: f32, viscosity: f32) {
+        if self.viscosities.len() < particles.len() {
+            self.viscosities.resize(particles.len(), self.viscosity);
+        }
+        for (p, v) in particles.iter().zip(&mut self.viscosities) {
+            let d = [p.pos[0] - center[0], p.pos[1] - center[1]];
+            if !boundary::is_removed(p) && d[0] * d[0] + d[1] * d[1] <= radius * radius {
+                *v = viscosity;
+            }
+        }
+    }
+
+    /// Share of the velocity difference to the neighbors taken away in a step
+    /// of `dt` with kernel `radius`, for the particles without a viscosity of
+    /// their own.
+    pub fn share(&self, dt: f32, radius: f32) -> f32 {
+        share(self.viscosity, dt, radius)
+    }
+
+    /// Whether a step of `dt` needs the implicit solve, for the most viscous
+    /// particle at ambient temperature.
+    pub fn is_implicit(&self, dt: f32, radius: f32) -> bool {
+        let most = self
+            .viscosities
+            .iter()
+            .fold(self.viscosity, |a, &b| a.max(b));
+        share(most, dt, radius) > 1.0
+    }
+
+    /// Diffuses the velocities of `particles` over a step of `dt`.
+    /// `temperatures` are per particle, like
+    /// [`Backend::temperatures`](crate::backend::Backend::temperatures), and
+    /// `cells` has to be built from `particles`.
+    pub fn apply(
+        &mut self,
+        cells: &CellList,
+        particles: &mut [Instance],
+        temperatures: &[f32],
+        dt: f32,
+    ) {
+        let radius = cells.radius();
+        self.shares.clear();
+        for i in 0..particles.len() {
+            let temperature = temperatures.get(i).copied().unwrap_or(0.0);
+            self.shares.push(share(self.at(i, temperature), dt, radius));
+        }
+        let most = self.shares.iter().fold(0.0f32, |a, &b| a.max(b));
+        if most <= 0.0 {
+            return;
+        }
+        self.gather(cells, particles);
+        self.initial.clear();
+        self.initial.extend(particles.iter().map(|p| p.vel));
+
+        // the shares are folded into the weights
+        if most <= 1.0 {
+            for (i, p) in particles.iter_mut().enumerate() {
+                let (sum, weight) = self.average(i, |j| self.initial[j]);
+                p.vel[0] += sum[0] - weight * p.vel[0];
+                p.vel[1] += sum[1] - weight * p.vel[1];
+            }
+            return;
+        }
+
+        // v - Σ c w (v_j - v) = v_initial, solved for v in place
+        for _ in 0..self.iterations {
+            for i in 0..particles.len() {
+                let (sum, weight) = self.average(i, |j| particles[j].vel);
+                let initial = self.initial[i];
+                particles[i].vel = [
+                    (initial[0] + sum[0]) / (1.0 + weight),
+                    (initial[1] + sum[1]) / (1.0 + weight),
+                ];
+            }
+        }
+    }
+
+    /// Normalized kernel weights from every particle to its neighbors, which
+    /// sum to less than 1 as the particle itself is left out.
+    fn gather(&mut self, cells: &CellList, particles: &[Instance]) {
+        let radius = cells.radius();
+        self.neighbors.clear();
+        self.start.clear();
+        for (i, p) in particles.iter().enumerate() {
+            self.start.push(self.neighbors.len() as u32);
+            if boundary::is_removed(p) {
+                continue;
+            }
+            let first = self.neighbors.len();
+            let mut total = 0.0;
+            cells.for_each_neighbor(particles, p.pos, |j, _, dist| {
+                let w = kernel(dist, radius);
+                total += w;
+                if j != i {
+                    self.neighbors.push((j as u32, w));
+                }
+            });
+            for (_, w) in &mut self.neighbors[first..] {
+                *w /= total;
+            }
+        }
+        self.start.push(self.neighbors.len() as u32);
+    }
+
+    fn of(&self, i: usize) -> &[(u32, f32)] {
+        &self.neighbors[self.start[i] as usize..self.start[i + 1] as usize]
+    }
+
+    /// Sum of the neighbor velocities and the sum of the weights, each
+    /// weighted by the kernel and the mean share of the pair.
+    fn average(&self, i: usize, vel: impl Fn(usize) -> [f32; 2]) -> ([f32; 2], f32) {
+        self.of(i)
+            .iter()
+            .fold(([0.0; 2], 0.0), |(sum, weight), &(j, w)| {
+                let j = j as usize;
+                let w = w * 0.5 * (self.shares[i] + self.shares[j]);
+                let v = vel(j);
+                ([sum[0] + w * v[0], sum[1] + w * v[1]], weight + w)
+            })
+    }
+}
+
+fn share(viscosity: f32, dt: f32, radius: f32) -> f32 {
+    20.0 * viscosity * dt / (radius * radius)
+}
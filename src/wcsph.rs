@@ -0,0 +1,316 @@
+//! Weakly compressible SPH after Becker and Teschner 2007, "Weakly
+//! compressible SPH for free surface flows", for comparing against the
+//! position based solver on the same scenes.
+//!
+//! Every step estimates the density around each particle, turns it into a
+//! pressure with the Tait equation and accelerates the particles down the
+//! pressure gradient. Everything else, from gravity and forces to the walls,
+//! is left to the [CPU backend](crate::cpu) with its particle collisions
+//! turned off. Densities are relative to rest like in [`crate::probe`], with
+//! [ghosts](crate::ghost) behind walls and [solid samples](crate::solid) of
+//! the obstacles filling in the neighborhood at the boundaries. Viscous
+//! fluids get their [viscosity](crate::viscosity) solved after the pressure,
+//! and [plastic](crate::plastic) phases have their bonds enforced after the
+//! positions are updated. With the `simd` feature, the particles next to
+//! each other are [summed 8 at a time](crate::simd).
+
+use crate::age::Ages;
+use crate::backend::{self, Backend, Config};
+use crate::boundary;
+use crate::cpu::CpuState;
+use crate::ghost;
+use crate::ids::ParticleIds;
+use crate::neighbors::CellList;
+use crate::plastic::Bonds;
+use crate::probe::{kernel, kernel_slope, rest_weight};
+use crate::scene::{Scene, SceneEdit};
+use crate::sim::Instance;
+#[cfg(feature = "simd")]
+use crate::simd::Lanes;
+use crate::solid::SolidParticles;
+use crate::stats::ParticleStats;
+use crate::viscosity::Viscosity;
+use crate::TIME_STEP;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WcsphParams {
+    /// Smoothing radius of the kernel.
+    pub radius: f32,
+    /// Particle spacing of the fluid at rest.
+    pub rest_spacing: f32,
+    /// Speed of sound, the higher the stiffer the fluid. About ten times the
+    /// fastest the fluid flows keeps it within a percent of rest density. The
+    /// frame is split into steps of at most `0.4 * radius / speed_of_sound`.
+    pub speed_of_sound: f32,
+    /// Exponent of the Tait equation.
+    pub gamma: f32,
+    /// Strength of the artificial viscosity, which damps the oscillations a
+    /// weakly compressible fluid is prone to. The physical viscosity is
+    /// [`Config::viscosity`].
+    pub viscosity: f32,
+}
+
+impl Default for WcsphParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.05,
+            rest_spacing: 0.02,
+            speed_of_sound: 10.0,
+            gamma: 7.0,
+            viscosity: 0.1,
+        }
+    }
+}
+
+impl WcsphParams {
+    /// Tait equation of state, clamped at zero so the fluid doesn't pull
+    /// itself together at the free surface.
+    pub fn pressure(&self, density: f32) -> f32 {
+        let stiffness = self.speed_of_sound * self.speed_of_sound / self.gamma;
+        (stiffness * (density.powf(self.gamma) - 1.0)).max(0.0)
+    }
+
+    /// Steps per [`TIME_STEP`] for the pressure waves to stay stable.
+    pub fn substeps(&self) -> u32 {
+        (TIME_STEP * self.speed_of_sound / (0.4 * self.radius))
+            .ceil()
+            .max(1.0) as u32
+    }
+}
+
+pub struct WcsphState {
+    cpu: CpuState,
+    params: WcsphParams,
+    /// Kernel weight summed over a lattice at rest, the inverse of the mass
+    /// of a particle in units of rest density.
+    rest_weight: f32,
+    cells: CellList,
+    #[cfg(feature = "simd")]
+    lanes: Lanes,
+    solids: SolidParticles,
+    densities: Vec<f32>,
+    pressures: Vec<f32>,
+    accelerations: Vec<[f32; 2]>,
+    viscosity: Viscosity,
+    bonds: Bonds,
+}
+
+impl WcsphState {
+    pub fn new(scene: &Scene, config: &Config, params: WcsphParams) -> Self {
+        let solids = SolidParticles::sample(
+            &scene.obstacles,
+            params.rest_spacing * 0.5,
+            params.radius,
+            params.rest_spacing,
+        );
+        Self {
+            cpu: CpuState::new(scene, config).without_collisions(),
+            rest_weight: rest_weight(params.radius, params.rest_spacing),
+            cells: CellList::new(params.radius),
+            #[cfg(feature = "simd")]
+            lanes: Lanes::default(),
+            solids,
+            densities: vec![],
+            pressures: vec![],
+            accelerations: vec![],
+            viscosity: Viscosity::new(config.viscosity)
+                .with_phases(&scene.phases, &scene.phase_ids)
+                .with_thinning(config.viscosity_thinning),
+            bonds: Bonds::new(&scene.phases, &scene.phase_ids),
+            params,
+        }
+    }
+
+    /// Relative to rest, per particle as of the last step.
+    pub fn densities(&self) -> &[f32] {
+        &self.densities
+    }
+
+    fn update_densities(&mut self) {
+        let particles = self.cpu.particles();
+        let boundaries = self.cpu.boundaries();
+        let radius = self.params.radius;
+        self.cells.build(particles);
+        #[cfg(feature = "simd")]
+        self.lanes.gather(&self.cells, particles);
+
+        self.densities.clear();
+        self.densities.extend(particles.iter().map(|p| {
+            if boundary::is_removed(p) {
+                return 1.0;
+            }
+            let mut weight = self.solids.weight_at(p.pos);
+            #[cfg(feature = "simd")]
+            {
+                weight += self.lanes.weight_at(&self.cells, p.pos);
+            }
+            #[cfg(not(feature = "simd"))]
+            self.cells
+                .for_each_neighbor(particles, p.pos, |_, _, dist| {
+                    weight += kernel(dist, radius)
+                });
+            ghost::for_each_ghost(
+                &self.cells,
+                particles,
+                boundaries,
+                p.pos,
+                |_, _, _, dist| weight += kernel(dist, radius),
+            );
+            weight / self.rest_weight
+        }));
+
+        self.pressures.clear();
+        let params = &self.params;
+        self.pressures
+            .extend(self.densities.iter().map(|&d| params.pressure(d)));
+    }
+
+    /// Pressure and viscosity accelerations from the densities.
+    fn update_accelerations(&mut self) {
+        let particles = self.cpu.particles();
+        let boundaries = self.cpu.boundaries();
+        let params = &self.params;
+        let mass = 1.0 / self.rest_weight;
+        let (densities, pressures) = (&self.densities, &self.pressures);
+        #[cfg(feature = "simd")]
+        self.lanes
+            .gather_pressures(&self.cells, densities, pressures);
+
+        self.accelerations.clear();
+        self.accelerations
+            .extend(particles.iter().enumerate().map(|(i, p)| {
+                if boundary::is_removed(p) {
+                    return [0.0; 2];
+                }
+                let own = pressures[i] / (densities[i] * densities[i]);
+                #[cfg(feature = "simd")]
+                let mut acc = self.lanes.pressure_acceleration(
+                    &self.cells,
+                    params,
+                    mass,
+                    p,
+                    densities[i],
+                    own,
+                );
+                #[cfg(not(feature = "simd"))]
+                let mut acc = [0.0; 2];
+                // `d` points from the particle to its neighbor, so a positive
+                // `scale` pushes the particle away from it
+                let mut push = |d: [f32; 2], dist: f32, scale: f32| {
+                    if dist > 1e-6 {
+                        let slope = -kernel_slope(dist, params.radius) / dist;
+                        acc[0] -= scale * slope * d[0];
+                        acc[1] -= scale * slope * d[1];
+                    }
+                };
+                let mut neighbor = |j: usize, d: [f32; 2], vel: [f32; 2], dist: f32| {
+                    let other = pressures[j] / (densities[j] * densities[j]);
+                    let viscous = artificial_viscosity(
+                        params,
+                        d,
+                        [vel[0] - p.vel[0], vel[1] - p.vel[1]],
+                        dist,
+                        0.5 * (densities[i] + densities[j]),
+                    );
+                    push(d, dist, mass * (own + other + viscous));
+                };
+
+                #[cfg(not(feature = "simd"))]
+                self.cells
+                    .for_each_neighbor(particles, p.pos, |j, d, dist| {
+                        if j != i {
+                            neighbor(j, d, particles[j].vel, dist)
+                        }
+                    });
+                ghost::for_each_ghost(&self.cells, particles, boundaries, p.pos, &mut neighbor);
+                self.solids
+                    .for_each_near(p.pos, |d, dist, volume| push(d, dist, mass * volume * own));
+                acc
+            }));
+    }
+}
+
+/// Monaghan's viscosity term between two particles `d` apart, moving at
+/// `vel` relative to each other, zero when they move apart.
+fn artificial_viscosity(
+    params: &WcsphParams,
+    d: [f32; 2],
+    vel: [f32; 2],
+    dist: f32,
+    density: f32,
+) -> f32 {
+    let approach = vel[0] * d[0] + vel[1] * d[1];
+    if approach >= 0.0 {
+        return 0.0;
+    }
+    let h = params.radius;
+    params.viscosity * params.speed_of_sound * h * -approach
+        / (density * (dist * dist + 0.01 * h * h))
+}
+
+impl Backend for WcsphState {
+    fn name(&self) -> &'static str {
+        "wcsph"
+    }
+
+    fn step(&mut self) -> Result<(), backend::Error> {
+        let substeps = self.params.substeps();
+        let dt = TIME_STEP / substeps as f32;
+        for _ in 0..substeps {
+            self.update_densities();
+            self.update_accelerations();
+            if self.bonds.is_active() {
+                self.bonds.stick(&self.cells, self.cpu.particles());
+            }
+            let (particles, temperatures) = self.cpu.particles_mut_with_temperatures();
+            for (p, acc) in particles.iter_mut().zip(&self.accelerations) {
+                p.vel[0] += acc[0] * dt;
+                p.vel[1] += acc[1] * dt;
+            }
+            self.viscosity
+                .apply(&self.cells, particles, temperatures, dt);
+            self.cpu.step_by(dt);
+            if self.bonds.is_active() {
+                self.bonds.project(self.cpu.particles_mut(), dt);
+            }
+        }
+        Ok(())
+    }
+
+    fn particles(&self) -> &[Instance] {
+        self.cpu.particles()
+    }
+
+    fn temperatures(&self) -> &[f32] {
+        self.cpu.temperatures()
+    }
+
+    fn ages(&self) -> &Ages {
+        self.cpu.ages()
+    }
+
+    fn ids(&self) -> &ParticleIds {
+        self.cpu.ids()
+    }
+
+    fn edit(&mut self, edit: &SceneEdit) -> Result<(), backend::Error> {
+        if let SceneEdit::PaintViscosity {
+            center,
+            radius,
+            viscosity,
+        } = *edit
+        {
+            self.viscosity
+                .paint(self.cpu.particles(), center, radius, viscosity);
+        }
+        self.cpu.edit(edit)
+    }
+
+    fn restore(&mut self, particles: &[Instance], time: f32) -> Result<(), backend::Error> {
+        self.cpu.restore(particles, time)
+    }
+
+    fn stats(&mut self) -> Result<ParticleStats, backend::Error> {
+        self.cpu.stats()
+    }
+}
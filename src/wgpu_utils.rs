@@ -1,4 +1,9 @@
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+use std::sync::Arc;
 use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
 use winit::window;
 use winit::window::WindowId;
 
@@ -22,14 +27,14 @@ pub struct VertexModule {
     buffers: Vec<wgpu::VertexBufferLayout<'static>>,
 }
 impl private::Sealed for VertexModule {}
-impl<'a> ShaderModuleState for VertexModule {}
+impl ShaderModuleState for VertexModule {}
 
 #[derive(Debug)]
 pub struct FragmentModule {
     targets: Vec<Option<wgpu::ColorTargetState>>,
 }
 impl private::Sealed for FragmentModule {}
-impl<'a> ShaderModuleState for FragmentModule {}
+impl ShaderModuleState for FragmentModule {}
 
 #[derive(Debug)]
 pub struct ShaderModule<'a, S: ShaderModuleState> {
@@ -120,6 +125,7 @@ pub struct RenderPipelineBuilder<'a> {
     vertex_module: Option<&'a ShaderModule<'a, VertexModule>>,
     fragment_module: Option<&'a ShaderModule<'a, FragmentModule>>,
     bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    topology: Option<wgpu::PrimitiveTopology>,
 }
 
 impl<'a> RenderPipelineBuilder<'a> {
@@ -143,6 +149,12 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Defaults to [`TriangleList`](wgpu::PrimitiveTopology::TriangleList).
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = Some(topology);
+        self
+    }
+
     pub fn build(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: self.label,
@@ -159,7 +171,9 @@ impl<'a> RenderPipelineBuilder<'a> {
                 .map(|f| Some(f.state()))
                 .unwrap_or(None),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: self
+                    .topology
+                    .unwrap_or(wgpu::PrimitiveTopology::TriangleList),
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
                 cull_mode: Some(wgpu::Face::Back),
@@ -250,7 +264,7 @@ impl<'a, S: BufferState> BufferBuilder<'a, S> {
         self
     }
 
-    pub fn data<T: bytemuck::Pod>(self, data: &[T]) -> BufferBuilder<'a, InitBuffer> {
+    pub fn data<T: bytemuck::Pod>(self, data: &[T]) -> BufferBuilder<'a, InitBuffer<'_>> {
         BufferBuilder {
             usage: self.usage,
             label: self.label,
@@ -293,6 +307,109 @@ impl<'a> BufferBuilder<'a, EmptyBuffer> {
     }
 }
 
+/// Elements closer together than this are uploaded in one write instead of two.
+const DIRTY_RANGE_MERGE_GAP: usize = 64;
+
+/// The ranges of elements that differ between `old` and `new`.
+///
+/// Elements past the end of `old` are always dirty. Ranges separated by
+/// fewer than `merge_gap` clean elements are joined.
+pub fn dirty_ranges<T: bytemuck::Pod>(
+    old: &[T],
+    new: &[T],
+    merge_gap: usize,
+) -> Vec<std::ops::Range<usize>> {
+    let mut ranges: Vec<std::ops::Range<usize>> = vec![];
+
+    let changed = new.iter().enumerate().filter(|&(i, n)| {
+        old.get(i)
+            .is_none_or(|o| bytemuck::bytes_of(o) != bytemuck::bytes_of(n))
+    });
+
+    for (i, _) in changed {
+        match ranges.last_mut() {
+            Some(last) if i - last.end <= merge_gap => last.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+
+    ranges
+}
+
+/// A GPU buffer with a host copy of its contents, so that updates only upload
+/// what actually changed.
+#[derive(Debug)]
+pub struct MirroredBuffer<T> {
+    pub buffer: wgpu::Buffer,
+    mirror: Vec<T>,
+    capacity: usize,
+    usage: wgpu::BufferUsages,
+    label: &'static str,
+}
+
+impl<T: bytemuck::Pod> MirroredBuffer<T> {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> Self {
+        let capacity = capacity.max(1);
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+
+        Self {
+            buffer: Self::create(device, label, usage, capacity),
+            mirror: vec![],
+            capacity,
+            usage,
+            label,
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        BufferBuilder::new(usage)
+            .label(label)
+            .size((capacity * std::mem::size_of::<T>()) as _)
+            .build(device)
+    }
+
+    /// Number of elements last written with [`update`](Self::update).
+    pub fn len(&self) -> usize {
+        self.mirror.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirror.is_empty()
+    }
+
+    /// Makes the buffer hold `data`, writing only the ranges that differ from
+    /// the previous contents. Returns the number of bytes uploaded.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) -> usize {
+        if data.len() > self.capacity {
+            self.capacity = data.len().max(self.capacity * 2);
+            self.buffer = Self::create(device, self.label, self.usage, self.capacity);
+            self.mirror.clear();
+        }
+
+        let size = std::mem::size_of::<T>();
+        let mut uploaded = 0;
+        for range in dirty_ranges(&self.mirror, data, DIRTY_RANGE_MERGE_GAP) {
+            let bytes: &[u8] = bytemuck::cast_slice(&data[range.clone()]);
+            queue.write_buffer(&self.buffer, (range.start * size) as _, bytes);
+            uploaded += bytes.len();
+        }
+
+        self.mirror.clear();
+        self.mirror.extend_from_slice(data);
+        uploaded
+    }
+}
+
 #[derive(Debug)]
 pub struct BindGroup {
     pub layout: wgpu::BindGroupLayout,
@@ -336,11 +453,127 @@ impl<'a> BindGroupBuilder<'a> {
         self
     }
 
+    /// A uniform buffer bound `size` bytes at a time, at an offset given when
+    /// the group is set.
+    pub fn dynamic_uniform_buffer(
+        mut self,
+        buffer: &'a wgpu::Buffer,
+        size: wgpu::BufferSize,
+        visibility: wgpu::ShaderStages,
+    ) -> Self {
+        debug_assert!(buffer.usage().contains(wgpu::BufferUsages::UNIFORM));
+
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: Some(size),
+            },
+            count: None,
+        });
+
+        self.group_entries.push(wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset: 0,
+                size: Some(size),
+            }),
+        });
+
+        self.binding += 1;
+
+        self
+    }
+
+    pub fn storage_buffer(
+        mut self,
+        buffer: &'a wgpu::Buffer,
+        read_only: bool,
+        visibility: wgpu::ShaderStages,
+    ) -> Self {
+        debug_assert!(buffer.usage().contains(wgpu::BufferUsages::STORAGE));
+
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+
+        self.group_entries.push(wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: buffer.as_entire_binding(),
+        });
+
+        self.binding += 1;
+
+        self
+    }
+
+    /// A filterable 2D float texture.
+    pub fn texture(mut self, view: &'a wgpu::TextureView, visibility: wgpu::ShaderStages) -> Self {
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        });
+
+        self.group_entries.push(wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: wgpu::BindingResource::TextureView(view),
+        });
+
+        self.binding += 1;
+
+        self
+    }
+
+    /// A filtering sampler.
+    pub fn sampler(mut self, sampler: &'a wgpu::Sampler, visibility: wgpu::ShaderStages) -> Self {
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+
+        self.group_entries.push(wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        });
+
+        self.binding += 1;
+
+        self
+    }
+
     pub fn label(mut self, label: &'a str) -> Self {
         self.label = Some(label);
         self
     }
 
+    /// Binds the same resources again against an existing, compatible layout,
+    /// for when a resource has to be replaced after pipelines were built.
+    pub fn rebuild(self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: self.group_entries.as_slice(),
+            label: self.label,
+        })
+    }
+
     pub fn build(self, device: &wgpu::Device) -> BindGroup {
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: self.layout_entries.as_slice(),
@@ -357,51 +590,180 @@ impl<'a> BindGroupBuilder<'a> {
     }
 }
 
+/// The handles of a window owned by another application, see
+/// [`WGPUContext::from_raw`].
+#[derive(Debug, Clone, Copy)]
+struct RawHandles {
+    window: RawWindowHandle,
+    display: RawDisplayHandle,
+}
+
+// SAFETY: the handles were taken from a valid window, which `from_raw`
+// requires to outlive the context
+unsafe impl HasRawWindowHandle for RawHandles {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.window
+    }
+}
+
+unsafe impl HasRawDisplayHandle for RawHandles {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.display
+    }
+}
+
+/// What a context draws into.
+#[derive(Debug)]
+enum Target {
+    Window(Arc<window::Window>),
+    Raw(RawHandles),
+    /// Textures of the caller's, see [`WGPUContext::offscreen`].
+    Offscreen,
+}
+
+impl Target {
+    fn window_id(&self) -> Option<WindowId> {
+        match self {
+            Target::Window(window) => Some(window.id()),
+            Target::Raw(_) | Target::Offscreen => None,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct WGPUContext<'guard> {
-    pub window_id: WindowId,
-    pub surface: wgpu::Surface,
+/// The device and the surface of one window. More windows can draw with the
+/// same device through contexts made [`for_window`](Self::for_window).
+///
+/// The context holds on to a winit window, so the surface can never outlive
+/// it. A window of another application, embedded through
+/// [`from_raw`](Self::from_raw), is up to that application to keep alive.
+/// The surface itself can go away while the application is
+/// [suspended](Self::suspend), as mobile platforms take it back then.
+pub struct WGPUContext {
+    /// `None` for windows not made with winit.
+    pub window_id: Option<WindowId>,
+    /// `None` while suspended, and always [offscreen](Self::offscreen).
+    pub surface: Option<wgpu::Surface>,
     pub config: wgpu::SurfaceConfiguration,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub marker: std::marker::PhantomData<&'guard ()>,
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    /// Whether the window is covered by others, as reported by
+    /// [`WindowEvent::Occluded`](winit::event::WindowEvent::Occluded).
+    pub occluded: bool,
+    /// Whether the window was last resized to nothing.
+    minimized: bool,
+    instance: Arc<wgpu::Instance>,
+    adapter: Arc<wgpu::Adapter>,
+    // dropped after the surface
+    target: Target,
 }
 
-impl<'guard> WGPUContext<'guard> {
-    pub async fn from_window(window: &'guard window::Window) -> WGPUContext<'guard> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+impl WGPUContext {
+    pub async fn from_window(window: Arc<window::Window>) -> WGPUContext {
+        let size = window.inner_size();
+        Self::new(Target::Window(window), size).await
+    }
 
-        let window_id = window.id();
-        // The surface needs to live as long as the window that created it.
-        // thats why we need the guard
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+    /// A context drawing into a window another application owns, such as the
+    /// viewport of an editor or a widget of a Qt shell, `size` pixels large.
+    /// The application forwards resizes to [`resize`](Self::resize) and
+    /// asks for frames itself.
+    ///
+    /// # Safety
+    ///
+    /// The window behind `handle` has to stay alive for as long as the
+    /// context.
+    pub async unsafe fn from_raw(
+        handle: &(impl HasRawWindowHandle + HasRawDisplayHandle),
+        size: PhysicalSize<u32>,
+    ) -> WGPUContext {
+        let handles = RawHandles {
+            window: handle.raw_window_handle(),
+            display: handle.raw_display_handle(),
+        };
+        Self::new(Target::Raw(handles), size).await
+    }
 
+    /// A context without a window that draws into textures of `format`,
+    /// `size` pixels large, for rendering in tests and batch jobs. `None`
+    /// without an adapter, as on machines without a GPU or a software
+    /// renderer.
+    pub async fn offscreen(
+        size: PhysicalSize<u32>,
+        format: wgpu::TextureFormat,
+    ) -> Option<WGPUContext> {
+        let instance = Self::instance();
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: None,
                 force_fallback_adapter: false,
             })
-            .await
-            .unwrap();
+            .await?;
+        let (device, queue) = Self::request_device(&adapter).await;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        Some(Self {
+            window_id: None,
+            surface: None,
+            config,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            occluded: false,
+            minimized: false,
+            instance: Arc::new(instance),
+            adapter: Arc::new(adapter),
+            target: Target::Offscreen,
+        })
+    }
+
+    fn instance() -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        })
+    }
 
-        let (device, queue) = adapter
+    async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+        adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
-                    features: wgpu::Features::default(),
+                    // Timestamps only feed the timing overlay, when available.
+                    features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
                     limits: wgpu::Limits::default(),
                 },
                 None, // Trace path
             )
             .await
+            .unwrap()
+    }
+
+    async fn new(target: Target, size: PhysicalSize<u32>) -> WGPUContext {
+        let instance = Self::instance();
+
+        let surface = Self::create_surface(&instance, &target);
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
             .unwrap();
 
+        let (device, queue) = Self::request_device(&adapter).await;
+
         let surface_caps = surface.get_capabilities(&adapter);
 
         let surface_format = surface_caps
@@ -411,40 +773,151 @@ impl<'guard> WGPUContext<'guard> {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
-        let size = window.inner_size();
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
         surface.configure(&device, &config);
 
         Self {
-            window_id,
-            surface,
+            window_id: target.window_id(),
+            surface: Some(surface),
+            config,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            occluded: false,
+            minimized: false,
+            instance: Arc::new(instance),
+            adapter: Arc::new(adapter),
+            target,
+        }
+    }
+
+    /// A context for another window on the same device, with the surface
+    /// configured like this one's.
+    pub fn for_window(&self, window: Arc<window::Window>) -> WGPUContext {
+        let size = window.inner_size();
+        let target = Target::Window(window);
+        let surface = Self::create_surface(&self.instance, &target);
+        let config = wgpu::SurfaceConfiguration {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            ..self.config.clone()
+        };
+        surface.configure(&self.device, &config);
+
+        Self {
+            window_id: target.window_id(),
+            surface: Some(surface),
             config,
-            device,
-            queue,
-            marker: Default::default(),
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+            occluded: false,
+            minimized: false,
+            instance: self.instance.clone(),
+            adapter: self.adapter.clone(),
+            target,
+        }
+    }
+
+    fn create_surface(instance: &wgpu::Instance, target: &Target) -> wgpu::Surface {
+        // SAFETY: the context keeps a winit window alive for as long as the
+        // surface, `from_raw` leaves that to the caller
+        match target {
+            Target::Window(window) => unsafe { instance.create_surface(window.as_ref()) },
+            Target::Raw(handles) => unsafe { instance.create_surface(handles) },
+            Target::Offscreen => unreachable!("offscreen contexts have no surface"),
+        }
+        .unwrap()
+    }
+
+    /// `None` for windows not made with winit.
+    pub fn window(&self) -> Option<&window::Window> {
+        match &self.target {
+            Target::Window(window) => Some(window),
+            Target::Raw(_) | Target::Offscreen => None,
+        }
+    }
+
+    /// Drops the surface, for [`Event::Suspended`](winit::event::Event::Suspended).
+    /// Rendering does nothing until [`resume`](Self::resume).
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    /// Creates the surface again if it was dropped, for
+    /// [`Event::Resumed`](winit::event::Event::Resumed). The window may have
+    /// changed size in between.
+    pub fn resume(&mut self) {
+        if self.surface.is_some() || matches!(self.target, Target::Offscreen) {
+            return;
+        }
+        let surface = Self::create_surface(&self.instance, &self.target);
+        let caps = surface.get_capabilities(&self.adapter);
+        if !caps.formats.contains(&self.config.format) {
+            log::warn!(
+                "surface format changed on resume, keeping {:?}",
+                self.config.format
+            );
+        }
+        // other applications report a change of size with a resize
+        let size = self
+            .window()
+            .map_or(self.size(), |window| window.inner_size());
+        if size.width > 0 && size.height > 0 {
+            self.config.width = size.width;
+            self.config.height = size.height;
         }
+        surface.configure(&self.device, &self.config);
+        self.surface = Some(surface);
     }
 
-    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
-        winit::dpi::PhysicalSize {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize {
             width: self.config.width,
             height: self.config.height,
         }
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
+    /// Presents with `mode` if the surface supports it, returns whether it
+    /// does. Fifo, the default, is always supported.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        let supported = self.surface.as_ref().is_none_or(|surface| {
+            surface
+                .get_capabilities(&self.adapter)
+                .present_modes
+                .contains(&mode)
+        });
+        if supported {
+            self.config.present_mode = mode;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+        }
+        supported
+    }
+
+    /// Whether there is nothing to render to or nothing of it would be seen,
+    /// while suspended, minimized or occluded.
+    pub fn is_hidden(&self) -> bool {
+        self.surface.is_none() || self.minimized || self.occluded
+    }
+
+    /// Keeps the old size while the window is minimized to nothing, surfaces
+    /// can't be empty.
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        self.minimized = new_size.width == 0 || new_size.height == 0;
+        if !self.minimized {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
         }
     }
 }
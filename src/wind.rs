@@ -0,0 +1,122 @@
+//! An ambient breeze, for smoke or leaves drifting about rather than
+//! falling straight down.
+//!
+//! The wind is a velocity field of [tiling noise](crate::noise::simplex),
+//! one layer per component, that wraps around the unit domain without a seam
+//! and slowly churns as its gradients rotate. Particles don't take its
+//! velocity outright, they are dragged towards it, so heavier effects still
+//! win and a gust takes a moment to carry them along.
+
+use crate::noise;
+
+/// Shifts the noise of the vertical component away from the horizontal one,
+/// so they aren't the same field.
+const V_OFFSET: [f32; 2] = [17.3, 9.1];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wind {
+    /// Fastest the wind blows, in domain units per second.
+    pub strength: f32,
+    /// Noise cells across the domain, larger for smaller eddies. Rounded
+    /// to an even number for the field to tile.
+    pub scale: f32,
+    /// How fast the field churns, in radians per second.
+    pub speed: f32,
+    /// Share of the difference to the wind velocity a particle makes up per
+    /// second, no wind at all if 0.
+    pub drag: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            strength: 0.0,
+            scale: 4.0,
+            speed: 0.5,
+            drag: 0.0,
+        }
+    }
+}
+
+impl Wind {
+    pub fn is_active(&self) -> bool {
+        self.drag > 0.0
+    }
+
+    /// [`scale`](Self::scale) as the period of the noise.
+    pub fn cells(&self) -> f32 {
+        noise::tiling_period(self.scale)
+    }
+
+    /// What the integrate kernel gets as `wind`: the strength, the cells,
+    /// the rotation of the gradients at `time` and the drag.
+    pub fn params(&self, time: f32) -> [f32; 4] {
+        match self.is_active() {
+            true => [self.strength, self.cells(), self.speed * time, self.drag],
+            false => [0.0; 4],
+        }
+    }
+
+    /// The wind at `pos` and `time`.
+    pub fn velocity(&self, pos: [f32; 2], time: f32) -> [f32; 2] {
+        velocity(self.params(time), pos)
+    }
+
+    /// Acceleration dragging a particle at `pos` with `vel` towards the
+    /// wind at `time`.
+    pub fn acceleration(&self, pos: [f32; 2], vel: [f32; 2], time: f32) -> [f32; 2] {
+        acceleration(self.params(time), pos, vel)
+    }
+}
+
+/// Mirrors `wind_velocity` in `sorting.ocl`.
+fn velocity([strength, cells, alpha, _]: [f32; 4], pos: [f32; 2]) -> [f32; 2] {
+    let p = pos.map(|x| x * cells);
+    let period = [cells, cells];
+    let (u, _) = noise::simplex(p, period, alpha);
+    let (v, _) = noise::simplex([p[0] + V_OFFSET[0], p[1] + V_OFFSET[1]], period, alpha);
+    [strength * u, strength * v]
+}
+
+/// Mirrors `wind_acceleration` in `sorting.ocl`.
+fn acceleration(params: [f32; 4], pos: [f32; 2], vel: [f32; 2]) -> [f32; 2] {
+    let drag = params[3];
+    if drag <= 0.0 {
+        return [0.0, 0.0];
+    }
+    let wind = velocity(params, pos);
+    [drag * (wind[0] - vel[0]), drag * (wind[1] - vel[1])]
+}
+
+impl std::str::FromStr for Wind {
+    type Err = String;
+
+    /// `<strength>,<scale>,<speed>[,<drag>]`, with a drag of 2 if left out.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|x| x.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("invalid wind `{s}`: {err}"))?;
+        let (strength, scale, speed, drag) = match values[..] {
+            [strength, scale, speed] => (strength, scale, speed, 2.0),
+            [strength, scale, speed, drag] => (strength, scale, speed, drag),
+            _ => {
+                return Err(format!(
+                    "invalid wind `{s}`, expected <strength>,<scale>,<speed>[,<drag>]"
+                ))
+            }
+        };
+        if scale <= 0.0 || drag <= 0.0 {
+            return Err(format!(
+                "invalid wind `{s}`, the scale and drag have to be positive"
+            ));
+        }
+        Ok(Wind {
+            strength,
+            scale,
+            speed,
+            drag,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use bevy::time::{TimePlugin, TimeUpdateStrategy};
+use pos_based_fluids::backend::BackendKind;
+use pos_based_fluids::bevy_plugin::{Fluid, FluidParticle, FluidParticles, FluidPlugin};
+use pos_based_fluids::options::Options;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::TIME_STEP;
+use std::time::Duration;
+
+#[test]
+fn particles_are_entities_that_follow_the_fluid() {
+    let options = Options {
+        backend: BackendKind::Cpu,
+        blocks: vec![FluidBlock::new([0.4, 0.4], [0.6, 0.6], Phase::WATER)],
+        ..Options::default()
+    };
+    let mut app = App::new();
+    app.add_plugins((TimePlugin, FluidPlugin::new(options).with_scale(10.0)))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            TIME_STEP * 1.5,
+        )));
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let sim = app
+        .world
+        .non_send_resource::<Fluid>()
+        .sim
+        .sim
+        .particles()
+        .to_vec();
+    let count = sim.len();
+    let instances = app.world.resource::<FluidParticles>().instances.clone();
+    assert_eq!(instances.len(), count);
+
+    let mut query = app.world.query::<(&FluidParticle, &Transform)>();
+    let particles = query.iter(&app.world).collect::<Vec<_>>();
+    assert_eq!(particles.len(), count);
+    for (particle, transform) in particles {
+        assert!(!particle.removed);
+        let p = instances[particle.index];
+        assert_eq!(transform.translation.truncate(), Vec2::from(p.pos));
+        // interpolated towards the latest step, in world units
+        let latest = Vec2::from(sim[particle.index].pos) * 10.0;
+        assert!(transform.translation.truncate().distance(latest) < 0.5);
+    }
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "render")]
+
+use glam::{Mat4, Vec3};
+use pos_based_fluids::options::AspectMode;
+use pos_based_fluids::render::{self, Camera, WorldTransform};
+
+#[test]
+fn camera_pans_zooms_and_resets() {
+    let size = [100.0, 100.0];
+    let center = |camera: &Camera| camera.to_world([50.0, 50.0], size);
+    let corner = |camera: &Camera| camera.to_world([0.0, 100.0], size);
+    let close = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5;
+
+    let mut camera = Camera::new(1.0);
+    assert!(close(center(&camera), [0.5, 0.5]));
+
+    camera.pan(0.1, -0.2);
+    assert!(close(center(&camera), [0.6, 0.3]));
+
+    camera.zoom(2.0);
+    assert!(close(center(&camera), [0.6, 0.3]));
+    assert!(close(corner(&camera), [0.35, 0.05]));
+
+    camera.reset();
+    assert!(close(corner(&camera), [0.0, 0.0]));
+}
+
+#[test]
+fn pick_camera_fills_the_clip_space_with_the_pixels_around_the_cursor() {
+    let size = [200.0, 100.0];
+    let mut camera = Camera::new(2.0);
+    camera.zoom(1.5);
+    let pick = Mat4::from_cols_array(&camera.pick_raw([120.0, 30.0], size, 10.0));
+    let clip = |cursor: [f32; 2]| {
+        let [x, y] = camera.to_world(cursor, size);
+        pick.project_point3(Vec3::new(x, y, 0.0))
+    };
+    assert!(clip([120.0, 30.0]).truncate().length() < 1e-4);
+    assert!((clip([125.0, 25.0]).truncate() - glam::Vec2::new(1.0, 1.0)).length() < 1e-4);
+}
+
+#[test]
+fn world_transform_fits_the_view_to_the_aspect_ratio_and_round_trips() {
+    let transform = Camera::new(2.0).transform();
+    assert_eq!(transform, WorldTransform::new([-0.5, 0.0], [1.5, 1.0]));
+
+    let clip = transform.to_clip([1.0, 0.25]);
+    assert!((clip[0] - 0.5).abs() < 1e-6 && (clip[1] + 0.5).abs() < 1e-6);
+    assert_eq!(transform.from_clip(clip), [1.0, 0.25]);
+    let matrix = Mat4::from_cols_array(&transform.raw());
+    let projected = matrix.project_point3(Vec3::new(1.0, 0.25, 0.0));
+    assert!((projected.truncate() - glam::Vec2::from(clip)).length() < 1e-5);
+}
+
+#[test]
+fn aspect_modes_fit_fill_or_stretch_the_view() {
+    let transform = |mode| {
+        let mut camera = Camera::new(2.0);
+        camera.set_mode(mode);
+        camera.transform()
+    };
+    let square = |min: f32, max: f32| WorldTransform::new([min, 0.0], [max, 1.0]);
+    assert_eq!(transform(AspectMode::Fit), square(-0.5, 1.5));
+    assert_eq!(transform(AspectMode::Stretch), square(0.0, 1.0));
+    assert_eq!(
+        transform(AspectMode::Fill),
+        WorldTransform::new([0.0, 0.25], [1.0, 0.75])
+    );
+
+    // bars left and right, and the view fills what is left
+    let mut camera = Camera::new(2.0);
+    camera.set_mode(AspectMode::Letterbox);
+    let (offset, size) = camera.viewport([200.0, 100.0]);
+    assert_eq!((offset, size), ([50.0, 0.0], [100.0, 100.0]));
+    camera.set_aspect(size[0] / size[1]);
+    assert_eq!(camera.transform(), square(0.0, 1.0));
+}
+
+#[test]
+fn nearest_hit_prefers_the_center() {
+    // 4 by 4 with a stride of 5, index plus one
+    #[rustfmt::skip]
+    let texels = [
+        3, 0, 0, 0, 9,
+        0, 0, 0, 0, 9,
+        0, 0, 0, 8, 9,
+        0, 0, 0, 0, 9,
+    ];
+    assert_eq!(render::nearest_hit(&texels, 5, 4), Some(7));
+    assert_eq!(render::nearest_hit(&[0; 16], 4, 4), None);
+}
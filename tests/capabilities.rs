@@ -0,0 +1,63 @@
+use pos_based_fluids::capabilities::{DeviceCaps, Svm, Variants};
+
+#[test]
+fn variants_follow_the_device_capabilities() {
+    let caps = DeviceCaps::new(
+        "cl_khr_global_int32_base_atomics cl_khr_fp16  cl_khr_subgroups",
+        32 * 1024,
+        Svm::from_bits(0b11),
+    );
+    assert_eq!(caps.svm, Svm::FineGrain);
+    assert!(caps.fp16() && caps.subgroups() && !caps.has("cl_khr_fp64"));
+
+    let all = Variants::select(&caps, Some(32 * 1024));
+    assert_eq!(
+        all.build_options(),
+        "-D FUSED -D FP16 -D SUBGROUPS -cl-std=CL2.0"
+    );
+    // the grid doesn't fit, or there are too many particles for one work group
+    assert!(!Variants::select(&caps, Some(32 * 1024 + 1)).fused);
+    assert!(!Variants::select(&caps, None).fused);
+
+    let bare = DeviceCaps::new("", 16 * 1024, Svm::from_bits(0));
+    assert_eq!(Variants::select(&bare, None).build_options(), "");
+    assert_eq!(
+        bare.to_string(),
+        "fp16 no, subgroups no, SVM no, 16 KiB local memory"
+    );
+}
+
+#[test]
+fn grid_images_need_image_support_and_room() {
+    let caps = DeviceCaps::new("", 32 * 1024, Svm::None);
+    assert!(
+        !Variants::select(&caps, None)
+            .with_grid_images(&caps, true, [64, 64])
+            .grid_images
+    );
+
+    let caps = caps.with_image2d_max(8192, 8192);
+    let images = Variants::select(&caps, None).with_grid_images(&caps, true, [64 * 16, 64]);
+    assert_eq!(images.build_options(), "-D GRID_IMAGES");
+    assert!(
+        !Variants::select(&caps, None)
+            .with_grid_images(&caps, true, [8193, 64])
+            .grid_images
+    );
+    assert!(
+        !Variants::select(&caps, None)
+            .with_grid_images(&caps, false, [64, 64])
+            .grid_images
+    );
+}
+
+#[test]
+fn fp64_only_when_wanted_and_supported() {
+    let caps = DeviceCaps::new("cl_khr_fp64", 32 * 1024, Svm::None);
+    let fp64 = Variants::select(&caps, None).with_fp64(&caps, true);
+    assert_eq!(fp64.build_options(), "-D FP64");
+    assert!(!Variants::select(&caps, None).with_fp64(&caps, false).fp64);
+
+    let caps = DeviceCaps::new("", 32 * 1024, Svm::None);
+    assert!(!Variants::select(&caps, None).with_fp64(&caps, true).fp64);
+}
@@ -0,0 +1,107 @@
+//! Runs single kernels from `sorting.ocl` on small hand-written inputs.
+//!
+//! Every test that needs OpenCL should start with
+//! `let Some(cl) = KernelHarness::new() else { return };`, so machines
+//! without an OpenCL runtime skip instead of failing.
+
+use opencl3 as cl;
+use opencl3::{command_queue, context, device, kernel, memory, program, types};
+use pos_based_fluids::capabilities::{DeviceCaps, Svm, Variants};
+use std::ffi::c_void;
+
+pub struct KernelHarness {
+    pub context: context::Context,
+    pub queue: command_queue::CommandQueue,
+    pub program: program::Program,
+}
+
+impl KernelHarness {
+    /// Builds `sorting.ocl` on the first GPU, falling back to any OpenCL device.
+    ///
+    /// Returns `None` (and says so on stderr) if there is no device at all.
+    pub fn new() -> Option<Self> {
+        let device_id = [device::CL_DEVICE_TYPE_GPU, device::CL_DEVICE_TYPE_ALL]
+            .into_iter()
+            .find_map(|ty| device::get_all_devices(ty).ok()?.into_iter().next());
+
+        let Some(device_id) = device_id else {
+            eprintln!("no OpenCL device found, skipping kernel test");
+            return None;
+        };
+
+        let device = device::Device::new(device_id);
+        let context = context::Context::from_device(&device).expect("could not create context");
+        let queue = command_queue::CommandQueue::create_default_with_properties(&context, 0, 0)
+            .expect("could not create queue");
+        // every variant the device supports
+        let caps = DeviceCaps::new(
+            &device.extensions().expect("could not query extensions"),
+            device
+                .local_mem_size()
+                .expect("could not query local memory"),
+            Svm::from_bits(device.svm_mem_capability()),
+        );
+        let options = Variants::select(&caps, Some(0)).build_options();
+        let program = program::Program::create_and_build_from_source(
+            &context,
+            pos_based_fluids::PROGRAM_SOURCE,
+            &options,
+        )
+        .unwrap_or_else(|log| panic!("sorting.ocl failed to build:\n{log}"));
+
+        Some(Self {
+            context,
+            queue,
+            program,
+        })
+    }
+
+    pub fn kernel(&self, name: &str) -> kernel::Kernel {
+        kernel::Kernel::create(&self.program, name)
+            .unwrap_or_else(|err| panic!("no kernel named {name}: {err}"))
+    }
+
+    /// A read-write device buffer initialized with `data`.
+    pub fn buffer<T>(&self, data: &[T]) -> memory::Buffer<T> {
+        unsafe {
+            memory::Buffer::<T>::create(
+                &self.context,
+                memory::CL_MEM_READ_WRITE | memory::CL_MEM_COPY_HOST_PTR,
+                data.len(),
+                data.as_ptr() as *mut c_void,
+            )
+            .expect("could not create buffer")
+        }
+    }
+
+    /// Blocking read of the first `len` elements of `buffer`.
+    pub fn read<T: Default + Clone>(&self, buffer: &memory::Buffer<T>, len: usize) -> Vec<T> {
+        let mut data = vec![T::default(); len];
+        unsafe {
+            self.queue
+                .enqueue_read_buffer(buffer, types::CL_BLOCKING, 0, &mut data, &[])
+                .expect("could not read buffer");
+        }
+        data
+    }
+
+    /// Runs `name` over `global_size` work items and waits for it to finish.
+    ///
+    /// `set_args` receives the kernel call and has to set every argument in order.
+    pub fn run(
+        &self,
+        name: &str,
+        global_size: usize,
+        set_args: impl FnOnce(&mut kernel::ExecuteKernel),
+    ) -> cl::Result<()> {
+        let kernel = self.kernel(name);
+        let mut exec = kernel::ExecuteKernel::new(&kernel);
+        set_args(&mut exec);
+
+        let event = unsafe {
+            exec.set_global_work_size(global_size)
+                .enqueue_nd_range(&self.queue)?
+        };
+        event.wait()
+    }
+}
@@ -0,0 +1,16 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+#[cfg(feature = "opencl")]
+pub mod harness;
+
+use pos_based_fluids::sim::Instance;
+
+/// A particle at rest at `(x, y)`.
+pub fn particle(x: f32, y: f32) -> Instance {
+    Instance {
+        pos: [x, y],
+        vel: [0.0, 0.0],
+    }
+}
@@ -0,0 +1,59 @@
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::cpu::CpuState;
+use pos_based_fluids::damping::Damping;
+use pos_based_fluids::scene::{Scene, SceneEdit};
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::stats::ParticleStats;
+
+fn moving(speed: f32) -> Vec<Instance> {
+    (0..10)
+        .map(|i| Instance {
+            pos: [0.1 + 0.08 * i as f32, 0.5],
+            vel: [0.0, speed],
+        })
+        .collect()
+}
+
+#[test]
+fn target_steers_the_energy_down_and_no_further() {
+    let damping = Damping {
+        target: Some(0.5),
+        ..Damping::default()
+    };
+    assert!(damping.is_active());
+    assert!(!Damping::default().is_active());
+    // 2 per particle, four times the target
+    let fast = ParticleStats::from_particles(&moving(2.0));
+    let drag = damping.drag(&fast);
+    assert!(
+        (drag - 0.5 * 4f32.ln() / damping.settle).abs() < 1e-5,
+        "{drag}"
+    );
+    let slow = ParticleStats::from_particles(&moving(0.5));
+    assert_eq!(damping.drag(&slow), 0.0);
+
+    let constant = Damping {
+        rate: 2.0,
+        ..Damping::default()
+    };
+    assert_eq!(constant.drag(&slow), 1.0);
+}
+
+#[test]
+fn damping_brings_the_particles_to_rest() {
+    let scene = Scene::new(vec![Instance {
+        pos: [0.5, 0.2],
+        vel: [0.0, 1.0],
+    }]);
+    let mut cpu = CpuState::new(&scene, &Config::default());
+    cpu.edit(&SceneEdit::Damping(4.0)).unwrap();
+    for _ in 0..60 {
+        cpu.step().unwrap();
+    }
+    let vel = cpu.particles()[0].vel[1];
+    assert!(vel > 0.0 && vel < 0.05, "{vel}");
+
+    cpu.edit(&SceneEdit::Damping(0.0)).unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.particles()[0].vel[1], vel);
+}
@@ -0,0 +1,90 @@
+use pos_based_fluids::backend::{Backend, BackendKind};
+use pos_based_fluids::compare::{self, Divergence};
+#[cfg(feature = "opencl")]
+use pos_based_fluids::opencl::OpenClState;
+use pos_based_fluids::options::Options;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::simulation::Simulation;
+
+const STEPS: usize = 500;
+
+fn options(backend: BackendKind) -> Options {
+    let mut options = Options {
+        backend,
+        blocks: vec![FluidBlock::new([0.1, 0.1], [0.4, 0.6], Phase::WATER)],
+        ..Options::default()
+    };
+    options.config.deterministic = true;
+    options
+}
+
+/// The backend `options` asks for, `None` if it can't start here.
+/// [`BackendKind::create`] would fall back to the cpu backend instead.
+fn backend(scene: &Scene, options: &Options) -> Option<Box<dyn Backend>> {
+    match options.backend {
+        #[cfg(feature = "opencl")]
+        BackendKind::OpenCl => match OpenClState::new(scene, &options.config) {
+            Ok(state) => Some(Box::new(state)),
+            Err(err) => {
+                eprintln!("skipping, no OpenCL device: {err}");
+                None
+            }
+        },
+        #[cfg(not(feature = "opencl"))]
+        BackendKind::OpenCl => {
+            eprintln!("skipping, built without the `opencl` feature");
+            None
+        }
+        kind => Some(kind.create(scene, &options.config).unwrap()),
+    }
+}
+
+/// The particles after [`STEPS`] steps, `None` if the backend can't start
+/// here.
+fn run(options: &Options) -> Option<Simulation> {
+    let scene = options.scene().unwrap();
+    let mut sim = Simulation::Single(backend(&scene, options)?);
+    for _ in 0..STEPS {
+        sim.step().unwrap();
+    }
+    sim.wait_read().unwrap();
+    Some(sim)
+}
+
+#[test]
+fn runs_on_the_cpu_repeat_exactly() {
+    let options = options(BackendKind::Cpu);
+    let first = run(&options).unwrap();
+    assert!(!first.particles().is_empty());
+    let second = run(&options).unwrap();
+    assert_eq!(
+        compare::state_hash(first.particles()),
+        compare::state_hash(second.particles())
+    );
+}
+
+#[test]
+fn runs_on_the_same_device_repeat_exactly() {
+    let options = options(BackendKind::OpenCl);
+    let Some(first) = run(&options) else {
+        return;
+    };
+    let second = run(&options).unwrap();
+    assert_eq!(
+        compare::state_hash(first.particles()),
+        compare::state_hash(second.particles())
+    );
+}
+
+#[test]
+fn devices_stay_close_to_the_cpu() {
+    let Some(device) = run(&options(BackendKind::OpenCl)) else {
+        return;
+    };
+    let host = run(&options(BackendKind::Cpu)).unwrap();
+    // rounding differs between devices, so only the fluid as a whole has to agree
+    let divergence = Divergence::between(STEPS, device.particles(), host.particles());
+    assert!(divergence.max.is_finite(), "{divergence}");
+    assert!(divergence.mean < 0.05, "{divergence}");
+}
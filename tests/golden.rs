@@ -0,0 +1,135 @@
+//! Golden-image tests of the particle shaders: known particles are drawn
+//! offscreen and compared with the reference images in `tests/golden`.
+//!
+//! Missing references are written instead of compared, as are all of them
+//! with `UPDATE_GOLDEN=1` after a change that is meant to look different.
+//! On a mismatch the image drawn is written next to the reference as
+//! `<name>.actual.png`. Machines without a GPU adapter skip the tests.
+
+mod common;
+
+use common::particle;
+use pos_based_fluids::png;
+use pos_based_fluids::render::{self, RenderParams};
+use pos_based_fluids::sim::{self, Instance, SimParams};
+use pos_based_fluids::timelapse::write_png;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+const SIZE: u32 = 64;
+
+/// How far an image may be off its reference, for the differences in
+/// rasterization and blending between adapters.
+struct Tolerance {
+    /// Largest difference of a channel for a pixel to count as the same.
+    channel: u8,
+    /// Share of the pixels that may differ by more.
+    pixels: f32,
+}
+
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 3,
+    pixels: 0.01,
+};
+
+/// Compares `pixels`, `SIZE` squared, with the reference called `name`.
+fn assert_golden(name: &str, pixels: &[u8], tolerance: &Tolerance) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let path = dir.join(format!("{name}.png"));
+    if !path.exists() || std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(File::create(&path).unwrap(), SIZE, SIZE, pixels).unwrap();
+        eprintln!("wrote the reference {}", path.display());
+        return;
+    }
+    let reference = png::read_png(BufReader::new(File::open(&path).unwrap())).unwrap();
+    assert_eq!([reference.width, reference.height], [SIZE, SIZE]);
+
+    let off = pixels
+        .chunks_exact(4)
+        .zip(reference.pixels.chunks_exact(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(*b)
+                .any(|(a, b)| a.abs_diff(*b) > tolerance.channel)
+        })
+        .count();
+    let share = off as f32 / (SIZE * SIZE) as f32;
+    if share > tolerance.pixels {
+        let actual = dir.join(format!("{name}.actual.png"));
+        write_png(File::create(&actual).unwrap(), SIZE, SIZE, pixels).unwrap();
+        panic!(
+            "{off} pixels of {name} differ from the reference, see {}",
+            actual.display()
+        );
+    }
+}
+
+fn params(radius: f32) -> SimParams {
+    SimParams {
+        particle_radius: radius,
+        ..SimParams::default()
+    }
+}
+
+fn render(
+    particles: &[Instance],
+    colors: &[u32],
+    params: &SimParams,
+    style: &RenderParams,
+) -> Option<Vec<u8>> {
+    let pixels = pollster::block_on(render::render_offscreen(
+        particles, colors, params, style, SIZE, SIZE,
+    ));
+    if pixels.is_none() {
+        eprintln!("no GPU adapter found, skipping golden-image test");
+    }
+    pixels
+}
+
+#[test]
+fn particles_are_rings_in_their_colors() {
+    let particles = [
+        particle(0.25, 0.25),
+        particle(0.75, 0.5),
+        particle(0.4, 0.8),
+    ];
+    let colors = [
+        sim::rgba_to_u32(255, 60, 60, 255),
+        sim::rgba_to_u32(60, 255, 60, 255),
+        sim::rgba_to_u32(60, 60, 255, 255),
+    ];
+    let Some(pixels) = render(&particles, &colors, &params(0.15), &RenderParams::default()) else {
+        return;
+    };
+    assert_golden("rings", &pixels, &TOLERANCE);
+}
+
+#[test]
+fn translucent_particles_blend_over_each_other() {
+    let particles = [particle(0.4, 0.5), particle(0.6, 0.5)];
+    let colors = [
+        sim::rgba_to_u32(255, 200, 0, 128),
+        sim::rgba_to_u32(0, 120, 255, 128),
+    ];
+    let Some(pixels) = render(&particles, &colors, &params(0.3), &RenderParams::default()) else {
+        return;
+    };
+    assert_golden("blending", &pixels, &TOLERANCE);
+}
+
+#[test]
+fn discs_clipped_to_the_domain() {
+    let particles = [particle(0.3, 0.5), particle(0.7, 0.5), particle(1.2, 0.5)];
+    let colors = [sim::rgba_to_u32(255, 200, 0, 128); 3];
+    let mut style = RenderParams {
+        radius_scale: 0.5,
+        ..RenderParams::default()
+    };
+    style.set_discs(true);
+    style.set_flag(RenderParams::OPAQUE | RenderParams::CLIP, true);
+    let Some(pixels) = render(&particles, &colors, &params(0.3), &style) else {
+        return;
+    };
+    assert_golden("discs", &pixels, &TOLERANCE);
+}
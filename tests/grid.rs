@@ -0,0 +1,145 @@
+use pos_based_fluids::grid::Grid;
+use proptest::prelude::*;
+
+fn grid() -> impl Strategy<Value = Grid> {
+    (1u32..64).prop_map(Grid::with_cells)
+}
+
+fn inside() -> impl Strategy<Value = f32> {
+    0f32..1f32
+}
+
+/// Positions on or right next to a cell boundary, `k / n` nudged by a few ulps.
+fn on_boundary(grid: Grid) -> impl Strategy<Value = f32> {
+    (0..=grid.n_cells(), -2i32..=2).prop_map(move |(k, ulps)| {
+        let p = k as f32 / grid.n_cells() as f32;
+        f32::from_bits((p.to_bits() as i32 + ulps).max(0) as u32)
+    })
+}
+
+proptest! {
+    #[test]
+    fn inside_positions_map_to_a_valid_cell(grid in grid(), x in inside(), y in inside()) {
+        let [cx, cy] = grid.cell_coords([x, y]).unwrap();
+        prop_assert!(cx < grid.n_cells() && cy < grid.n_cells());
+
+        let cell = grid.cell_index([x, y]).unwrap();
+        prop_assert!((cell as usize) < grid.cell_count());
+        prop_assert_eq!(grid.coords_of(cell), [cx, cy]);
+    }
+
+    #[test]
+    fn outside_positions_have_no_cell(
+        grid in grid(),
+        x in prop_oneof![-10f32..0.0, 1f32..10.0, Just(f32::NAN), Just(f32::INFINITY)],
+        y in inside(),
+    ) {
+        prop_assert_eq!(grid.cell_index([x, y]), None);
+        prop_assert_eq!(grid.cell_index([y, x]), None);
+    }
+
+    #[test]
+    fn boundary_positions_stay_in_bounds(
+        (grid, x, y) in grid().prop_flat_map(|g| (Just(g), on_boundary(g), on_boundary(g)))
+    ) {
+        match grid.cell_coords([x, y]) {
+            Some([cx, cy]) => {
+                let n = grid.n_cells() as f32;
+                prop_assert!(x < 1.0 && y < 1.0);
+                prop_assert!(cx as f32 <= x * n && cy as f32 <= y * n);
+            }
+            None => prop_assert!(x >= 1.0 || y >= 1.0),
+        }
+    }
+
+    #[test]
+    fn neighborhood_covers_everything_within_one_cell(
+        (grid, x, y) in grid().prop_flat_map(|g| (Just(g), on_boundary(g), on_boundary(g))),
+        dx in -1f32..1f32,
+        dy in -1f32..1f32,
+    ) {
+        let h = grid.cell_size();
+        let other = [x + dx * h, y + dy * h];
+
+        if let (Some(cell), Some(other_cell)) = (grid.cell_index([x, y]), grid.cell_index(other)) {
+            prop_assert!(grid.neighbors(cell).any(|c| c == other_cell));
+        }
+    }
+
+    #[test]
+    fn neighbor_relation_is_symmetric(grid in grid(), a in 0u32..4096, b in 0u32..4096) {
+        let a = a % grid.cell_count() as u32;
+        let b = b % grid.cell_count() as u32;
+        prop_assert_eq!(grid.neighbors(a).any(|c| c == b), grid.neighbors(b).any(|c| c == a));
+    }
+}
+
+#[test]
+fn neighbor_counts_respect_the_border() {
+    let grid = Grid::with_cells(4);
+    assert_eq!(grid.neighbors(grid.index_of(0, 0)).count(), 4);
+    assert_eq!(grid.neighbors(grid.index_of(1, 0)).count(), 6);
+    assert_eq!(grid.neighbors(grid.index_of(1, 1)).count(), 9);
+    assert_eq!(grid.neighbors(grid.index_of(3, 3)).count(), 4);
+
+    let single = Grid::with_cells(1);
+    assert_eq!(single.neighbors(0).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn neighbor_cell_does_not_wrap_rows() {
+    let grid = Grid::with_cells(4);
+    assert_eq!(grid.neighbor_cell(grid.index_of(3, 0), 1, 0), None);
+    assert_eq!(grid.neighbor_cell(grid.index_of(0, 1), -1, 0), None);
+    assert_eq!(grid.neighbor_cell(grid.index_of(0, 0), 0, -1), None);
+}
+
+#[test]
+fn periodic_neighbors_wrap_around() {
+    let grid = Grid::with_cells(4).with_periodic([true, false]);
+    assert_eq!(
+        grid.neighbor_cell(grid.index_of(3, 0), 1, 0),
+        Some(grid.index_of(0, 0))
+    );
+    assert_eq!(
+        grid.neighbor_cell(grid.index_of(0, 1), -1, 0),
+        Some(grid.index_of(3, 1))
+    );
+    assert_eq!(grid.neighbor_cell(grid.index_of(0, 0), 0, -1), None);
+    assert_eq!(grid.neighbors(grid.index_of(0, 0)).count(), 6);
+
+    let periodic = Grid::with_cells(4).with_periodic([true, true]);
+    assert_eq!(periodic.neighbors(periodic.index_of(0, 0)).count(), 9);
+}
+
+#[test]
+fn periodic_neighbors_are_unique_on_small_grids() {
+    for n in 1..4 {
+        let grid = Grid::with_cells(n).with_periodic([true, true]);
+        let mut cells: Vec<_> = grid.neighbors(0).collect();
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), grid.neighbors(0).count());
+        assert_eq!(cells.len(), (n * n).min(9) as usize);
+    }
+}
+
+#[test]
+fn periodic_positions_wrap_into_the_domain() {
+    let grid = Grid::with_cells(4).with_periodic([true, false]);
+    assert_eq!(grid.wrap_position([1.25, 1.25]), [0.25, 1.25]);
+    assert_eq!(grid.wrap_position([-0.25, -0.25]), [0.75, -0.25]);
+    assert!(grid.cell_index(grid.wrap_position([-1e-9, 0.5])).is_some());
+
+    let [dx, dy] = grid.wrap_delta([0.95, 0.95], [0.05, 0.05]);
+    assert!((dx + 0.1).abs() < 1e-6);
+    assert!((dy - 0.9).abs() < 1e-6);
+}
+
+#[test]
+fn new_picks_cells_at_least_as_large_as_requested() {
+    assert_eq!(Grid::new(1.0).n_cells(), 1);
+    assert_eq!(Grid::new(2.0).n_cells(), 1);
+    assert_eq!(Grid::new(0.3).n_cells(), 3);
+    assert!(Grid::new(0.3).cell_size() >= 0.3);
+}
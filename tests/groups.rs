@@ -0,0 +1,45 @@
+mod common;
+
+use common::particle;
+use pos_based_fluids::boundary;
+use pos_based_fluids::groups::{Group, Groups};
+use pos_based_fluids::phase::Phase;
+use pos_based_fluids::scene::Scene;
+
+#[test]
+fn regions_and_inlets_fill_groups() {
+    let left = "left=0,0,0.5,1:color=ff0000:phase=oil"
+        .parse::<Group>()
+        .unwrap();
+    let fed = "fed:inlets".parse::<Group>().unwrap();
+    let scene = Scene::new(vec![
+        particle(0.2, 0.5),
+        particle(0.8, 0.5),
+        boundary::REMOVED,
+    ])
+    .with_group(left)
+    .unwrap()
+    .with_group(fed)
+    .unwrap();
+    assert_eq!(scene.group_masks, [1, 0, 0]);
+    assert_eq!(scene.phases[scene.phase_ids[0] as usize], Phase::OIL);
+    assert_eq!(scene.phase_ids[1], 0);
+
+    let mut particles = scene.particles.clone();
+    let mut groups = Groups::new(&scene, &particles);
+    // an inlet fills the free slot while the first particle leaves
+    particles[2] = particle(0.0, 0.5);
+    particles[0] = boundary::REMOVED;
+    groups.update(&particles);
+
+    assert_eq!(groups.members("left"), Vec::<usize>::new());
+    assert_eq!(groups.members("fed"), [2]);
+    assert_eq!(groups.mask("fed"), Some(2));
+
+    let mut colors = vec![0xff00ff00; 3];
+    Groups::new(&scene, &scene.particles).apply_colors(&mut colors);
+    assert_eq!(colors, [0xffff0000, 0xff00ff00, 0xff00ff00]);
+
+    assert!("=0,0,1,1".parse::<Group>().is_err());
+    assert!("a:color=red".parse::<Group>().is_err());
+}
@@ -0,0 +1,41 @@
+use pos_based_fluids::histogram::{Histogram, BINS};
+use pos_based_fluids::sim::Instance;
+
+#[test]
+fn histogram_bins_and_quantiles() {
+    let mut histogram = Histogram::from_values(1.0, [0.0, 0.01, 0.5, 0.99]);
+    // out of range values land in the ends
+    histogram.add(5.0);
+    histogram.add(-1.0);
+    assert_eq!(histogram.count(), 6);
+    assert_eq!(histogram.bins[0], 3);
+    assert_eq!(histogram.bins[BINS / 2], 1);
+    assert_eq!(histogram.bins[BINS - 1], 2);
+
+    assert_eq!(histogram.quantile(0.5), 1.0 / BINS as f32);
+    assert_eq!(histogram.quantile(1.0), 1.0);
+    assert_eq!(Histogram::new(1.0).quantile(0.5), 0.0);
+}
+
+#[test]
+fn speed_histogram_spans_the_fastest_particle() {
+    let particles = [
+        Instance {
+            pos: [0.5, 0.5],
+            vel: [3.0, 4.0],
+        },
+        Instance {
+            pos: [0.5, 0.5],
+            vel: [0.0, 0.0],
+        },
+        // removed
+        Instance {
+            pos: [f32::NAN, f32::NAN],
+            vel: [9.0, 9.0],
+        },
+    ];
+    let histogram = Histogram::speeds(&particles);
+    assert_eq!(histogram.max, 5.0);
+    assert_eq!((histogram.bins[0], histogram.bins[BINS - 1]), (1, 1));
+    assert_eq!(histogram.count(), 2);
+}
@@ -0,0 +1,69 @@
+use pos_based_fluids::backend::{StepTimings, WorkGroupSizes};
+use pos_based_fluids::hud::{FrameTimings, SCALE};
+
+#[test]
+fn timing_bar_stacks_the_parts_in_order() {
+    let timings = FrameTimings {
+        step: Some(StepTimings {
+            grid: SCALE * 0.25,
+            solve: SCALE * 0.5,
+            ..StepTimings::default()
+        }),
+        instances: 0.0,
+        render: None,
+        present: SCALE,
+    };
+    let names = timings
+        .parts()
+        .iter()
+        .map(|part| part.0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "upload",
+            "integrate",
+            "grid",
+            "solve",
+            "readback",
+            "instances",
+            "present"
+        ]
+    );
+
+    let vertices = timings.vertices(0);
+    let run = |color: u32| {
+        let xs = vertices
+            .iter()
+            .filter(|v| v.color == color)
+            .map(|v| v.pos[0]);
+        xs.clone().fold(f32::MAX, f32::min)..xs.fold(f32::MIN, f32::max)
+    };
+    let parts = timings.parts();
+    let (grid, solve, present) = (run(parts[2].2), run(parts[3].2), run(parts[6].2));
+    assert!(grid.end <= solve.start + 1e-6);
+    assert!((solve.end - solve.start - 2.0 * (grid.end - grid.start)).abs() < 1e-4);
+    // clamped to the end of the bar
+    assert!(present.start < present.end && present.end <= 0.97 + 1e-6);
+}
+
+#[test]
+fn summary_names_the_work_group_sizes() {
+    let sizes = WorkGroupSizes {
+        integrate: 256,
+        sort: 128,
+        collide: 64,
+        fused: None,
+    };
+    let mut timings = FrameTimings {
+        step: Some(StepTimings {
+            work_groups: Some(sizes),
+            ..StepTimings::default()
+        }),
+        ..FrameTimings::default()
+    };
+    assert!(timings.summary().ends_with("| work groups 256 / 128 / 64"));
+
+    timings.step = Some(StepTimings::default());
+    assert!(!timings.summary().contains("work groups"));
+}
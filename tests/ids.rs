@@ -0,0 +1,25 @@
+mod common;
+
+use common::particle;
+use pos_based_fluids::boundary;
+use pos_based_fluids::ids::ParticleIds;
+
+#[test]
+fn reused_slots_get_new_ids() {
+    let mut particles = vec![particle(0.1, 0.5), boundary::REMOVED, particle(0.3, 0.5)];
+    let mut ids = ParticleIds::new(&particles);
+    assert_eq!(ids.ids(), [Some(0), None, Some(1)]);
+
+    // the first leaves, an inlet fills both free slots
+    particles[0] = boundary::REMOVED;
+    ids.update(&particles);
+    particles[0] = particle(0.0, 0.5);
+    particles[1] = particle(0.0, 0.5);
+    ids.update(&particles);
+
+    assert_eq!(ids.ids(), [Some(2), Some(3), Some(1)]);
+    assert_eq!(ids.slot(0), None);
+    assert_eq!(ids.slot(1), Some(2));
+    assert_eq!(ids.slot(3), Some(1));
+    assert_eq!(ids.id(2), Some(1));
+}
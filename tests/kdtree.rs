@@ -0,0 +1,99 @@
+mod common;
+
+use common::particle;
+use pos_based_fluids::grid::Grid;
+use pos_based_fluids::kdtree::KdTree;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::verify::{self, GridCells, Missed};
+
+#[test]
+fn kd_tree_finds_the_same_neighbors_as_brute_force() {
+    // a scrambled but deterministic point set
+    let points = (0..500u32)
+        .map(|i| {
+            let x = (i.wrapping_mul(2654435761) % 1000) as f32 / 1000.0;
+            let y = (i.wrapping_mul(40503) % 997) as f32 / 997.0;
+            (i as usize, [x, y])
+        })
+        .collect::<Vec<_>>();
+    let tree = KdTree::new(points.iter().copied().chain([(500, [f32::NAN, 0.5])]));
+    assert_eq!(tree.len(), 500);
+
+    for &(_, pos) in points.iter().step_by(37) {
+        for radius in [0.0, 0.03, 0.2] {
+            let expected = points
+                .iter()
+                .filter(|(_, p)| (p[0] - pos[0]).hypot(p[1] - pos[1]) <= radius)
+                .map(|&(id, _)| id)
+                .collect::<Vec<_>>();
+            assert_eq!(tree.within(pos, radius), expected);
+        }
+    }
+}
+
+/// Sorts like `sort_particles`, dropping particles past `n_per_cell`.
+fn sort(particles: &[Instance], grid: Grid, n_per_cell: u32) -> (Vec<u32>, Vec<i32>) {
+    let mut counts = vec![0; grid.cell_count()];
+    let mut ids = vec![-1; grid.cell_count() * n_per_cell as usize];
+    for (id, p) in particles.iter().enumerate() {
+        let Some(cell) = grid.cell_index(p.pos) else {
+            continue;
+        };
+        let count = &mut counts[cell as usize];
+        if *count < n_per_cell {
+            ids[(cell * n_per_cell + *count) as usize] = id as i32;
+        }
+        *count += 1;
+    }
+    (counts, ids)
+}
+
+#[test]
+fn verify_reports_neighbors_dropped_from_full_cells() {
+    let grid = Grid::with_cells(4).with_periodic([true, false]);
+    let particles = [
+        particle(0.01, 0.1),
+        particle(0.03, 0.1),
+        particle(0.05, 0.1),
+        // across the periodic edge from the first
+        particle(0.99, 0.1),
+        particle(0.6, 0.6),
+    ];
+    let tree = KdTree::from_particles(&particles);
+    let sample = verify::sample(particles.len(), 0);
+    assert_eq!(sample, [0, 1, 2, 3, 4]);
+
+    let (counts, ids) = sort(&particles, grid, 4);
+    let cells = GridCells {
+        grid,
+        n_per_cell: 4,
+        counts: &counts,
+        ids: &ids,
+    };
+    assert_eq!(cells.neighbors(&particles, 0, 0.05), [1, 2, 3]);
+    assert!(verify::check(&particles, &cells, &tree, sample.clone(), 0.05).is_empty());
+
+    // the third particle in the first cell doesn't fit
+    let (counts, ids) = sort(&particles, grid, 2);
+    let cells = GridCells {
+        n_per_cell: 2,
+        counts: &counts,
+        ids: &ids,
+        ..cells
+    };
+    let missed = verify::check(&particles, &cells, &tree, sample, 0.05);
+    assert_eq!(
+        missed,
+        [
+            Missed {
+                particle: 0,
+                neighbors: vec![2]
+            },
+            Missed {
+                particle: 1,
+                neighbors: vec![2]
+            },
+        ]
+    );
+    assert_eq!(missed[0].to_string(), "particle 0 misses neighbors 2");
+}
@@ -0,0 +1,34 @@
+use pos_based_fluids::kernel_cache::{self, KernelCache};
+
+#[test]
+fn keys_change_with_the_device_source_and_options() {
+    let key = kernel_cache::key("gpu/driver 1", "kernel void f() {}", "-D FUSED");
+    assert_eq!(key.len(), 16);
+    assert_eq!(
+        key,
+        kernel_cache::key("gpu/driver 1", "kernel void f() {}", "-D FUSED")
+    );
+    for other in [
+        kernel_cache::key("gpu/driver 2", "kernel void f() {}", "-D FUSED"),
+        kernel_cache::key("gpu/driver 1", "kernel void g() {}", "-D FUSED"),
+        kernel_cache::key("gpu/driver 1", "kernel void f() {}", ""),
+        kernel_cache::key("gpu/driver 1", "kernel void f() {}-D", " FUSED"),
+    ] {
+        assert_ne!(key, other);
+    }
+}
+
+#[test]
+fn stored_binaries_load_again() {
+    let dir = std::env::temp_dir().join(format!("kernel-cache-{}", std::process::id()));
+    let cache = KernelCache::new(&dir);
+    assert_eq!(cache.load("0123"), None);
+
+    cache.store("0123", b"binary").unwrap();
+    assert_eq!(cache.load("0123").as_deref(), Some(&b"binary"[..]));
+    assert_eq!(cache.load("4567"), None);
+    // nothing left over from writing
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
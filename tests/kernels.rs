@@ -0,0 +1,399 @@
+mod common;
+
+use common::harness::KernelHarness;
+use common::particle;
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::buffer_pool::{self, BufferPool};
+use pos_based_fluids::compare;
+use pos_based_fluids::grid::Grid;
+use pos_based_fluids::histogram::{self, Histogram};
+use pos_based_fluids::opencl::{Dispatch, OpenClState};
+use pos_based_fluids::options::Options;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::readback::{Completion, HostMirror};
+use pos_based_fluids::sim::{Instance, SimParams};
+use pos_based_fluids::stats::ParticleStats;
+
+fn params(grid: Grid, n_per_cell: u32, radius: f32, particles: &[Instance]) -> SimParams {
+    SimParams {
+        n_per_cell,
+        particle_radius: radius,
+        sleep_after: 0,
+        ..SimParams::new(&grid, &Config::default(), particles.len())
+    }
+}
+
+struct Sorted {
+    counts: Vec<u32>,
+    ids: Vec<i32>,
+}
+
+fn sort(cl: &KernelHarness, particles: &[Instance], grid: Grid, n_per_cell: u32) -> Sorted {
+    let counts = cl.buffer(&vec![0u32; grid.cell_count()]);
+    let ids = cl.buffer(&vec![-1i32; grid.cell_count() * n_per_cell as usize]);
+    let particle_buffer = cl.buffer(particles);
+    let params = cl.buffer(&[params(grid, n_per_cell, 0.0, particles)]);
+
+    cl.run("sort_particles", particles.len(), |k| unsafe {
+        k.set_arg(&counts)
+            .set_arg(&ids)
+            .set_arg(&particle_buffer)
+            .set_arg(&params);
+    })
+    .unwrap();
+
+    Sorted {
+        counts: cl.read(&counts, grid.cell_count()),
+        ids: cl.read(&ids, grid.cell_count() * n_per_cell as usize),
+    }
+}
+
+/// `sort_particles` then `collide_particles`, returning the particles after.
+fn collide(
+    cl: &KernelHarness,
+    particles: &[Instance],
+    grid: Grid,
+    n_per_cell: u32,
+    radius: f32,
+) -> Vec<Instance> {
+    let sorted = sort(cl, particles, grid, n_per_cell);
+    let counts = cl.buffer(&sorted.counts);
+    let ids = cl.buffer(&sorted.ids);
+    let particle_buffer = cl.buffer(particles);
+    let quiet_steps = cl.buffer(&vec![0u32; particles.len()]);
+    let params = cl.buffer(&[params(grid, n_per_cell, radius, particles)]);
+
+    cl.run("collide_particles", particles.len(), |k| unsafe {
+        k.set_arg(&counts)
+            .set_arg(&ids)
+            .set_arg(&particle_buffer)
+            .set_arg(&params)
+            .set_arg(&quiet_steps);
+    })
+    .unwrap();
+
+    cl.read(&particle_buffer, particles.len())
+}
+
+#[test]
+fn sort_counts_match_the_cpu_grid() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let grid = Grid::with_cells(4);
+    let particles = [
+        particle(0.1, 0.1),
+        particle(0.9, 0.1),
+        particle(0.1, 0.9),
+        particle(0.12, 0.13),
+        particle(0.5, 0.5),
+    ];
+
+    let sorted = sort(&cl, &particles, grid, 4);
+
+    let mut expected = vec![0u32; grid.cell_count()];
+    for p in &particles {
+        expected[grid.cell_index(p.pos).unwrap() as usize] += 1;
+    }
+    assert_eq!(sorted.counts, expected);
+
+    for (id, p) in particles.iter().enumerate() {
+        let cell = grid.cell_index(p.pos).unwrap() as usize;
+        let slots = &sorted.ids[cell * 4..cell * 4 + 4];
+        assert!(
+            slots.contains(&(id as i32)),
+            "particle {id} missing from cell {cell}"
+        );
+    }
+}
+
+#[test]
+fn sort_skips_particles_outside_the_domain() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let grid = Grid::with_cells(2);
+    let particles = [
+        particle(-0.1, 0.5),
+        particle(0.5, 1.0),
+        particle(0.25, 0.25),
+    ];
+
+    let sorted = sort(&cl, &particles, grid, 2);
+
+    assert_eq!(sorted.counts, vec![1, 0, 0, 0]);
+    assert_eq!(sorted.ids.iter().filter(|&&id| id != -1).count(), 1);
+    assert_eq!(sorted.ids[0], 2);
+}
+
+#[test]
+fn sort_does_not_write_past_a_full_cell() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let grid = Grid::with_cells(2);
+    let particles = vec![particle(0.1, 0.1); 5];
+
+    let sorted = sort(&cl, &particles, grid, 2);
+
+    assert_eq!(sorted.counts[0], 5);
+    assert!(sorted.ids[..2].iter().all(|&id| id != -1));
+    assert!(sorted.ids[2..].iter().all(|&id| id == -1));
+}
+
+#[test]
+fn collide_only_touches_overlapping_particles() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let grid = Grid::with_cells(4);
+    let n_per_cell = 4u32;
+    let radius = 0.05f32;
+    // the first two overlap across a cell border, the third is alone
+    let particles = [particle(0.24, 0.5), particle(0.26, 0.5), particle(0.9, 0.9)];
+
+    let out = collide(&cl, &particles, grid, n_per_cell, radius);
+    assert_eq!(out[0].vel[0], 1.0);
+    assert_eq!(out[1].vel[0], 1.0);
+    assert_eq!(out[2].vel[0], 0.0);
+    assert!(out.iter().zip(&particles).all(|(a, b)| a.pos == b.pos));
+}
+
+#[test]
+fn fused_kernel_matches_the_separate_kernels() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let grid = Grid::with_cells(4);
+    let n_per_cell = 4u32;
+    let radius = 0.05f32;
+    let particles = [
+        particle(0.24, 0.5),
+        particle(0.26, 0.5),
+        particle(0.9, 0.9),
+        particle(0.6, 0.1),
+        particle(0.62, 0.12),
+    ];
+
+    let particle_buffer = cl.buffer(&particles);
+    let quiet_steps = cl.buffer(&vec![0u32; particles.len()]);
+    let params = cl.buffer(&[params(grid, n_per_cell, radius, &particles)]);
+    cl.run("sort_and_collide_particles", particles.len(), |k| unsafe {
+        k.set_arg(&particle_buffer)
+            .set_arg_local_buffer(grid.cell_count() * 4)
+            .set_arg_local_buffer(grid.cell_count() * n_per_cell as usize * 4)
+            .set_arg(&params)
+            .set_arg(&quiet_steps)
+            .set_local_work_size(particles.len());
+    })
+    .unwrap();
+
+    let fused = cl.read(&particle_buffer, particles.len());
+    let separate = collide(&cl, &particles, grid, n_per_cell, radius);
+    assert_eq!(fused, separate);
+}
+
+#[test]
+fn reduction_matches_the_host_stats() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let particles: Vec<Instance> = (0..100)
+        .map(|i| Instance {
+            pos: [0.5, 0.5],
+            vel: [i as f32 * 0.01, -(i as f32) * 0.02],
+        })
+        .collect();
+    let expected = ParticleStats::from_particles(&particles);
+
+    let work_size = 16;
+    let groups = 4;
+    let particle_buffer = cl.buffer(&particles);
+    let partials = cl.buffer(&vec![ParticleStats::default(); groups]);
+    let result = cl.buffer(&[ParticleStats::default()]);
+    let scratch = work_size * std::mem::size_of::<ParticleStats>();
+
+    cl.run("reduce_particles", work_size * groups, |k| unsafe {
+        k.set_arg(&particle_buffer)
+            .set_arg(&(particles.len() as u32))
+            .set_arg(&partials)
+            .set_arg_local_buffer(scratch)
+            .set_local_work_size(work_size);
+    })
+    .unwrap();
+
+    cl.run("reduce_partials", work_size, |k| unsafe {
+        k.set_arg(&partials)
+            .set_arg(&(groups as u32))
+            .set_arg(&result)
+            .set_arg_local_buffer(scratch)
+            .set_local_work_size(work_size);
+    })
+    .unwrap();
+
+    let stats = cl.read(&result, 1)[0];
+    assert_eq!(stats.count, expected.count);
+    assert_eq!(stats.max_speed, expected.max_speed);
+    assert!((stats.kinetic_energy - expected.kinetic_energy).abs() < 1e-3);
+    assert!((stats.speed_sum - expected.speed_sum).abs() < 1e-3);
+}
+
+#[test]
+fn speed_histogram_matches_the_host() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+
+    let particles: Vec<Instance> = (0..100)
+        .map(|i| Instance {
+            pos: [0.5, 0.5],
+            vel: [i as f32 * 0.01, 0.0],
+        })
+        .collect();
+    let expected = Histogram::speeds(&particles);
+
+    let work_size = 16;
+    let particle_buffer = cl.buffer(&particles);
+    let bins = cl.buffer(&[0u32; histogram::BINS]);
+    cl.run("histogram_speeds", work_size * 4, |k| unsafe {
+        k.set_arg(&particle_buffer)
+            .set_arg(&(particles.len() as u32))
+            .set_arg(&expected.max)
+            .set_arg(&bins)
+            .set_local_work_size(work_size);
+    })
+    .unwrap();
+
+    assert_eq!(cl.read(&bins, histogram::BINS), expected.bins);
+}
+
+#[test]
+fn dispatch_splits_large_launches_into_whole_work_groups() {
+    let dispatch = Dispatch::new(64, 1000);
+    assert_eq!(dispatch.max_chunk, 960);
+
+    let chunks = dispatch.chunks(2000).collect::<Vec<_>>();
+    assert_eq!(chunks, [(0, 960), (960, 960), (1920, 128)]);
+    assert!(chunks.iter().all(|&(_, size)| size % 64 == 0));
+
+    assert_eq!(
+        Dispatch::new(64, 10).chunks(1).collect::<Vec<_>>(),
+        [(0, 64)]
+    );
+}
+
+#[test]
+fn pooled_buffers_are_reused_within_a_bucket() {
+    assert_eq!(buffer_pool::bucket_bytes(1), buffer_pool::MIN_BUCKET_BYTES);
+    assert_eq!(buffer_pool::bucket_bytes(5000), 8192);
+    assert_eq!(buffer_pool::bucket_bytes(8192), 8192);
+
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+    let flags = opencl3::memory::CL_MEM_READ_WRITE;
+    let mut pool = BufferPool::new(1 << 20);
+    let first = pool.acquire::<Instance>(&cl.context, flags, 600).unwrap();
+    assert_eq!(first.capacity(), 8192 / std::mem::size_of::<Instance>());
+    pool.release(first);
+    assert_eq!(pool.idle_bytes(), 8192);
+
+    // a different count in the same bucket
+    let second = pool.acquire::<u32>(&cl.context, flags, 2000).unwrap();
+    assert_eq!((pool.created, pool.reused), (1, 1));
+    let third = pool.acquire::<u32>(&cl.context, flags, 2000).unwrap();
+    assert_eq!((pool.created, pool.reused), (2, 1));
+
+    let mut small = BufferPool::new(8192);
+    small.release(second);
+    small.release(third);
+    assert_eq!(small.idle_bytes(), 8192);
+}
+
+#[test]
+fn the_particle_buffer_comes_from_the_pool() {
+    let scene = Options {
+        blocks: vec![FluidBlock::new([0.1, 0.1], [0.4, 0.6], Phase::WATER)],
+        ..Options::default()
+    }
+    .scene()
+    .unwrap();
+    let Ok(mut state) = OpenClState::new(&scene, &Config::default()) else {
+        return;
+    };
+    assert_eq!(state.buffer_pool().created, 1);
+    Backend::step(&mut state).unwrap();
+    Backend::wait_read(&mut state).unwrap();
+    assert_eq!(state.particles().len(), scene.particles.len());
+}
+
+#[test]
+fn non_blocking_reads_land_in_the_host_mirror() {
+    let Some(cl) = KernelHarness::new() else {
+        return;
+    };
+    let particles = [particle(0.1, 0.2), particle(0.3, 0.4)];
+    let mut mirror = HostMirror::new(&cl.context, &cl.queue, &[particle(0.0, 0.0); 2]);
+    assert_eq!(mirror.len(), 2);
+
+    let buffer = cl.buffer(&particles);
+    let read = unsafe {
+        cl.queue
+            .enqueue_read_buffer(
+                &buffer,
+                opencl3::types::CL_NON_BLOCKING,
+                0,
+                &mut mirror,
+                &[],
+            )
+            .unwrap()
+    };
+    let read = Completion::new(read).unwrap();
+    read.wait().unwrap();
+    let positions: Vec<_> = mirror.iter().map(|p| p.pos).collect();
+    assert_eq!(positions, [[0.1, 0.2], [0.3, 0.4]]);
+}
+
+#[test]
+fn steps_run_while_the_last_read_is_in_flight() {
+    const STEPS: usize = 20;
+    let options = Options {
+        blocks: vec![FluidBlock::new([0.1, 0.1], [0.4, 0.6], Phase::WATER)],
+        ..Options::default()
+    };
+    let scene = options.scene().unwrap();
+    let config = Config {
+        deterministic: true,
+        ..Config::default()
+    };
+    let Ok(mut waiting) = OpenClState::new(&scene, &config) else {
+        return;
+    };
+    let mut overlapped = OpenClState::new(&scene, &config).unwrap();
+    for _ in 0..STEPS {
+        Backend::step(&mut waiting).unwrap();
+        Backend::wait_read(&mut waiting).unwrap();
+    }
+
+    let before = compare::state_hash(overlapped.particles());
+    Backend::step(&mut overlapped).unwrap();
+    // the read lands in the other mirror, the host keeps what it had
+    if !Backend::read_complete(&mut overlapped).unwrap() {
+        assert_eq!(compare::state_hash(overlapped.particles()), before);
+    }
+    for _ in 1..STEPS {
+        // enqueued with the read of the step before still pending
+        Backend::step(&mut overlapped).unwrap();
+    }
+    Backend::wait_read(&mut overlapped).unwrap();
+    assert_eq!(
+        compare::state_hash(overlapped.particles()),
+        compare::state_hash(waiting.particles())
+    );
+}
@@ -0,0 +1,28 @@
+use pos_based_fluids::measure::{Measurement, Summary};
+
+#[test]
+fn leaves_out_the_warmup_frames() {
+    let mut measurement = Measurement::new(2);
+    for (frame, seconds) in [(0, 1.0), (1, 0.5), (1, 0.5), (2, 0.01), (3, 0.03)] {
+        measurement.record(frame, seconds);
+    }
+    let summary = measurement.summary().unwrap();
+    assert_eq!(summary.count, 2);
+    assert!((summary.mean - 0.02).abs() < 1e-6);
+    assert_eq!(summary.max, 0.03);
+
+    assert_eq!(Measurement::new(5).summary(), None);
+}
+
+#[test]
+fn percentiles_take_the_nearest_rank() {
+    let samples = (1..=100).rev().map(|ms| ms as f32).collect::<Vec<_>>();
+    let summary = Summary::of(&samples).unwrap();
+    assert_eq!(summary.p50, 50.0);
+    assert_eq!(summary.p95, 95.0);
+    assert_eq!(summary.p99, 99.0);
+    assert_eq!(summary.max, 100.0);
+
+    let single = Summary::of(&[3.0]).unwrap();
+    assert_eq!((single.p50, single.p99), (3.0, 3.0));
+}
@@ -0,0 +1,76 @@
+// the expected ranges are lists of ranges, not of the numbers in them
+#![allow(clippy::single_range_in_vec_init)]
+
+use pos_based_fluids::wgpu_utils::{dirty_ranges, MirroredBuffer, WGPUContext};
+use winit::dpi::PhysicalSize;
+
+/// `old` with the elements in `ranges` changed.
+fn written(old: &[u32], ranges: &[std::ops::Range<usize>]) -> Vec<u32> {
+    let mut new = old.to_vec();
+    for range in ranges {
+        for value in &mut new[range.clone()] {
+            *value += 1;
+        }
+    }
+    new
+}
+
+#[test]
+fn adjacent_writes_are_one_range() {
+    let old = vec![0u32; 100];
+    let new = written(&old, &[10..20, 20..30]);
+    assert_eq!(dirty_ranges(&old, &new, 0), [10..30]);
+}
+
+#[test]
+fn overlapping_writes_are_one_range() {
+    let old = vec![0u32; 100];
+    let new = written(&old, &[10..25, 20..30]);
+    assert_eq!(dirty_ranges(&old, &new, 0), [10..30]);
+    assert!(dirty_ranges(&new, &new, 0).is_empty());
+}
+
+#[test]
+fn disjoint_writes_merge_across_small_gaps() {
+    let old = vec![0u32; 100];
+    let new = written(&old, &[10..20, 25..30, 80..90]);
+    assert_eq!(dirty_ranges(&old, &new, 0), [10..20, 25..30, 80..90]);
+    assert_eq!(dirty_ranges(&old, &new, 5), [10..30, 80..90]);
+    assert_eq!(dirty_ranges(&old, &new, 4), [10..20, 25..30, 80..90]);
+    assert_eq!(dirty_ranges(&old, &new, 64), [10..90]);
+}
+
+#[test]
+fn full_writes_and_growth_are_dirty() {
+    let old = vec![0u32; 100];
+    let new = written(&old, &[0..100]);
+    assert_eq!(dirty_ranges(&old, &new, 0), [0..100]);
+    assert_eq!(dirty_ranges(&[], &new, 0), [0..100]);
+    // past the end of `old`, even where unchanged
+    let longer = vec![0u32; 120];
+    assert_eq!(dirty_ranges(&old, &longer, 0), [100..120]);
+    assert!(dirty_ranges(&longer, &old, 0).is_empty());
+}
+
+#[test]
+fn updates_upload_only_what_changed() {
+    let size = PhysicalSize::new(1, 1);
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let Some(context) = pollster::block_on(WGPUContext::offscreen(size, format)) else {
+        eprintln!("no GPU adapter found, skipping");
+        return;
+    };
+    let (device, queue) = (&context.device, &context.queue);
+    let mut buffer = MirroredBuffer::new(device, "test", wgpu::BufferUsages::VERTEX, 100);
+
+    let data = vec![0u32; 100];
+    assert_eq!(buffer.update(device, queue, &data), 400);
+    assert_eq!(buffer.update(device, queue, &data), 0);
+    let data = written(&data, &[10..20, 30..35]);
+    assert_eq!(buffer.update(device, queue, &data), 25 * 4);
+    assert_eq!(buffer.len(), 100);
+
+    // growing past the capacity makes a new buffer, written in full
+    let grown = vec![1u32; 150];
+    assert_eq!(buffer.update(device, queue, &grown), 600);
+}
@@ -0,0 +1,53 @@
+use pos_based_fluids::neighbors::CellList;
+use pos_based_fluids::phase::Phase;
+use pos_based_fluids::plastic::{Bonds, Plastic};
+use pos_based_fluids::sim::Instance;
+
+fn pair(dist: f32) -> Vec<Instance> {
+    [0.5 - dist / 2.0, 0.5 + dist / 2.0]
+        .map(|x| Instance {
+            pos: [x, 0.5],
+            vel: [0.0; 2],
+        })
+        .to_vec()
+}
+
+fn bonded(particles: &[Instance]) -> Bonds {
+    let mut bonds = Bonds::new(&[Phase::WATER, Phase::SNOW], &[1, 1]);
+    let mut cells = CellList::new(0.05);
+    cells.build(particles);
+    bonds.stick(&cells, particles);
+    bonds
+}
+
+#[test]
+fn bonds_yield_then_break_when_stretched() {
+    let snow = Plastic::SNOW;
+    let mut particles = pair(0.02);
+    let mut bonds = bonded(&particles);
+    assert_eq!(bonds.bonds().len(), 1);
+
+    // past yield but not breaking: the bond keeps some of the stretch
+    particles[1].pos[0] += 0.02 * 2.0 * snow.yield_strain;
+    bonds.project(&mut particles, 1.0 / 60.0);
+    let rest = bonds.bonds()[0].rest;
+    assert!(
+        rest > 0.02 && rest < 0.02 * (1.0 + 2.0 * snow.yield_strain),
+        "{rest}"
+    );
+
+    particles[1].pos[0] += 0.02 * 2.0 * snow.break_strain;
+    bonds.project(&mut particles, 1.0 / 60.0);
+    assert!(bonds.bonds().is_empty());
+}
+
+#[test]
+fn only_plastic_phases_bond() {
+    let particles = pair(0.02);
+    let mut bonds = Bonds::new(&[Phase::WATER, Phase::SNOW], &[0, 1]);
+    assert!(bonds.is_active());
+    let mut cells = CellList::new(0.05);
+    cells.build(&particles);
+    bonds.stick(&cells, &particles);
+    assert!(bonds.bonds().is_empty());
+}
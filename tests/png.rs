@@ -0,0 +1,50 @@
+use pos_based_fluids::png::read_png;
+use pos_based_fluids::timelapse::write_png;
+
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+    bytes.extend(kind);
+    bytes.extend(data);
+    // not checked when reading
+    bytes.extend([0; 4]);
+    bytes
+}
+
+#[test]
+fn reads_back_what_the_timelapse_writes() {
+    let pixels = [255, 0, 0, 255, 0, 0, 255, 128, 1, 2, 3, 4, 5, 6, 7, 8];
+    let mut bytes = vec![];
+    write_png(&mut bytes, 2, 2, &pixels).unwrap();
+
+    let image = read_png(&bytes[..]).unwrap();
+    assert_eq!((image.width, image.height), (2, 2));
+    assert_eq!(image.pixels, pixels);
+    // bottom row first
+    assert_eq!(image.packed()[0], 0x04010203);
+}
+
+#[test]
+fn undoes_row_filters() {
+    // 2x2 RGB, the first row filtered with sub, the second with paeth
+    let rows = [
+        1, 10, 20, 30, 5, 5, 5, //
+        4, 1, 1, 1, 2, 2, 2,
+    ];
+    let mut header = vec![];
+    header.extend(2u32.to_be_bytes());
+    header.extend(2u32.to_be_bytes());
+    header.extend([8, 2, 0, 0, 0]);
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    bytes.extend(chunk(b"IHDR", &header));
+    bytes.extend(chunk(
+        b"IDAT",
+        &miniz_oxide::deflate::compress_to_vec_zlib(&rows, 6),
+    ));
+    bytes.extend(chunk(b"IEND", &[]));
+
+    let image = read_png(&bytes[..]).unwrap();
+    assert_eq!(
+        image.pixels,
+        [10, 20, 30, 255, 15, 25, 35, 255, 11, 21, 31, 255, 17, 27, 37, 255]
+    );
+}
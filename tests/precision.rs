@@ -0,0 +1,59 @@
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::cpu::CpuState;
+use pos_based_fluids::precision::Precise;
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::TIME_STEP;
+
+#[test]
+fn precise_takes_what_changed_in_single_precision() {
+    let mut p = Instance {
+        pos: [0.5, 0.25],
+        vel: [0.1, 0.0],
+    };
+    let mut precise = Precise::of(&p);
+    precise.kick(&mut p.vel, [1.0 / 1024.0, 0.0], 1.0 / 1024.0);
+    assert_eq!(precise.vel[0], 0.1f32 as f64 + 1.0 / (1 << 20) as f64);
+    assert_eq!(p.vel[0], precise.vel[0] as f32);
+
+    // a collision moves the particle, the rest keeps its extra digits
+    p.pos[1] = 0.75;
+    let kept = precise.vel;
+    precise.sync(&p);
+    assert_eq!(precise.pos, [0.5, 0.75]);
+    assert_eq!(precise.vel, kept);
+}
+
+/// Where a particle starting at rest in the middle ends up after `steps`
+/// steps of a gravity too weak to move it in single precision at first.
+fn drift(double_precision: bool, steps: u32) -> f32 {
+    let particle = Instance {
+        pos: [0.5, 0.5],
+        vel: [0.0, 0.0],
+    };
+    let scene = Scene::new(vec![particle]).with_gravity([1e-6, 0.0]);
+    let config = Config {
+        sleep_after: 0,
+        double_precision,
+        ..Config::default()
+    };
+    let mut cpu = CpuState::new(&scene, &config);
+    for _ in 0..steps {
+        cpu.step().unwrap();
+    }
+    cpu.particles()[0].pos[0]
+}
+
+#[test]
+fn double_precision_accumulates_small_steps() {
+    let steps = 120;
+    let dt = TIME_STEP as f64;
+    let n = steps as f64;
+    let exact = 0.5 + 1e-6 * dt * dt * n * (n + 1.0) / 2.0;
+
+    let single = (drift(false, steps) as f64 - exact).abs();
+    let double = (drift(true, steps) as f64 - exact).abs();
+    // no further off than rounding the result
+    assert!(double < 3e-8, "{double:e}");
+    assert!(double < single / 10.0, "{double:e} vs {single:e}");
+}
@@ -0,0 +1,113 @@
+use pos_based_fluids::boundary::{Boundaries, Boundary, Edge, Wall};
+use pos_based_fluids::geometry::Polygon;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::probe::{Heatmap, Probe, ProbeParams, Probes};
+use pos_based_fluids::solid::SolidParticles;
+
+#[test]
+fn density_is_one_inside_fluid_at_rest() {
+    let mut particles = FluidBlock::new([0.2, 0.2], [0.6, 0.6], Phase::WATER).particles();
+    particles.iter_mut().for_each(|p| p.vel = [0.5, 0.0]);
+    let probes = vec![
+        Probe::point("inside", [0.4, 0.4]),
+        "outside=0.9,0.9".parse().unwrap(),
+    ];
+    let mut probes = Probes::new(ProbeParams::default(), probes);
+    probes.record(0.0, &particles);
+
+    let inside = probes.series("inside").unwrap()[0].samples[0];
+    assert!((inside.density - 1.0).abs() < 0.05, "{inside:?}");
+    assert!((inside.vel[0] - 0.5).abs() < 1e-5);
+    let outside = probes.series("outside").unwrap()[0].samples[0];
+    assert_eq!(outside.density, 0.0);
+    assert_eq!(outside.vel, [0.0; 2]);
+}
+
+#[test]
+fn csv_has_a_row_per_point_and_time() {
+    let particles = FluidBlock::new([0.2, 0.2], [0.6, 0.6], Phase::WATER).particles();
+    let line: Probe = "gauge=0.1,0.4,0.7,0.4:4".parse().unwrap();
+    assert_eq!(line.points.len(), 4);
+    let mut probes = Probes::new(ProbeParams::default(), vec![line]);
+    probes.record(0.0, &particles);
+    probes.record(0.1, &particles);
+
+    let mut csv = vec![];
+    probes.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("probe,point,x,y,time,density,pressure,vx,vy")
+    );
+    assert_eq!(lines.count(), 8);
+}
+
+#[test]
+fn ghosts_fill_in_the_density_at_a_wall() {
+    let particles = FluidBlock::new([0.0, 0.0], [0.6, 0.6], Phase::WATER).particles();
+    let probe = || vec![Probe::point("floor", [0.31, 0.01])];
+    let density = |probes: &mut Probes| {
+        probes.record(0.0, &particles);
+        probes.series("floor").unwrap()[0].samples[0].density
+    };
+
+    let without = density(&mut Probes::new(ProbeParams::default(), probe()));
+    assert!(without < 0.8, "{without}");
+
+    let mut walls = Boundaries::default();
+    for edge in Edge::ALL {
+        walls.set(edge, Boundary::Wall(Wall::default()));
+    }
+    let with = density(&mut Probes::new(ProbeParams::default(), probe()).with_boundaries(walls));
+    assert!((with - 1.0).abs() < 0.05, "{with}");
+}
+
+#[test]
+fn obstacles_fill_in_the_density_next_to_them() {
+    let particles = FluidBlock::new([0.2, 0.2], [0.6, 0.6], Phase::WATER).particles();
+    let edge = particles.iter().fold(0.0f32, |max, p| max.max(p.pos[0]));
+    let params = ProbeParams::default();
+    // the obstacle starts where the next column of fluid would be
+    let wall = Polygon::rect([edge + params.rest_spacing, 0.0], [1.0, 1.0]);
+    let solids = SolidParticles::sample(&[wall], 0.01, params.radius, params.rest_spacing);
+    let probe = || vec![Probe::point("edge", [edge, 0.4])];
+
+    let mut bare = Probes::new(params.clone(), probe());
+    let mut solid = Probes::new(params, probe()).with_solids(solids);
+    bare.record(0.0, &particles);
+    solid.record(0.0, &particles);
+
+    let bare = bare.series("edge").unwrap()[0].samples[0];
+    let solid = solid.series("edge").unwrap()[0].samples[0];
+    assert!(bare.density < 0.8, "{bare:?}");
+    assert!((solid.density - 1.0).abs() < 0.15, "{solid:?}");
+}
+
+#[test]
+fn heatmap_shows_where_the_fluid_is_compressed() {
+    let at_rest = FluidBlock::new([0.0, 0.0], [0.5, 0.5], Phase::WATER).particles();
+    let squeezed = FluidBlock::new([0.5, 0.0], [0.75, 0.5], Phase::WATER)
+        .with_spacing(0.015)
+        .particles();
+    let particles = [at_rest, squeezed].concat();
+    let mut probes = Probes::new(ProbeParams::default(), vec![]);
+    // cell (x, y) is centered on ((x + 0.5) / 10, (y + 0.5) / 10)
+    let value = |grid: &pos_based_fluids::field::ScalarGrid, x: usize, y: usize| {
+        grid.values[x + y * grid.resolution as usize]
+    };
+
+    let pressure = probes.heatmap(&particles, Heatmap::Pressure, 10);
+    assert_eq!(pressure.values.len(), 100);
+    assert!(value(&pressure, 2, 2) < 0.05);
+    assert!(value(&pressure, 6, 2) > 0.3);
+    assert_eq!(value(&pressure, 9, 9), 0.0);
+
+    let error = probes.heatmap(&particles, Heatmap::DensityError, 10);
+    assert!(value(&error, 2, 2).abs() < 0.05);
+    assert!(value(&error, 6, 2) > 0.3);
+    // thinned out at the free surface, nothing away from the fluid
+    assert!(value(&error, 2, 4) < 0.0);
+    assert_eq!(value(&error, 9, 9), 0.0);
+    assert!("speed".parse::<Heatmap>().is_err());
+}
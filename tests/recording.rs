@@ -0,0 +1,38 @@
+use pos_based_fluids::recording::{Playback, Recorder};
+use pos_based_fluids::sim::Instance;
+
+#[test]
+fn frames_round_trip() {
+    let particles = vec![
+        Instance {
+            pos: [0.25, 0.5],
+            vel: [-1.0, 2.0],
+        },
+        Instance {
+            pos: [0.75, 0.125],
+            vel: [0.0, 0.5],
+        },
+    ];
+    let mut bytes = vec![];
+    let mut recorder = Recorder::new(&mut bytes).unwrap();
+    recorder
+        .write(0, &particles, &[1, 2], &[Some(0), Some(7)])
+        .unwrap();
+    recorder
+        .write(3, &particles[..1], &[0xff00ff00], &[None])
+        .unwrap();
+    recorder.flush().unwrap();
+
+    let frames = Playback::new(&bytes[..]).unwrap().read_all().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].colors, [1, 2]);
+    assert_eq!(frames[0].particles[1].pos, [0.75, 0.125]);
+    assert_eq!(frames[0].particles[0].vel, [-1.0, 2.0]);
+    assert_eq!(frames[0].ids, [Some(0), Some(7)]);
+    assert_eq!(frames[1].step, 3);
+    assert_eq!(frames[1].particles.len(), 1);
+    assert_eq!(frames[1].colors, [0xff00ff00]);
+    assert_eq!(frames[1].ids, [None]);
+
+    assert!(Playback::new(&b"nope"[..]).is_err());
+}
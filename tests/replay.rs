@@ -0,0 +1,25 @@
+use pos_based_fluids::replay::{InputRecorder, Replay};
+use pos_based_fluids::simulation::Command;
+
+#[test]
+fn recorded_input_replays_at_its_steps() {
+    let mut log = vec![];
+    let mut recorder = InputRecorder::new(&mut log);
+    let paint = Command::Paint {
+        pos: [0.5, 0.25],
+        radius: 0.05,
+        value: 1.0,
+    };
+    recorder.write(3, &paint).unwrap();
+    recorder.write(3, &Command::SetPaused(true)).unwrap();
+    recorder.write(10, &Command::ToggleStreamlines).unwrap();
+
+    let mut replay: Replay = String::from_utf8(log).unwrap().parse().unwrap();
+    assert!(replay.due(2).is_empty());
+    assert_eq!(replay.due(3), [paint]);
+    // a step that was skipped still gets its commands
+    assert_eq!(replay.due(11), [Command::ToggleStreamlines]);
+    assert!(replay.is_empty());
+
+    assert!("5 paint 0.5".parse::<Replay>().is_err());
+}
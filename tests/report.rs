@@ -0,0 +1,58 @@
+use pos_based_fluids::backend::{Config, StepTimings};
+use pos_based_fluids::report::{Metadata, Report, SAMPLE_EVERY};
+use pos_based_fluids::stats::ParticleStats;
+
+fn stats(kinetic_energy: f32) -> ParticleStats {
+    ParticleStats {
+        kinetic_energy,
+        count: 100,
+        ..ParticleStats::default()
+    }
+}
+
+#[test]
+fn summarizes_the_run_and_samples_it_sparsely() {
+    let mut report = Report::default();
+    let timings = StepTimings {
+        solve: 0.002,
+        ..StepTimings::default()
+    };
+    for step in 1..=25 {
+        let energy = 2.0 - step as f32 / 25.0;
+        let error = if step == 7 { 0.3 } else { 0.01 };
+        report.record(step, 1, &stats(energy), error, 0.004, Some(&timings));
+    }
+
+    assert_eq!(report.steps, 25);
+    assert_eq!(report.max_density_error, 0.3);
+    // from 1.96 down to 1
+    assert!((report.energy_drift() - (1.0 - 1.96) / 1.96).abs() < 1e-5);
+    assert!((report.mean_step_time() - 0.004).abs() < 1e-6);
+    let solve = report.mean_timings().unwrap().solve;
+    assert!((solve - 0.002).abs() < 1e-6, "{solve}");
+    let steps = report.samples.iter().map(|s| s.step).collect::<Vec<_>>();
+    assert_eq!(steps, [1, 1 + SAMPLE_EVERY, 1 + 2 * SAMPLE_EVERY]);
+}
+
+#[test]
+fn writes_json_with_the_metadata() {
+    let mut report = Report::default();
+    report.record(1, 1, &stats(1.0), f32::NAN, 0.001, None);
+    let metadata = Metadata {
+        backend: "cpu",
+        device: Some("a \"quoted\" device".into()),
+        config: Config::default(),
+    };
+    let mut out = vec![];
+    report.write_json(&mut out, &metadata).unwrap();
+    let json = String::from_utf8(out).unwrap();
+
+    assert!(json.contains("\"backend\": \"cpu\""), "{json}");
+    assert!(
+        json.contains("\"device\": \"a \\\"quoted\\\" device\""),
+        "{json}"
+    );
+    assert!(json.contains("\"timings\": null"), "{json}");
+    assert!(json.contains("\"density_error\": null"), "{json}");
+    assert!(json.trim_end().ends_with('}'));
+}
@@ -0,0 +1,71 @@
+use pos_based_fluids::backend::BackendKind;
+use pos_based_fluids::options::Options;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::rewind::RewindBuffer;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::simulation::Simulation;
+use std::mem::size_of;
+
+fn row(x: f32) -> Vec<Instance> {
+    vec![
+        Instance {
+            pos: [x, 0.5],
+            vel: [0.0, 0.0],
+        };
+        4
+    ]
+}
+
+#[test]
+fn rewind_buffer_keeps_to_its_budget() {
+    let size = 4 * size_of::<Instance>();
+    let mut rewind = RewindBuffer::new(3 * size);
+    for step in 0..5 {
+        rewind.push(step, &row(step as f32));
+    }
+    assert_eq!(rewind.range(), Some((2, 4)));
+    assert_eq!(rewind.bytes(), 3 * size);
+
+    let (step, particles) = rewind.rewind(1).unwrap();
+    assert_eq!((step, particles[0].pos[0]), (3, 3.0));
+    // not as far back as asked, the oldest is kept
+    assert_eq!(rewind.rewind(10).unwrap().0, 2);
+    assert_eq!(rewind.len(), 1);
+
+    let mut tiny = RewindBuffer::new(size - 1);
+    tiny.push(0, &row(0.0));
+    assert!(tiny.is_empty() && tiny.rewind(1).is_none());
+}
+
+fn state(sim: &Simulation) -> Vec<([f32; 2], [f32; 2])> {
+    sim.instances().iter().map(|p| (p.pos, p.vel)).collect()
+}
+
+#[test]
+fn restored_simulation_steps_the_same_again() {
+    let options = Options {
+        backend: BackendKind::Cpu,
+        blocks: vec![FluidBlock::new([0.3, 0.3], [0.6, 0.6], Phase::WATER)],
+        ..Options::default()
+    };
+    let scene = options.scene().unwrap();
+    let mut sim = Simulation::new(&scene, &options).unwrap();
+    for _ in 0..5 {
+        sim.step().unwrap();
+    }
+    let saved = sim.instances();
+    for _ in 0..10 {
+        sim.step().unwrap();
+    }
+    let first = state(&sim);
+
+    sim.restore(&saved, 5).unwrap();
+    assert_eq!(
+        state(&sim),
+        saved.iter().map(|p| (p.pos, p.vel)).collect::<Vec<_>>()
+    );
+    for _ in 0..10 {
+        sim.step().unwrap();
+    }
+    assert_eq!(state(&sim), first);
+}
@@ -0,0 +1,71 @@
+use pos_based_fluids::rng::Rng;
+
+#[test]
+fn same_seed_and_stream_give_the_same_numbers() {
+    let numbers = |mut rng: Rng| (0..16).map(|_| rng.next_u32()).collect::<Vec<_>>();
+    assert_eq!(numbers(Rng::new(7)), numbers(Rng::new(7)));
+    assert_ne!(numbers(Rng::new(7)), numbers(Rng::new(8)));
+    assert_ne!(
+        numbers(Rng::with_stream(7, 1)),
+        numbers(Rng::with_stream(7, 2))
+    );
+
+    let mut parent = Rng::new(7);
+    let (a, b) = (parent.split(), parent.split());
+    assert_ne!(numbers(a), numbers(b));
+}
+
+#[test]
+fn floats_are_uniform_in_the_unit_interval() {
+    let mut rng = Rng::new(42);
+    let n = 100_000;
+    let mut bins = [0u32; 10];
+    let mut sum = 0.0f64;
+    for _ in 0..n {
+        let x = rng.next_f32();
+        assert!((0.0..1.0).contains(&x));
+        bins[(x * 10.0) as usize] += 1;
+        sum += x as f64;
+    }
+    assert!((sum / n as f64 - 0.5).abs() < 0.005);
+    // chi-squared with 9 degrees of freedom, far beyond the 99.9th percentile at 27.9
+    let expected = n as f64 / 10.0;
+    let chi_sq: f64 = bins
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum();
+    assert!(chi_sq < 27.9, "{chi_sq}");
+
+    let range = (0..1000).map(|_| rng.range(-2.0, 3.0));
+    assert!(range.into_iter().all(|x| (-2.0..3.0).contains(&x)));
+}
+
+#[test]
+fn below_covers_every_value_evenly() {
+    let mut rng = Rng::new(3);
+    let mut counts = [0u32; 7];
+    for _ in 0..70_000 {
+        counts[rng.below(7) as usize] += 1;
+    }
+    assert!(
+        counts.iter().all(|&c| (9_500..10_500).contains(&c)),
+        "{counts:?}"
+    );
+    assert_eq!(rng.below(0), 0);
+    assert_eq!(rng.below(1), 0);
+}
+
+#[test]
+fn streams_are_uncorrelated() {
+    // neighboring streams of one seed, like the particles of a block take
+    let n = 10_000;
+    let mut a = Rng::with_stream(1, 0);
+    let mut b = Rng::with_stream(1, 1);
+    let pairs = (0..n)
+        .map(|_| (a.next_f32() as f64 - 0.5, b.next_f32() as f64 - 0.5))
+        .collect::<Vec<_>>();
+    let covariance = pairs.iter().map(|(x, y)| x * y).sum::<f64>() / n as f64;
+    // the variance of each is 1/12
+    let correlation = covariance * 12.0;
+    assert!(correlation.abs() < 0.05, "{correlation}");
+}
@@ -0,0 +1,65 @@
+use pos_based_fluids::geometry::Polygon;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::sampling::{self, Sampling};
+
+fn min_distance(points: &[[f32; 2]]) -> f32 {
+    let mut min = f32::MAX;
+    for (i, a) in points.iter().enumerate() {
+        for b in &points[i + 1..] {
+            min = min.min(((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt());
+        }
+    }
+    min
+}
+
+#[test]
+fn samplings_keep_their_distance_at_about_the_lattice_density() {
+    let (min, max, spacing) = ([0.0, 0.0], [0.5, 0.4], 0.02);
+    let lattice = Sampling::Lattice.sample(min, max, spacing, 1);
+    for sampling in [Sampling::Jittered, Sampling::Poisson] {
+        let points = sampling.sample(min, max, spacing, 1);
+        assert_eq!(points, sampling.sample(min, max, spacing, 1));
+        let ratio = points.len() as f32 / lattice.len() as f32;
+        assert!((0.9..1.1).contains(&ratio), "{sampling:?}: {ratio}");
+        assert!(points
+            .iter()
+            .all(|p| (min[0]..max[0]).contains(&p[0]) && (min[1]..max[1]).contains(&p[1])));
+    }
+    let poisson = sampling::poisson_disk(min, max, spacing, 7);
+    assert!(min_distance(&poisson) >= spacing * 0.999);
+    let jittered = sampling::jittered(min, max, spacing, 7);
+    assert!(min_distance(&jittered) >= spacing * (1.0 - 2.0 * sampling::JITTER) * 0.999);
+}
+
+#[test]
+fn polygon_blocks_fill_only_the_inside() {
+    let triangle: Polygon = "0,0;0.4,0;0,0.4".parse().unwrap();
+    let block: FluidBlock = "0,0;0.4,0;0,0.4=oil:poisson".parse().unwrap();
+    assert_eq!(block.phase, Phase::OIL);
+    assert_eq!(block.sampling, Sampling::Poisson);
+    let particles = block.particles();
+    assert!(!particles.is_empty());
+    assert!(particles.iter().all(|p| triangle.contains(p.pos)));
+
+    // half the square the triangle spans
+    let square = FluidBlock::new([0.0, 0.0], [0.4, 0.4], Phase::WATER).particles();
+    let lattice = FluidBlock::polygon(triangle, Phase::WATER).particles();
+    let ratio = lattice.len() as f32 / square.len() as f32;
+    assert!((0.45..0.55).contains(&ratio), "{ratio}");
+}
+
+#[test]
+fn blocks_at_rest_hold_particles_per_area_by_rest_density() {
+    let radius = 0.01;
+    let water = FluidBlock::at_rest([0.0, 0.0], [0.4, 0.4], radius, Phase::WATER);
+    assert_eq!(water.spacing, 2.0 * radius);
+    assert_eq!(water.particles().len(), 400);
+
+    let oil = FluidBlock::at_rest([0.0, 0.0], [0.4, 0.4], radius, Phase::OIL);
+    let per_area = (water.spacing / oil.spacing).powi(2);
+    assert!((per_area - Phase::OIL.rest_density).abs() < 1e-5);
+
+    let dam = FluidBlock::dam_break(0.3, 0.6, radius);
+    assert_eq!((dam.min, dam.max), ([0.0, 0.0], [0.3, 0.6]));
+    assert_eq!(dam.phase, Phase::WATER);
+}
@@ -0,0 +1,52 @@
+use pos_based_fluids::paddle::Paddle;
+use pos_based_fluids::scene::{Scene, SceneEdit};
+use pos_based_fluids::script::Script;
+
+#[test]
+fn move_obstacle_moves_a_paddle() {
+    let mut script = Script::new(
+        "move_obstacle(0, 0.3, 0.4);
+        fn update(time) {
+            move_obstacle(0, 0.5 + time, 0.5);
+        }",
+    )
+    .unwrap();
+    let edits = script.update(0.25).unwrap();
+    assert_eq!(
+        edits,
+        [
+            SceneEdit::MovePaddle {
+                index: 0,
+                center: [0.3, 0.4],
+            },
+            SceneEdit::MovePaddle {
+                index: 0,
+                center: [0.75, 0.5],
+            },
+        ]
+    );
+
+    let mut scene = Scene::new(vec![]).with_paddle(Paddle::new([0.5, 0.5], 0.2, 1.0));
+    for edit in &edits {
+        scene.apply(edit).unwrap();
+    }
+    assert_eq!(scene.paddles[0].center, [0.75, 0.5]);
+
+    // the top level ran once, later updates only call `update`
+    assert_eq!(
+        script.update(0.5).unwrap(),
+        [SceneEdit::MovePaddle {
+            index: 0,
+            center: [1.0, 0.5],
+        }]
+    );
+
+    let missing = SceneEdit::MovePaddle {
+        index: 1,
+        center: [0.1, 0.1],
+    };
+    assert!(scene.apply(&missing).is_err());
+    assert_eq!(scene.paddles[0].center, [0.75, 0.5]);
+
+    assert!(Script::new("move_obstacle(-1, 0.5, 0.5);").is_err());
+}
@@ -0,0 +1,98 @@
+use pos_based_fluids::boundary::{Boundaries, Boundary, Edge};
+use pos_based_fluids::geometry::Polygon;
+use pos_based_fluids::material::Material;
+use pos_based_fluids::sdf::DistanceField;
+
+fn ring() -> DistanceField {
+    let outer = Polygon::rect([0.2, 0.2], [0.8, 0.8]);
+    let hole = Polygon::rect([0.4, 0.4], [0.6, 0.6]);
+    DistanceField::from_polygons(&[outer, hole], 101)
+}
+
+#[test]
+fn holes_are_outside() {
+    let field = ring();
+    let at = |p| field.distance_at(p).unwrap();
+    assert!((at([0.3, 0.5]) + 0.1).abs() < 1e-4);
+    assert!((at([0.5, 0.5]) - 0.1).abs() < 1e-4);
+    assert!((at([0.1, 0.5]) - 0.1).abs() < 1e-4);
+    assert!(DistanceField::default().distance_at([0.5, 0.5]).is_none());
+}
+
+#[test]
+fn particles_inside_are_pushed_out() {
+    let field = ring();
+    // closer to the hole than to the outer edge
+    let mut pos = [0.37, 0.5];
+    let mut vel = [1.0, 0.5];
+    field.collide(&mut pos, &mut vel);
+    assert!((pos[0] - 0.4).abs() < 1e-3, "{pos:?}");
+    assert!((pos[1] - 0.5).abs() < 1e-3, "{pos:?}");
+    assert_eq!(vel, [1.0, 0.5]);
+
+    // going back into the obstacle
+    let mut vel = [-1.0, 0.5];
+    let mut pos = [0.37, 0.5];
+    field.collide(&mut pos, &mut vel);
+    assert!(
+        vel[0].abs() < 1e-3 && (vel[1] - 0.5).abs() < 1e-3,
+        "{vel:?}"
+    );
+
+    let mut outside = [0.1, 0.1];
+    field.collide(&mut outside, &mut vel);
+    assert_eq!(outside, [0.1, 0.1]);
+}
+
+#[test]
+fn obstacles_respond_by_their_material() {
+    let bouncy: Material = "restitution=1,friction=0.5".parse().unwrap();
+    let field = DistanceField::from_polygons(
+        &[
+            Polygon::rect([0.0, 0.0], [0.5, 0.3]),
+            Polygon::rect([0.5, 0.0], [1.0, 0.3]),
+        ],
+        101,
+    )
+    .with_materials(vec![Material::default(), bouncy]);
+
+    let mut pos = [0.25, 0.29];
+    let mut vel = [1.0, -2.0];
+    field.collide(&mut pos, &mut vel);
+    assert!(
+        (vel[0] - 1.0).abs() < 1e-3 && vel[1].abs() < 1e-3,
+        "{vel:?}"
+    );
+
+    let mut pos = [0.75, 0.29];
+    let mut vel = [1.0, -2.0];
+    field.collide(&mut pos, &mut vel);
+    assert!(
+        (vel[0] - 0.5).abs() < 1e-3 && (vel[1] - 2.0).abs() < 1e-3,
+        "{vel:?}"
+    );
+
+    assert!("restitution=2".parse::<Material>().is_err());
+    assert!("bounce=1".parse::<Material>().is_err());
+}
+
+#[test]
+fn wetting_pulls_fluid_in_or_pushes_it_off() {
+    let floor = |wetting: &str| {
+        DistanceField::from_polygons(&[Polygon::rect([0.0, 0.0], [1.0, 0.3])], 101)
+            .with_materials(vec![wetting.parse().unwrap()])
+    };
+    let above = [0.5, 0.31];
+    assert_eq!(floor("").adhesion(above), [0.0, 0.0]);
+    // hydrophilic pulls down onto the floor, hydrophobic pushes up off it
+    assert!(floor("wetting=1").adhesion(above)[1] < -1.0);
+    assert!(floor("wetting=-1").adhesion(above)[1] > 1.0);
+
+    let wall: Boundary = "wall:0:-0.5".parse().unwrap();
+    let mut boundaries = Boundaries::default();
+    boundaries.set(Edge::Bottom, wall);
+    assert!(boundaries.wall_adhesion([0.5, 0.01])[1] > 0.0);
+
+    assert!("wetting=2".parse::<Material>().is_err());
+    assert!("wall:0:2".parse::<Boundary>().is_err());
+}
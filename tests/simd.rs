@@ -0,0 +1,151 @@
+use pos_based_fluids::grid::Grid;
+use pos_based_fluids::neighbors::CellList;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::simd::{Lanes, Slots};
+use pos_based_fluids::wcsph::WcsphParams;
+
+/// A jittered lattice with a few particles piled onto each other, so some
+/// cells don't fill whole lanes and others spill over.
+fn particles() -> Vec<Instance> {
+    let mut seed = 1u32;
+    let mut jitter = || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 8) as f32 / (1 << 24) as f32 * 0.01
+    };
+    let mut particles = vec![];
+    for y in 0..30 {
+        for x in 0..30 {
+            let pos = [
+                0.2 + x as f32 * 0.02 + jitter(),
+                0.2 + y as f32 * 0.02 + jitter(),
+            ];
+            particles.push(Instance {
+                pos,
+                vel: [jitter() - 0.005, jitter() - 0.005],
+            });
+        }
+    }
+    particles.extend(vec![particles[400]; 9]);
+    particles
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.0)
+}
+
+#[test]
+fn lanes_sum_the_same_as_the_scalar_loops() {
+    let params = WcsphParams::default();
+    let h = params.radius;
+    let particles = particles();
+    let mut cells = CellList::new(h);
+    cells.build(&particles);
+    let mut lanes = Lanes::default();
+    lanes.gather(&cells, &particles);
+
+    let kernel = |dist: f32| (1.0 - dist * dist / (h * h)).max(0.0).powi(3);
+    let densities = particles
+        .iter()
+        .map(|p| {
+            let mut weight = 0.0;
+            cells.for_each_neighbor(&particles, p.pos, |_, _, dist| weight += kernel(dist));
+            let simd = lanes.weight_at(&cells, p.pos);
+            assert!(close(weight, simd), "{weight} vs {simd}");
+            weight
+        })
+        .collect::<Vec<_>>();
+
+    let pressures = densities.iter().map(|d| d - 1.0).collect::<Vec<_>>();
+    lanes.gather_pressures(&cells, &densities, &pressures);
+    let term = |i: usize| pressures[i] / (densities[i] * densities[i]);
+    for (i, p) in particles.iter().enumerate() {
+        let mut acc = [0.0f32; 2];
+        cells.for_each_neighbor(&particles, p.pos, |j, d, dist| {
+            if dist <= 1e-6 {
+                return;
+            }
+            let rel = [
+                particles[j].vel[0] - p.vel[0],
+                particles[j].vel[1] - p.vel[1],
+            ];
+            let approach = rel[0] * d[0] + rel[1] * d[1];
+            let viscous = match approach < 0.0 {
+                true => {
+                    params.viscosity * params.speed_of_sound * h * -approach
+                        / (0.5 * (densities[i] + densities[j]) * (dist * dist + 0.01 * h * h))
+                }
+                false => 0.0,
+            };
+            let falloff = (1.0 - dist * dist / (h * h)).max(0.0);
+            let push = (term(i) + term(j) + viscous) * 6.0 / (h * h) * falloff * falloff;
+            acc[0] -= push * d[0];
+            acc[1] -= push * d[1];
+        });
+        let simd = lanes.pressure_acceleration(&cells, &params, 1.0, p, densities[i], term(i));
+        assert!(
+            close(acc[0], simd[0]) && close(acc[1], simd[1]),
+            "{i}: {acc:?} vs {simd:?}"
+        );
+    }
+}
+
+#[test]
+fn slots_touch_the_same_as_the_scalar_loop() {
+    let n_per_cell = 4;
+    let radius = 0.03;
+    let mut particles = particles();
+    // pairs only touching across the edges of a periodic domain
+    particles.extend(
+        [
+            [0.005, 0.5],
+            [0.99, 0.5],
+            [0.5, 0.01],
+            [0.5, 0.995],
+            [0.0, 0.0],
+            [0.99, 0.99],
+        ]
+        .map(|pos| Instance {
+            pos,
+            vel: [0.0, 0.0],
+        }),
+    );
+    // every third asleep
+    let asleep = |id: usize| id.is_multiple_of(3);
+    for periodic in [[false, false], [true, true]] {
+        let grid = Grid::with_cells(16).with_periodic(periodic);
+        // as `sort_particles` fills them, crowded cells drop the rest
+        let mut cell_ids = vec![-1; grid.cell_count() * n_per_cell];
+        let mut counts = vec![0; grid.cell_count()];
+        for (id, p) in particles.iter().enumerate() {
+            let cell = grid.cell_index(p.pos).unwrap() as usize;
+            if counts[cell] < n_per_cell {
+                cell_ids[cell * n_per_cell + counts[cell]] = id as i32;
+            }
+            counts[cell] += 1;
+        }
+        let mut slots = Slots::default();
+        slots.gather(&cell_ids, &particles, asleep);
+
+        let mut touching = 0;
+        for (id, p) in particles.iter().enumerate() {
+            let cell = grid.cell_index(p.pos).unwrap();
+            let scalar = grid.neighbors(cell).any(|neighbor| {
+                let start = neighbor as usize * n_per_cell;
+                cell_ids[start..start + n_per_cell].iter().any(|&other| {
+                    let Ok(other) = usize::try_from(other) else {
+                        return false;
+                    };
+                    if other == id || (asleep(id) && asleep(other)) {
+                        return false;
+                    }
+                    let [dx, dy] = grid.wrap_delta(p.pos, particles[other].pos);
+                    dx * dx + dy * dy <= radius * radius
+                })
+            });
+            let simd = slots.touches(&grid, n_per_cell, cell, id, p.pos, radius, asleep(id));
+            assert_eq!(scalar, simd, "{id} with periodic {periodic:?}");
+            touching += scalar as usize;
+        }
+        assert!(touching > 0 && touching < particles.len(), "{touching}");
+    }
+}
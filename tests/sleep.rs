@@ -0,0 +1,47 @@
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::boundary::{Boundaries, Boundary, Edge, Wall};
+use pos_based_fluids::cpu::CpuState;
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::sim::Instance;
+
+#[test]
+fn particles_resting_on_the_floor_fall_asleep() {
+    let mut walls = Boundaries::default();
+    for edge in Edge::ALL {
+        walls.set(edge, Boundary::Wall(Wall::default()));
+    }
+    // further apart than a radius, so only gravity and the floor act on them
+    let particles = [0.1, 0.7]
+        .map(|x| Instance {
+            pos: [x, 0.2],
+            vel: [0.0, 0.0],
+        })
+        .to_vec();
+    let scene = Scene::new(particles)
+        .with_boundaries(walls)
+        .with_gravity([0.0, -9.81]);
+    let config = Config {
+        sleep_after: 30,
+        ..Config::default()
+    };
+    let mut cpu = CpuState::new(&scene, &config);
+    for _ in 0..20 {
+        cpu.step().unwrap();
+    }
+    // landed after about 12 steps, not quiet for long enough yet
+    assert_eq!(cpu.asleep(), 0);
+    for _ in 0..40 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(cpu.asleep(), 2);
+    assert!(cpu
+        .particles()
+        .iter()
+        .all(|p| p.pos[1] == 0.0 && p.vel == [0.0, 0.0]));
+
+    let mut awake = CpuState::new(&scene, &Config::default());
+    for _ in 0..120 {
+        awake.step().unwrap();
+    }
+    assert_eq!(awake.asleep(), 0);
+}
@@ -0,0 +1,76 @@
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::cpu::CpuState;
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::stability::{self, Brake};
+use pos_based_fluids::TIME_STEP;
+
+#[test]
+fn clamping_caps_speed_and_displacement() {
+    let config = Config {
+        max_speed: 2.0,
+        max_displacement: 0.01,
+        ..Config::default()
+    };
+    let limit = stability::speed_limit(&config, TIME_STEP);
+    assert!((limit - 0.6).abs() < 1e-5);
+
+    let mut vel = [3.0, 4.0];
+    assert!(stability::clamp_velocity(&mut vel, 1.0));
+    assert!((vel[0] - 0.6).abs() < 1e-6 && (vel[1] - 0.8).abs() < 1e-6);
+    assert!(!stability::clamp_velocity(&mut vel, 1.0));
+
+    let scene = Scene::new(vec![Instance {
+        pos: [0.5, 0.5],
+        vel: [50.0, 0.0],
+    }]);
+    let mut cpu = CpuState::new(&scene, &config);
+    cpu.step().unwrap();
+    assert!(cpu.particles()[0].vel[0] <= limit + 1e-5);
+}
+
+#[test]
+fn brake_halves_the_step_until_things_calm_down() {
+    let mut brake = Brake::default();
+    brake.strikes = 2;
+    brake.calm = 3;
+    assert_eq!(brake.record(50, 100), None);
+    assert_eq!(brake.record(50, 100), Some(2));
+    assert_eq!(brake.record(50, 100), None);
+    assert_eq!(brake.record(50, 100), Some(4));
+
+    // a single clamped particle out of many doesn't count
+    assert_eq!(brake.record(1, 1000), None);
+    assert_eq!(brake.record(0, 100), None);
+    assert_eq!(brake.record(0, 100), Some(2));
+    assert_eq!(brake.substeps(), 2);
+}
+
+#[test]
+fn drag_slows_particles_without_turning_them_around() {
+    let acc = stability::drag([2.0, 0.0], 1.0, 0.5, 0.1);
+    // slowed by a factor of 1 + (1 + 0.5 * 2) * 0.1
+    assert!((2.0 + acc[0] * 0.1 - 2.0 / 1.2).abs() < 1e-6);
+    assert_eq!(acc[1], 0.0);
+    assert_eq!(stability::drag([2.0, 0.0], 0.0, 0.0, 0.1), [0.0, 0.0]);
+
+    let acc = stability::drag([2.0, 0.0], 1e6, 0.0, 0.1);
+    assert!(2.0 + acc[0] * 0.1 >= 0.0);
+
+    let moving = Instance {
+        pos: [0.5, 0.5],
+        vel: [1.0, 0.0],
+    };
+    let config = Config {
+        linear_drag: 5.0,
+        ..Config::default()
+    };
+    let mut scene = Scene::new(vec![moving]);
+    scene.gravity = [0.0, 0.0];
+    let mut cpu = CpuState::new(&scene, &config);
+    for _ in 0..60 {
+        cpu.step().unwrap();
+    }
+    let vel = cpu.particles()[0].vel[0];
+    assert!(vel > 0.0 && vel < 0.01, "{vel}");
+}
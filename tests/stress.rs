@@ -0,0 +1,29 @@
+use pos_based_fluids::stress::{self, Round};
+
+#[test]
+fn stress_search_doubles_until_too_slow() {
+    // a microsecond per particle
+    let rounds = stress::search(1000, 1 << 20, 0.01, |particles| {
+        Ok::<_, ()>(Round {
+            particles,
+            step_time: particles as f32 * 1e-6,
+        })
+    })
+    .unwrap();
+    let counts = rounds
+        .iter()
+        .map(|round| round.particles)
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [1000, 2000, 4000, 8000, 16000]);
+    assert_eq!(stress::best(&rounds, 0.01).unwrap().particles, 8000);
+
+    let capped = stress::search(1000, 3000, 1.0, |particles| {
+        Ok::<_, ()>(Round {
+            particles,
+            step_time: 0.0,
+        })
+    })
+    .unwrap();
+    assert_eq!(capped.len(), 2);
+    assert_eq!(stress::best(&[], 1.0), None);
+}
@@ -0,0 +1,71 @@
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::surface::{Polyline, SurfaceExtractor, SurfaceParams};
+
+/// Particles on a square lattice filling `[min, max]`.
+fn block(min: [f32; 2], max: [f32; 2], spacing: f32) -> Vec<Instance> {
+    let mut particles = vec![];
+    let mut y = min[1];
+    while y <= max[1] {
+        let mut x = min[0];
+        while x <= max[0] {
+            particles.push(Instance {
+                pos: [x, y],
+                vel: [0.0; 2],
+            });
+            x += spacing;
+        }
+        y += spacing;
+    }
+    particles
+}
+
+fn signed_area(line: &Polyline) -> f32 {
+    let n = line.points.len();
+    (0..n)
+        .map(|i| {
+            let [a, b] = [line.points[i], line.points[(i + 1) % n]];
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+#[test]
+fn a_blob_has_one_closed_counter_clockwise_outline() {
+    let particles = block([0.4, 0.4], [0.6, 0.6], 0.01);
+    let surface = SurfaceExtractor::new(SurfaceParams::default()).extract(&particles);
+
+    assert_eq!(surface.len(), 1);
+    let outline = &surface[0];
+    assert!(outline.closed);
+    // the fluid is on the left
+    let area = signed_area(outline);
+    assert!(area > 0.04 && area < 0.07, "area {area}");
+    for p in &outline.points {
+        let outside = p[0] < 0.4 || p[0] > 0.6 || p[1] < 0.4 || p[1] > 0.6;
+        assert!(outside, "{p:?} is inside the blob");
+    }
+}
+
+#[test]
+fn separate_blobs_have_separate_outlines() {
+    let mut particles = block([0.1, 0.1], [0.2, 0.2], 0.01);
+    particles.extend(block([0.7, 0.7], [0.8, 0.8], 0.01));
+    let surface = SurfaceExtractor::new(SurfaceParams::default()).extract(&particles);
+
+    assert_eq!(surface.len(), 2);
+    assert!(surface.iter().all(|line| line.closed));
+}
+
+#[test]
+fn fluid_touching_the_edge_has_an_open_outline() {
+    let particles = block([0.0, 0.0], [1.0, 0.3], 0.01);
+    let surface = SurfaceExtractor::new(SurfaceParams::default()).extract(&particles);
+
+    assert_eq!(surface.len(), 1);
+    let line = &surface[0];
+    assert!(!line.closed);
+    assert!(line.points.iter().all(|p| (p[1] - 0.3).abs() < 0.03));
+    // runs from right to left with the fluid below
+    assert!(line.points[0][0] > line.points.last().unwrap()[0]);
+}
@@ -0,0 +1,28 @@
+use pos_based_fluids::phase::Phase;
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::theme::Theme;
+
+#[test]
+fn theme_files_start_from_a_base_and_recolor_the_phases() {
+    let theme: Theme = "
+        # printable
+        base = light
+        clear = 102030
+        palette = ff0000, 8000ff00
+    "
+    .parse()
+    .unwrap();
+    assert_eq!(theme.clear, 0xff102030);
+    assert_eq!(theme.lines, Theme::light().lines);
+
+    let mut scene = Scene {
+        phases: vec![Phase::WATER, Phase::OIL, Phase::SNOW],
+        ..Scene::default()
+    };
+    theme.apply(&mut scene);
+    let colors = scene.phases.iter().map(|p| p.color).collect::<Vec<_>>();
+    assert_eq!(colors, [0xffff0000, 0x8000ff00, 0xffff0000]);
+
+    assert!("clear = blue".parse::<Theme>().is_err());
+    assert!("base = neon".parse::<Theme>().is_err());
+}
@@ -0,0 +1,64 @@
+use pos_based_fluids::backend::BackendKind;
+use pos_based_fluids::compare;
+use pos_based_fluids::options::Options;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::simulation::{Simulation, TickedSimulation};
+use pos_based_fluids::timestep::MAX_STEPS_PER_FRAME;
+use pos_based_fluids::TIME_STEP;
+
+#[test]
+fn ticks_run_whole_steps_and_carry_the_rest() {
+    let options = Options {
+        backend: BackendKind::Cpu,
+        blocks: vec![FluidBlock::new([0.4, 0.4], [0.6, 0.6], Phase::WATER)],
+        ..Options::default()
+    };
+    let scene = options.scene().unwrap();
+    let mut simulation = TickedSimulation::new(Simulation::new(&scene, &options).unwrap());
+
+    assert_eq!(simulation.tick(TIME_STEP * 0.6).unwrap(), 0.0);
+    assert!((simulation.alpha() - 0.6).abs() < 1e-3);
+    // the carried over time makes up the difference
+    assert_eq!(simulation.tick(TIME_STEP * 1.6).unwrap(), 2.0 * TIME_STEP);
+    assert!((simulation.alpha() - 0.2).abs() < 1e-3);
+
+    // a long hitch doesn't run away
+    let advanced = simulation.tick(1.0).unwrap();
+    assert_eq!(advanced, MAX_STEPS_PER_FRAME as f32 * TIME_STEP);
+    assert_eq!(simulation.tick(-1.0).unwrap(), 0.0);
+    assert_eq!(simulation.tick(f32::NAN).unwrap(), 0.0);
+}
+
+#[test]
+fn ticks_show_the_steps_they_ran() {
+    // falls back to the cpu backend without an OpenCL device
+    for backend in [BackendKind::Cpu, BackendKind::OpenCl] {
+        let mut options = Options {
+            backend,
+            blocks: vec![FluidBlock::new([0.4, 0.4], [0.6, 0.6], Phase::WATER)],
+            ..Options::default()
+        };
+        options.config.deterministic = true;
+        let scene = options.scene().unwrap();
+        let mut ticked = TickedSimulation::new(Simulation::new(&scene, &options).unwrap());
+        let mut stepped = Simulation::new(&scene, &options).unwrap();
+
+        let mut steps = 0;
+        for real_dt in [TIME_STEP * 1.5, TIME_STEP * 2.2, TIME_STEP * 0.2] {
+            let advanced = ticked.tick(real_dt).unwrap();
+            for _ in 0..(advanced / TIME_STEP).round() as usize {
+                stepped.step().unwrap();
+                steps += 1;
+            }
+            stepped.wait_read().unwrap();
+            // without waiting the device would still show an older step
+            assert_eq!(
+                compare::state_hash(ticked.sim.particles()),
+                compare::state_hash(stepped.particles()),
+                "{backend:?} after {steps} steps"
+            );
+        }
+        // the last tick ran no step
+        assert_eq!(steps, 3);
+    }
+}
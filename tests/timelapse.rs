@@ -0,0 +1,34 @@
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::timelapse::{write_png, Timelapse};
+
+#[test]
+fn png_has_valid_chunks() {
+    let mut bytes = vec![];
+    write_png(&mut bytes, 2, 1, &[255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+    assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&bytes[12..16], b"IHDR");
+    // the IEND chunk is always the same, checksum included
+    assert_eq!(&bytes[bytes.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+}
+
+#[test]
+fn saves_every_nth_frame_with_a_manifest() {
+    let dir = std::env::temp_dir().join(format!("timelapse-{}", std::process::id()));
+    let particles = [Instance {
+        pos: [0.5, 0.5],
+        vel: [0.0, 0.0],
+    }];
+    let mut timelapse = Timelapse::create(&dir, 2, 16).unwrap();
+    for step in 0..5 {
+        timelapse.capture(step, &particles, &[0xffff0000]).unwrap();
+    }
+    timelapse.flush().unwrap();
+
+    assert!(timelapse.path(2).exists());
+    assert!(!timelapse.path(3).exists());
+    let manifest = std::fs::read_to_string(dir.join("manifest.csv")).unwrap();
+    let lines = manifest.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[2].starts_with("1,2,"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
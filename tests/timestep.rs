@@ -0,0 +1,32 @@
+use pos_based_fluids::boundary;
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::timestep::{self, FrameLimiter};
+use std::time::{Duration, Instant};
+
+#[test]
+fn frame_limiter_keeps_to_the_rate_without_bursts() {
+    let ms = Duration::from_millis;
+    let start = Instant::now();
+    let mut limiter = FrameLimiter::new(100.0);
+
+    assert!(limiter.due(start));
+    assert!(!limiter.due(start + ms(5)));
+    // a little late, the next frame makes up for it
+    assert!(limiter.due(start + ms(12)));
+    assert_eq!(limiter.next(), Some(start + ms(20)));
+    // a stall, starting over from there
+    assert!(limiter.due(start + ms(100)));
+    assert!(!limiter.due(start + ms(101)));
+}
+
+#[test]
+fn extrapolation_moves_on_by_the_velocity() {
+    let moving = Instance {
+        pos: [0.5, 0.5],
+        vel: [1.0, -2.0],
+    };
+    let ahead = timestep::extrapolate(&[moving, boundary::REMOVED], 0.25);
+    assert_eq!(ahead[0].pos, [0.75, 0.0]);
+    assert_eq!(ahead[0].vel, moving.vel);
+    assert!(boundary::is_removed(&ahead[1]));
+}
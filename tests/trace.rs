@@ -0,0 +1,57 @@
+use pos_based_fluids::trace::{DeviceSpan, Trace, Track};
+use std::time::{Duration, Instant};
+
+fn span(name: &'static str, start: u64, end: u64) -> DeviceSpan {
+    DeviceSpan {
+        name,
+        category: "solve",
+        start,
+        end,
+    }
+}
+
+#[test]
+fn lines_device_tracks_up_with_the_host_once() {
+    let started = Instant::now();
+    let mut trace = Trace::new(started);
+    let now = started + Duration::from_millis(10);
+    // the device clock is far off from the host's
+    trace.add_device(
+        Track::Simulation,
+        &[span("a", 5_000_000_000, 5_002_000_000)],
+        1,
+        now,
+    );
+    trace.add_device(
+        Track::Simulation,
+        &[span("b", 5_003_000_000, 5_004_000_000)],
+        2,
+        now + Duration::from_secs(1),
+    );
+
+    let starts = trace.events.iter().map(|e| e.start).collect::<Vec<_>>();
+    // `a` ended at 10 ms, and `b` keeps its distance to it
+    assert!((starts[0] - 8_000.0).abs() < 1e-6, "{starts:?}");
+    assert!((starts[1] - 11_000.0).abs() < 1e-6, "{starts:?}");
+    assert!((trace.events[1].duration - 1_000.0).abs() < 1e-6);
+    assert_eq!(trace.events[1].frame, 2);
+}
+
+#[test]
+fn writes_named_tracks_and_complete_events() {
+    let started = Instant::now();
+    let mut trace = Trace::new(started);
+    trace.add_host("step", started, started + Duration::from_micros(1500), 3);
+    let mut out = vec![];
+    trace.write_json(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+
+    assert!(json.starts_with("{\"displayTimeUnit\""), "{json}");
+    assert!(json.contains("\"args\": {\"name\": \"OpenCL\"}"), "{json}");
+    assert!(
+        json.contains("\"name\": \"step\", \"cat\": \"host\", \"ph\": \"X\", \"ts\": 0.000, \"dur\": 1500.000"),
+        "{json}"
+    );
+    assert!(json.contains("\"args\": {\"frame\": 3}"), "{json}");
+    assert!(json.trim_end().ends_with("]}"));
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::particle;
+use pos_based_fluids::boundary;
+use pos_based_fluids::trigger::{Trigger, TriggerCounts, Triggers};
+use std::sync::{Arc, Mutex};
+
+fn basin() -> Trigger {
+    "basin=0,0,0.5,0.5".parse().unwrap()
+}
+
+#[test]
+fn counts_particles_entering_and_leaving() {
+    let mut particles = vec![
+        particle(0.25, 0.25),
+        particle(0.75, 0.75),
+        particle(0.75, 0.25),
+    ];
+    let mut triggers = Triggers::new(vec![basin()], &particles);
+    assert_eq!(
+        triggers.counts()[0],
+        TriggerCounts {
+            entered: 0,
+            left: 0,
+            inside: 1
+        }
+    );
+
+    let changes = Arc::new(Mutex::new(vec![]));
+    triggers.on_change({
+        let changes = changes.clone();
+        move |trigger, counts| {
+            changes
+                .lock()
+                .unwrap()
+                .push((trigger.name.clone(), *counts))
+        }
+    });
+
+    particles[0] = particle(0.75, 0.5);
+    particles[1] = particle(0.1, 0.1);
+    particles[2] = particle(0.4, 0.1);
+    triggers.update(&particles);
+    let expected = TriggerCounts {
+        entered: 2,
+        left: 1,
+        inside: 2,
+    };
+    assert_eq!(*changes.lock().unwrap(), [("basin".to_string(), expected)]);
+
+    triggers.update(&particles);
+    assert_eq!(changes.lock().unwrap().len(), 1, "nothing moved");
+}
+
+#[test]
+fn fraction_leaves_out_removed_particles() {
+    let particles = vec![
+        particle(0.1, 0.1),
+        particle(0.2, 0.2),
+        particle(0.9, 0.9),
+        boundary::REMOVED,
+    ];
+    let triggers = Triggers::new(vec![basin()], &particles);
+    assert_eq!(triggers.fraction("basin"), Some(2.0 / 3.0));
+    assert_eq!(triggers.fraction("spill"), None);
+}
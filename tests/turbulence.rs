@@ -0,0 +1,44 @@
+mod common;
+
+use common::particle;
+use pos_based_fluids::turbulence::{Surface, Turbulence};
+
+fn strength(acc: [f32; 2]) -> f32 {
+    (acc[0] * acc[0] + acc[1] * acc[1]).sqrt()
+}
+
+#[test]
+fn eddies_fade_below_the_surface() {
+    let mut surface = Surface::default();
+    surface.update(&[particle(0.5, 0.2), particle(0.5, 0.6)]);
+    assert_eq!(surface.height_at(0.5), 0.6);
+    assert_eq!(surface.height_at(0.1), 0.0);
+
+    let turbulence: Turbulence = "1,8,0,0.05".parse().unwrap();
+    let at = |y| turbulence.acceleration([0.5, y], &surface, 0.0);
+    let top = strength(at(0.6));
+    let deep = strength(at(0.4));
+    assert!(top > 0.0);
+    // four depths of falloff down, apart from the noise itself changing
+    assert!(deep < top * 0.5, "{top} {deep}");
+    assert_eq!(
+        Turbulence::default().acceleration([0.5, 0.6], &surface, 0.0),
+        [0.0, 0.0]
+    );
+}
+
+#[test]
+fn regions_limit_the_eddies() {
+    let surface = Surface::default();
+    let mut turbulence: Turbulence = "1,8,0,0.05".parse().unwrap();
+    turbulence.regions.push(([0.0, 0.0], [0.3, 0.3]));
+    assert_eq!(
+        turbulence.acceleration([0.5, 0.0], &surface, 0.0),
+        [0.0, 0.0]
+    );
+    assert_ne!(
+        turbulence.acceleration([0.13, 0.0], &surface, 0.0),
+        [0.0, 0.0]
+    );
+    assert!("1,8,0".parse::<Turbulence>().is_err());
+}
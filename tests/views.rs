@@ -0,0 +1,19 @@
+use pos_based_fluids::views::Views;
+
+#[test]
+fn views_round_trip_through_their_file() {
+    let mut views = Views {
+        current: Some(([0.0, 0.0], [1.0, 1.0])),
+        ..Views::default()
+    };
+    views.set_bookmark(3, ([0.25, 0.5], [0.75, 1.5]));
+    views.set_bookmark(10, ([0.0, 0.0], [2.0, 2.0]));
+
+    let parsed: Views = views.to_string().parse().unwrap();
+    assert_eq!(parsed, views);
+    assert_eq!(parsed.bookmark(3), Some(([0.25, 0.5], [0.75, 1.5])));
+    assert_eq!(parsed.bookmark(1), None);
+
+    assert!("10 = 0, 0, 1, 1".parse::<Views>().is_err());
+    assert!("view = 1, 0, 0, 1".parse::<Views>().is_err());
+}
@@ -0,0 +1,101 @@
+use pos_based_fluids::neighbors::CellList;
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::sim::Instance;
+use pos_based_fluids::viscosity::Viscosity;
+
+#[test]
+fn implicit_solve_smooths_where_an_explicit_step_would_blow_up() {
+    let mut particles = FluidBlock::new([0.2, 0.2], [0.6, 0.6], Phase::WATER).particles();
+    // columns moving up and down in turn
+    for p in &mut particles {
+        let column = ((p.pos[0] - 0.2) / 0.02) as i32;
+        p.vel = [0.0, if column % 2 == 0 { 1.0 } else { -1.0 }];
+    }
+    let mut cells = CellList::new(0.05);
+    cells.build(&particles);
+
+    let dt = 1.0 / 60.0;
+    let mut viscosity = Viscosity::new(1.0);
+    assert!(viscosity.is_implicit(dt, cells.radius()));
+    viscosity.apply(&cells, &mut particles, &[], dt);
+
+    let max = particles
+        .iter()
+        .fold(0.0f32, |max, p| max.max(p.vel[1].abs()));
+    assert!(max < 0.2, "{max}");
+    let mean = particles.iter().map(|p| p.vel[1]).sum::<f32>() / particles.len() as f32;
+    assert!(mean.abs() < 0.05, "{mean}");
+}
+
+/// Columns moving up and down in turn, the left half syrup and the right
+/// half water.
+fn striped_syrup_and_water() -> (Vec<Instance>, Vec<u32>) {
+    let mut particles = FluidBlock::new([0.2, 0.2], [0.8, 0.6], Phase::WATER).particles();
+    for p in &mut particles {
+        let column = ((p.pos[0] - 0.2) / 0.02) as i32;
+        p.vel = [0.0, if column % 2 == 0 { 1.0 } else { -1.0 }];
+    }
+    let phase_ids = particles
+        .iter()
+        .map(|p| if p.pos[0] < 0.5 { 1 } else { 0 })
+        .collect();
+    (particles, phase_ids)
+}
+
+/// Largest vertical speed of the particles in `[x0, x1)`.
+fn fastest(particles: &[Instance], x0: f32, x1: f32) -> f32 {
+    particles
+        .iter()
+        .filter(|p| (x0..x1).contains(&p.pos[0]))
+        .fold(0.0f32, |max, p| max.max(p.vel[1].abs()))
+}
+
+#[test]
+fn syrup_and_water_diffuse_at_their_own_viscosity() {
+    let (mut particles, phase_ids) = striped_syrup_and_water();
+    let mut cells = CellList::new(0.05);
+    cells.build(&particles);
+
+    let dt = 1.0 / 60.0;
+    let mut viscosity = Viscosity::new(0.0).with_phases(&[Phase::WATER, Phase::SYRUP], &phase_ids);
+    assert!(viscosity.is_implicit(dt, cells.radius()));
+    viscosity.apply(&cells, &mut particles, &[], dt);
+
+    let syrup = fastest(&particles, 0.2, 0.4);
+    assert!(syrup < 0.2, "{syrup}");
+    // away from the syrup, the water keeps its stripes
+    let water = fastest(&particles, 0.6, 0.8);
+    assert_eq!(water, 1.0);
+}
+
+#[test]
+fn heat_thins_and_painting_sets_the_viscosity() {
+    let (particles, phase_ids) = striped_syrup_and_water();
+    let mut cells = CellList::new(0.05);
+    cells.build(&particles);
+    let dt = 1.0 / 60.0;
+    let phases = [Phase::WATER, Phase::SYRUP];
+
+    let hot = vec![10.0; particles.len()];
+    let mut thinned = particles.clone();
+    Viscosity::new(0.0)
+        .with_phases(&phases, &phase_ids)
+        .with_thinning(1.0)
+        .apply(&cells, &mut thinned, &hot, dt);
+    let mut cold = particles.clone();
+    Viscosity::new(0.0)
+        .with_phases(&phases, &phase_ids)
+        .apply(&cells, &mut cold, &hot, dt);
+    assert!(fastest(&thinned, 0.2, 0.4) > fastest(&cold, 0.2, 0.4));
+
+    let mut painted = particles.clone();
+    let mut viscosity = Viscosity::new(0.0);
+    viscosity.paint(&particles, [0.7, 0.4], 0.05, 1.0);
+    viscosity.apply(&cells, &mut painted, &[], dt);
+    let inside = painted
+        .iter()
+        .filter(|p| (p.pos[0] - 0.7).hypot(p.pos[1] - 0.4) < 0.03)
+        .fold(0.0f32, |max, p| max.max(p.vel[1].abs()));
+    assert!(inside < 0.5, "{inside}");
+    assert_eq!(fastest(&painted, 0.2, 0.6), 1.0);
+}
@@ -0,0 +1,36 @@
+use pos_based_fluids::backend::{Backend, Config};
+use pos_based_fluids::boundary::{self, Boundaries, Boundary, Edge, Wall};
+use pos_based_fluids::phase::{FluidBlock, Phase};
+use pos_based_fluids::scene::Scene;
+use pos_based_fluids::wcsph::{WcsphParams, WcsphState};
+
+#[test]
+fn column_in_a_tank_stays_near_rest_density() {
+    let mut walls = Boundaries::default();
+    for edge in Edge::ALL {
+        walls.set(edge, Boundary::Wall(Wall::default()));
+    }
+    let scene = Scene::new(vec![])
+        .with_block(FluidBlock::new([0.0, 0.0], [0.2, 0.3], Phase::WATER))
+        .with_boundaries(walls)
+        .with_gravity([0.0, -1.0]);
+    let config = Config {
+        sleep_after: 0,
+        ..Config::default()
+    };
+    let mut wcsph = WcsphState::new(&scene, &config, WcsphParams::default());
+
+    for _ in 0..120 {
+        wcsph.step().unwrap();
+    }
+
+    let particles = wcsph.particles();
+    assert!(particles.iter().all(|p| !boundary::is_removed(p)
+        && (0.0..=1.0).contains(&p.pos[0])
+        && (0.0..=1.0).contains(&p.pos[1])));
+    let max = wcsph.densities().iter().fold(0.0f32, |max, &d| max.max(d));
+    assert!(max < 1.1, "{max}");
+    // the column collapsed sideways instead of compressing in place
+    let right = particles.iter().fold(0.0f32, |max, p| max.max(p.pos[0]));
+    assert!(right > 0.3, "{right}");
+}
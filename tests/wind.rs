@@ -0,0 +1,39 @@
+use pos_based_fluids::noise;
+use pos_based_fluids::wind::Wind;
+
+#[test]
+fn noise_tiles_and_matches_its_gradient() {
+    let period = [4.0, 4.0];
+    for p in [[0.3, 0.7], [1.9, 2.2], [3.5, 0.1]] {
+        let (n, gradient) = noise::simplex(p, period, 0.4);
+        let (wrapped, _) = noise::simplex([p[0] + 4.0, p[1] - 4.0], period, 0.4);
+        assert!((n - wrapped).abs() < 1e-4, "{n} {wrapped}");
+
+        let h = 1e-3;
+        let (right, _) = noise::simplex([p[0] + h, p[1]], period, 0.4);
+        let (up, _) = noise::simplex([p[0], p[1] + h], period, 0.4);
+        assert!(((right - n) / h - gradient[0]).abs() < 0.05, "{gradient:?}");
+        assert!(((up - n) / h - gradient[1]).abs() < 0.05, "{gradient:?}");
+    }
+}
+
+#[test]
+fn particles_are_dragged_towards_the_wind() {
+    let wind: Wind = "0.5,3,1".parse().unwrap();
+    assert_eq!(wind.cells(), 4.0);
+    assert!(Wind::default().velocity([0.2, 0.2], 0.0) == [0.0, 0.0]);
+
+    let pos = [0.3, 0.6];
+    let blowing = wind.velocity(pos, 2.0);
+    // no seam where the domain wraps around
+    let across = wind.velocity([pos[0] + 1.0, pos[1]], 2.0);
+    assert!((blowing[0] - across[0]).abs() < 1e-4);
+
+    let acc = wind.acceleration(pos, blowing, 2.0);
+    assert!(acc[0].abs() < 1e-6 && acc[1].abs() < 1e-6);
+    let acc = wind.acceleration(pos, [0.0, 0.0], 2.0);
+    assert!((acc[0] - 2.0 * blowing[0]).abs() < 1e-6);
+
+    assert!("1,0,1".parse::<Wind>().is_err());
+    assert!("1,2".parse::<Wind>().is_err());
+}